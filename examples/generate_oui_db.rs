@@ -0,0 +1,83 @@
+// Generate src/db/oui.rs from the IEEE MA-L registry.
+//
+// Usage:
+//   curl -o oui.csv https://standards-oui.ieee.org/oui/oui.csv
+//   cargo run --example generate_oui_db -- oui.csv > oui.rs && mv oui.rs src/db/oui.rs
+//
+// Write to another file first. Redirecting to src/db/oui.rs truncates it before the library is built.
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::process;
+
+/// Split the CSV line into fields. Quoted fields may contain separators and escaped quotes
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Escape the vendor name for a Rust string literal
+fn escape(name: &str) -> String {
+    name.chars().flat_map(|c| c.escape_default()).collect()
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: generate_oui_db <oui.csv>");
+            process::exit(1);
+        }
+    };
+    let csv = fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path, e);
+        process::exit(1);
+    });
+    // Registry,Assignment,Organization Name,Organization Address
+    let mut vendors: BTreeMap<u32, String> = BTreeMap::new();
+    for line in csv.lines().skip(1) {
+        let fields = split_csv_line(line);
+        if fields.len() < 3 || fields[0] != "MA-L" {
+            continue;
+        }
+        let oui = match u32::from_str_radix(fields[1].trim(), 16) {
+            Ok(oui) if fields[1].trim().len() == 6 => oui,
+            _ => continue,
+        };
+        let name = fields[2].split_whitespace().collect::<Vec<&str>>().join(" ");
+        if !name.is_empty() {
+            vendors.entry(oui).or_insert(name);
+        }
+    }
+    if vendors.is_empty() {
+        eprintln!("No MA-L assignments in {}", path);
+        process::exit(1);
+    }
+    println!("use phf::{{phf_map, Map}};");
+    println!();
+    println!("/// MAC address prefix (OUI) to vendor name.");
+    println!("///");
+    println!("/// Generated from the IEEE MA-L registry (https://standards-oui.ieee.org/oui/oui.csv) by `examples/generate_oui_db.rs`.");
+    println!("/// Key is the first 3 octets of the MAC address.");
+    println!("/// Regenerate with `cargo run --example generate_oui_db -- oui.csv > oui.rs && mv oui.rs src/db/oui.rs`.");
+    println!("pub(crate) static OUI_VENDOR_MAP: Map<u32, &'static str> = phf_map! {{");
+    for (oui, name) in &vendors {
+        println!("    0x{:06X}u32 => \"{}\",", oui, escape(name));
+    }
+    println!("}};");
+}
@@ -1,5 +1,6 @@
 use ipnet::Ipv4Net;
 use netscan::host::Host;
+use netscan::report::table::TableWriter;
 use netscan::scan::scanner::HostScanner;
 use netscan::scan::setting::{HostScanSetting, HostScanType};
 use std::net::{IpAddr, Ipv4Addr};
@@ -34,9 +35,7 @@ fn main() {
     // Print results
     println!("Status: {:?}", result.scan_status);
     println!("UP Hosts:");
-    for host in result.hosts {
-        println!("{:?}", host);
-    }
+    print!("{}", TableWriter::new().to_text(&result));
     println!("Fingerprints:");
     for fingerprint in result.fingerprints {
         println!("{:?}", fingerprint);
@@ -0,0 +1,33 @@
+use netscan::report::table::TableWriter;
+use netscan::scan::scanner::{PortScanner, ServiceBrowser};
+use netscan::scan::setting::{PortScanSetting, PortScanType, ServiceBrowseSetting};
+use std::thread;
+use std::time::Duration;
+
+fn main() {
+    let interface = netdev::get_default_interface().unwrap();
+    // Browse DNS-SD services on the local link
+    let browse_setting = ServiceBrowseSetting::default()
+        .set_if_index(interface.index)
+        .set_wait_time(Duration::from_millis(2000));
+    let browse_result = ServiceBrowser::new(browse_setting).browse();
+    println!("Status: {:?}", browse_result.scan_status);
+    for service in &browse_result.services {
+        println!("{} {}:{} {:?} {:?}", service.instance, service.hostname, service.port, service.addresses, service.txt);
+    }
+    // Scan the advertised ports. Names resolved by mDNS are not used unless merged into dns_map
+    let mut scan_setting = PortScanSetting::default()
+        .set_if_index(interface.index)
+        .set_scan_type(PortScanType::TcpSynScan)
+        .set_dns_map(browse_result.dns_map())
+        .set_timeout(Duration::from_millis(10000))
+        .set_wait_time(Duration::from_millis(500));
+    for host in browse_result.hosts() {
+        scan_setting = scan_setting.add_target(host);
+    }
+    let port_scanner = PortScanner::new(scan_setting);
+    let handle = thread::spawn(move || port_scanner.scan());
+    let result = handle.join().unwrap();
+    println!("Results:");
+    print!("{}", TableWriter::new().to_text(&result));
+}
@@ -1,4 +1,5 @@
-use netscan::host::{Host, PortStatus};
+use netscan::host::Host;
+use netscan::report::table::TableWriter;
 use netscan::scan::scanner::PortScanner;
 use netscan::scan::setting::{PortScanSetting, PortScanType};
 use std::net::IpAddr;
@@ -30,14 +31,7 @@ fn main() {
     // Print results
     println!("Status: {:?}", result.scan_status);
     println!("Results:");
-    for host_info in result.hosts {
-        println!("{} {}", host_info.ip_addr, host_info.hostname);
-        for port_info in host_info.ports {
-            if port_info.status == PortStatus::Open {
-                println!("{}: {:?}", port_info.number, port_info.status);
-            }
-        }
-    }
+    print!("{}", TableWriter::new().set_open_only(true).to_text(&result));
     println!("Fingerprints:");
     for fingerprint in result.fingerprints {
         println!("{:?}", fingerprint);
@@ -28,32 +28,39 @@ fn main() {
     while let Ok(_socket_addr) = rx.lock().unwrap().recv() {
         //println!("Check: {}", socket_addr);
     }
-    let result = handle.join().unwrap();
-    // Print results
+    let mut result = handle.join().unwrap();
     println!("Status: {:?}", result.scan_status);
+    // Run service detection for all open ports in the scan result
+    let mut probe_setting: ServiceProbeSetting = ServiceProbeSetting::default(
+        dst_ip,
+        "scanme.nmap.org".to_string(),
+        vec![],
+    );
+    probe_setting.set_read_timeout_millis(2000);
+    let service_detector = ServiceDetector::new(probe_setting);
+    let service_rx = service_detector.get_progress_receiver();
+    let service_handle = thread::spawn(move || {
+        let service_result = service_detector.run_with_scan_result(&mut result);
+        (result, service_result)
+    });
+    // Print progress
+    while let Ok(socket_addr) = service_rx.lock().unwrap().recv() {
+        println!("Checked: {}", socket_addr);
+    }
+    let (result, service_result) = service_handle.join().unwrap();
+    // Print results
     println!("Results:");
     for host_info in result.hosts {
         println!("{} {}", host_info.ip_addr, host_info.hostname);
         for port_info in &host_info.ports {
             if port_info.status == PortStatus::Open {
-                println!("{}: {:?}", port_info.number, port_info.status);
+                println!("{}: {} {}", port_info.number, port_info.service_name, port_info.service_version);
             }
         }
-        let probe_setting: ServiceProbeSetting = ServiceProbeSetting::default(
-            host_info.ip_addr,
-            "scanme.nmap.org".to_string(),
-            host_info.get_open_port_numbers(),
-        );
-        let service_detector = ServiceDetector::new(probe_setting);
-        let service_rx = service_detector.get_progress_receiver();
-        let service_handle = thread::spawn(move || service_detector.run());
-        // Print progress
-        while let Ok(socket_addr) = service_rx.lock().unwrap().recv() {
-            println!("Checked: {}", socket_addr);
-        }
-        let service_result = service_handle.join().unwrap();
-        for (port, result) in service_result {
-            println!("{}: {:?}", port, result);
+        if let Some(probe_results) = service_result.get(&host_info.ip_addr) {
+            for (port, probe_result) in probe_results {
+                println!("{}: {:?}", port, probe_result);
+            }
         }
     }
 }
@@ -4,3 +4,18 @@ pub const DEFAULT_HOP_LIMIT: u8 = 64;
 pub const DEFAULT_HOSTS_CONCURRENCY: usize = 50;
 pub const DEFAULT_PORTS_CONCURRENCY: usize = 100;
 pub const PCAP_WAIT_TIME_MILLIS: u64 = 10;
+pub const PCAP_DUMP_PENDING_LIMIT: usize = 4096;
+pub const UPTIME_PROBE_COUNT: usize = 6;
+pub const UPTIME_PROBE_INTERVAL_MILLIS: u64 = 500;
+pub const UPTIME_PROBE_BASE_PORT: u16 = 44460;
+pub const IDLE_PROBE_PORT: u16 = 44480;
+pub const IDLE_ZOMBIE_PROBE_COUNT: usize = 6;
+pub const IDLE_PROBE_TIMEOUT_MILLIS: u64 = 1000;
+pub const IDLE_SCAN_RETRIES: usize = 3;
+pub const NETBIOS_WAIT_TIME_MILLIS: u64 = 1000;
+pub const SMB_TIMEOUT_MILLIS: u64 = 3000;
+pub const SMB_CONCURRENT_LIMIT: usize = 10;
+pub const SNMP_WAIT_TIME_MILLIS: u64 = 1000;
+pub const SNMP_TIMEOUT_MILLIS: u64 = 1000;
+pub const SNMP_RETRIES: usize = 2;
+pub const SNMP_MAX_WALK_ENTRIES: usize = 2048;
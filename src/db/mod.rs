@@ -1 +1,4 @@
 pub mod tcp_service;
+pub mod os_fingerprint;
+pub mod os_db;
+pub mod oui;
@@ -0,0 +1,169 @@
+/// Active OS fingerprint database in nmap-os-db format.
+///
+/// Only the tests sent by `crate::os::probe` (T1-T7, IE, U1) are used.
+/// Test values may have alternatives separated by `|` and hex ranges like `3B-45`.
+pub(crate) static OS_DB: &str = r#"
+# Linux
+Fingerprint Linux 4.15 - 6.x
+Class Linux | Linux | 4.X | general purpose
+Class Linux | Linux | 5.X | general purpose
+Class Linux | Linux | 6.X | general purpose
+T1(R=Y%DF=Y%T=40%W=FE88|FAF0|FFFF|FD5C|7210%S=O%A=S+%F=AS%O=M5B4ST11NW7|M5B4ST11NW9|M5B4ST11NWA)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=40%CD=S)
+U1(R=Y%DF=N%T=40%IPL=164%UN=0)
+
+Fingerprint Linux 2.6.32 - 3.10
+Class Linux | Linux | 2.6.X | general purpose
+Class Linux | Linux | 3.X | general purpose
+T1(R=Y%DF=Y%T=40%W=3890|16A0|7210%S=O%A=S+%F=AS%O=M5B4ST11NW4|M5B4ST11NW5|M5B4ST11NW6|M5B4ST11NW7)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=40%CD=S)
+U1(R=Y%DF=N%T=40%IPL=164%UN=0)
+
+Fingerprint Linux 2.6.x (embedded)
+Class Linux | Linux | 2.6.X | embedded
+T1(R=Y%DF=Y%T=40%W=16D0|3908|1680%S=O%A=S+%F=AS%O=M5B4ST11NW0|M5B4ST11NW1|M5B4ST11NW2|M5B4NNSNW2)
+T2(R=N)
+T3(R=Y%DF=Y%T=40%W=16D0%S=O%A=S+%F=AS%O=M109NNSNW2|M109ST11NW2)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=40%CD=S)
+U1(R=Y%DF=N%T=40%IPL=164%UN=0)
+
+# Windows
+Fingerprint Microsoft Windows 10 / 11 / Server 2016 - 2022
+Class Microsoft | Windows | 10 | general purpose
+Class Microsoft | Windows | 11 | general purpose
+Class Microsoft | Windows | 2016 | general purpose
+T1(R=Y%DF=Y%T=80%W=FFFF|FAF0%S=O%A=S+%F=AS%O=M5B4NW8ST11|M5B4NW8NNS)
+T2(R=Y%DF=Y%T=80%W=0%S=Z%A=S%F=AR%O=)
+T3(R=N)
+T4(R=Y%DF=Y%T=80%W=0%S=A%A=O%F=R%O=)
+T5(R=Y%DF=Y%T=80%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=80%W=0%S=A%A=O%F=R%O=)
+T7(R=N)
+IE(R=Y%T=80%CD=Z)
+U1(R=Y%DF=N%T=80%IPL=164%UN=0)
+
+Fingerprint Microsoft Windows 7 / 8.1 / Server 2008 R2 - 2012
+Class Microsoft | Windows | 7 | general purpose
+Class Microsoft | Windows | 8.1 | general purpose
+Class Microsoft | Windows | 2008 | general purpose
+T1(R=Y%DF=Y%T=80%W=2000%S=O%A=S+%F=AS%O=M5B4NW8ST11|M5B4NW8NNS)
+T2(R=Y%DF=Y%T=80%W=0%S=Z%A=S%F=AR%O=)
+T3(R=Y%DF=Y%T=80%W=0%S=Z%A=O%F=AR%O=)
+T4(R=Y%DF=Y%T=80%W=0%S=A%A=O%F=R%O=)
+T5(R=Y%DF=Y%T=80%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=80%W=0%S=A%A=O%F=R%O=)
+T7(R=Y%DF=Y%T=80%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=80%CD=Z)
+U1(R=Y%DF=N%T=80%IPL=164%UN=0)
+
+# Apple
+Fingerprint Apple macOS 11 - 14
+Class Apple | macOS | 11.X | general purpose
+Class Apple | macOS | 14.X | general purpose
+T1(R=Y%DF=Y%T=40%W=FFFF%S=O%A=S+%F=AS%O=M5B4NW6NNT11SLL|M5B4NW5NNT11SLL)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=N%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=N%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=40%CD=S)
+U1(R=Y%DF=N%T=40%IPL=38%UN=0)
+
+Fingerprint Apple iOS 15 - 17
+Class Apple | iOS | 15.X | phone
+Class Apple | iOS | 17.X | phone
+T1(R=Y%DF=Y%T=40%W=FFFF%S=O%A=S+%F=AS%O=M5B4NW6NNT11SLL)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=N%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=N%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=N)
+U1(R=Y%DF=N%T=40%IPL=38%UN=0)
+
+# BSD
+Fingerprint FreeBSD 12.0 - 14.0
+Class FreeBSD | FreeBSD | 12.X | general purpose
+Class FreeBSD | FreeBSD | 13.X | general purpose
+Class FreeBSD | FreeBSD | 14.X | general purpose
+T1(R=Y%DF=Y%T=40%W=FFFF%S=O%A=S+%F=AS%O=M5B4NW6ST11)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=40%CD=S)
+U1(R=Y%DF=N%T=40%IPL=38%UN=0)
+
+Fingerprint OpenBSD 6.0 - 7.x
+Class OpenBSD | OpenBSD | 6.X | general purpose
+Class OpenBSD | OpenBSD | 7.X | general purpose
+T1(R=Y%DF=Y%T=40%W=4000%S=O%A=S+%F=AS%O=M5B4NNSNW6NNT11|M5B4NNSNW3NNT11)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=FF%CD=S)
+U1(R=Y%DF=N%T=FF%IPL=38%UN=0)
+
+# Solaris
+Fingerprint Oracle Solaris 11
+Class Oracle | Solaris | 11 | general purpose
+T1(R=Y%DF=Y%T=40%W=FA4C|FFF7%S=O%A=S+%F=AS%O=NNT11M5B4NW1NNS|ST11M5B4NW1)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=FF%CD=S)
+U1(R=Y%DF=Y%T=FF%IPL=70%UN=0)
+
+# Network devices
+Fingerprint Cisco IOS 12.X - 15.X
+Class Cisco | Cisco IOS | 12.X | router
+Class Cisco | Cisco IOS | 15.X | router
+T1(R=Y%DF=N%T=FF%W=1020|1000|FE4%S=O%A=S+%F=AS%O=M5B4|M218)
+T2(R=Y%DF=N%T=FF%W=0%S=Z%A=S%F=AR%O=)
+T3(R=Y%DF=N%T=FF%W=0%S=Z%A=S+%F=AR%O=)
+T4(R=Y%DF=N%T=FF%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=N%T=FF%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=N%T=FF%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=N%T=FF%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=FF%CD=S)
+U1(R=Y%DF=N%T=FF%IPL=38%UN=0)
+
+Fingerprint Juniper Junos 12.X - 21.X
+Class Juniper | Junos | 12.X | router
+Class Juniper | Junos | 21.X | router
+T1(R=Y%DF=Y%T=40%W=4000%S=O%A=S+%F=AS%O=M5B4NW0NNT11SLL|M5B4NNSNW0NNT11)
+T2(R=N)
+T3(R=N)
+T4(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T5(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+T6(R=Y%DF=Y%T=40%W=0%S=A%A=Z%F=R%O=)
+T7(R=Y%DF=Y%T=40%W=0%S=Z%A=S+%F=AR%O=)
+IE(R=Y%T=40%CD=S)
+U1(R=Y%DF=N%T=40%IPL=38%UN=0)
+"#;
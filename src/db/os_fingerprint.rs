@@ -0,0 +1,149 @@
+use crate::os::IpIdBehavior;
+
+/// Passive OS signature of SYN/ACK response.
+///
+/// Empty `window_sizes` and `None` fields match any value.
+/// `option_layout` is the TCP option order: M=MSS, N=NOP, W=Window scale, S=SACK permitted, T=Timestamps, E=EOL
+pub(crate) struct OsSignature {
+    pub family: &'static str,
+    pub name: &'static str,
+    pub initial_ttl: u8,
+    pub window_sizes: &'static [u16],
+    pub window_scale: Option<u8>,
+    pub option_layout: &'static str,
+    pub dont_fragment: Option<bool>,
+    pub ip_id: Option<IpIdBehavior>,
+}
+
+pub(crate) static OS_SIGNATURES: &[OsSignature] = &[
+    OsSignature {
+        family: "Linux",
+        name: "Linux 3.x - 6.x",
+        initial_ttl: 64,
+        window_sizes: &[65160, 64240, 65535, 28960, 43440],
+        window_scale: Some(7),
+        option_layout: "MSTNW",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Zero),
+    },
+    OsSignature {
+        family: "Linux",
+        name: "Linux 2.6.x",
+        initial_ttl: 64,
+        window_sizes: &[5792, 5840, 14480, 14600],
+        window_scale: None,
+        option_layout: "MSTNW",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Zero),
+    },
+    OsSignature {
+        family: "Linux",
+        name: "Linux (no timestamps)",
+        initial_ttl: 64,
+        window_sizes: &[],
+        window_scale: None,
+        option_layout: "MNNSNW",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Zero),
+    },
+    OsSignature {
+        family: "Linux",
+        name: "Embedded Linux",
+        initial_ttl: 64,
+        window_sizes: &[5840, 14600, 29200],
+        window_scale: None,
+        option_layout: "MNNS",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Zero),
+    },
+    OsSignature {
+        family: "Windows",
+        name: "Windows 10 / 11 / Server 2016+",
+        initial_ttl: 128,
+        window_sizes: &[65535, 64240],
+        window_scale: Some(8),
+        option_layout: "MNWNNS",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Incremental),
+    },
+    OsSignature {
+        family: "Windows",
+        name: "Windows 7 / 8 / Server 2008-2012",
+        initial_ttl: 128,
+        window_sizes: &[8192],
+        window_scale: Some(8),
+        option_layout: "MNWNNS",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Incremental),
+    },
+    OsSignature {
+        family: "Windows",
+        name: "Windows XP / Server 2003",
+        initial_ttl: 128,
+        window_sizes: &[65535, 64240, 16384],
+        window_scale: None,
+        option_layout: "MNNS",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Incremental),
+    },
+    OsSignature {
+        family: "macOS",
+        name: "macOS / iOS",
+        initial_ttl: 64,
+        window_sizes: &[65535],
+        window_scale: Some(6),
+        option_layout: "MNWNNTSE",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Random),
+    },
+    OsSignature {
+        family: "FreeBSD",
+        name: "FreeBSD",
+        initial_ttl: 64,
+        window_sizes: &[65535],
+        window_scale: Some(6),
+        option_layout: "MNWST",
+        dont_fragment: Some(true),
+        ip_id: None,
+    },
+    OsSignature {
+        family: "OpenBSD",
+        name: "OpenBSD",
+        initial_ttl: 64,
+        window_sizes: &[16384],
+        window_scale: Some(3),
+        option_layout: "MNNSNWNNT",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Random),
+    },
+    OsSignature {
+        family: "Solaris",
+        name: "Solaris 10 / 11",
+        initial_ttl: 64,
+        window_sizes: &[49232, 64240, 32850],
+        window_scale: None,
+        option_layout: "NNTMNWNNS",
+        dont_fragment: Some(true),
+        ip_id: Some(IpIdBehavior::Incremental),
+    },
+    OsSignature {
+        family: "Cisco IOS",
+        name: "Cisco IOS",
+        initial_ttl: 255,
+        window_sizes: &[4128, 16384],
+        window_scale: None,
+        option_layout: "M",
+        dont_fragment: Some(false),
+        ip_id: Some(IpIdBehavior::Incremental),
+    },
+    OsSignature {
+        family: "Network Device",
+        name: "Generic network device (TTL 255)",
+        initial_ttl: 255,
+        window_sizes: &[],
+        window_scale: None,
+        option_layout: "",
+        dont_fragment: None,
+        ip_id: None,
+    },
+];
@@ -1,9 +1,18 @@
 use std::net::IpAddr;
+use std::time::Duration;
 use netdev::mac::MacAddr;
+use crate::db::oui::OUI_VENDOR_MAP;
 use crate::dns;
+use crate::os::uptime::UptimeEstimate;
+use crate::scan::smb::{NetbiosInfo, SmbInfo};
+use crate::scan::passive::PassiveInfo;
+use crate::scan::snmp::SnmpInfo;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Status of the scanned port
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PortStatus {
     Open,
     Closed,
@@ -32,6 +41,7 @@ impl PortStatus {
 
 /// Port Information
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Port {
     /// Port number
     pub number: u16,
@@ -41,6 +51,8 @@ pub struct Port {
     pub service_name: String,
     /// Service version
     pub service_version: String,
+    /// Round-trip time of the probe
+    pub rtt: Option<Duration>,
 }
 
 impl Port {
@@ -50,12 +62,54 @@ impl Port {
             status: PortStatus::Unknown,
             service_name: String::new(),
             service_version: String::new(),
+            rtt: None,
         }
     }
 }
 
+/// Round-trip time statistics of the responses from the host
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RttStats {
+    pub min: Duration,
+    pub avg: Duration,
+    pub max: Duration,
+    /// Number of the measured responses
+    pub count: usize,
+}
+
+impl RttStats {
+    /// Statistics of the RTT samples. None if empty
+    pub fn from_samples(samples: &[Duration]) -> Option<RttStats> {
+        let min: Duration = *samples.iter().min()?;
+        let max: Duration = *samples.iter().max()?;
+        let total: Duration = samples.iter().sum();
+        Some(RttStats {
+            min,
+            avg: total / samples.len() as u32,
+            max,
+            count: samples.len(),
+        })
+    }
+}
+
+/// Protocol-specific details gathered from the host
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HostDetails {
+    /// NetBIOS node status
+    pub netbios: Option<NetbiosInfo>,
+    /// SMB dialects, signing and server names
+    pub smb: Option<SmbInfo>,
+    /// SNMP system group, interfaces and ARP table
+    pub snmp: Option<SnmpInfo>,
+    /// Protocols and description seen by passive discovery
+    pub passive: Option<PassiveInfo>,
+}
+
 /// Host Information
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Host {
     /// IP address of the host
     pub ip_addr: IpAddr,
@@ -67,6 +121,16 @@ pub struct Host {
     pub mac_addr: MacAddr,
     /// TTL
     pub ttl: u8,
+    /// OS family guessed from TCP/IP fingerprint
+    pub os_family: String,
+    /// Confidence of the OS guess (0-100)
+    pub os_confidence: u8,
+    /// Uptime estimated from TCP timestamps
+    pub uptime: Option<UptimeEstimate>,
+    /// Round-trip time statistics of the responses
+    pub rtt: Option<RttStats>,
+    /// Protocol-specific details
+    pub details: HostDetails,
 }
 
 impl Host {
@@ -77,6 +141,11 @@ impl Host {
             ports: Vec::new(),
             mac_addr: MacAddr::zero(),
             ttl: 0,
+            os_family: String::new(),
+            os_confidence: 0,
+            uptime: None,
+            rtt: None,
+            details: HostDetails::default(),
         }
     }
     pub fn with_port_range(mut self, start: u16, end: u16) -> Self {
@@ -101,6 +170,10 @@ impl Host {
             .map(|port| port.number)
             .collect()
     }
+    /// Get vendor name of the MAC address from the OUI database
+    pub fn vendor(&self) -> Option<&'static str> {
+        lookup_vendor(&self.mac_addr)
+    }
     pub fn get_open_ports(&self) -> Vec<Port> {
         self.ports
             .iter()
@@ -110,8 +183,21 @@ impl Host {
     }
 }
 
+/// Get vendor name of the MAC address from the OUI database.
+///
+/// None for a locally administered address (e.g. randomized MAC), since its prefix is not assigned by the vendor.
+pub fn lookup_vendor(mac_addr: &MacAddr) -> Option<&'static str> {
+    let octets = mac_addr.octets();
+    if octets[0] & 0x02 != 0 {
+        return None;
+    }
+    let oui: u32 = u32::from_be_bytes([0, octets[0], octets[1], octets[2]]);
+    OUI_VENDOR_MAP.get(&oui).copied()
+}
+
 /// Node type
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum NodeType {
     DefaultGateway,
     Relay,
@@ -152,3 +238,54 @@ pub fn is_valid_hostname(target: &str) -> bool {
 pub fn is_valid_target(target: &str) -> bool {
     is_valid_ip_addr(target) || is_valid_hostname(target)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_vendor_by_oui() {
+        assert_eq!(lookup_vendor(&MacAddr::new(0x00, 0x00, 0x0c, 0x12, 0x34, 0x56)), Some("Cisco Systems, Inc"));
+        assert_eq!(lookup_vendor(&MacAddr::new(0xf4, 0xf5, 0xd8, 0x01, 0x02, 0x03)), Some("Google, Inc."));
+        // Not assigned
+        assert_eq!(lookup_vendor(&MacAddr::new(0xfc, 0xff, 0xff, 0x01, 0x02, 0x03)), None);
+        // Locally administered. 02:60:8C is a legacy 3COM assignment
+        assert_eq!(lookup_vendor(&MacAddr::new(0x02, 0x60, 0x8c, 0x01, 0x02, 0x03)), None);
+        assert_eq!(lookup_vendor(&MacAddr::new(0x02, 0x42, 0xac, 0x11, 0x00, 0x02)), None);
+        let mut host = Host::new(IpAddr::V4(std::net::Ipv4Addr::new(192, 168, 1, 1)), String::new());
+        host.mac_addr = MacAddr::new(0x00, 0x00, 0x0c, 0x12, 0x34, 0x56);
+        assert_eq!(host.vendor(), Some("Cisco Systems, Inc"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn host_round_trip() {
+        use std::net::Ipv4Addr;
+        use crate::scan::smb::{NetbiosName, SmbDialect};
+        let mut host = Host::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)), String::from("fileserver"))
+            .with_ports(vec![139, 445]);
+        host.mac_addr = MacAddr::new(0x00, 0x15, 0x5d, 0x01, 0x02, 0x03);
+        host.ttl = 128;
+        host.os_family = String::from("Windows");
+        host.ports[1].status = PortStatus::Open;
+        host.details.netbios = Some(NetbiosInfo {
+            hostname: Some(String::from("FILESERVER")),
+            workgroup: Some(String::from("WORKGROUP")),
+            names: vec![NetbiosName {
+                name: String::from("FILESERVER"),
+                suffix: 0x20,
+                group: false,
+            }],
+            mac_addr: host.mac_addr,
+        });
+        host.details.smb = Some(SmbInfo {
+            dialects: vec![SmbDialect::Smb21, SmbDialect::Smb311],
+            signing_enabled: true,
+            netbios_computer_name: Some(String::from("FILESERVER")),
+            ..Default::default()
+        });
+        let json = serde_json::to_string(&host).unwrap();
+        let decoded: Host = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, host);
+    }
+}
@@ -8,3 +8,7 @@ pub mod protocol;
 pub mod scan;
 pub(crate) mod ip;
 pub mod dns;
+pub mod os;
+pub mod report;
+#[cfg(feature = "sqlite")]
+pub mod store;
@@ -0,0 +1,371 @@
+pub mod probe;
+pub mod uptime;
+
+use std::net::IpAddr;
+use nex::packet::ipv4::Ipv4Flags;
+use nex::packet::tcp::{TcpFlags, TcpOptionKind};
+use crate::db::os_fingerprint::{OsSignature, OS_SIGNATURES};
+use crate::packet::frame::PacketFrame;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Minimum confidence to report the OS guess
+const MIN_CONFIDENCE: u8 = 30;
+
+/// IP ID generation behavior of the host
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IpIdBehavior {
+    /// Always zero
+    Zero,
+    /// Incremented by small steps
+    Incremental,
+    /// Random
+    Random,
+    /// Incremented separately for each destination host
+    PerHost,
+    /// Not enough samples
+    Unknown,
+}
+
+impl IpIdBehavior {
+    /// Classify IP ID behavior from the IDs in received order
+    pub fn classify(ids: &[u16]) -> IpIdBehavior {
+        if ids.is_empty() {
+            return IpIdBehavior::Unknown;
+        }
+        if ids.iter().all(|&id| id == 0) {
+            return IpIdBehavior::Zero;
+        }
+        if ids.len() < 2 {
+            return IpIdBehavior::Unknown;
+        }
+        let incremental = ids
+            .windows(2)
+            .all(|w| w[1].wrapping_sub(w[0]) < 1000);
+        if incremental {
+            IpIdBehavior::Incremental
+        } else {
+            IpIdBehavior::Random
+        }
+    }
+    /// Classify IP ID behavior from the IDs of repeated probes and the ID after a packet sent to another host.
+    ///
+    /// `ids` are the IDs seen by us in received order. `id_after_other` is the ID seen by us right after
+    /// the host replied to another host. A counter shared between destinations is incremented twice in between.
+    pub fn classify_with_other(ids: &[u16], id_after_other: Option<u16>) -> IpIdBehavior {
+        let behavior = IpIdBehavior::classify(ids);
+        if behavior != IpIdBehavior::Incremental {
+            return behavior;
+        }
+        match (ids.last(), id_after_other) {
+            (Some(last), Some(id)) if id.wrapping_sub(*last) == 1 => IpIdBehavior::PerHost,
+            _ => behavior,
+        }
+    }
+}
+
+/// TCP/IP fingerprint derived from SYN/ACK response
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TcpFingerprint {
+    /// Guessed initial TTL (or hop limit)
+    pub initial_ttl: u8,
+    /// Observed TTL (or hop limit)
+    pub ttl: u8,
+    /// TCP window size
+    pub window_size: u16,
+    /// Maximum segment size
+    pub mss: Option<u16>,
+    /// Window scale
+    pub window_scale: Option<u8>,
+    /// TCP option order: M=MSS, N=NOP, W=Window scale, S=SACK permitted, T=Timestamps, E=EOL
+    pub option_layout: String,
+    /// DF (Don't Fragment) bit. None for IPv6
+    pub dont_fragment: Option<bool>,
+    /// IP ID behavior. Unknown for IPv6
+    pub ip_id: IpIdBehavior,
+}
+
+impl TcpFingerprint {
+    /// Create fingerprint from SYN/ACK frame
+    pub fn from_frame(frame: &PacketFrame) -> Option<TcpFingerprint> {
+        let tcp_header = frame.tcp_header.as_ref()?;
+        if tcp_header.flags != TcpFlags::SYN | TcpFlags::ACK {
+            return None;
+        }
+        let (ttl, dont_fragment, ip_id) = if let Some(ipv4_header) = &frame.ipv4_header {
+            (
+                ipv4_header.ttl,
+                Some(ipv4_header.flags & Ipv4Flags::DontFragment != 0),
+                IpIdBehavior::classify(&[ipv4_header.identification]),
+            )
+        } else if let Some(ipv6_header) = &frame.ipv6_header {
+            (ipv6_header.hop_limit, None, IpIdBehavior::Unknown)
+        } else {
+            return None;
+        };
+        let mut mss: Option<u16> = None;
+        let mut window_scale: Option<u8> = None;
+        let mut option_layout = String::new();
+        for option in &tcp_header.options {
+            let c = match option.kind {
+                TcpOptionKind::MSS => {
+                    mss = Some(option.get_mss());
+                    'M'
+                }
+                TcpOptionKind::NOP => 'N',
+                TcpOptionKind::WSCALE => {
+                    window_scale = Some(option.get_wscale());
+                    'W'
+                }
+                TcpOptionKind::SACK_PERMITTED => 'S',
+                TcpOptionKind::TIMESTAMPS => 'T',
+                TcpOptionKind::EOL => 'E',
+                _ => '?',
+            };
+            option_layout.push(c);
+        }
+        Some(TcpFingerprint {
+            initial_ttl: guess_initial_ttl(ttl),
+            ttl,
+            window_size: tcp_header.window,
+            mss,
+            window_scale,
+            option_layout,
+            dont_fragment,
+            ip_id,
+        })
+    }
+}
+
+/// OS guess with confidence
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OsMatch {
+    /// OS family (e.g. Linux, Windows)
+    pub family: String,
+    /// OS name of the matched signature
+    pub name: String,
+    /// Confidence (0-100)
+    pub confidence: u8,
+}
+
+/// Guess initial TTL from the observed TTL
+pub fn guess_initial_ttl(ttl: u8) -> u8 {
+    match ttl {
+        0..=32 => 32,
+        33..=64 => 64,
+        65..=128 => 128,
+        _ => 255,
+    }
+}
+
+/// Get source IP address of the frame
+pub(crate) fn frame_source_ip(frame: &PacketFrame) -> Option<IpAddr> {
+    if let Some(ipv4_header) = &frame.ipv4_header {
+        Some(IpAddr::V4(ipv4_header.source))
+    } else {
+        frame.ipv6_header.as_ref().map(|ipv6_header| IpAddr::V6(ipv6_header.source))
+    }
+}
+
+/// Create fingerprint of the host from captured frames.
+///
+/// The first SYN/ACK frame is used for TCP features. IP ID behavior is classified from all SYN/ACK frames of the host,
+/// since some hosts use a different IP ID scheme for RSTs from closed ports.
+pub fn fingerprint_host(ip_addr: IpAddr, frames: &[PacketFrame]) -> Option<TcpFingerprint> {
+    let syn_ack_frames: Vec<&PacketFrame> = frames
+        .iter()
+        .filter(|frame| frame_source_ip(frame) == Some(ip_addr))
+        .filter(|frame| matches!(&frame.tcp_header, Some(tcp_header) if tcp_header.flags == TcpFlags::SYN | TcpFlags::ACK))
+        .collect();
+    let mut fingerprint: TcpFingerprint = syn_ack_frames.iter().find_map(|frame| TcpFingerprint::from_frame(frame))?;
+    if ip_addr.is_ipv4() {
+        let ids: Vec<u16> = syn_ack_frames
+            .iter()
+            .filter_map(|frame| frame.ipv4_header.as_ref().map(|ipv4_header| ipv4_header.identification))
+            .collect();
+        fingerprint.ip_id = IpIdBehavior::classify(&ids);
+    }
+    Some(fingerprint)
+}
+
+/// Score the fingerprint against the signature. Returns (score, max score)
+fn score_signature(fingerprint: &TcpFingerprint, signature: &OsSignature) -> (u32, u32) {
+    let mut score: u32 = 0;
+    let mut max: u32 = 0;
+    // Initial TTL
+    max += 25;
+    if fingerprint.initial_ttl == signature.initial_ttl {
+        score += 25;
+    } else {
+        // TTL mismatch rules out the signature
+        return (0, max);
+    }
+    // TCP option layout
+    if !signature.option_layout.is_empty() {
+        max += 35;
+        if fingerprint.option_layout == signature.option_layout {
+            score += 35;
+        }
+    }
+    // Window size
+    if !signature.window_sizes.is_empty() {
+        max += 20;
+        let window_size = fingerprint.window_size;
+        let mss_multiple = match fingerprint.mss {
+            Some(mss) if mss > 0 => window_size.is_multiple_of(mss),
+            _ => false,
+        };
+        if signature.window_sizes.contains(&window_size) {
+            score += 20;
+        } else if mss_multiple {
+            score += 10;
+        }
+    }
+    // Window scale
+    if let Some(window_scale) = signature.window_scale {
+        max += 10;
+        if fingerprint.window_scale == Some(window_scale) {
+            score += 10;
+        }
+    }
+    // DF bit (IPv4 only)
+    if let (Some(expected), Some(dont_fragment)) = (signature.dont_fragment, fingerprint.dont_fragment) {
+        max += 5;
+        if dont_fragment == expected {
+            score += 5;
+        }
+    }
+    // IP ID behavior
+    if let Some(ip_id) = signature.ip_id {
+        if fingerprint.ip_id != IpIdBehavior::Unknown {
+            max += 5;
+            if fingerprint.ip_id == ip_id {
+                score += 5;
+            }
+        }
+    }
+    (score, max)
+}
+
+/// Match the fingerprint against the bundled signature database.
+///
+/// Returns the best match, or None if no signature reaches the minimum confidence.
+pub fn match_fingerprint(fingerprint: &TcpFingerprint) -> Option<OsMatch> {
+    let mut best: Option<OsMatch> = None;
+    for signature in OS_SIGNATURES {
+        let (score, max) = score_signature(fingerprint, signature);
+        if max == 0 || score == 0 {
+            continue;
+        }
+        // Signatures with fewer features are less specific
+        let specificity: u32 = std::cmp::min(max, 75);
+        let confidence: u8 = (score * specificity * 100 / (max * 75)) as u8;
+        if confidence < MIN_CONFIDENCE {
+            continue;
+        }
+        let better = match &best {
+            Some(best) => confidence > best.confidence,
+            None => true,
+        };
+        if better {
+            best = Some(OsMatch {
+                family: signature.family.to_string(),
+                name: signature.name.to_string(),
+                confidence,
+            });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use nex::packet::ipv4::Ipv4Header;
+    use nex::packet::tcp::TcpHeader;
+
+    /// TCP options of Linux SYN/ACK: MSS 1460, SACK permitted, timestamps, NOP, window scale 7
+    const LINUX_OPTIONS: [u8; 20] = [2, 4, 5, 180, 4, 2, 8, 10, 0, 0, 0, 1, 0, 0, 0, 0, 1, 3, 3, 7];
+    /// TCP options of Windows SYN/ACK: MSS 1460, NOP, window scale 8, NOP, NOP, SACK permitted
+    const WINDOWS_OPTIONS: [u8; 12] = [2, 4, 5, 180, 1, 3, 3, 8, 1, 1, 4, 2];
+
+    fn tcp_frame(source: Ipv4Addr, ttl: u8, id: u16, flags: u8, window: u16, options: &[u8]) -> PacketFrame {
+        let mut ipv4: Vec<u8> = vec![0x45, 0, 0, 0];
+        ipv4.extend_from_slice(&id.to_be_bytes());
+        ipv4.extend_from_slice(&[0x40, 0, ttl, 6, 0, 0]);
+        ipv4.extend_from_slice(&source.octets());
+        ipv4.extend_from_slice(&[192, 168, 1, 2]);
+        let mut tcp: Vec<u8> = vec![0, 22, 0xad, 0x0c, 0, 0, 0, 1, 0, 0, 0, 2];
+        tcp.push((((20 + options.len()) / 4) as u8) << 4);
+        tcp.push(flags);
+        tcp.extend_from_slice(&window.to_be_bytes());
+        tcp.extend_from_slice(&[0, 0, 0, 0]);
+        tcp.extend_from_slice(options);
+        let mut frame = PacketFrame::new();
+        frame.ipv4_header = Some(Ipv4Header::from_bytes(&ipv4).unwrap());
+        frame.tcp_header = Some(TcpHeader::from_bytes(&tcp).unwrap());
+        frame
+    }
+
+    #[test]
+    fn fingerprint_linux_syn_ack() {
+        let ip_addr = Ipv4Addr::new(192, 168, 1, 10);
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+        let rst = TcpFlags::RST | TcpFlags::ACK;
+        let frames: Vec<PacketFrame> = vec![
+            tcp_frame(ip_addr, 63, 0, syn_ack, 65160, &LINUX_OPTIONS),
+            tcp_frame(ip_addr, 63, 3021, rst, 0, &[]),
+            tcp_frame(ip_addr, 63, 0, syn_ack, 65160, &LINUX_OPTIONS),
+            tcp_frame(ip_addr, 63, 3022, rst, 0, &[]),
+        ];
+        let fingerprint = fingerprint_host(IpAddr::V4(ip_addr), &frames).unwrap();
+        assert_eq!(fingerprint.initial_ttl, 64);
+        assert_eq!(fingerprint.mss, Some(1460));
+        assert_eq!(fingerprint.window_scale, Some(7));
+        assert_eq!(fingerprint.option_layout, "MSTNW");
+        assert_eq!(fingerprint.dont_fragment, Some(true));
+        // RSTs from closed ports are not used for IP ID behavior
+        assert_eq!(fingerprint.ip_id, IpIdBehavior::Zero);
+        let signature = OS_SIGNATURES.iter().find(|s| s.name == "Linux 3.x - 6.x").unwrap();
+        assert_eq!(score_signature(&fingerprint, signature), (100, 100));
+        let os_match = match_fingerprint(&fingerprint).unwrap();
+        assert_eq!(os_match.family, "Linux");
+        assert_eq!(os_match.name, "Linux 3.x - 6.x");
+        assert_eq!(os_match.confidence, 100);
+    }
+
+    #[test]
+    fn fingerprint_windows_syn_ack() {
+        let ip_addr = Ipv4Addr::new(192, 168, 1, 20);
+        let syn_ack = TcpFlags::SYN | TcpFlags::ACK;
+        let frames: Vec<PacketFrame> = vec![
+            tcp_frame(ip_addr, 127, 17001, syn_ack, 64240, &WINDOWS_OPTIONS),
+            tcp_frame(ip_addr, 127, 17002, syn_ack, 64240, &WINDOWS_OPTIONS),
+            tcp_frame(ip_addr, 127, 17004, syn_ack, 64240, &WINDOWS_OPTIONS),
+        ];
+        let fingerprint = fingerprint_host(IpAddr::V4(ip_addr), &frames).unwrap();
+        assert_eq!(fingerprint.initial_ttl, 128);
+        assert_eq!(fingerprint.option_layout, "MNWNNS");
+        assert_eq!(fingerprint.ip_id, IpIdBehavior::Incremental);
+        let os_match = match_fingerprint(&fingerprint).unwrap();
+        assert_eq!(os_match.family, "Windows");
+        assert_eq!(os_match.name, "Windows 10 / 11 / Server 2016+");
+        // TTL rules out the Linux signature
+        let linux = OS_SIGNATURES.iter().find(|s| s.name == "Linux 3.x - 6.x").unwrap();
+        assert_eq!(score_signature(&fingerprint, linux), (0, 25));
+    }
+
+    #[test]
+    fn fingerprint_requires_syn_ack() {
+        let ip_addr = Ipv4Addr::new(192, 168, 1, 30);
+        let frames: Vec<PacketFrame> = vec![tcp_frame(ip_addr, 64, 0, TcpFlags::RST | TcpFlags::ACK, 0, &[])];
+        assert_eq!(fingerprint_host(IpAddr::V4(ip_addr), &frames), None);
+        // Frames of other hosts are ignored
+        let frames: Vec<PacketFrame> = vec![tcp_frame(ip_addr, 64, 0, TcpFlags::SYN | TcpFlags::ACK, 65160, &LINUX_OPTIONS)];
+        assert_eq!(fingerprint_host(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 31)), &frames), None);
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use nex::packet::icmp::IcmpType;
+use nex::packet::ipv4::Ipv4Flags;
+use nex::packet::tcp::{TcpFlags, TcpOption, TcpOptionKind};
+use crate::db::os_db::OS_DB;
+use crate::packet::frame::PacketFrame;
+use super::{frame_source_ip, guess_initial_ttl, OsMatch};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Base source port of TCP and UDP probes. Each probe uses its own source port.
+pub(crate) const PROBE_BASE_PORT: u16 = 44440;
+/// Sequence number of TCP probes
+pub(crate) const PROBE_SEQ: u32 = 0x3c5a_19e7;
+/// Acknowledgement number of TCP probes
+pub(crate) const PROBE_ACK: u32 = 0x7e31_0b42;
+/// ICMP echo identifier of IE probe
+pub(crate) const PROBE_ICMP_ID: u16 = 0x2f1d;
+/// ICMP echo sequence number of IE probe
+pub(crate) const PROBE_ICMP_SEQ: u16 = 295;
+/// ICMP code of IE probe. Non-zero code for echo request is unusual.
+pub(crate) const PROBE_ICMP_CODE: u8 = 9;
+/// Minimum confidence to report the active OS match
+const MIN_CONFIDENCE: u8 = 80;
+
+/// Probes of active OS detection suite
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OsProbe {
+    /// SYN to open port with unusual options
+    T1,
+    /// No flags to open port
+    T2,
+    /// SYN, FIN, URG and PSH to open port
+    T3,
+    /// ACK to open port
+    T4,
+    /// SYN to closed port
+    T5,
+    /// ACK to closed port
+    T6,
+    /// FIN, PSH and URG to closed port
+    T7,
+    /// ICMP echo request with non-zero code
+    IE,
+    /// UDP to closed port
+    U1,
+}
+
+impl OsProbe {
+    /// All probes in sending order
+    pub fn all() -> Vec<OsProbe> {
+        vec![
+            OsProbe::T1,
+            OsProbe::T2,
+            OsProbe::T3,
+            OsProbe::T4,
+            OsProbe::T5,
+            OsProbe::T6,
+            OsProbe::T7,
+            OsProbe::IE,
+            OsProbe::U1,
+        ]
+    }
+    /// Test name in nmap-os-db format
+    pub fn name(&self) -> &'static str {
+        match *self {
+            OsProbe::T1 => "T1",
+            OsProbe::T2 => "T2",
+            OsProbe::T3 => "T3",
+            OsProbe::T4 => "T4",
+            OsProbe::T5 => "T5",
+            OsProbe::T6 => "T6",
+            OsProbe::T7 => "T7",
+            OsProbe::IE => "IE",
+            OsProbe::U1 => "U1",
+        }
+    }
+    /// Source port of the probe
+    pub(crate) fn src_port(&self) -> u16 {
+        let index = OsProbe::all().iter().position(|p| p == self).unwrap_or(0);
+        PROBE_BASE_PORT + index as u16
+    }
+    /// Check if the probe is sent to the open port
+    pub(crate) fn targets_open_port(&self) -> bool {
+        matches!(*self, OsProbe::T1 | OsProbe::T2 | OsProbe::T3 | OsProbe::T4)
+    }
+    /// Check if the probe is a TCP probe
+    pub(crate) fn is_tcp(&self) -> bool {
+        !matches!(*self, OsProbe::IE | OsProbe::U1)
+    }
+    /// TCP flags of the probe
+    pub(crate) fn tcp_flags(&self) -> u8 {
+        match *self {
+            OsProbe::T1 | OsProbe::T5 => TcpFlags::SYN,
+            OsProbe::T2 => 0,
+            OsProbe::T3 => TcpFlags::SYN | TcpFlags::FIN | TcpFlags::URG | TcpFlags::PSH,
+            OsProbe::T4 | OsProbe::T6 => TcpFlags::ACK,
+            OsProbe::T7 => TcpFlags::FIN | TcpFlags::PSH | TcpFlags::URG,
+            OsProbe::IE | OsProbe::U1 => 0,
+        }
+    }
+    /// TCP window size of the probe
+    pub(crate) fn tcp_window(&self) -> u16 {
+        match *self {
+            OsProbe::T1 => 1,
+            OsProbe::T2 => 128,
+            OsProbe::T3 => 256,
+            OsProbe::T4 => 1024,
+            OsProbe::T5 => 31337,
+            OsProbe::T6 => 32768,
+            OsProbe::T7 => 65535,
+            OsProbe::IE | OsProbe::U1 => 0,
+        }
+    }
+    /// TCP options of the probe
+    pub(crate) fn tcp_options(&self) -> Vec<TcpOption> {
+        let (wscale, mss) = match *self {
+            OsProbe::T1 => (10, 1460),
+            OsProbe::T7 => (15, 265),
+            _ => (10, 265),
+        };
+        vec![
+            TcpOption::wscale(wscale),
+            TcpOption::nop(),
+            TcpOption::mss(mss),
+            TcpOption::timestamp(u32::MAX, 0),
+            TcpOption::sack_perm(),
+        ]
+    }
+    /// DF bit of the probe
+    pub(crate) fn dont_fragment(&self) -> bool {
+        matches!(*self, OsProbe::T2 | OsProbe::T4 | OsProbe::T6 | OsProbe::IE)
+    }
+    /// Payload of the probe
+    pub(crate) fn payload(&self) -> Vec<u8> {
+        match *self {
+            OsProbe::IE => vec![0x00; 120],
+            OsProbe::U1 => vec![0x43; 300],
+            _ => vec![],
+        }
+    }
+}
+
+/// Result of a probe. Attributes are in nmap-os-db format
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OsTestResult {
+    pub probe: OsProbe,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl OsTestResult {
+    fn new(probe: OsProbe) -> OsTestResult {
+        OsTestResult {
+            probe,
+            attributes: vec![],
+        }
+    }
+    fn add(&mut self, name: &str, value: String) {
+        self.attributes.push((name.to_string(), value));
+    }
+    /// Get attribute value
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl fmt::Display for OsTestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let attributes: Vec<String> = self
+            .attributes
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        write!(f, "{}({})", self.probe.name(), attributes.join("%"))
+    }
+}
+
+/// Response vector of the active OS detection suite
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OsProbeFingerprint {
+    pub tests: Vec<OsTestResult>,
+}
+
+impl OsProbeFingerprint {
+    /// Get test result of the probe
+    pub fn get(&self, probe: OsProbe) -> Option<&OsTestResult> {
+        self.tests.iter().find(|t| t.probe == probe)
+    }
+}
+
+impl fmt::Display for OsProbeFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tests: Vec<String> = self.tests.iter().map(|t| t.to_string()).collect();
+        write!(f, "{}", tests.join("\n"))
+    }
+}
+
+/// Format TCP flags in nmap order (ECE, URG, ACK, PSH, RST, SYN, FIN)
+fn format_tcp_flags(flags: u8) -> String {
+    let mut s = String::new();
+    for (flag, c) in [
+        (TcpFlags::ECE, 'E'),
+        (TcpFlags::URG, 'U'),
+        (TcpFlags::ACK, 'A'),
+        (TcpFlags::PSH, 'P'),
+        (TcpFlags::RST, 'R'),
+        (TcpFlags::SYN, 'S'),
+        (TcpFlags::FIN, 'F'),
+    ] {
+        if flags & flag != 0 {
+            s.push(c);
+        }
+    }
+    s
+}
+
+/// Format TCP options in nmap format (e.g. M5B4ST11NW7)
+fn format_tcp_options(frame: &PacketFrame) -> String {
+    let tcp_header = match &frame.tcp_header {
+        Some(tcp_header) => tcp_header,
+        None => return String::new(),
+    };
+    let mut s = String::new();
+    for option in &tcp_header.options {
+        match option.kind {
+            TcpOptionKind::MSS => s.push_str(&format!("M{:X}", option.get_mss())),
+            TcpOptionKind::NOP => s.push('N'),
+            TcpOptionKind::WSCALE => s.push_str(&format!("W{:X}", option.get_wscale())),
+            TcpOptionKind::SACK_PERMITTED => s.push('S'),
+            TcpOptionKind::TIMESTAMPS => {
+                let (tsval, tsecr) = option.get_timestamp();
+                s.push('T');
+                s.push(if tsval == 0 { '0' } else { '1' });
+                s.push(if tsecr == 0 { '0' } else { '1' });
+            }
+            TcpOptionKind::EOL => s.push('L'),
+            _ => {}
+        }
+    }
+    s
+}
+
+/// Get TTL and DF bit of the frame
+fn ip_attributes(frame: &PacketFrame) -> Option<(u8, Option<bool>)> {
+    if let Some(ipv4_header) = &frame.ipv4_header {
+        Some((ipv4_header.ttl, Some(ipv4_header.flags & Ipv4Flags::DontFragment != 0)))
+    } else {
+        frame.ipv6_header.as_ref().map(|ipv6_header| (ipv6_header.hop_limit, None))
+    }
+}
+
+fn add_ip_attributes(result: &mut OsTestResult, frame: &PacketFrame, with_df: bool) {
+    if let Some((ttl, dont_fragment)) = ip_attributes(frame) {
+        if with_df {
+            if let Some(dont_fragment) = dont_fragment {
+                result.add("DF", if dont_fragment { "Y" } else { "N" }.to_string());
+            }
+        }
+        result.add("T", format!("{:X}", guess_initial_ttl(ttl)));
+    }
+}
+
+/// Build test result of the TCP probe
+fn tcp_test_result(probe: OsProbe, frame: Option<&PacketFrame>) -> OsTestResult {
+    let mut result = OsTestResult::new(probe);
+    let (frame, tcp_header) = match frame.and_then(|f| f.tcp_header.as_ref().map(|t| (f, t))) {
+        Some(v) => v,
+        None => {
+            result.add("R", "N".to_string());
+            return result;
+        }
+    };
+    result.add("R", "Y".to_string());
+    add_ip_attributes(&mut result, frame, true);
+    result.add("W", format!("{:X}", tcp_header.window));
+    let seq = match tcp_header.sequence {
+        0 => "Z",
+        s if s == PROBE_ACK => "A",
+        s if s == PROBE_ACK.wrapping_add(1) => "A+",
+        _ => "O",
+    };
+    result.add("S", seq.to_string());
+    let ack = match tcp_header.acknowledgement {
+        0 => "Z",
+        a if a == PROBE_SEQ => "S",
+        a if a == PROBE_SEQ.wrapping_add(1) => "S+",
+        _ => "O",
+    };
+    result.add("A", ack.to_string());
+    result.add("F", format_tcp_flags(tcp_header.flags));
+    result.add("O", format_tcp_options(frame));
+    result
+}
+
+/// Build test result of the ICMP echo probe
+fn ie_test_result(frame: Option<&PacketFrame>) -> OsTestResult {
+    let mut result = OsTestResult::new(OsProbe::IE);
+    let (frame, icmp_header) = match frame.and_then(|f| f.icmp_header.as_ref().map(|h| (f, h))) {
+        Some(v) => v,
+        None => {
+            result.add("R", "N".to_string());
+            return result;
+        }
+    };
+    result.add("R", "Y".to_string());
+    add_ip_attributes(&mut result, frame, false);
+    let cd = match icmp_header.icmp_code.0 {
+        0 => "Z",
+        PROBE_ICMP_CODE => "S",
+        _ => "O",
+    };
+    result.add("CD", cd.to_string());
+    result
+}
+
+/// Build test result of the UDP probe
+fn u1_test_result(frame: Option<&PacketFrame>) -> OsTestResult {
+    let mut result = OsTestResult::new(OsProbe::U1);
+    let frame = match frame {
+        Some(frame) => frame,
+        None => {
+            result.add("R", "N".to_string());
+            return result;
+        }
+    };
+    result.add("R", "Y".to_string());
+    add_ip_attributes(&mut result, frame, true);
+    if let Some(ipv4_header) = &frame.ipv4_header {
+        result.add("IPL", format!("{:X}", ipv4_header.total_length));
+    }
+    if frame.payload.len() >= 4 {
+        let unused = u32::from_be_bytes([frame.payload[0], frame.payload[1], frame.payload[2], frame.payload[3]]);
+        result.add("UN", format!("{:X}", unused));
+    }
+    result
+}
+
+/// Get source port of the UDP header quoted in ICMP destination unreachable payload
+fn quoted_udp_src_port(payload: &[u8]) -> Option<u16> {
+    // 4 bytes unused, then original IPv4 header
+    let ip_header = payload.get(4..)?;
+    let ihl = (*ip_header.first()? & 0x0f) as usize * 4;
+    let udp_header = ip_header.get(ihl..ihl + 2)?;
+    Some(u16::from_be_bytes([udp_header[0], udp_header[1]]))
+}
+
+/// Find the response to the probe from the captured frames
+fn find_response(probe: OsProbe, ip_addr: IpAddr, frames: &[PacketFrame], open_port: u16, closed_port: u16) -> Option<&PacketFrame> {
+    let target_port: u16 = if probe.targets_open_port() { open_port } else { closed_port };
+    frames.iter().find(|frame| {
+        if frame_source_ip(frame) != Some(ip_addr) {
+            return false;
+        }
+        match probe {
+            OsProbe::IE => match &frame.icmp_header {
+                Some(icmp_header) => {
+                    icmp_header.icmp_type == IcmpType::EchoReply
+                        && frame.payload.len() >= 4
+                        && u16::from_be_bytes([frame.payload[0], frame.payload[1]]) == PROBE_ICMP_ID
+                }
+                None => false,
+            },
+            OsProbe::U1 => match &frame.icmp_header {
+                Some(icmp_header) => {
+                    icmp_header.icmp_type == IcmpType::DestinationUnreachable
+                        && quoted_udp_src_port(&frame.payload) == Some(probe.src_port())
+                }
+                None => false,
+            },
+            _ => match &frame.tcp_header {
+                Some(tcp_header) => tcp_header.source == target_port && tcp_header.destination == probe.src_port(),
+                None => false,
+            },
+        }
+    })
+}
+
+/// Build response vector of the host from the captured frames
+pub fn parse_probe_responses(ip_addr: IpAddr, frames: &[PacketFrame], open_port: u16, closed_port: u16) -> OsProbeFingerprint {
+    let mut tests: Vec<OsTestResult> = vec![];
+    for probe in OsProbe::all() {
+        // ICMP and UDP probes are sent only for IPv4
+        if !probe.is_tcp() && ip_addr.is_ipv6() {
+            continue;
+        }
+        let frame = find_response(probe, ip_addr, frames, open_port, closed_port);
+        let result = match probe {
+            OsProbe::IE => ie_test_result(frame),
+            OsProbe::U1 => u1_test_result(frame),
+            _ => tcp_test_result(probe, frame),
+        };
+        tests.push(result);
+    }
+    OsProbeFingerprint { tests }
+}
+
+/// Entry of OS fingerprint database
+struct OsDbEntry {
+    name: String,
+    family: String,
+    tests: HashMap<String, Vec<(String, String)>>,
+}
+
+/// Parse nmap-os-db formatted database
+fn parse_os_db(db: &str) -> Vec<OsDbEntry> {
+    let mut entries: Vec<OsDbEntry> = vec![];
+    for line in db.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("Fingerprint ") {
+            entries.push(OsDbEntry {
+                name: name.trim().to_string(),
+                family: String::new(),
+                tests: HashMap::new(),
+            });
+            continue;
+        }
+        let entry = match entries.last_mut() {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if let Some(class) = line.strip_prefix("Class ") {
+            if entry.family.is_empty() {
+                entry.family = class.split('|').nth(1).unwrap_or("").trim().to_string();
+            }
+            continue;
+        }
+        let (test_name, rest) = match line.split_once('(') {
+            Some(v) => v,
+            None => continue,
+        };
+        let attributes: Vec<(String, String)> = rest
+            .trim_end_matches(')')
+            .split('%')
+            .filter_map(|attr| attr.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        entry.tests.insert(test_name.to_string(), attributes);
+    }
+    entries
+}
+
+fn os_db() -> &'static Vec<OsDbEntry> {
+    static DB: OnceLock<Vec<OsDbEntry>> = OnceLock::new();
+    DB.get_or_init(|| parse_os_db(OS_DB))
+}
+
+/// Weight of the attribute in matching
+fn attribute_weight(name: &str) -> u32 {
+    match name {
+        "R" | "F" => 30,
+        "W" => 25,
+        "DF" | "S" | "A" | "O" | "CD" | "IPL" => 20,
+        "T" => 15,
+        _ => 10,
+    }
+}
+
+/// Check if the observed value matches the expected value.
+///
+/// Expected value may have alternatives separated by `|`, hex ranges like `3B-45` and comparisons like `>10` or `<10`.
+fn value_matches(expected: &str, observed: &str) -> bool {
+    expected.split('|').any(|alt| {
+        if alt == observed {
+            return true;
+        }
+        if let Some(limit) = alt.strip_prefix('>') {
+            return matches!((u32::from_str_radix(limit, 16), u32::from_str_radix(observed, 16)), (Ok(limit), Ok(value)) if value > limit);
+        }
+        if let Some(limit) = alt.strip_prefix('<') {
+            return matches!((u32::from_str_radix(limit, 16), u32::from_str_radix(observed, 16)), (Ok(limit), Ok(value)) if value < limit);
+        }
+        if let Some((min, max)) = alt.split_once('-') {
+            if let (Ok(min), Ok(max), Ok(value)) = (
+                u32::from_str_radix(min, 16),
+                u32::from_str_radix(max, 16),
+                u32::from_str_radix(observed, 16),
+            ) {
+                return min <= value && value <= max;
+            }
+        }
+        false
+    })
+}
+
+/// Score the fingerprint against the entry. Returns (score, max score)
+fn score_entry(fingerprint: &OsProbeFingerprint, entry: &OsDbEntry) -> (u32, u32) {
+    let mut score: u32 = 0;
+    let mut max: u32 = 0;
+    for test in &fingerprint.tests {
+        let expected_attributes = match entry.tests.get(test.probe.name()) {
+            Some(attributes) => attributes,
+            None => continue,
+        };
+        for (name, expected) in expected_attributes {
+            let observed = match test.get(name) {
+                Some(observed) => observed,
+                None => continue,
+            };
+            let weight = attribute_weight(name);
+            max += weight;
+            if value_matches(expected, observed) {
+                score += weight;
+            }
+        }
+    }
+    (score, max)
+}
+
+/// Match the response vector against the OS fingerprint database.
+///
+/// Returns the best match, or None if no entry reaches the minimum confidence.
+pub fn match_probe_fingerprint(fingerprint: &OsProbeFingerprint) -> Option<OsMatch> {
+    // No response at all
+    if fingerprint.tests.iter().all(|t| t.get("R") == Some("N")) {
+        return None;
+    }
+    let mut best: Option<OsMatch> = None;
+    for entry in os_db() {
+        let (score, max) = score_entry(fingerprint, entry);
+        if max == 0 {
+            continue;
+        }
+        let confidence: u8 = (score * 100 / max) as u8;
+        if confidence < MIN_CONFIDENCE {
+            continue;
+        }
+        let better = match &best {
+            Some(best) => confidence > best.confidence,
+            None => true,
+        };
+        if better {
+            best = Some(OsMatch {
+                family: entry.family.clone(),
+                name: entry.name.clone(),
+                confidence,
+            });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build the response vector from the first alternative of each attribute of the entry
+    fn fingerprint_of(entry: &OsDbEntry) -> OsProbeFingerprint {
+        let tests: Vec<OsTestResult> = OsProbe::all()
+            .into_iter()
+            .filter_map(|probe| {
+                let attributes = entry.tests.get(probe.name())?;
+                let mut result = OsTestResult::new(probe);
+                for (name, value) in attributes {
+                    result.add(name, value.split('|').next().unwrap_or("").to_string());
+                }
+                Some(result)
+            })
+            .collect();
+        OsProbeFingerprint { tests }
+    }
+
+    #[test]
+    fn match_value_expressions() {
+        assert!(value_matches("S+", "S+"));
+        assert!(!value_matches("S+", "S"));
+        // Alternatives
+        assert!(value_matches("FFFF|FAF0", "FAF0"));
+        assert!(!value_matches("FFFF|FAF0", "FAF1"));
+        // Hex ranges
+        assert!(value_matches("40-50", "40"));
+        assert!(value_matches("40-50", "4A"));
+        assert!(value_matches("40-50", "50"));
+        assert!(!value_matches("40-50", "3F"));
+        assert!(!value_matches("40-50", "51"));
+        assert!(value_matches("3B-45|80", "80"));
+        // Comparisons
+        assert!(value_matches(">10", "11"));
+        assert!(!value_matches(">10", "10"));
+        assert!(value_matches("<10", "F"));
+        assert!(!value_matches("<10", "10"));
+        // Non-hex observed value never matches a range or comparison
+        assert!(!value_matches("40-50", "Z"));
+        assert!(!value_matches(">10", "Z"));
+    }
+
+    #[test]
+    fn parse_bundled_os_db() {
+        let entries = parse_os_db(OS_DB);
+        let linux = entries.iter().find(|e| e.name == "Linux 4.15 - 6.x").unwrap();
+        assert_eq!(linux.family, "Linux");
+        assert_eq!(linux.tests.len(), 9);
+        let t1 = &linux.tests["T1"];
+        assert!(t1.contains(&("W".to_string(), "FE88|FAF0|FFFF|FD5C|7210".to_string())));
+        assert!(t1.contains(&("O".to_string(), "M5B4ST11NW7|M5B4ST11NW9|M5B4ST11NWA".to_string())));
+        assert_eq!(linux.tests["T2"], vec![("R".to_string(), "N".to_string())]);
+        // Empty value
+        assert!(linux.tests["T4"].contains(&("O".to_string(), String::new())));
+        let windows = entries.iter().find(|e| e.name.starts_with("Microsoft Windows 10")).unwrap();
+        assert_eq!(windows.family, "Windows");
+    }
+
+    #[test]
+    fn match_fingerprint_from_os_db() {
+        let entries = parse_os_db(OS_DB);
+        let linux = entries.iter().find(|e| e.name == "Linux 4.15 - 6.x").unwrap();
+        let fingerprint = fingerprint_of(linux);
+        let (score, max) = score_entry(&fingerprint, linux);
+        assert_eq!(score, max);
+        let os_match = match_probe_fingerprint(&fingerprint).unwrap();
+        assert_eq!(os_match.family, "Linux");
+        assert_eq!(os_match.name, "Linux 4.15 - 6.x");
+        assert_eq!(os_match.confidence, 100);
+    }
+
+    #[test]
+    fn low_confidence_fingerprint_is_not_matched() {
+        let mut t1 = OsTestResult::new(OsProbe::T1);
+        for (name, value) in [("R", "Y"), ("DF", "N"), ("T", "FF"), ("W", "1"), ("S", "Z"), ("A", "Z"), ("F", "S"), ("O", "M1")] {
+            t1.add(name, value.to_string());
+        }
+        let fingerprint = OsProbeFingerprint { tests: vec![t1] };
+        for entry in parse_os_db(OS_DB) {
+            let (score, max) = score_entry(&fingerprint, &entry);
+            assert!(score * 100 / max < MIN_CONFIDENCE as u32, "{}", entry.name);
+        }
+        assert_eq!(match_probe_fingerprint(&fingerprint), None);
+        // No response at all
+        let mut t1 = OsTestResult::new(OsProbe::T1);
+        t1.add("R", "N".to_string());
+        assert_eq!(match_probe_fingerprint(&OsProbeFingerprint { tests: vec![t1] }), None);
+    }
+}
@@ -0,0 +1,258 @@
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+use nex::packet::tcp::{TcpFlags, TcpOptionKind};
+use crate::packet::frame::PacketFrame;
+use super::frame_source_ip;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Common TCP timestamp clock rates (Hz)
+const COMMON_CLOCK_RATES: [u32; 7] = [1, 2, 10, 100, 250, 300, 1000];
+/// Maximum clock rate considered as the same clock
+const MAX_CLOCK_RATE: f64 = 1100.0;
+/// Minimum clock rate considered as the same clock
+const MIN_CLOCK_RATE: f64 = 0.5;
+
+/// TSval of a SYN/ACK with the elapsed time when the probe was sent
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimestampSample {
+    /// Elapsed time since the first probe
+    pub elapsed: Duration,
+    /// TCP timestamp value
+    pub tsval: u32,
+}
+
+/// Remote TCP timestamp clock
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimestampClock {
+    /// Estimated clock rate (Hz)
+    pub rate: u32,
+    /// Measured clock rate (Hz) before rounding to the common rate
+    pub measured_rate: f64,
+    /// Estimated uptime at the last sample
+    pub uptime: Duration,
+    /// Number of samples from the clock
+    pub samples: usize,
+}
+
+/// Uptime estimation of the host
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UptimeEstimate {
+    /// Independent clocks seen behind the address, ordered by number of samples
+    pub clocks: Vec<TimestampClock>,
+    /// Time of the estimation
+    pub estimated_at: SystemTime,
+}
+
+impl UptimeEstimate {
+    /// Uptime of the host.
+    ///
+    /// None if several clocks are seen, since the address is shared by several hosts. Uptime of each clock is in `clocks`.
+    pub fn uptime(&self) -> Option<Duration> {
+        if self.is_load_balanced() {
+            return None;
+        }
+        self.clocks.first().map(|clock| clock.uptime)
+    }
+    /// Last boot time of the host. None if several clocks are seen
+    pub fn last_boot(&self) -> Option<SystemTime> {
+        let uptime = self.uptime()?;
+        self.estimated_at.checked_sub(uptime)
+    }
+    /// Several independent clocks indicate multiple hosts behind a load balancer
+    pub fn is_load_balanced(&self) -> bool {
+        self.clocks.len() > 1
+    }
+}
+
+/// Get TSval of the SYN/ACK frame
+pub fn frame_tsval(frame: &PacketFrame) -> Option<u32> {
+    let tcp_header = frame.tcp_header.as_ref()?;
+    if tcp_header.flags != TcpFlags::SYN | TcpFlags::ACK {
+        return None;
+    }
+    tcp_header
+        .options
+        .iter()
+        .find(|option| option.kind == TcpOptionKind::TIMESTAMPS)
+        .map(|option| option.get_timestamp().0)
+}
+
+/// Collect timestamp samples of the host.
+///
+/// `sent` is the list of (source port, elapsed time) of each probe. SYN/ACK is matched to the probe by destination port.
+pub fn timestamp_samples(ip_addr: IpAddr, frames: &[PacketFrame], sent: &[(u16, Duration)]) -> Vec<TimestampSample> {
+    let mut samples: Vec<TimestampSample> = vec![];
+    for (src_port, elapsed) in sent {
+        let tsval = frames
+            .iter()
+            .filter(|frame| frame_source_ip(frame) == Some(ip_addr))
+            .filter(|frame| frame.tcp_header.as_ref().map(|tcp_header| tcp_header.destination) == Some(*src_port))
+            .find_map(frame_tsval);
+        if let Some(tsval) = tsval {
+            samples.push(TimestampSample {
+                elapsed: *elapsed,
+                tsval,
+            });
+        }
+    }
+    samples
+}
+
+/// Round the measured rate to the nearest common rate if it is within 20%
+fn round_clock_rate(measured_rate: f64) -> u32 {
+    for rate in COMMON_CLOCK_RATES {
+        let rate_f = rate as f64;
+        if (measured_rate - rate_f).abs() <= rate_f * 0.2 {
+            return rate;
+        }
+    }
+    measured_rate.round().max(1.0) as u32
+}
+
+/// Check if the sample can be from the same clock as the reference sample
+fn same_clock(reference: &TimestampSample, sample: &TimestampSample) -> bool {
+    let ticks = sample.tsval.wrapping_sub(reference.tsval);
+    // Clock went backward
+    if ticks > i32::MAX as u32 {
+        return false;
+    }
+    let seconds = sample.elapsed.saturating_sub(reference.elapsed).as_secs_f64();
+    if seconds < 0.01 {
+        // Too close to measure the rate
+        return (ticks as f64) < MAX_CLOCK_RATE * 0.01 + 1.0;
+    }
+    let rate = ticks as f64 / seconds;
+    (MIN_CLOCK_RATE..=MAX_CLOCK_RATE).contains(&rate)
+}
+
+/// Estimate uptime and clock rate from the timestamp samples.
+///
+/// Samples are grouped into independent clocks. Each clock needs at least 2 samples.
+/// Note that some OSes (e.g. Linux 4.10+) add a random offset to TSval, so the uptime may not reflect the real boot time.
+pub fn estimate_uptime(samples: &[TimestampSample]) -> Option<UptimeEstimate> {
+    let mut samples: Vec<TimestampSample> = samples.iter().filter(|sample| sample.tsval != 0).copied().collect();
+    samples.sort_by_key(|sample| sample.elapsed);
+    let mut groups: Vec<Vec<TimestampSample>> = vec![];
+    for sample in samples {
+        match groups.iter_mut().find(|group| same_clock(&group[0], &sample)) {
+            Some(group) => group.push(sample),
+            None => groups.push(vec![sample]),
+        }
+    }
+    let mut clocks: Vec<TimestampClock> = vec![];
+    for group in groups {
+        if group.len() < 2 {
+            continue;
+        }
+        let first = group[0];
+        let last = group[group.len() - 1];
+        let seconds = last.elapsed.saturating_sub(first.elapsed).as_secs_f64();
+        if seconds <= 0.0 {
+            continue;
+        }
+        let measured_rate = last.tsval.wrapping_sub(first.tsval) as f64 / seconds;
+        if measured_rate <= 0.0 {
+            continue;
+        }
+        let rate = round_clock_rate(measured_rate);
+        clocks.push(TimestampClock {
+            rate,
+            measured_rate,
+            uptime: Duration::from_secs(last.tsval as u64 / rate as u64),
+            samples: group.len(),
+        });
+    }
+    if clocks.is_empty() {
+        return None;
+    }
+    clocks.sort_by_key(|clock| std::cmp::Reverse(clock.samples));
+    Some(UptimeEstimate {
+        clocks,
+        estimated_at: SystemTime::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples every 500 ms from the clock starting at `tsval`
+    fn clock_samples(tsval: u32, rate: u32, count: usize) -> Vec<TimestampSample> {
+        (0..count)
+            .map(|i| TimestampSample {
+                elapsed: Duration::from_millis(500 * i as u64),
+                tsval: tsval.wrapping_add(rate / 2 * i as u32),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_to_common_clock_rate() {
+        assert_eq!(round_clock_rate(98.7), 100);
+        assert_eq!(round_clock_rate(1020.0), 1000);
+        assert_eq!(round_clock_rate(0.9), 1);
+        assert_eq!(round_clock_rate(533.0), 533);
+    }
+
+    #[test]
+    fn estimate_100hz_clock() {
+        // Up for an hour
+        let estimate = estimate_uptime(&clock_samples(360_000, 100, 4)).unwrap();
+        assert_eq!(estimate.clocks.len(), 1);
+        assert_eq!(estimate.clocks[0].rate, 100);
+        assert_eq!(estimate.clocks[0].samples, 4);
+        assert_eq!(estimate.uptime(), Some(Duration::from_secs(3601)));
+        assert!(!estimate.is_load_balanced());
+    }
+
+    #[test]
+    fn estimate_1000hz_clock() {
+        // Up for a day
+        let estimate = estimate_uptime(&clock_samples(86_400_000, 1000, 4)).unwrap();
+        assert_eq!(estimate.clocks[0].rate, 1000);
+        assert_eq!(estimate.uptime(), Some(Duration::from_secs(86_401)));
+        assert!(estimate.last_boot().is_some());
+    }
+
+    #[test]
+    fn estimate_wrapped_clock() {
+        let samples = clock_samples(u32::MAX - 600, 1000, 4);
+        assert!(samples[3].tsval < samples[0].tsval);
+        let estimate = estimate_uptime(&samples).unwrap();
+        assert_eq!(estimate.clocks.len(), 1);
+        assert_eq!(estimate.clocks[0].rate, 1000);
+        assert_eq!(estimate.clocks[0].samples, 4);
+        // Uptime since the counter wrapped
+        assert_eq!(estimate.uptime(), Some(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn load_balanced_clocks_have_no_uptime() {
+        let mut samples: Vec<TimestampSample> = clock_samples(360_000, 100, 3);
+        samples.extend(clock_samples(5_000_000, 1000, 3).into_iter().map(|mut sample| {
+            sample.elapsed += Duration::from_millis(250);
+            sample
+        }));
+        let estimate = estimate_uptime(&samples).unwrap();
+        assert!(estimate.is_load_balanced());
+        assert_eq!(estimate.clocks.len(), 2);
+        assert_eq!(estimate.uptime(), None);
+        assert_eq!(estimate.last_boot(), None);
+        // Clock going backward is another clock
+        assert!(!same_clock(&samples[1], &samples[0]));
+    }
+
+    #[test]
+    fn estimate_needs_two_samples() {
+        assert_eq!(estimate_uptime(&[]), None);
+        assert_eq!(estimate_uptime(&clock_samples(360_000, 100, 1)), None);
+        // Zero TSval is not a clock value
+        let mut samples = clock_samples(360_000, 100, 2);
+        samples[1].tsval = 0;
+        assert_eq!(estimate_uptime(&samples), None);
+    }
+}
@@ -7,9 +7,13 @@ use nex::packet::ipv6::Ipv6Header;
 use nex::packet::tcp::TcpHeader;
 use nex::packet::udp::UdpHeader;
 use nex::packet::frame::Frame;
+use std::time::{Duration, SystemTime};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Packet Frame. Contains all the possible packet types
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PacketFrame {
     pub ethernet_header: Option<EthernetHeader>,
     pub arp_header: Option<ArpHeader>,
@@ -20,6 +24,10 @@ pub struct PacketFrame {
     pub tcp_header: Option<TcpHeader>,
     pub udp_header: Option<UdpHeader>,
     pub payload: Vec<u8>,
+    /// Capture time
+    pub timestamp: Option<SystemTime>,
+    /// Round-trip time from the probe the frame responded to
+    pub rtt: Option<Duration>,
 }
 
 impl PacketFrame {
@@ -35,6 +43,8 @@ impl PacketFrame {
             tcp_header: None,
             udp_header: None,
             payload: vec![],
+            timestamp: None,
+            rtt: None,
         }
     }
     pub fn from_nex_frame(frame: &Frame) -> PacketFrame {
@@ -1,8 +1,10 @@
 use std::net::IpAddr;
-use nex::packet::ethernet::EtherType;
+use nex::packet::ethernet::{EtherType, ETHERNET_HEADER_LEN};
 use nex::packet::icmp::IcmpType;
 use nex::packet::icmpv6::Icmpv6Type;
 use nex::packet::ip::IpNextLevelProtocol;
+use nex::packet::ipv4::{Ipv4Flags, IPV4_HEADER_LEN};
+use nex::packet::ipv6::IPV6_HEADER_LEN;
 use nex::util::packet_builder::builder::PacketBuilder;
 use nex::util::packet_builder::ethernet::EthernetPacketBuilder;
 use nex::util::packet_builder::icmp::IcmpPacketBuilder;
@@ -99,3 +101,80 @@ pub fn build_ip_next_icmp_packet(setting: PacketBuildSetting) -> Vec<u8> {
         },
     }
 }
+
+/// Build ICMPv4 echo request with code, identifier, sequence number, payload and DF bit in the setting.
+///
+/// For IPv6, default ICMPv6 echo request is built.
+pub fn build_icmp_echo_packet(setting: PacketBuildSetting) -> Vec<u8> {
+    let (src_ipv4, dst_ipv4) = match (setting.src_ip, setting.dst_ip) {
+        (IpAddr::V4(src_ipv4), IpAddr::V4(dst_ipv4)) => (src_ipv4, dst_ipv4),
+        _ => return build_icmp_packet(setting),
+    };
+    // Type (Echo Request), Code, Checksum
+    let mut icmp_packet: Vec<u8> = vec![8, setting.icmp_code, 0, 0];
+    icmp_packet.extend_from_slice(&setting.icmp_identifier.to_be_bytes());
+    icmp_packet.extend_from_slice(&setting.icmp_sequence.to_be_bytes());
+    icmp_packet.extend_from_slice(&setting.payload);
+    let checksum: u16 = nex::packet::util::checksum(&icmp_packet, 1);
+    icmp_packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut packet_builder = PacketBuilder::new();
+    let ethernet_packet_builder = EthernetPacketBuilder {
+        src_mac: setting.src_mac,
+        dst_mac: setting.dst_mac,
+        ether_type: EtherType::Ipv4,
+    };
+    packet_builder.set_ethernet(ethernet_packet_builder);
+    let mut ipv4_packet_builder =
+        Ipv4PacketBuilder::new(src_ipv4, dst_ipv4, IpNextLevelProtocol::Icmp);
+    ipv4_packet_builder.total_length = Some((IPV4_HEADER_LEN + icmp_packet.len()) as u16);
+    ipv4_packet_builder.ttl = Some(setting.hop_limit);
+    ipv4_packet_builder.flags = Some(if setting.dont_fragment { Ipv4Flags::DontFragment } else { 0 });
+    packet_builder.set_ipv4(ipv4_packet_builder);
+    let mut packet: Vec<u8> = packet_builder.packet();
+    packet.truncate(ETHERNET_HEADER_LEN + IPV4_HEADER_LEN);
+    packet.extend_from_slice(&icmp_packet);
+    if setting.ip_packet {
+        packet.split_off(ETHERNET_HEADER_LEN)
+    } else {
+        packet
+    }
+}
+
+/// Build ICMPv6 Router Solicitation with the source link-layer address option.
+///
+/// Hop limit must be 255 for routers to accept it.
+pub fn build_router_solicitation_packet(setting: PacketBuildSetting) -> Vec<u8> {
+    let (src_ipv6, dst_ipv6) = match (setting.src_ip, setting.dst_ip) {
+        (IpAddr::V6(src_ipv6), IpAddr::V6(dst_ipv6)) => (src_ipv6, dst_ipv6),
+        _ => return Vec::new(),
+    };
+    // Type (Router Solicitation), Code, Checksum, Reserved
+    let mut icmpv6_packet: Vec<u8> = vec![133, 0, 0, 0, 0, 0, 0, 0];
+    // Source link-layer address option (Type 1, Length 1 = 8 octets)
+    icmpv6_packet.extend_from_slice(&[1, 1]);
+    icmpv6_packet.extend_from_slice(&setting.src_mac.octets());
+    let checksum: u16 = nex::packet::util::ipv6_checksum(&icmpv6_packet, 1, &[], &src_ipv6, &dst_ipv6, IpNextLevelProtocol::Icmpv6);
+    icmpv6_packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    let mut packet_builder = PacketBuilder::new();
+    let ethernet_packet_builder = EthernetPacketBuilder {
+        src_mac: setting.src_mac,
+        dst_mac: setting.dst_mac,
+        ether_type: EtherType::Ipv6,
+    };
+    packet_builder.set_ethernet(ethernet_packet_builder);
+    let mut ipv6_packet_builder =
+        Ipv6PacketBuilder::new(src_ipv6, dst_ipv6, IpNextLevelProtocol::Icmpv6);
+    ipv6_packet_builder.payload_length = Some(icmpv6_packet.len() as u16);
+    ipv6_packet_builder.hop_limit = Some(setting.hop_limit);
+    packet_builder.set_ipv6(ipv6_packet_builder);
+    let mut packet: Vec<u8> = packet_builder.packet();
+    packet.truncate(ETHERNET_HEADER_LEN + IPV6_HEADER_LEN);
+    packet.extend_from_slice(&icmpv6_packet);
+    if setting.ip_packet {
+        packet.split_off(ETHERNET_HEADER_LEN)
+    } else {
+        packet
+    }
+}
@@ -1,7 +1,11 @@
 use std::net::{IpAddr, Ipv4Addr};
 use netdev::mac::MacAddr;
+use nex::packet::tcp::{TcpFlags, TcpOption};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PacketBuildSetting {
     pub src_mac: MacAddr,
     pub dst_mac: MacAddr,
@@ -12,6 +16,25 @@ pub struct PacketBuildSetting {
     pub hop_limit: u8,
     pub payload: Vec<u8>,
     pub ip_packet: bool,
+    /// Set DF (Don't Fragment) bit. IPv4 only
+    pub dont_fragment: bool,
+    /// TCP flags
+    pub tcp_flags: u8,
+    /// TCP window size
+    pub tcp_window: u16,
+    /// TCP options in order
+    #[cfg_attr(feature = "serde", serde(with = "tcp_options_serde"))]
+    pub tcp_options: Vec<TcpOption>,
+    /// TCP sequence number
+    pub tcp_seq: u32,
+    /// TCP acknowledgement number
+    pub tcp_ack: u32,
+    /// ICMP code
+    pub icmp_code: u8,
+    /// ICMP echo identifier
+    pub icmp_identifier: u16,
+    /// ICMP echo sequence number
+    pub icmp_sequence: u16,
 }
 
 impl PacketBuildSetting {
@@ -26,6 +49,106 @@ impl PacketBuildSetting {
             hop_limit: 64,
             payload: Vec::new(),
             ip_packet: false,
+            dont_fragment: true,
+            tcp_flags: TcpFlags::SYN,
+            tcp_window: 65535,
+            tcp_options: default_tcp_options(),
+            tcp_seq: 0,
+            tcp_ack: 0,
+            icmp_code: 0,
+            icmp_identifier: rand::random::<u16>(),
+            icmp_sequence: rand::random::<u16>(),
         }
     }
 }
+
+/// Default TCP options for SYN packet
+pub fn default_tcp_options() -> Vec<TcpOption> {
+    vec![
+        TcpOption::mss(1460),
+        TcpOption::nop(),
+        TcpOption::wscale(6),
+        TcpOption::nop(),
+        TcpOption::nop(),
+        TcpOption::timestamp(u32::MAX, u32::MIN),
+        TcpOption::sack_perm(),
+    ]
+}
+
+/// Serialize TCP options as the bytes of each option on the wire
+#[cfg(feature = "serde")]
+mod tcp_options_serde {
+    use nex::packet::FromPacket;
+    use nex::packet::tcp::{MutableTcpOptionPacket, TcpOption, TcpOptionKind, TcpOptionPacket};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(options: &[TcpOption], serializer: S) -> Result<S::Ok, S::Error> {
+        let options: Vec<Vec<u8>> = options
+            .iter()
+            .map(|option| {
+                let mut bytes: Vec<u8> = vec![0; TcpOptionPacket::packet_size(option)];
+                if let Some(mut packet) = MutableTcpOptionPacket::new(&mut bytes) {
+                    packet.populate(option);
+                }
+                bytes
+            })
+            .collect();
+        options.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<TcpOption>, D::Error> {
+        let options: Vec<Vec<u8>> = Vec::deserialize(deserializer)?;
+        options
+            .iter()
+            .map(|bytes| {
+                // EOL and NOP are 1 byte. Others have the length of the whole option in the second byte
+                let valid = match bytes.first().map(|kind| TcpOptionKind::new(*kind)) {
+                    Some(TcpOptionKind::EOL) | Some(TcpOptionKind::NOP) => bytes.len() == 1,
+                    Some(_) => bytes.len() >= 2 && bytes[1] as usize == bytes.len(),
+                    None => false,
+                };
+                if !valid {
+                    return Err(D::Error::custom(format!("Invalid TCP option: {:?}", bytes)));
+                }
+                TcpOptionPacket::new(bytes)
+                    .map(|packet| packet.from_packet())
+                    .ok_or_else(|| D::Error::custom(format!("Invalid TCP option: {:?}", bytes)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tcp_options_round_trip() {
+        let mut setting = PacketBuildSetting::new();
+        setting.tcp_options = vec![
+            TcpOption::mss(1460),
+            TcpOption::sack_perm(),
+            TcpOption::timestamp(743951781, 44056978),
+            TcpOption::nop(),
+            TcpOption::wscale(7),
+        ];
+        let json = serde_json::to_string(&setting).unwrap();
+        let decoded: PacketBuildSetting = serde_json::from_str(&json).unwrap();
+        // TcpOption does not implement PartialEq
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", setting));
+        assert_eq!(decoded.tcp_options[2].get_timestamp(), (743951781, 44056978));
+    }
+
+    #[test]
+    fn reject_invalid_tcp_option() {
+        let json = serde_json::to_string(&PacketBuildSetting::new()).unwrap();
+        // MSS with the length longer than the option
+        let invalid = json.replace("[2,4,5,180]", "[2,6,5,180]");
+        assert_ne!(invalid, json);
+        assert!(serde_json::from_str::<PacketBuildSetting>(&invalid).is_err());
+        // NOP with trailing bytes
+        let invalid = json.replacen("[1]", "[1,1]", 1);
+        assert!(serde_json::from_str::<PacketBuildSetting>(&invalid).is_err());
+    }
+}
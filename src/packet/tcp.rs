@@ -1,7 +1,9 @@
 use std::net::{IpAddr, SocketAddr};
-use nex::packet::ethernet::EtherType;
+use nex::packet::ethernet::{EtherType, ETHERNET_HEADER_LEN};
 use nex::packet::ip::IpNextLevelProtocol;
-use nex::packet::tcp::{TcpFlags, TcpOption};
+use nex::packet::ipv4::{Ipv4Flags, IPV4_HEADER_LEN};
+use nex::packet::ipv6::IPV6_HEADER_LEN;
+use nex::packet::tcp::{MutableTcpPacket, TcpFlags};
 use nex::util::packet_builder::{
     builder::PacketBuilder, 
     ethernet::EthernetPacketBuilder,
@@ -9,10 +11,39 @@ use nex::util::packet_builder::{
     ipv6::Ipv6PacketBuilder,
     tcp::TcpPacketBuilder,
 };
-use crate::packet::setting::PacketBuildSetting;
+use crate::packet::setting::{default_tcp_options, PacketBuildSetting};
 
-/// Build TCP SYN packet with default options
-pub fn build_tcp_syn_packet(setting: PacketBuildSetting) -> Vec<u8> {
+/// Build TCP segment (without IP header) with flags, window, options, sequence and acknowledgement numbers in the setting
+fn build_tcp_segment(setting: &PacketBuildSetting) -> Vec<u8> {
+    let mut tcp_packet_builder = TcpPacketBuilder::new(
+        SocketAddr::new(setting.src_ip, setting.src_port),
+        SocketAddr::new(setting.dst_ip, setting.dst_port),
+    );
+    tcp_packet_builder.flags = setting.tcp_flags;
+    tcp_packet_builder.window = setting.tcp_window;
+    tcp_packet_builder.options = setting.tcp_options.clone();
+    tcp_packet_builder.payload = setting.payload.clone();
+    let mut segment: Vec<u8> = tcp_packet_builder.build();
+    if setting.tcp_seq == 0 && setting.tcp_ack == 0 {
+        return segment;
+    }
+    // Sequence and acknowledgement numbers are not supported by the builder. Set them and recalculate checksum.
+    if let Some(mut tcp_packet) = MutableTcpPacket::new(&mut segment) {
+        tcp_packet.set_sequence(setting.tcp_seq);
+        tcp_packet.set_acknowledgement(setting.tcp_ack);
+        let checksum = match (setting.src_ip, setting.dst_ip) {
+            (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => nex::packet::tcp::ipv4_checksum(&tcp_packet.to_immutable(), &src_ip, &dst_ip),
+            (IpAddr::V6(src_ip), IpAddr::V6(dst_ip)) => nex::packet::tcp::ipv6_checksum(&tcp_packet.to_immutable(), &src_ip, &dst_ip),
+            _ => 0,
+        };
+        tcp_packet.set_checksum(checksum);
+    }
+    segment
+}
+
+/// Build TCP packet with flags, window, options, sequence and acknowledgement numbers in the setting
+pub fn build_tcp_packet(setting: PacketBuildSetting) -> Vec<u8> {
+    let segment: Vec<u8> = build_tcp_segment(&setting);
     let mut packet_builder = PacketBuilder::new();
     let ethernet_packet_builder = EthernetPacketBuilder {
         src_mac: setting.src_mac,
@@ -23,67 +54,52 @@ pub fn build_tcp_syn_packet(setting: PacketBuildSetting) -> Vec<u8> {
         },
     };
     packet_builder.set_ethernet(ethernet_packet_builder);
-    match setting.src_ip {
+    let header_len: usize = match setting.src_ip {
         IpAddr::V4(src_ipv4) => match setting.dst_ip {
             IpAddr::V4(dst_ipv4) => {
                 let mut ipv4_packet_builder =
                     Ipv4PacketBuilder::new(src_ipv4, dst_ipv4, IpNextLevelProtocol::Tcp);
-                ipv4_packet_builder.total_length = Some(64);
+                ipv4_packet_builder.total_length = Some((IPV4_HEADER_LEN + segment.len()) as u16);
                 ipv4_packet_builder.ttl = Some(setting.hop_limit);
+                ipv4_packet_builder.flags = Some(if setting.dont_fragment { Ipv4Flags::DontFragment } else { 0 });
                 packet_builder.set_ipv4(ipv4_packet_builder);
+                ETHERNET_HEADER_LEN + IPV4_HEADER_LEN
             }
-            IpAddr::V6(_) => {}
+            IpAddr::V6(_) => return Vec::new(),
         },
         IpAddr::V6(src_ipv6) => match setting.dst_ip {
-            IpAddr::V4(_) => {}
+            IpAddr::V4(_) => return Vec::new(),
             IpAddr::V6(dst_ipv6) => {
                 let mut ipv6_packet_builder =
                     Ipv6PacketBuilder::new(src_ipv6, dst_ipv6, IpNextLevelProtocol::Tcp);
-                ipv6_packet_builder.payload_length = Some(44);
+                ipv6_packet_builder.payload_length = Some(segment.len() as u16);
                 ipv6_packet_builder.hop_limit = Some(setting.hop_limit);
                 packet_builder.set_ipv6(ipv6_packet_builder);
+                ETHERNET_HEADER_LEN + IPV6_HEADER_LEN
             }
         },
-    }
-    let mut tcp_packet_builder = TcpPacketBuilder::new(
-        SocketAddr::new(setting.src_ip, setting.src_port),
-        SocketAddr::new(setting.dst_ip, setting.dst_port),
-    );
-    tcp_packet_builder.flags = TcpFlags::SYN;
-    tcp_packet_builder.window = 65535;
-    tcp_packet_builder.options = vec![
-        TcpOption::mss(1460),
-        TcpOption::nop(),
-        TcpOption::wscale(6),
-        TcpOption::nop(),
-        TcpOption::nop(),
-        TcpOption::timestamp(u32::MAX, u32::MIN),
-        TcpOption::sack_perm(),
-    ];
-    packet_builder.set_tcp(tcp_packet_builder);
-
+    };
+    let mut packet: Vec<u8> = packet_builder.packet();
+    packet.truncate(header_len);
+    packet.extend_from_slice(&segment);
     if setting.ip_packet {
-        packet_builder.ip_packet()
+        packet.split_off(ETHERNET_HEADER_LEN)
     } else {
-        packet_builder.packet()
+        packet
     }
 }
 
-pub fn build_ip_next_tcp_syn_packet(setting: PacketBuildSetting) -> Vec<u8> {
-    let mut tcp_packet_builder = TcpPacketBuilder::new(
-        SocketAddr::new(setting.src_ip, setting.src_port),
-        SocketAddr::new(setting.dst_ip, setting.dst_port),
-    );
-    tcp_packet_builder.flags = TcpFlags::SYN;
-    tcp_packet_builder.window = 65535;
-    tcp_packet_builder.options = vec![
-        TcpOption::mss(1460),
-        TcpOption::nop(),
-        TcpOption::wscale(6),
-        TcpOption::nop(),
-        TcpOption::nop(),
-        TcpOption::timestamp(u32::MAX, u32::MIN),
-        TcpOption::sack_perm(),
-    ];
-    tcp_packet_builder.build()
-}
\ No newline at end of file
+/// Build TCP SYN packet with default options
+pub fn build_tcp_syn_packet(mut setting: PacketBuildSetting) -> Vec<u8> {
+    setting.tcp_flags = TcpFlags::SYN;
+    setting.tcp_window = 65535;
+    setting.tcp_options = default_tcp_options();
+    build_tcp_packet(setting)
+}
+
+pub fn build_ip_next_tcp_syn_packet(mut setting: PacketBuildSetting) -> Vec<u8> {
+    setting.tcp_flags = TcpFlags::SYN;
+    setting.tcp_window = 65535;
+    setting.tcp_options = default_tcp_options();
+    build_tcp_segment(&setting)
+}
@@ -1,6 +1,8 @@
 use std::net::{IpAddr, SocketAddr};
 use nex::packet::ethernet::EtherType;
 use nex::packet::ip::IpNextLevelProtocol;
+use nex::packet::ipv4::{Ipv4Flags, IPV4_HEADER_LEN};
+use nex::packet::udp::UDP_HEADER_LEN;
 use nex::util::packet_builder::{
     builder::PacketBuilder, 
     ethernet::EthernetPacketBuilder,
@@ -31,7 +33,9 @@ pub fn build_udp_packet(setting: PacketBuildSetting) -> Vec<u8> {
             IpAddr::V4(src_ipv4) => {
                 let mut ipv4_packet_builder =
                     Ipv4PacketBuilder::new(src_ipv4, dst_ipv4, IpNextLevelProtocol::Udp);
+                ipv4_packet_builder.total_length = Some((IPV4_HEADER_LEN + UDP_HEADER_LEN + setting.payload.len()) as u16);
                 ipv4_packet_builder.ttl = Some(setting.hop_limit);
+                ipv4_packet_builder.flags = Some(if setting.dont_fragment { Ipv4Flags::DontFragment } else { 0 });
                 packet_builder.set_ipv4(ipv4_packet_builder);
             }
             IpAddr::V6(_) => {}
@@ -41,6 +45,7 @@ pub fn build_udp_packet(setting: PacketBuildSetting) -> Vec<u8> {
             IpAddr::V6(src_ipv4) => {
                 let mut ipv6_packet_builder =
                     Ipv6PacketBuilder::new(src_ipv4, dst_ipv6, IpNextLevelProtocol::Udp);
+                ipv6_packet_builder.payload_length = Some((UDP_HEADER_LEN + setting.payload.len()) as u16);
                 ipv6_packet_builder.hop_limit = Some(setting.hop_limit);
                 packet_builder.set_ipv6(ipv6_packet_builder);
             }
@@ -50,7 +55,7 @@ pub fn build_udp_packet(setting: PacketBuildSetting) -> Vec<u8> {
     match setting.dst_ip {
         IpAddr::V4(dst_ipv4) => match setting.src_ip {
             IpAddr::V4(src_ipv4) => {
-                let udp_packet_builder = UdpPacketBuilder::new(
+                let mut udp_packet_builder = UdpPacketBuilder::new(
                     SocketAddr::new(
                         IpAddr::V4(src_ipv4),
                         setting.src_port,
@@ -60,6 +65,7 @@ pub fn build_udp_packet(setting: PacketBuildSetting) -> Vec<u8> {
                         setting.dst_port,
                     ),
                 );
+                udp_packet_builder.payload = setting.payload.clone();
                 packet_builder.set_udp(udp_packet_builder);
             }
             IpAddr::V6(_) => {}
@@ -67,7 +73,7 @@ pub fn build_udp_packet(setting: PacketBuildSetting) -> Vec<u8> {
         IpAddr::V6(dst_ipv6) => match setting.src_ip {
             IpAddr::V4(_) => {}
             IpAddr::V6(src_ipv6) => {
-                let udp_packet_builder = UdpPacketBuilder::new(
+                let mut udp_packet_builder = UdpPacketBuilder::new(
                     SocketAddr::new(
                         IpAddr::V6(src_ipv6),
                         setting.src_port,
@@ -77,6 +83,7 @@ pub fn build_udp_packet(setting: PacketBuildSetting) -> Vec<u8> {
                         setting.dst_port,
                     ),
                 );
+                udp_packet_builder.payload = setting.payload.clone();
                 packet_builder.set_udp(udp_packet_builder);
             }
         },
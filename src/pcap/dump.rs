@@ -0,0 +1,669 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use netdev::mac::MacAddr;
+use netdev::Interface;
+use crate::config::PCAP_DUMP_PENDING_LIMIT;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+const PCAP_SNAPLEN: u32 = 65535;
+const PCAPNG_SECTION_HEADER_BLOCK: u32 = 0x0a0d0d0a;
+const PCAPNG_INTERFACE_DESCRIPTION_BLOCK: u32 = 0x00000001;
+const PCAPNG_SIMPLE_PACKET_BLOCK: u32 = 0x00000003;
+const PCAPNG_ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_OPT_ENDOFOPT: u16 = 0;
+const PCAPNG_OPT_IF_NAME: u16 = 2;
+const PCAPNG_OPT_IF_TSRESOL: u16 = 9;
+const PCAPNG_OPT_EPB_FLAGS: u16 = 2;
+
+/// Capture file format
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PcapFormat {
+    /// Classic libpcap format. Packet direction is not recorded
+    Pcap,
+    /// pcapng format. Packet direction is recorded in the packet flags
+    PcapNg,
+}
+
+/// Link-layer header type of the recorded packets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LinkType {
+    /// Ethernet frames
+    Ethernet,
+    /// Raw IPv4 or IPv6 packets (e.g. TUN interface)
+    RawIp,
+}
+
+impl LinkType {
+    /// LINKTYPE_ value in the file header
+    pub fn value(&self) -> u16 {
+        match self {
+            LinkType::Ethernet => 1,
+            LinkType::RawIp => 101,
+        }
+    }
+    /// Link type from the LINKTYPE_ value. Unsupported link types are None
+    pub fn from_value(value: u16) -> Option<LinkType> {
+        match value {
+            1 => Some(LinkType::Ethernet),
+            // LINKTYPE_RAW, LINKTYPE_IPV4 and LINKTYPE_IPV6
+            101 | 228 | 229 => Some(LinkType::RawIp),
+            _ => None,
+        }
+    }
+}
+
+/// Direction of the recorded packet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PacketDirection {
+    Unknown,
+    Inbound,
+    Outbound,
+}
+
+/// Pad the length to a multiple of 4 bytes
+fn padded_len(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Writer for pcap and pcapng files.
+///
+/// Timestamps are written in microseconds.
+#[derive(Debug)]
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    format: PcapFormat,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Create new writer and write the file header.
+    ///
+    /// For pcapng, `if_name` is written to the interface description block.
+    pub fn new(mut writer: W, format: PcapFormat, link_type: LinkType, if_name: &str) -> io::Result<PcapWriter<W>> {
+        match format {
+            PcapFormat::Pcap => {
+                writer.write_all(&PCAP_MAGIC.to_le_bytes())?;
+                writer.write_all(&2u16.to_le_bytes())?;
+                writer.write_all(&4u16.to_le_bytes())?;
+                // Time zone offset and timestamp accuracy
+                writer.write_all(&0i32.to_le_bytes())?;
+                writer.write_all(&0u32.to_le_bytes())?;
+                writer.write_all(&PCAP_SNAPLEN.to_le_bytes())?;
+                writer.write_all(&(link_type.value() as u32).to_le_bytes())?;
+            }
+            PcapFormat::PcapNg => {
+                // Section header block without options. Section length is unspecified
+                let block_len: u32 = 28;
+                writer.write_all(&PCAPNG_SECTION_HEADER_BLOCK.to_le_bytes())?;
+                writer.write_all(&block_len.to_le_bytes())?;
+                writer.write_all(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes())?;
+                writer.write_all(&1u16.to_le_bytes())?;
+                writer.write_all(&0u16.to_le_bytes())?;
+                writer.write_all(&(-1i64).to_le_bytes())?;
+                writer.write_all(&block_len.to_le_bytes())?;
+                // Interface description block with if_name option
+                let mut options: Vec<u8> = vec![];
+                if !if_name.is_empty() {
+                    options.extend_from_slice(&PCAPNG_OPT_IF_NAME.to_le_bytes());
+                    options.extend_from_slice(&(if_name.len() as u16).to_le_bytes());
+                    options.extend_from_slice(if_name.as_bytes());
+                    options.resize(padded_len(options.len()), 0);
+                    options.extend_from_slice(&PCAPNG_OPT_ENDOFOPT.to_le_bytes());
+                    options.extend_from_slice(&0u16.to_le_bytes());
+                }
+                let block_len: u32 = 20 + options.len() as u32;
+                writer.write_all(&PCAPNG_INTERFACE_DESCRIPTION_BLOCK.to_le_bytes())?;
+                writer.write_all(&block_len.to_le_bytes())?;
+                writer.write_all(&link_type.value().to_le_bytes())?;
+                writer.write_all(&0u16.to_le_bytes())?;
+                // No snapshot length limit
+                writer.write_all(&0u32.to_le_bytes())?;
+                writer.write_all(&options)?;
+                writer.write_all(&block_len.to_le_bytes())?;
+            }
+        }
+        Ok(PcapWriter { writer, format })
+    }
+    /// Write the packet with the capture time.
+    ///
+    /// Packets longer than 65535 bytes are truncated in the pcap format.
+    pub fn write_packet(&mut self, data: &[u8], time: SystemTime, direction: PacketDirection) -> io::Result<()> {
+        let micros: u64 = match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_micros() as u64,
+            Err(_) => 0,
+        };
+        match self.format {
+            PcapFormat::Pcap => {
+                let captured: &[u8] = &data[..data.len().min(PCAP_SNAPLEN as usize)];
+                self.writer.write_all(&((micros / 1_000_000) as u32).to_le_bytes())?;
+                self.writer.write_all(&((micros % 1_000_000) as u32).to_le_bytes())?;
+                self.writer.write_all(&(captured.len() as u32).to_le_bytes())?;
+                self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+                self.writer.write_all(captured)?;
+            }
+            PcapFormat::PcapNg => {
+                let flags: u32 = match direction {
+                    PacketDirection::Unknown => 0,
+                    PacketDirection::Inbound => 1,
+                    PacketDirection::Outbound => 2,
+                };
+                // epb_flags and opt_endofopt
+                let options_len: usize = if flags != 0 { 12 } else { 0 };
+                let block_len: u32 = (32 + padded_len(data.len()) + options_len) as u32;
+                self.writer.write_all(&PCAPNG_ENHANCED_PACKET_BLOCK.to_le_bytes())?;
+                self.writer.write_all(&block_len.to_le_bytes())?;
+                // Interface ID
+                self.writer.write_all(&0u32.to_le_bytes())?;
+                self.writer.write_all(&((micros >> 32) as u32).to_le_bytes())?;
+                self.writer.write_all(&(micros as u32).to_le_bytes())?;
+                self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+                self.writer.write_all(&(data.len() as u32).to_le_bytes())?;
+                self.writer.write_all(data)?;
+                self.writer.write_all(&[0; 3][..padded_len(data.len()) - data.len()])?;
+                if flags != 0 {
+                    self.writer.write_all(&PCAPNG_OPT_EPB_FLAGS.to_le_bytes())?;
+                    self.writer.write_all(&4u16.to_le_bytes())?;
+                    self.writer.write_all(&flags.to_le_bytes())?;
+                    self.writer.write_all(&PCAPNG_OPT_ENDOFOPT.to_le_bytes())?;
+                    self.writer.write_all(&0u16.to_le_bytes())?;
+                }
+                self.writer.write_all(&block_len.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Recently sent frames that have not been seen by the capture yet, indexed by the frame bytes
+#[derive(Debug, Default)]
+struct PendingFrames {
+    /// Sequence numbers of the unmatched sends of each frame, oldest first
+    frames: HashMap<Vec<u8>, VecDeque<u64>>,
+    /// Sends in order, for dropping the oldest ones over the limit. Matched sends are skipped when dropped
+    order: VecDeque<(u64, Vec<u8>)>,
+    next_seq: u64,
+}
+
+impl PendingFrames {
+    fn push(&mut self, data: &[u8]) {
+        if self.order.len() >= PCAP_DUMP_PENDING_LIMIT {
+            if let Some((seq, frame)) = self.order.pop_front() {
+                if let Some(seqs) = self.frames.get_mut(&frame) {
+                    if seqs.front() == Some(&seq) {
+                        seqs.pop_front();
+                    }
+                    if seqs.is_empty() {
+                        self.frames.remove(&frame);
+                    }
+                }
+            }
+        }
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.frames.entry(data.to_vec()).or_default().push_back(seq);
+        self.order.push_back((seq, data.to_vec()));
+    }
+    /// Match the frame with the oldest unmatched send of the same bytes
+    fn take(&mut self, data: &[u8]) -> bool {
+        let seqs = match self.frames.get_mut(data) {
+            Some(seqs) => seqs,
+            None => return false,
+        };
+        seqs.pop_front();
+        if seqs.is_empty() {
+            self.frames.remove(data);
+        }
+        true
+    }
+}
+
+#[derive(Debug)]
+struct DumpState {
+    writer: PcapWriter<BufWriter<File>>,
+    link_type: LinkType,
+    mac_addr: Option<MacAddr>,
+    /// Recently sent frames. Copies seen by the capture are not written twice
+    pending: PendingFrames,
+}
+
+/// Capture file shared by the sender and the capture thread of a scan.
+///
+/// Sent probes are recorded as outbound. Received frames are recorded as inbound,
+/// except the frames sent from the interface MAC address (e.g. probes sent through raw sockets).
+#[derive(Clone, Debug)]
+pub struct PacketDump {
+    state: Arc<Mutex<DumpState>>,
+}
+
+impl PacketDump {
+    /// Create the capture file for the packets of the interface
+    pub fn create(path: &Path, format: PcapFormat, interface: &Interface) -> Result<PacketDump, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let link_type: LinkType = if interface.is_tun() { LinkType::RawIp } else { LinkType::Ethernet };
+        let writer = PcapWriter::new(BufWriter::new(file), format, link_type, &interface.name)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(PacketDump {
+            state: Arc::new(Mutex::new(DumpState {
+                writer,
+                link_type,
+                mac_addr: interface.mac_addr,
+                pending: PendingFrames::default(),
+            })),
+        })
+    }
+    /// Record the sent frame
+    pub fn record_sent(&self, data: &[u8]) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Failed to lock packet dump: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = state.writer.write_packet(data, SystemTime::now(), PacketDirection::Outbound) {
+            eprintln!("Failed to write packet: {}", e);
+        }
+        state.pending.push(data);
+    }
+    /// Record the received frame
+    pub fn record_received(&self, data: &[u8]) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Failed to lock packet dump: {}", e);
+                return;
+            }
+        };
+        // Copy of the frame recorded by `record_sent`
+        if state.pending.take(data) {
+            return;
+        }
+        let direction: PacketDirection = match (state.link_type, state.mac_addr) {
+            (LinkType::Ethernet, Some(mac_addr)) if data.len() >= 12 => {
+                if data[6..12] == mac_addr.octets() {
+                    PacketDirection::Outbound
+                } else {
+                    PacketDirection::Inbound
+                }
+            }
+            _ => PacketDirection::Unknown,
+        };
+        if let Err(e) = state.writer.write_packet(data, SystemTime::now(), direction) {
+            eprintln!("Failed to write packet: {}", e);
+        }
+    }
+    /// Flush buffered packets to the file
+    pub fn flush(&self) -> Result<(), String> {
+        match self.state.lock() {
+            Ok(mut state) => state.writer.flush().map_err(|e| format!("Failed to write packets: {}", e)),
+            Err(e) => Err(format!("Failed to lock packet dump: {}", e)),
+        }
+    }
+}
+
+/// Packet read from the capture file
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CapturedPacket {
+    pub data: Vec<u8>,
+    /// Capture time. UNIX epoch if not recorded
+    pub time: SystemTime,
+    pub direction: PacketDirection,
+}
+
+/// Little or big endian reader of the capture file
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> ByteReader<'a> {
+    fn slice(&self, offset: usize, len: usize) -> Result<&'a [u8], String> {
+        match offset.checked_add(len) {
+            Some(end) if end <= self.bytes.len() => Ok(&self.bytes[offset..end]),
+            _ => Err(format!("Truncated capture file at {}", offset)),
+        }
+    }
+    fn u16(&self, offset: usize) -> Result<u16, String> {
+        let s = self.slice(offset, 2)?;
+        let b: [u8; 2] = [s[0], s[1]];
+        Ok(if self.big_endian { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) })
+    }
+    fn u32(&self, offset: usize) -> Result<u32, String> {
+        let s = self.slice(offset, 4)?;
+        let b: [u8; 4] = [s[0], s[1], s[2], s[3]];
+        Ok(if self.big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    }
+}
+
+/// Time from the timestamp in units of `1 / units_per_sec` seconds.
+///
+/// Timestamps out of the range of `SystemTime` are errors.
+fn timestamp_to_time(ticks: u64, units_per_sec: u64) -> Result<SystemTime, String> {
+    let secs = ticks / units_per_sec;
+    let nanos = (ticks % units_per_sec) as u128 * 1_000_000_000 / units_per_sec as u128;
+    UNIX_EPOCH
+        .checked_add(Duration::new(secs, nanos as u32))
+        .ok_or(format!("Invalid timestamp {}", ticks))
+}
+
+fn read_pcap(bytes: &[u8]) -> Result<(LinkType, Vec<CapturedPacket>), String> {
+    let magic = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let (big_endian, units_per_sec) = if magic == PCAP_MAGIC {
+        (false, 1_000_000)
+    } else if magic == PCAP_MAGIC_NANOS {
+        (false, 1_000_000_000)
+    } else if magic == PCAP_MAGIC.swap_bytes() {
+        (true, 1_000_000)
+    } else {
+        (true, 1_000_000_000)
+    };
+    let reader = ByteReader { bytes, big_endian };
+    // Upper bits of the link type field are FCS information
+    let link_value: u16 = (reader.u32(20)? & 0xffff) as u16;
+    let link_type: LinkType = LinkType::from_value(link_value).ok_or(format!("Unsupported link type {}", link_value))?;
+    let mut packets: Vec<CapturedPacket> = vec![];
+    let mut offset: usize = 24;
+    while offset < bytes.len() {
+        let secs = reader.u32(offset)? as u64;
+        let frac = reader.u32(offset + 4)? as u64;
+        let captured_len = reader.u32(offset + 8)? as usize;
+        packets.push(CapturedPacket {
+            data: reader.slice(offset + 16, captured_len)?.to_vec(),
+            time: timestamp_to_time(secs * units_per_sec + frac, units_per_sec)?,
+            direction: PacketDirection::Unknown,
+        });
+        offset += 16 + captured_len;
+    }
+    Ok((link_type, packets))
+}
+
+/// Interface of the pcapng section
+struct PcapNgInterface {
+    link_type: Option<LinkType>,
+    units_per_sec: u64,
+}
+
+/// Check that the packet of the interface has the link type of the first packet
+fn accept_link_type(link_type: &mut Option<LinkType>, interface: &PcapNgInterface) -> bool {
+    match (interface.link_type, *link_type) {
+        (Some(interface_link_type), None) => {
+            *link_type = Some(interface_link_type);
+            true
+        }
+        (Some(interface_link_type), Some(first_link_type)) => interface_link_type == first_link_type,
+        (None, _) => false,
+    }
+}
+
+fn read_pcapng(bytes: &[u8]) -> Result<(LinkType, Vec<CapturedPacket>), String> {
+    let mut reader = ByteReader { bytes, big_endian: false };
+    let mut interfaces: Vec<PcapNgInterface> = vec![];
+    // Link type of the first packet. Packets of other link types are skipped
+    let mut link_type: Option<LinkType> = None;
+    let mut packets: Vec<CapturedPacket> = vec![];
+    let mut offset: usize = 0;
+    while offset < bytes.len() {
+        if reader.u32(offset)? == PCAPNG_SECTION_HEADER_BLOCK {
+            reader.big_endian = reader.slice(offset + 8, 4)? == PCAPNG_BYTE_ORDER_MAGIC.to_be_bytes();
+            interfaces.clear();
+        }
+        let block_type = reader.u32(offset)?;
+        let block_len = reader.u32(offset + 4)? as usize;
+        if block_len < 12 || block_len & 3 != 0 {
+            return Err(format!("Invalid block length {} at {}", block_len, offset));
+        }
+        let block = ByteReader {
+            bytes: reader.slice(offset, block_len)?,
+            big_endian: reader.big_endian,
+        };
+        match block_type {
+            PCAPNG_INTERFACE_DESCRIPTION_BLOCK => {
+                let mut units_per_sec: u64 = 1_000_000;
+                let mut option_offset: usize = 16;
+                while option_offset + 4 <= block_len - 4 {
+                    let code = block.u16(option_offset)?;
+                    let len = block.u16(option_offset + 2)? as usize;
+                    if code == PCAPNG_OPT_ENDOFOPT {
+                        break;
+                    }
+                    if code == PCAPNG_OPT_IF_TSRESOL && len == 1 {
+                        let resolution = block.slice(option_offset + 4, 1)?[0];
+                        units_per_sec = if resolution & 0x80 == 0 {
+                            10u64.checked_pow(resolution as u32).unwrap_or(1_000_000)
+                        } else {
+                            1u64.checked_shl((resolution & 0x7f) as u32).unwrap_or(1_000_000)
+                        };
+                    }
+                    option_offset += 4 + padded_len(len);
+                }
+                interfaces.push(PcapNgInterface {
+                    link_type: LinkType::from_value(block.u16(8)?),
+                    units_per_sec,
+                });
+            }
+            PCAPNG_ENHANCED_PACKET_BLOCK => {
+                let interface = interfaces.get(block.u32(8)? as usize).ok_or("Packet of unknown interface")?;
+                let ticks = ((block.u32(12)? as u64) << 32) | block.u32(16)? as u64;
+                let captured_len = block.u32(20)? as usize;
+                let data = block.slice(28, captured_len)?;
+                let mut direction = PacketDirection::Unknown;
+                let mut option_offset: usize = 28 + padded_len(captured_len);
+                while option_offset + 4 <= block_len - 4 {
+                    let code = block.u16(option_offset)?;
+                    let len = block.u16(option_offset + 2)? as usize;
+                    if code == PCAPNG_OPT_ENDOFOPT {
+                        break;
+                    }
+                    if code == PCAPNG_OPT_EPB_FLAGS && len == 4 {
+                        direction = match block.u32(option_offset + 4)? & 0x3 {
+                            1 => PacketDirection::Inbound,
+                            2 => PacketDirection::Outbound,
+                            _ => PacketDirection::Unknown,
+                        };
+                    }
+                    option_offset += 4 + padded_len(len);
+                }
+                if accept_link_type(&mut link_type, interface) {
+                    packets.push(CapturedPacket {
+                        data: data.to_vec(),
+                        time: timestamp_to_time(ticks, interface.units_per_sec)?,
+                        direction,
+                    });
+                }
+            }
+            PCAPNG_SIMPLE_PACKET_BLOCK => {
+                // Block header, original length and block trailer
+                if block_len < 16 {
+                    return Err(format!("Invalid simple packet block length {} at {}", block_len, offset));
+                }
+                let interface = interfaces.first().ok_or("Packet of unknown interface")?;
+                let original_len = block.u32(8)? as usize;
+                let data = block.slice(12, original_len.min(block_len - 16))?;
+                if accept_link_type(&mut link_type, interface) {
+                    packets.push(CapturedPacket {
+                        data: data.to_vec(),
+                        time: UNIX_EPOCH,
+                        direction: PacketDirection::Unknown,
+                    });
+                }
+            }
+            _ => {}
+        }
+        offset += block_len;
+    }
+    match link_type {
+        Some(link_type) => Ok((link_type, packets)),
+        None => match interfaces.first() {
+            Some(interface) => Ok((interface.link_type.ok_or("Unsupported link type")?, packets)),
+            None => Err("No interface in the capture file".to_string()),
+        },
+    }
+}
+
+/// Read packets of the pcap or pcapng capture
+pub fn read_capture(bytes: &[u8]) -> Result<(LinkType, Vec<CapturedPacket>), String> {
+    if bytes.len() < 24 {
+        return Err("Not a capture file".to_string());
+    }
+    let magic = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    if magic == PCAPNG_SECTION_HEADER_BLOCK {
+        read_pcapng(bytes)
+    } else if [PCAP_MAGIC, PCAP_MAGIC_NANOS, PCAP_MAGIC.swap_bytes(), PCAP_MAGIC_NANOS.swap_bytes()].contains(&magic) {
+        read_pcap(bytes)
+    } else {
+        Err("Not a capture file".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// pcapng section header block and interface description block of an Ethernet interface
+    fn pcapng_header(if_tsresol: Option<u8>) -> Vec<u8> {
+        let mut bytes: Vec<u8> = vec![];
+        bytes.extend_from_slice(&PCAPNG_SECTION_HEADER_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&28u32.to_le_bytes());
+        bytes.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&(-1i64).to_le_bytes());
+        bytes.extend_from_slice(&28u32.to_le_bytes());
+        let mut options: Vec<u8> = vec![];
+        if let Some(resolution) = if_tsresol {
+            options.extend_from_slice(&PCAPNG_OPT_IF_TSRESOL.to_le_bytes());
+            options.extend_from_slice(&1u16.to_le_bytes());
+            options.extend_from_slice(&[resolution, 0, 0, 0]);
+            options.extend_from_slice(&[0; 4]);
+        }
+        let block_len: u32 = 20 + options.len() as u32;
+        bytes.extend_from_slice(&PCAPNG_INTERFACE_DESCRIPTION_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&block_len.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&options);
+        bytes.extend_from_slice(&block_len.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn pcap_round_trip() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_000);
+        let mut writer = PcapWriter::new(vec![], PcapFormat::Pcap, LinkType::Ethernet, "eth0").unwrap();
+        writer.write_packet(&[1, 2, 3, 4, 5], time, PacketDirection::Outbound).unwrap();
+        let (link_type, packets) = read_capture(&writer.writer).unwrap();
+        assert_eq!(link_type, LinkType::Ethernet);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, vec![1, 2, 3, 4, 5]);
+        assert_eq!(packets[0].time, time);
+        assert_eq!(packets[0].direction, PacketDirection::Unknown);
+    }
+
+    #[test]
+    fn pcapng_round_trip() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 654_321_000);
+        let mut writer = PcapWriter::new(vec![], PcapFormat::PcapNg, LinkType::RawIp, "tun0").unwrap();
+        writer.write_packet(&[0x45, 0, 0], time, PacketDirection::Inbound).unwrap();
+        writer.write_packet(&[0x60; 8], time, PacketDirection::Outbound).unwrap();
+        let (link_type, packets) = read_capture(&writer.writer).unwrap();
+        assert_eq!(link_type, LinkType::RawIp);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].data, vec![0x45, 0, 0]);
+        assert_eq!(packets[0].time, time);
+        assert_eq!(packets[0].direction, PacketDirection::Inbound);
+        assert_eq!(packets[1].data, vec![0x60; 8]);
+        assert_eq!(packets[1].direction, PacketDirection::Outbound);
+    }
+
+    #[test]
+    fn pcapng_short_simple_packet_block() {
+        let mut bytes = pcapng_header(None);
+        bytes.extend_from_slice(&PCAPNG_SIMPLE_PACKET_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&12u32.to_le_bytes());
+        bytes.extend_from_slice(&12u32.to_le_bytes());
+        assert!(read_capture(&bytes).is_err());
+    }
+
+    #[test]
+    fn pcapng_simple_packet_block() {
+        let mut bytes = pcapng_header(None);
+        bytes.extend_from_slice(&PCAPNG_SIMPLE_PACKET_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&20u32.to_le_bytes());
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[7, 8, 9, 0]);
+        bytes.extend_from_slice(&20u32.to_le_bytes());
+        let (_, packets) = read_capture(&bytes).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, vec![7, 8, 9]);
+        assert_eq!(packets[0].time, UNIX_EPOCH);
+    }
+
+    #[test]
+    fn pcapng_timestamp_overflow() {
+        // Timestamps in seconds
+        let mut bytes = pcapng_header(Some(0));
+        bytes.extend_from_slice(&PCAPNG_ENHANCED_PACKET_BLOCK.to_le_bytes());
+        bytes.extend_from_slice(&32u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&32u32.to_le_bytes());
+        assert!(read_capture(&bytes).is_err());
+    }
+
+    #[test]
+    fn pending_frames_match_each_send_once() {
+        let mut pending = PendingFrames::default();
+        pending.push(b"probe");
+        pending.push(b"probe");
+        assert!(pending.take(b"probe"));
+        assert!(pending.take(b"probe"));
+        assert!(!pending.take(b"probe"));
+        assert!(!pending.take(b"reply"));
+    }
+
+    #[test]
+    fn pending_frames_drop_oldest_over_limit() {
+        let mut pending = PendingFrames::default();
+        pending.push(b"first");
+        assert!(pending.take(b"first"));
+        // Matched send is skipped when it is dropped
+        pending.push(b"first");
+        for i in 0..PCAP_DUMP_PENDING_LIMIT - 2 {
+            pending.push(&(i as u32).to_be_bytes());
+        }
+        pending.push(b"second");
+        assert!(pending.take(b"first"));
+        for i in 0..PCAP_DUMP_PENDING_LIMIT {
+            pending.push(&(i as u32).to_be_bytes());
+        }
+        assert!(!pending.take(b"second"));
+        assert_eq!(pending.order.len(), PCAP_DUMP_PENDING_LIMIT);
+    }
+
+    #[test]
+    fn truncated_pcap_packet() {
+        let mut writer = PcapWriter::new(vec![], PcapFormat::Pcap, LinkType::Ethernet, "").unwrap();
+        writer.write_packet(&[0; 16], UNIX_EPOCH, PacketDirection::Unknown).unwrap();
+        let mut bytes = writer.writer;
+        bytes.truncate(bytes.len() - 4);
+        assert!(read_capture(&bytes).is_err());
+    }
+}
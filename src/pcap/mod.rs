@@ -1,18 +1,23 @@
+pub mod dump;
 pub mod setting;
 use std::net::IpAddr;
 //use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::collections::HashSet;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 use std::time::Duration;
 use nex::datalink::FrameReceiver;
-use nex::packet::{ip::IpNextLevelProtocol, ethernet::EtherType};
+use nex::packet::{ip::IpNextLevelProtocol, ethernet::{EtherType, ETHERNET_HEADER_LEN}};
 use nex::packet::frame::Frame;
 use nex::packet::frame::ParseOption;
 use crate::packet::frame::PacketFrame;
+use dump::PacketDump;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Packet capture options
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PacketCaptureOptions {
     /// Interface index
     pub interface_index: u32,
@@ -40,6 +45,9 @@ pub struct PacketCaptureOptions {
     pub tunnel: bool,
     /// Loopback interface
     pub loopback: bool,
+    /// Record every received frame to the capture file
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub dump: Option<PacketDump>,
 }
 
 /// Start packet capture
@@ -49,29 +57,27 @@ pub fn start_capture(
     stop: &Arc<Mutex<bool>>,
 ) -> Vec<PacketFrame> {
     let mut frames = Vec::new();
+    capture_each(rx, capture_options, stop, |packet_frame| frames.push(packet_frame));
+    frames
+}
+
+/// Start packet capture and pass each frame to `on_frame` as it arrives, without keeping the frames
+pub fn capture_each<F: FnMut(PacketFrame)>(
+    rx: &mut Box<dyn FrameReceiver>,
+    capture_options: PacketCaptureOptions,
+    stop: &Arc<Mutex<bool>>,
+    mut on_frame: F,
+) {
     let start_time = Instant::now();
     loop {
         match rx.next() {
             Ok(packet) => {
-                let mut parse_option: ParseOption = ParseOption::default();
-                if capture_options.tunnel || (cfg!(any(target_os = "macos", target_os = "ios")) && capture_options.loopback) {
-                    let payload_offset;
-                    if capture_options.loopback {
-                        payload_offset = 14;
-                    } else {
-                        payload_offset = 0;
-                    }
-                    parse_option.from_ip_packet = true;
-                    parse_option.offset = payload_offset;
+                if let Some(dump) = &capture_options.dump {
+                    dump.record_received(packet);
                 }
-                let frame: Frame = Frame::from_bytes(&packet, parse_option);
-                if filter_packet(&frame, &capture_options) {
-                    let packet_frame = PacketFrame::from_nex_frame(&frame);
-                    frames.push(packet_frame);
-                    /* match msg_tx.send(packet_frame) {
-                        Ok(_) => {}
-                        Err(_) => {}
-                    } */
+                if let Some(mut packet_frame) = parse_packet(packet, &capture_options) {
+                    packet_frame.timestamp = Some(SystemTime::now());
+                    on_frame(packet_frame);
                 }
             }
             Err(_) => {}
@@ -88,7 +94,32 @@ pub fn start_capture(
             break;
         }
     }
-    frames
+}
+
+/// Parse the frame and apply the filters of the capture options
+pub(crate) fn parse_packet(packet: &[u8], capture_options: &PacketCaptureOptions) -> Option<PacketFrame> {
+    let mut parse_option: ParseOption = ParseOption::default();
+    if capture_options.tunnel || (cfg!(any(target_os = "macos", target_os = "ios")) && capture_options.loopback) {
+        let payload_offset;
+        if capture_options.loopback {
+            payload_offset = 14;
+        } else {
+            payload_offset = 0;
+        }
+        parse_option.from_ip_packet = true;
+        parse_option.offset = payload_offset;
+    }
+    let from_ip_packet: bool = parse_option.from_ip_packet;
+    let frame: Frame = Frame::from_bytes(packet, parse_option);
+    if !filter_packet(&frame, capture_options) {
+        return None;
+    }
+    let mut packet_frame = PacketFrame::from_nex_frame(&frame);
+    // Keep the payload of non-IP frames (e.g. LLDP, CDP)
+    if !from_ip_packet && frame.ip.is_none() && packet_frame.arp_header.is_none() && packet.len() > ETHERNET_HEADER_LEN {
+        packet_frame.payload = packet[ETHERNET_HEADER_LEN..].to_vec();
+    }
+    Some(packet_frame)
 }
 
 /* /// Start packet capture
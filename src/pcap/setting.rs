@@ -3,9 +3,12 @@ use std::collections::HashSet;
 use std::time::Duration;
 use nex::packet::{ip::IpNextLevelProtocol, ethernet::EtherType};
 use nex::net::interface::Interface;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Packet capture options
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PacketCaptureSetting {
     /// Interface index
     pub interface_index: u32,
@@ -0,0 +1,93 @@
+use std::net::Ipv4Addr;
+use netdev::mac::MacAddr;
+
+/// DHCP magic cookie
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+/// Offset of DHCP options
+const OPTIONS_OFFSET: usize = 240;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_HOST_NAME: u8 = 12;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_VENDOR_CLASS_ID: u8 = 60;
+const OPTION_END: u8 = 255;
+
+/// DHCP message seen on the network
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DhcpMessage {
+    /// BOOTP op code. 1: request, 2: reply
+    pub op: u8,
+    /// DHCP message type (option 53)
+    pub message_type: Option<u8>,
+    /// Client MAC address
+    pub client_mac: MacAddr,
+    /// Client IP address (ciaddr)
+    pub client_ip: Ipv4Addr,
+    /// Assigned IP address (yiaddr)
+    pub your_ip: Ipv4Addr,
+    /// Requested IP address (option 50)
+    pub requested_ip: Option<Ipv4Addr>,
+    /// Host name (option 12)
+    pub hostname: Option<String>,
+    /// Vendor class identifier (option 60)
+    pub vendor_class: Option<String>,
+}
+
+impl DhcpMessage {
+    /// IP address of the client if known
+    pub fn client_addr(&self) -> Option<Ipv4Addr> {
+        [Some(self.client_ip), Some(self.your_ip), self.requested_ip]
+            .into_iter()
+            .flatten()
+            .find(|ip| !ip.is_unspecified())
+    }
+}
+
+fn read_ipv4(data: &[u8], offset: usize) -> Option<Ipv4Addr> {
+    let b = data.get(offset..offset + 4)?;
+    Some(Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+}
+
+/// Parse DHCP (BOOTP) message
+pub(crate) fn parse_message(data: &[u8]) -> Option<DhcpMessage> {
+    if data.len() < OPTIONS_OFFSET || data[236..240] != MAGIC_COOKIE {
+        return None;
+    }
+    // Ethernet hardware type with 6 bytes address
+    if data[1] != 1 || data[2] != 6 {
+        return None;
+    }
+    let mut message = DhcpMessage {
+        op: data[0],
+        message_type: None,
+        client_mac: MacAddr::new(data[28], data[29], data[30], data[31], data[32], data[33]),
+        client_ip: read_ipv4(data, 12)?,
+        your_ip: read_ipv4(data, 16)?,
+        requested_ip: None,
+        hostname: None,
+        vendor_class: None,
+    };
+    let mut offset = OPTIONS_OFFSET;
+    while offset < data.len() {
+        let code = data[offset];
+        if code == OPTION_END {
+            break;
+        }
+        if code == OPTION_PAD {
+            offset += 1;
+            continue;
+        }
+        let len = *data.get(offset + 1)? as usize;
+        let value = data.get(offset + 2..offset + 2 + len)?;
+        match code {
+            OPTION_HOST_NAME => message.hostname = Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_string()),
+            OPTION_REQUESTED_IP => message.requested_ip = read_ipv4(value, 0),
+            OPTION_MESSAGE_TYPE => message.message_type = value.first().copied(),
+            OPTION_VENDOR_CLASS_ID => message.vendor_class = Some(String::from_utf8_lossy(value).to_string()),
+            _ => {}
+        }
+        offset += 2 + len;
+    }
+    Some(message)
+}
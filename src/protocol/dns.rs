@@ -0,0 +1,68 @@
+use hickory_resolver::proto::op::{Message, MessageType, OpCode, Query};
+use hickory_resolver::proto::rr::{DNSClass, Name, RData, Record, RecordType};
+
+/// Build DNS query message
+pub(crate) fn build_query(id: u16, name: &str, record_type: RecordType, query_class: DNSClass, recursion_desired: bool) -> Vec<u8> {
+    let name = match Name::from_ascii(name) {
+        Ok(name) => name,
+        Err(_) => return Vec::new(),
+    };
+    build_name_query(id, name, record_type, query_class, recursion_desired)
+}
+
+/// Build DNS query message for the parsed name
+pub(crate) fn build_name_query(id: u16, name: Name, record_type: RecordType, query_class: DNSClass, recursion_desired: bool) -> Vec<u8> {
+    let mut query = Query::query(name, record_type);
+    query.set_query_class(query_class);
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(recursion_desired)
+        .add_query(query);
+    message.to_vec().unwrap_or_default()
+}
+
+/// Build `version.bind` CHAOS TXT query
+pub(crate) fn build_version_bind_query(id: u16) -> Vec<u8> {
+    build_query(id, "version.bind.", RecordType::TXT, DNSClass::CH, false)
+}
+
+/// Parse DNS response message
+pub(crate) fn parse_response(data: &[u8]) -> Option<Message> {
+    match Message::from_vec(data) {
+        Ok(message) if message.message_type() == MessageType::Response => Some(message),
+        _ => None,
+    }
+}
+
+/// Parse DNS message (query or response)
+pub(crate) fn parse_message(data: &[u8]) -> Option<Message> {
+    Message::from_vec(data).ok()
+}
+
+/// Get TXT strings from the record
+pub(crate) fn txt_strings(record: &Record) -> Vec<String> {
+    match record.data() {
+        Some(RData::TXT(txt)) => txt
+            .txt_data()
+            .iter()
+            .map(|data| String::from_utf8_lossy(data).to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Format DNS name without the trailing dot
+pub(crate) fn name_to_string(name: &Name) -> String {
+    name.to_utf8().trim_end_matches('.').to_string()
+}
+
+/// Format DNS name with unescaped labels (e.g. DNS-SD instance names with spaces)
+pub(crate) fn name_to_display_string(name: &Name) -> String {
+    name.iter()
+        .map(|label| String::from_utf8_lossy(label).to_string())
+        .collect::<Vec<String>>()
+        .join(".")
+}
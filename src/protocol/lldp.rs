@@ -0,0 +1,139 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Destination MAC address of CDP
+pub(crate) const CDP_MAC_ADDR: [u8; 6] = [0x01, 0x00, 0x0c, 0xcc, 0xcc, 0xcc];
+/// LLC/SNAP header of CDP (Cisco OUI, protocol 0x2000)
+const CDP_SNAP_HEADER: [u8; 8] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00];
+
+const LLDP_TLV_END: u8 = 0;
+const LLDP_TLV_PORT_ID: u8 = 2;
+const LLDP_TLV_SYSTEM_NAME: u8 = 5;
+const LLDP_TLV_SYSTEM_DESCRIPTION: u8 = 6;
+const LLDP_TLV_MANAGEMENT_ADDRESS: u8 = 8;
+
+const CDP_TLV_DEVICE_ID: u16 = 0x0001;
+const CDP_TLV_ADDRESSES: u16 = 0x0002;
+const CDP_TLV_PORT_ID: u16 = 0x0003;
+const CDP_TLV_SOFTWARE_VERSION: u16 = 0x0005;
+const CDP_TLV_PLATFORM: u16 = 0x0006;
+
+/// Neighbor device announced by LLDP or CDP
+#[derive(Clone, Debug, PartialEq, Default)]
+pub(crate) struct NeighborInfo {
+    /// System name (LLDP) or device ID (CDP)
+    pub system_name: Option<String>,
+    /// System description (LLDP) or software version (CDP)
+    pub description: Option<String>,
+    /// Platform (CDP only)
+    pub platform: Option<String>,
+    /// Port ID
+    pub port_id: Option<String>,
+    /// Management addresses
+    pub addresses: Vec<IpAddr>,
+}
+
+fn ip_from_bytes(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(data);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+fn printable(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).trim_end_matches('\0').trim().to_string()
+}
+
+/// Parse LLDPDU (payload of ethertype 0x88cc)
+pub(crate) fn parse_lldp(data: &[u8]) -> Option<NeighborInfo> {
+    let mut info = NeighborInfo::default();
+    let mut offset = 0;
+    while offset + 2 <= data.len() {
+        let header = u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let tlv_type = (header >> 9) as u8;
+        let len = (header & 0x01ff) as usize;
+        if tlv_type == LLDP_TLV_END {
+            break;
+        }
+        let value = data.get(offset + 2..offset + 2 + len)?;
+        match tlv_type {
+            // Skip subtype
+            LLDP_TLV_PORT_ID if len > 1 => info.port_id = Some(printable(&value[1..])),
+            LLDP_TLV_SYSTEM_NAME => info.system_name = Some(printable(value)),
+            LLDP_TLV_SYSTEM_DESCRIPTION => info.description = Some(printable(value)),
+            LLDP_TLV_MANAGEMENT_ADDRESS if len > 2 => {
+                // Address string length (subtype + address), subtype, address
+                let addr_len = value[0] as usize;
+                if addr_len > 1 {
+                    if let Some(addr) = value.get(2..1 + addr_len).and_then(ip_from_bytes) {
+                        info.addresses.push(addr);
+                    }
+                }
+            }
+            _ => {}
+        }
+        offset += 2 + len;
+    }
+    Some(info)
+}
+
+/// Parse CDP frame (payload after 802.3 length field)
+pub(crate) fn parse_cdp(data: &[u8]) -> Option<NeighborInfo> {
+    if data.len() < CDP_SNAP_HEADER.len() + 4 || data[..CDP_SNAP_HEADER.len()] != CDP_SNAP_HEADER {
+        return None;
+    }
+    // Skip SNAP header, version, TTL and checksum
+    let data = &data[CDP_SNAP_HEADER.len() + 4..];
+    let mut info = NeighborInfo::default();
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let tlv_type = u16::from_be_bytes([data[offset], data[offset + 1]]);
+        let len = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        if len < 4 {
+            break;
+        }
+        let value = data.get(offset + 4..offset + len)?;
+        match tlv_type {
+            CDP_TLV_DEVICE_ID => info.system_name = Some(printable(value)),
+            CDP_TLV_PORT_ID => info.port_id = Some(printable(value)),
+            CDP_TLV_SOFTWARE_VERSION => info.description = Some(printable(value)),
+            CDP_TLV_PLATFORM => info.platform = Some(printable(value)),
+            CDP_TLV_ADDRESSES => info.addresses.extend(parse_cdp_addresses(value)),
+            _ => {}
+        }
+        offset += len;
+    }
+    Some(info)
+}
+
+/// Parse addresses TLV of CDP
+fn parse_cdp_addresses(data: &[u8]) -> Vec<IpAddr> {
+    let mut addresses: Vec<IpAddr> = vec![];
+    if data.len() < 4 {
+        return addresses;
+    }
+    let count = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let mut offset = 4;
+    for _ in 0..count {
+        // Protocol type, protocol length, protocol, address length, address
+        let proto_len = match data.get(offset + 1) {
+            Some(len) => *len as usize,
+            None => break,
+        };
+        let addr_len_offset = offset + 2 + proto_len;
+        let addr_len = match data.get(addr_len_offset..addr_len_offset + 2) {
+            Some(b) => u16::from_be_bytes([b[0], b[1]]) as usize,
+            None => break,
+        };
+        let addr_offset = addr_len_offset + 2;
+        if let Some(addr) = data.get(addr_offset..addr_offset + addr_len).and_then(ip_from_bytes) {
+            addresses.push(addr);
+        }
+        offset = addr_offset + addr_len;
+    }
+    addresses
+}
@@ -1,4 +1,17 @@
+pub(crate) mod dhcp;
+pub(crate) mod dns;
+pub(crate) mod lldp;
+pub(crate) mod netbios;
+pub(crate) mod ntp;
+pub(crate) mod smb;
+pub(crate) mod snmp;
+pub(crate) mod ssdp;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Protocol {
     ARP,
     NDP,
@@ -0,0 +1,236 @@
+use netdev::mac::MacAddr;
+
+/// NBSTAT (Node Status) query type
+const NBSTAT_TYPE: u16 = 0x0021;
+const IN_CLASS: u16 = 0x0001;
+/// Group name flag in NAME_FLAGS
+const GROUP_NAME_FLAG: u16 = 0x8000;
+
+/// NetBIOS name entry in node status response
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NetbiosNameEntry {
+    pub name: String,
+    pub suffix: u8,
+    pub group: bool,
+}
+
+/// NetBIOS node status response
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NodeStatus {
+    pub names: Vec<NetbiosNameEntry>,
+    pub mac_addr: MacAddr,
+}
+
+impl NodeStatus {
+    /// Workstation name (unique name with suffix 0x00)
+    pub fn hostname(&self) -> Option<String> {
+        self.names
+            .iter()
+            .find(|n| n.suffix == 0x00 && !n.group)
+            .map(|n| n.name.clone())
+    }
+    /// Domain or workgroup name (group name with suffix 0x00)
+    pub fn workgroup(&self) -> Option<String> {
+        self.names
+            .iter()
+            .find(|n| n.suffix == 0x00 && n.group)
+            .map(|n| n.name.clone())
+    }
+}
+
+/// Encode NetBIOS name with first-level encoding
+fn encode_name(name: &str, suffix: u8, buf: &mut Vec<u8>) {
+    let mut raw: [u8; 16] = [0x20; 16];
+    if name == "*" {
+        raw = [0x00; 16];
+        raw[0] = b'*';
+    } else {
+        for (i, b) in name.to_ascii_uppercase().bytes().take(15).enumerate() {
+            raw[i] = b;
+        }
+        raw[15] = suffix;
+    }
+    buf.push(0x20);
+    for b in raw {
+        buf.push(b'A' + (b >> 4));
+        buf.push(b'A' + (b & 0x0f));
+    }
+    buf.push(0x00);
+}
+
+/// Build NBSTAT (Node Status) request for wildcard name
+pub(crate) fn build_nbstat_request(transaction_id: u16) -> Vec<u8> {
+    let mut packet: Vec<u8> = vec![];
+    packet.extend_from_slice(&transaction_id.to_be_bytes());
+    // Flags: query, no recursion
+    packet.extend_from_slice(&[0x00, 0x00]);
+    // QDCOUNT, ANCOUNT, NSCOUNT, ARCOUNT
+    packet.extend_from_slice(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    encode_name("*", 0x00, &mut packet);
+    packet.extend_from_slice(&NBSTAT_TYPE.to_be_bytes());
+    packet.extend_from_slice(&IN_CLASS.to_be_bytes());
+    packet
+}
+
+/// Skip encoded name and return offset of the next field
+fn skip_name(data: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *data.get(offset)? as usize;
+        if len & 0xc0 == 0xc0 {
+            return Some(offset + 2);
+        }
+        offset += 1;
+        if len == 0 {
+            return Some(offset);
+        }
+        offset += len;
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*data.get(offset)?, *data.get(offset + 1)?]))
+}
+
+/// Transaction ID of the name service packet
+pub(crate) fn transaction_id(data: &[u8]) -> Option<u16> {
+    read_u16(data, 0)
+}
+
+/// Parse NBSTAT response
+pub(crate) fn parse_nbstat_response(data: &[u8]) -> Option<NodeStatus> {
+    if data.len() < 12 {
+        return None;
+    }
+    // Response flag
+    if data[2] & 0x80 == 0 {
+        return None;
+    }
+    let ancount = read_u16(data, 6)?;
+    if ancount == 0 {
+        return None;
+    }
+    let mut offset = skip_name(data, 12)?;
+    if read_u16(data, offset)? != NBSTAT_TYPE {
+        return None;
+    }
+    // TYPE, CLASS, TTL, RDLENGTH
+    offset += 10;
+    let num_names = *data.get(offset)? as usize;
+    offset += 1;
+    let mut names: Vec<NetbiosNameEntry> = vec![];
+    for _ in 0..num_names {
+        let entry = data.get(offset..offset + 18)?;
+        let name = String::from_utf8_lossy(&entry[0..15]).trim_end().to_string();
+        let flags = u16::from_be_bytes([entry[16], entry[17]]);
+        names.push(NetbiosNameEntry {
+            name,
+            suffix: entry[15],
+            group: flags & GROUP_NAME_FLAG != 0,
+        });
+        offset += 18;
+    }
+    let mac_addr = match data.get(offset..offset + 6) {
+        Some(mac) => MacAddr::new(mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]),
+        None => MacAddr::zero(),
+    };
+    Some(NodeStatus { names, mac_addr })
+}
+
+/// Decode first-level encoded NetBIOS name at the offset. Returns (name, suffix)
+fn decode_name(data: &[u8], offset: usize) -> Option<(String, u8)> {
+    if *data.get(offset)? != 0x20 {
+        return None;
+    }
+    let encoded = data.get(offset + 1..offset + 33)?;
+    let mut raw: Vec<u8> = vec![];
+    for pair in encoded.chunks(2) {
+        let high = pair[0].checked_sub(b'A')?;
+        let low = pair[1].checked_sub(b'A')?;
+        if high > 0x0f || low > 0x0f {
+            return None;
+        }
+        raw.push((high << 4) | low);
+    }
+    let name = String::from_utf8_lossy(&raw[0..15]).trim_end().to_string();
+    Some((name, raw[15]))
+}
+
+/// Parse name registration, refresh or multi-homed registration request. Returns (name, suffix)
+pub(crate) fn parse_name_registration(data: &[u8]) -> Option<(String, u8)> {
+    let flags = read_u16(data, 2)?;
+    // Request only
+    if flags & 0x8000 != 0 {
+        return None;
+    }
+    let opcode = (flags >> 11) & 0x0f;
+    if !matches!(opcode, 5 | 8 | 9 | 15) {
+        return None;
+    }
+    decode_name(data, 12)
+}
+
+/// Parse source name of NetBIOS datagram (direct unique, direct group or broadcast). Returns (name, suffix)
+pub(crate) fn parse_datagram_source_name(data: &[u8]) -> Option<(String, u8)> {
+    if !(0x10..=0x12).contains(data.first()?) {
+        return None;
+    }
+    decode_name(data, 14)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// NBSTAT response of host FS01 in workgroup CORP, up to the MAC address
+    const NBSTAT_RESPONSE: &str = "12348400000000010000000020434b4141414141414141414141414141414141414141414141414141414141410000\
+        21000100000000006503465330312020202020202020202020000400434f5250202020202020202020202000840046533031202020202020\
+        2020202020200400";
+    /// MAC address field of the response. Followed by 40 bytes of statistics
+    const NBSTAT_STATISTICS: &str = "00155d010203";
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn nbstat_response() -> Vec<u8> {
+        [hex(NBSTAT_RESPONSE), hex(NBSTAT_STATISTICS), vec![0; 40]].concat()
+    }
+
+    #[test]
+    fn parse_node_status() {
+        let data = nbstat_response();
+        assert_eq!(transaction_id(&data), Some(0x1234));
+        let node_status = parse_nbstat_response(&data).unwrap();
+        assert_eq!(node_status.names.len(), 3);
+        assert_eq!(node_status.hostname().as_deref(), Some("FS01"));
+        assert_eq!(node_status.workgroup().as_deref(), Some("CORP"));
+        assert_eq!(node_status.names[2], NetbiosNameEntry { name: "FS01".to_string(), suffix: 0x20, group: false });
+        assert_eq!(node_status.mac_addr, MacAddr::new(0x00, 0x15, 0x5d, 0x01, 0x02, 0x03));
+    }
+
+    #[test]
+    fn parse_truncated_node_status() {
+        let data = nbstat_response();
+        // MAC address cut off
+        let node_status = parse_nbstat_response(&hex(NBSTAT_RESPONSE)).unwrap();
+        assert_eq!(node_status.names.len(), 3);
+        assert_eq!(node_status.mac_addr, MacAddr::zero());
+        // Name table cut off
+        assert_eq!(parse_nbstat_response(&data[..100]), None);
+        // Question name cut off
+        assert_eq!(parse_nbstat_response(&data[..30]), None);
+        assert_eq!(parse_nbstat_response(&data[..11]), None);
+    }
+
+    #[test]
+    fn reject_nbstat_request() {
+        let request = build_nbstat_request(0x1234);
+        assert_eq!(transaction_id(&request), Some(0x1234));
+        assert_eq!(parse_nbstat_response(&request), None);
+        // Number of names beyond the packet
+        let mut data = nbstat_response();
+        data[56] = 0xff;
+        assert_eq!(parse_nbstat_response(&data), None);
+    }
+}
@@ -0,0 +1,78 @@
+/// NTP control message (mode 6) opcode: read variables
+const CTL_OP_READVAR: u8 = 0x02;
+/// NTP private message (mode 7) request code: MON_GETLIST_1
+const REQ_MON_GETLIST_1: u8 = 0x2a;
+/// NTP private message (mode 7) implementation: XNTPD
+const IMPL_XNTPD: u8 = 0x03;
+
+/// Build NTP mode 6 READVAR request
+pub(crate) fn build_readvar_request(sequence: u16) -> Vec<u8> {
+    let mut packet: Vec<u8> = vec![
+        // LI = 0, VN = 2, Mode = 6
+        0x16,
+        CTL_OP_READVAR,
+    ];
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    // Status, Association ID, Offset, Count
+    packet.extend_from_slice(&[0x00; 8]);
+    packet
+}
+
+/// Build NTP mode 7 MON_GETLIST_1 (monlist) request
+pub(crate) fn build_monlist_request() -> Vec<u8> {
+    let mut packet: Vec<u8> = vec![
+        // R = 0, M = 0, VN = 2, Mode = 7
+        0x17,
+        // Auth = 0, Sequence = 0
+        0x00,
+        IMPL_XNTPD,
+        REQ_MON_GETLIST_1,
+    ];
+    packet.extend_from_slice(&[0x00; 44]);
+    packet
+}
+
+/// Parse NTP mode 6 READVAR response and return variables
+pub(crate) fn parse_readvar_response(data: &[u8]) -> Option<Vec<(String, String)>> {
+    if data.len() < 12 || data[0] & 0x07 != 6 || data[1] & 0x80 == 0 {
+        return None;
+    }
+    let count = u16::from_be_bytes([data[10], data[11]]) as usize;
+    let end = std::cmp::min(12 + count, data.len());
+    let text = String::from_utf8_lossy(&data[12..end]);
+    let mut variables: Vec<(String, String)> = vec![];
+    let mut current = String::new();
+    let mut in_quote = false;
+    for c in text.chars() {
+        match c {
+            '"' => in_quote = !in_quote,
+            ',' if !in_quote => {
+                if let Some((k, v)) = current.split_once('=') {
+                    variables.push((k.trim().to_string(), v.trim().to_string()));
+                }
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        if c != '"' {
+            current.push(c);
+        }
+    }
+    if let Some((k, v)) = current.split_once('=') {
+        variables.push((k.trim().to_string(), v.trim().to_string()));
+    }
+    Some(variables)
+}
+
+/// Parse NTP mode 7 MON_GETLIST_1 response.
+///
+/// Returns (error code, number of items)
+pub(crate) fn parse_monlist_response(data: &[u8]) -> Option<(u8, u16)> {
+    if data.len() < 8 || data[0] & 0x07 != 7 || data[0] & 0x80 == 0 || data[3] != REQ_MON_GETLIST_1 {
+        return None;
+    }
+    let err = data[4] >> 4;
+    let items = (((data[4] & 0x0f) as u16) << 8) | data[5] as u16;
+    Some((err, items))
+}
@@ -0,0 +1,428 @@
+/// NetBIOS session service message type
+const SESSION_MESSAGE: u8 = 0x00;
+const SMB1_PROTOCOL_ID: [u8; 4] = [0xff, b'S', b'M', b'B'];
+const SMB2_PROTOCOL_ID: [u8; 4] = [0xfe, b'S', b'M', b'B'];
+const SMB1_COM_NEGOTIATE: u8 = 0x72;
+const SMB2_NEGOTIATE: u16 = 0x0000;
+const SMB2_SESSION_SETUP: u16 = 0x0001;
+const SMB2_HEADER_LEN: usize = 64;
+const STATUS_SUCCESS: u32 = 0x00000000;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc0000016;
+pub(crate) const SMB2_SIGNING_ENABLED: u16 = 0x0001;
+pub(crate) const SMB2_SIGNING_REQUIRED: u16 = 0x0002;
+pub(crate) const DIALECT_SMB_3_1_1: u16 = 0x0311;
+const SMB2_PREAUTH_INTEGRITY_CAPABILITIES: u16 = 0x0001;
+const SMB2_ENCRYPTION_CAPABILITIES: u16 = 0x0002;
+const NTLMSSP_SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+const NTLMSSP_NEGOTIATE_VERSION: u32 = 0x02000000;
+/// UNICODE, OEM, REQUEST_TARGET, NTLM, ALWAYS_SIGN, EXTENDED_SESSIONSECURITY, TARGET_INFO, VERSION, 128, 56
+const NTLMSSP_NEGOTIATE_FLAGS: u32 = 0xa2888207;
+/// OID 1.3.6.1.5.5.2
+const SPNEGO_OID: [u8; 6] = [0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+/// OID 1.3.6.1.4.1.311.2.2.10
+const NTLMSSP_OID: [u8; 10] = [0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a];
+
+/// SMB2 NEGOTIATE response
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Smb2NegotiateResponse {
+    pub security_mode: u16,
+    pub dialect: u16,
+    /// FILETIME
+    pub system_time: u64,
+}
+
+/// Server information in NTLMSSP CHALLENGE message
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct NtlmChallenge {
+    /// (major, minor, build)
+    pub version: Option<(u8, u8, u16)>,
+    pub target_name: Option<String>,
+    pub netbios_computer_name: Option<String>,
+    pub netbios_domain_name: Option<String>,
+    pub dns_computer_name: Option<String>,
+    pub dns_domain_name: Option<String>,
+    pub dns_tree_name: Option<String>,
+    /// FILETIME
+    pub timestamp: Option<u64>,
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64_le(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// Decode UTF-16LE string
+fn utf16le_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Add NetBIOS session header for direct TCP transport
+fn session_message(message: Vec<u8>) -> Vec<u8> {
+    let len = message.len() as u32;
+    let mut packet: Vec<u8> = vec![SESSION_MESSAGE, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    packet.extend(message);
+    packet
+}
+
+/// Length of the SMB message following the NetBIOS session header. None if not a session message
+pub(crate) fn session_message_len(header: &[u8; 4]) -> Option<usize> {
+    if header[0] != SESSION_MESSAGE {
+        return None;
+    }
+    Some(((header[1] as usize) << 16) | ((header[2] as usize) << 8) | header[3] as usize)
+}
+
+/// Build SMB1 NEGOTIATE request with `NT LM 0.12` dialect only
+pub(crate) fn build_smb1_negotiate_request() -> Vec<u8> {
+    let mut message: Vec<u8> = vec![];
+    message.extend_from_slice(&SMB1_PROTOCOL_ID);
+    message.push(SMB1_COM_NEGOTIATE);
+    // Status
+    message.extend_from_slice(&[0x00; 4]);
+    // Flags: case insensitive, canonicalized paths
+    message.push(0x18);
+    // Flags2: unicode, NT status, extended security, long names
+    message.extend_from_slice(&0xc801u16.to_le_bytes());
+    // PIDHigh, SecurityFeatures, Reserved
+    message.extend_from_slice(&[0x00; 12]);
+    // TID
+    message.extend_from_slice(&0xffffu16.to_le_bytes());
+    // PIDLow
+    message.extend_from_slice(&rand::random::<u16>().to_le_bytes());
+    // UID, MID
+    message.extend_from_slice(&[0x00; 4]);
+    // WordCount
+    message.push(0x00);
+    let dialects: &[u8] = b"\x02NT LM 0.12\x00";
+    message.extend_from_slice(&(dialects.len() as u16).to_le_bytes());
+    message.extend_from_slice(dialects);
+    session_message(message)
+}
+
+/// Check if SMB1 NEGOTIATE response accepted the dialect
+pub(crate) fn parse_smb1_negotiate_response(data: &[u8]) -> bool {
+    if data.get(0..4) != Some(&SMB1_PROTOCOL_ID[..]) || data.get(4) != Some(&SMB1_COM_NEGOTIATE) {
+        return false;
+    }
+    if read_u32_le(data, 5) != Some(STATUS_SUCCESS) {
+        return false;
+    }
+    match (data.get(32), read_u16_le(data, 33)) {
+        (Some(word_count), Some(dialect_index)) => *word_count > 0 && dialect_index != 0xffff,
+        _ => false,
+    }
+}
+
+fn smb2_header(command: u16, message_id: u64) -> Vec<u8> {
+    let mut header: Vec<u8> = Vec::with_capacity(SMB2_HEADER_LEN);
+    header.extend_from_slice(&SMB2_PROTOCOL_ID);
+    // StructureSize
+    header.extend_from_slice(&(SMB2_HEADER_LEN as u16).to_le_bytes());
+    // CreditCharge
+    header.extend_from_slice(&0u16.to_le_bytes());
+    // Status
+    header.extend_from_slice(&0u32.to_le_bytes());
+    header.extend_from_slice(&command.to_le_bytes());
+    // CreditRequest
+    header.extend_from_slice(&1u16.to_le_bytes());
+    // Flags, NextCommand
+    header.extend_from_slice(&[0x00; 8]);
+    header.extend_from_slice(&message_id.to_le_bytes());
+    // Reserved, TreeId, SessionId, Signature
+    header.extend_from_slice(&[0x00; 32]);
+    header
+}
+
+fn pad8(buf: &mut Vec<u8>) {
+    buf.resize(buf.len().next_multiple_of(8), 0x00);
+}
+
+fn push_negotiate_context(buf: &mut Vec<u8>, context_type: u16, data: &[u8]) {
+    buf.extend_from_slice(&context_type.to_le_bytes());
+    buf.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    // Reserved
+    buf.extend_from_slice(&[0x00; 4]);
+    buf.extend_from_slice(data);
+}
+
+/// Build SMB2 NEGOTIATE request. Negotiate contexts are added if SMB 3.1.1 is offered
+pub(crate) fn build_smb2_negotiate_request(dialects: &[u16]) -> Vec<u8> {
+    let mut message: Vec<u8> = smb2_header(SMB2_NEGOTIATE, 0);
+    // StructureSize
+    message.extend_from_slice(&36u16.to_le_bytes());
+    message.extend_from_slice(&(dialects.len() as u16).to_le_bytes());
+    message.extend_from_slice(&SMB2_SIGNING_ENABLED.to_le_bytes());
+    // Reserved, Capabilities
+    message.extend_from_slice(&[0x00; 6]);
+    // ClientGuid
+    message.extend_from_slice(&rand::random::<[u8; 16]>());
+    // NegotiateContextOffset, NegotiateContextCount, Reserved2 (ClientStartTime before 3.1.1)
+    let context_field: usize = message.len();
+    message.extend_from_slice(&[0x00; 8]);
+    for dialect in dialects {
+        message.extend_from_slice(&dialect.to_le_bytes());
+    }
+    if dialects.contains(&DIALECT_SMB_3_1_1) {
+        pad8(&mut message);
+        let context_offset = message.len() as u32;
+        message[context_field..context_field + 4].copy_from_slice(&context_offset.to_le_bytes());
+        message[context_field + 4..context_field + 6].copy_from_slice(&2u16.to_le_bytes());
+        // SHA-512 with 32 bytes salt
+        let mut preauth: Vec<u8> = vec![];
+        preauth.extend_from_slice(&1u16.to_le_bytes());
+        preauth.extend_from_slice(&32u16.to_le_bytes());
+        preauth.extend_from_slice(&0x0001u16.to_le_bytes());
+        preauth.extend_from_slice(&rand::random::<[u8; 32]>());
+        push_negotiate_context(&mut message, SMB2_PREAUTH_INTEGRITY_CAPABILITIES, &preauth);
+        pad8(&mut message);
+        // AES-128-GCM, AES-128-CCM
+        let mut encryption: Vec<u8> = vec![];
+        encryption.extend_from_slice(&2u16.to_le_bytes());
+        encryption.extend_from_slice(&0x0002u16.to_le_bytes());
+        encryption.extend_from_slice(&0x0001u16.to_le_bytes());
+        push_negotiate_context(&mut message, SMB2_ENCRYPTION_CAPABILITIES, &encryption);
+    }
+    session_message(message)
+}
+
+/// Parse SMB2 NEGOTIATE response
+pub(crate) fn parse_smb2_negotiate_response(data: &[u8]) -> Option<Smb2NegotiateResponse> {
+    if data.get(0..4)? != SMB2_PROTOCOL_ID {
+        return None;
+    }
+    if read_u32_le(data, 8)? != STATUS_SUCCESS || read_u16_le(data, 12)? != SMB2_NEGOTIATE {
+        return None;
+    }
+    let body = data.get(SMB2_HEADER_LEN..)?;
+    if read_u16_le(body, 0)? != 65 {
+        return None;
+    }
+    Some(Smb2NegotiateResponse {
+        security_mode: read_u16_le(body, 2)?,
+        dialect: read_u16_le(body, 4)?,
+        system_time: read_u64_le(body, 40)?,
+    })
+}
+
+/// Build SMB2 SESSION_SETUP request with the security buffer
+pub(crate) fn build_smb2_session_setup_request(message_id: u64, security_buffer: &[u8]) -> Vec<u8> {
+    let mut message: Vec<u8> = smb2_header(SMB2_SESSION_SETUP, message_id);
+    // StructureSize
+    message.extend_from_slice(&25u16.to_le_bytes());
+    // Flags
+    message.push(0x00);
+    message.push(SMB2_SIGNING_ENABLED as u8);
+    // Capabilities, Channel
+    message.extend_from_slice(&[0x00; 8]);
+    message.extend_from_slice(&((SMB2_HEADER_LEN + 24) as u16).to_le_bytes());
+    message.extend_from_slice(&(security_buffer.len() as u16).to_le_bytes());
+    // PreviousSessionId
+    message.extend_from_slice(&[0x00; 8]);
+    message.extend_from_slice(security_buffer);
+    session_message(message)
+}
+
+/// Check if SMB2 SESSION_SETUP response continues authentication
+pub(crate) fn is_session_setup_challenge(data: &[u8]) -> bool {
+    data.get(0..4) == Some(&SMB2_PROTOCOL_ID[..])
+        && read_u16_le(data, 12) == Some(SMB2_SESSION_SETUP)
+        && read_u32_le(data, 8) == Some(STATUS_MORE_PROCESSING_REQUIRED)
+}
+
+/// Build NTLMSSP NEGOTIATE message
+pub(crate) fn build_ntlm_negotiate() -> Vec<u8> {
+    let mut message: Vec<u8> = NTLMSSP_SIGNATURE.to_vec();
+    // MessageType
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&NTLMSSP_NEGOTIATE_FLAGS.to_le_bytes());
+    // DomainNameFields, WorkstationFields
+    message.extend_from_slice(&[0x00; 16]);
+    // Version: 6.1 build 7601, NTLMSSP revision 15
+    message.extend_from_slice(&[6, 1]);
+    message.extend_from_slice(&7601u16.to_le_bytes());
+    message.extend_from_slice(&[0x00, 0x00, 0x00, 0x0f]);
+    message
+}
+
+/// Encode DER element
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let len = content.len();
+    let mut element: Vec<u8> = vec![tag];
+    if len < 0x80 {
+        element.push(len as u8);
+    } else if len < 0x100 {
+        element.extend_from_slice(&[0x81, len as u8]);
+    } else {
+        element.extend_from_slice(&[0x82, (len >> 8) as u8, len as u8]);
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+/// Wrap the NTLMSSP token in SPNEGO NegTokenInit
+pub(crate) fn build_spnego_init(mech_token: &[u8]) -> Vec<u8> {
+    let mech_types = der(0xa0, &der(0x30, &der(0x06, &NTLMSSP_OID)));
+    let token = der(0xa2, &der(0x04, mech_token));
+    let neg_token_init = der(0xa0, &der(0x30, &[mech_types, token].concat()));
+    der(0x60, &[der(0x06, &SPNEGO_OID), neg_token_init].concat())
+}
+
+/// Find and parse NTLMSSP CHALLENGE message in the data (raw or SPNEGO wrapped)
+pub(crate) fn parse_ntlm_challenge(data: &[u8]) -> Option<NtlmChallenge> {
+    let start = data
+        .windows(NTLMSSP_SIGNATURE.len())
+        .position(|window| window == NTLMSSP_SIGNATURE)?;
+    let message = &data[start..];
+    if read_u32_le(message, 8)? != 2 {
+        return None;
+    }
+    let mut challenge = NtlmChallenge::default();
+    let target_name_len = read_u16_le(message, 12)? as usize;
+    let target_name_offset = read_u32_le(message, 16)? as usize;
+    if let Some(target_name) = message.get(target_name_offset..target_name_offset + target_name_len) {
+        if !target_name.is_empty() {
+            challenge.target_name = Some(utf16le_string(target_name));
+        }
+    }
+    let flags = read_u32_le(message, 20)?;
+    let target_info_len = read_u16_le(message, 40)? as usize;
+    let target_info_offset = read_u32_le(message, 44)? as usize;
+    if flags & NTLMSSP_NEGOTIATE_VERSION != 0 && target_info_offset >= 56 {
+        if let Some(version) = message.get(48..56) {
+            challenge.version = Some((version[0], version[1], u16::from_le_bytes([version[2], version[3]])));
+        }
+    }
+    let target_info = match message.get(target_info_offset..target_info_offset + target_info_len) {
+        Some(target_info) => target_info,
+        None => return Some(challenge),
+    };
+    // AV_PAIR list terminated by MsvAvEOL
+    let mut offset: usize = 0;
+    while let (Some(av_id), Some(av_len)) = (read_u16_le(target_info, offset), read_u16_le(target_info, offset + 2)) {
+        let value = match target_info.get(offset + 4..offset + 4 + av_len as usize) {
+            Some(value) => value,
+            None => break,
+        };
+        match av_id {
+            0 => break,
+            1 => challenge.netbios_computer_name = Some(utf16le_string(value)),
+            2 => challenge.netbios_domain_name = Some(utf16le_string(value)),
+            3 => challenge.dns_computer_name = Some(utf16le_string(value)),
+            4 => challenge.dns_domain_name = Some(utf16le_string(value)),
+            5 => challenge.dns_tree_name = Some(utf16le_string(value)),
+            7 => challenge.timestamp = read_u64_le(value, 0),
+            _ => {}
+        }
+        offset += 4 + av_len as usize;
+    }
+    Some(challenge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SMB2 NEGOTIATE response laid out as sent by Windows Server 2022: SMB 3.1.1, signing enabled
+    const NEGOTIATE_RESPONSE: &str = "fe534d4240000100000000000000010001000000000000000000000000000000fffe0000000000000000000000000000\
+        00000000000000000000000000000000410001001103020068af5e0d9c2b4e8f8a7d3c2b1a0987652f000000000080000000800000008000\
+        00a087fccf6bda0100000000000000008000000000000000";
+    /// NTLMSSP CHALLENGE message of the same server (domain CORP, computer FS01, version 10.0 build 20348)
+    const CHALLENGE_MESSAGE: &str = "4e544c4d5353500002000000080008003800000015828ae2887766554433221100000000000000007a007a004000\
+        00000a007c4f0000000f43004f00520050000200080043004f00520050000100080046005300300031000400140063006f00720070002e006c\
+        006f00630061006c0003001e0066007300300031002e0063006f00720070002e006c006f00630061006c000500140063006f00720070002e00\
+        6c006f00630061006c000700080000a087fccf6bda0100000000";
+    /// FILETIME of 2024-03-01 12:00:00 UTC
+    const FILETIME: u64 = 133537680000000000;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn parse_negotiate_response() {
+        let response = parse_smb2_negotiate_response(&hex(NEGOTIATE_RESPONSE)).unwrap();
+        assert_eq!(response.dialect, DIALECT_SMB_3_1_1);
+        assert_eq!(response.security_mode, SMB2_SIGNING_ENABLED);
+        assert_eq!(response.system_time, FILETIME);
+    }
+
+    #[test]
+    fn reject_invalid_negotiate_response() {
+        let data = hex(NEGOTIATE_RESPONSE);
+        // Truncated before SystemTime
+        assert_eq!(parse_smb2_negotiate_response(&data[..SMB2_HEADER_LEN + 40]), None);
+        assert_eq!(parse_smb2_negotiate_response(&data[..SMB2_HEADER_LEN]), None);
+        assert_eq!(parse_smb2_negotiate_response(&data[..3]), None);
+        // Error status
+        let mut error = data.clone();
+        error[8..12].copy_from_slice(&0xc0000022u32.to_le_bytes());
+        assert_eq!(parse_smb2_negotiate_response(&error), None);
+        // SMB1
+        let mut smb1 = data;
+        smb1[0] = 0xff;
+        assert_eq!(parse_smb2_negotiate_response(&smb1), None);
+    }
+
+    #[test]
+    fn parse_challenge_message() {
+        let challenge = parse_ntlm_challenge(&hex(CHALLENGE_MESSAGE)).unwrap();
+        assert_eq!(challenge.version, Some((10, 0, 20348)));
+        assert_eq!(challenge.target_name.as_deref(), Some("CORP"));
+        assert_eq!(challenge.netbios_computer_name.as_deref(), Some("FS01"));
+        assert_eq!(challenge.netbios_domain_name.as_deref(), Some("CORP"));
+        assert_eq!(challenge.dns_computer_name.as_deref(), Some("fs01.corp.local"));
+        assert_eq!(challenge.dns_domain_name.as_deref(), Some("corp.local"));
+        assert_eq!(challenge.dns_tree_name.as_deref(), Some("corp.local"));
+        assert_eq!(challenge.timestamp, Some(FILETIME));
+        // Wrapped in SPNEGO and SMB2 SESSION_SETUP response
+        let wrapped: Vec<u8> = [smb2_header(SMB2_SESSION_SETUP, 1), build_spnego_init(&hex(CHALLENGE_MESSAGE))].concat();
+        assert_eq!(parse_ntlm_challenge(&wrapped), Some(challenge));
+    }
+
+    #[test]
+    fn parse_truncated_challenge_message() {
+        let data = hex(CHALLENGE_MESSAGE);
+        // Target info cut off. Fixed fields are still read
+        let challenge = parse_ntlm_challenge(&data[..100]).unwrap();
+        assert_eq!(challenge.version, Some((10, 0, 20348)));
+        assert_eq!(challenge.target_name.as_deref(), Some("CORP"));
+        assert_eq!(challenge.netbios_computer_name, None);
+        assert_eq!(challenge.timestamp, None);
+        // Fixed fields cut off
+        assert_eq!(parse_ntlm_challenge(&data[..40]), None);
+        assert_eq!(parse_ntlm_challenge(&data[..8]), None);
+        // NEGOTIATE message
+        assert_eq!(parse_ntlm_challenge(&build_ntlm_negotiate()), None);
+    }
+
+    #[test]
+    fn parse_over_long_av_pair() {
+        let mut data = hex(CHALLENGE_MESSAGE);
+        // Length of the MsvAvNbComputerName pair (after MsvAvNbDomainName) beyond the target info
+        let pair_offset: usize = 64 + 4 + 8;
+        assert_eq!(read_u16_le(&data, pair_offset), Some(1));
+        data[pair_offset + 2..pair_offset + 4].copy_from_slice(&0xffffu16.to_le_bytes());
+        let challenge = parse_ntlm_challenge(&data).unwrap();
+        assert_eq!(challenge.netbios_domain_name.as_deref(), Some("CORP"));
+        assert_eq!(challenge.netbios_computer_name, None);
+        assert_eq!(challenge.dns_computer_name, None);
+        // Target info length beyond the message
+        let mut data = hex(CHALLENGE_MESSAGE);
+        data[40..42].copy_from_slice(&0xffffu16.to_le_bytes());
+        let challenge = parse_ntlm_challenge(&data).unwrap();
+        assert_eq!(challenge.target_name.as_deref(), Some("CORP"));
+        assert_eq!(challenge.netbios_domain_name, None);
+    }
+}
@@ -0,0 +1,348 @@
+use std::net::Ipv4Addr;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OBJECT_IDENTIFIER: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+pub(crate) const PDU_GET_REQUEST: u8 = 0xa0;
+pub(crate) const PDU_GET_NEXT_REQUEST: u8 = 0xa1;
+pub(crate) const PDU_GET_RESPONSE: u8 = 0xa2;
+
+/// SNMP version
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SnmpVersion {
+    V1,
+    V2c,
+}
+
+impl SnmpVersion {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SnmpVersion::V1 => "v1",
+            SnmpVersion::V2c => "v2c",
+        }
+    }
+    fn to_i64(self) -> i64 {
+        match self {
+            SnmpVersion::V1 => 0,
+            SnmpVersion::V2c => 1,
+        }
+    }
+    fn from_i64(version: i64) -> Option<SnmpVersion> {
+        match version {
+            0 => Some(SnmpVersion::V1),
+            1 => Some(SnmpVersion::V2c),
+            _ => None,
+        }
+    }
+}
+
+/// Value of SNMP variable binding
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SnmpValue {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Null,
+    ObjectIdentifier(Vec<u32>),
+    IpAddress(Ipv4Addr),
+    Counter32(u32),
+    Gauge32(u32),
+    TimeTicks(u32),
+    Counter64(u64),
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+    Other(u8, Vec<u8>),
+}
+
+impl SnmpValue {
+    /// Whether the value is an exception (noSuchObject, noSuchInstance, endOfMibView)
+    pub fn is_exception(&self) -> bool {
+        matches!(self, SnmpValue::NoSuchObject | SnmpValue::NoSuchInstance | SnmpValue::EndOfMibView)
+    }
+    /// String representation of the value
+    pub fn to_string_lossy(&self) -> String {
+        match self {
+            SnmpValue::Integer(v) => v.to_string(),
+            SnmpValue::OctetString(v) => String::from_utf8_lossy(v).trim_end_matches('\0').to_string(),
+            SnmpValue::Null => String::new(),
+            SnmpValue::ObjectIdentifier(v) => format_oid(v),
+            SnmpValue::IpAddress(v) => v.to_string(),
+            SnmpValue::Counter32(v) | SnmpValue::Gauge32(v) | SnmpValue::TimeTicks(v) => v.to_string(),
+            SnmpValue::Counter64(v) => v.to_string(),
+            SnmpValue::NoSuchObject => String::from("noSuchObject"),
+            SnmpValue::NoSuchInstance => String::from("noSuchInstance"),
+            SnmpValue::EndOfMibView => String::from("endOfMibView"),
+            SnmpValue::Other(_, v) => v.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// SNMP v1/v2c message
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SnmpMessage {
+    pub version: SnmpVersion,
+    pub community: Vec<u8>,
+    pub pdu_type: u8,
+    pub request_id: i32,
+    pub error_status: i64,
+    pub error_index: i64,
+    pub varbinds: Vec<(Vec<u32>, SnmpValue)>,
+}
+
+/// Parse dotted OID string (e.g. "1.3.6.1.2.1.1.1.0")
+pub(crate) fn parse_oid(oid: &str) -> Vec<u32> {
+    oid.trim_start_matches('.')
+        .split('.')
+        .filter_map(|s| s.parse::<u32>().ok())
+        .collect()
+}
+
+/// Format OID as dotted string
+pub(crate) fn format_oid(oid: &[u32]) -> String {
+    oid.iter().map(|n| n.to_string()).collect::<Vec<String>>().join(".")
+}
+
+fn encode_length(len: usize, buf: &mut Vec<u8>) {
+    if len < 0x80 {
+        buf.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().iter().skip_while(|b| **b == 0).cloned().collect();
+        buf.push(0x80 | bytes.len() as u8);
+        buf.extend_from_slice(&bytes);
+    }
+}
+
+fn encode_tlv(tag: u8, value: &[u8], buf: &mut Vec<u8>) {
+    buf.push(tag);
+    encode_length(value.len(), buf);
+    buf.extend_from_slice(value);
+}
+
+fn encode_integer(value: i64, buf: &mut Vec<u8>) {
+    let bytes = value.to_be_bytes();
+    let mut start: usize = 0;
+    // Remove redundant leading bytes (two's complement minimal form)
+    while start < 7 {
+        let b = bytes[start];
+        let next_msb = bytes[start + 1] & 0x80;
+        if (b == 0x00 && next_msb == 0) || (b == 0xff && next_msb != 0) {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+    encode_tlv(TAG_INTEGER, &bytes[start..], buf);
+}
+
+fn encode_oid(oid: &[u32], buf: &mut Vec<u8>) {
+    let mut value: Vec<u8> = vec![];
+    if oid.len() >= 2 {
+        encode_base128(oid[0] * 40 + oid[1], &mut value);
+        for n in &oid[2..] {
+            encode_base128(*n, &mut value);
+        }
+    }
+    encode_tlv(TAG_OBJECT_IDENTIFIER, &value, buf);
+}
+
+fn encode_base128(mut n: u32, buf: &mut Vec<u8>) {
+    let mut tmp: Vec<u8> = vec![(n & 0x7f) as u8];
+    n >>= 7;
+    while n > 0 {
+        tmp.push(((n & 0x7f) as u8) | 0x80);
+        n >>= 7;
+    }
+    tmp.reverse();
+    buf.extend_from_slice(&tmp);
+}
+
+/// Build SNMP request message
+pub(crate) fn build_request(version: SnmpVersion, community: &str, pdu_type: u8, request_id: i32, oids: &[Vec<u32>]) -> Vec<u8> {
+    let mut varbind_list: Vec<u8> = vec![];
+    for oid in oids {
+        let mut varbind: Vec<u8> = vec![];
+        encode_oid(oid, &mut varbind);
+        encode_tlv(TAG_NULL, &[], &mut varbind);
+        encode_tlv(TAG_SEQUENCE, &varbind, &mut varbind_list);
+    }
+    let mut pdu: Vec<u8> = vec![];
+    encode_integer(request_id as i64, &mut pdu);
+    encode_integer(0, &mut pdu);
+    encode_integer(0, &mut pdu);
+    encode_tlv(TAG_SEQUENCE, &varbind_list, &mut pdu);
+    let mut message: Vec<u8> = vec![];
+    encode_integer(version.to_i64(), &mut message);
+    encode_tlv(TAG_OCTET_STRING, community.as_bytes(), &mut message);
+    encode_tlv(pdu_type, &pdu, &mut message);
+    let mut packet: Vec<u8> = vec![];
+    encode_tlv(TAG_SEQUENCE, &message, &mut packet);
+    packet
+}
+
+/// Build SNMP GetRequest message
+pub(crate) fn build_get_request(version: SnmpVersion, community: &str, request_id: i32, oids: &[Vec<u32>]) -> Vec<u8> {
+    build_request(version, community, PDU_GET_REQUEST, request_id, oids)
+}
+
+/// Read TLV and return (tag, value, rest)
+fn decode_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first_len = *data.get(1)?;
+    let (len, header_len) = if first_len & 0x80 == 0 {
+        (first_len as usize, 2)
+    } else {
+        let num_bytes = (first_len & 0x7f) as usize;
+        if num_bytes == 0 || num_bytes > 4 {
+            return None;
+        }
+        let mut len: usize = 0;
+        for i in 0..num_bytes {
+            len = (len << 8) | *data.get(2 + i)? as usize;
+        }
+        (len, 2 + num_bytes)
+    };
+    let end = header_len.checked_add(len)?;
+    if data.len() < end {
+        return None;
+    }
+    Some((tag, &data[header_len..end], &data[end..]))
+}
+
+fn decode_integer(value: &[u8]) -> Option<i64> {
+    if value.is_empty() || value.len() > 8 {
+        return None;
+    }
+    let mut n: i64 = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    for b in value {
+        n = (n << 8) | *b as i64;
+    }
+    Some(n)
+}
+
+fn decode_unsigned(value: &[u8]) -> Option<u64> {
+    if value.len() > 9 {
+        return None;
+    }
+    let mut n: u64 = 0;
+    for b in value {
+        n = (n << 8) | *b as u64;
+    }
+    Some(n)
+}
+
+fn decode_oid(value: &[u8]) -> Option<Vec<u32>> {
+    let mut oid: Vec<u32> = vec![];
+    let mut n: u32 = 0;
+    for b in value {
+        if n > (u32::MAX >> 7) {
+            return None;
+        }
+        n = (n << 7) | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            if oid.is_empty() {
+                let first = std::cmp::min(n / 40, 2);
+                oid.push(first);
+                oid.push(n - first * 40);
+            } else {
+                oid.push(n);
+            }
+            n = 0;
+        }
+    }
+    Some(oid)
+}
+
+fn decode_value(tag: u8, value: &[u8]) -> SnmpValue {
+    match tag {
+        TAG_INTEGER => match decode_integer(value) {
+            Some(v) => SnmpValue::Integer(v),
+            None => SnmpValue::Other(tag, value.to_vec()),
+        },
+        TAG_OCTET_STRING => SnmpValue::OctetString(value.to_vec()),
+        TAG_NULL => SnmpValue::Null,
+        TAG_OBJECT_IDENTIFIER => match decode_oid(value) {
+            Some(oid) => SnmpValue::ObjectIdentifier(oid),
+            None => SnmpValue::Other(tag, value.to_vec()),
+        },
+        TAG_IP_ADDRESS if value.len() == 4 => SnmpValue::IpAddress(Ipv4Addr::new(value[0], value[1], value[2], value[3])),
+        TAG_COUNTER32 | TAG_GAUGE32 | TAG_TIMETICKS => match decode_unsigned(value) {
+            Some(v) => {
+                let v = v as u32;
+                match tag {
+                    TAG_COUNTER32 => SnmpValue::Counter32(v),
+                    TAG_GAUGE32 => SnmpValue::Gauge32(v),
+                    _ => SnmpValue::TimeTicks(v),
+                }
+            }
+            None => SnmpValue::Other(tag, value.to_vec()),
+        },
+        TAG_COUNTER64 => match decode_unsigned(value) {
+            Some(v) => SnmpValue::Counter64(v),
+            None => SnmpValue::Other(tag, value.to_vec()),
+        },
+        TAG_NO_SUCH_OBJECT => SnmpValue::NoSuchObject,
+        TAG_NO_SUCH_INSTANCE => SnmpValue::NoSuchInstance,
+        TAG_END_OF_MIB_VIEW => SnmpValue::EndOfMibView,
+        _ => SnmpValue::Other(tag, value.to_vec()),
+    }
+}
+
+/// Parse SNMP v1/v2c message
+pub(crate) fn parse_message(data: &[u8]) -> Option<SnmpMessage> {
+    let (tag, message, _) = decode_tlv(data)?;
+    if tag != TAG_SEQUENCE {
+        return None;
+    }
+    let (tag, version, rest) = decode_tlv(message)?;
+    if tag != TAG_INTEGER {
+        return None;
+    }
+    let version = SnmpVersion::from_i64(decode_integer(version)?)?;
+    let (tag, community, rest) = decode_tlv(rest)?;
+    if tag != TAG_OCTET_STRING {
+        return None;
+    }
+    let (pdu_type, pdu, _) = decode_tlv(rest)?;
+    let (_, request_id, rest) = decode_tlv(pdu)?;
+    let (_, error_status, rest) = decode_tlv(rest)?;
+    let (_, error_index, rest) = decode_tlv(rest)?;
+    let (tag, mut varbind_list, _) = decode_tlv(rest)?;
+    if tag != TAG_SEQUENCE {
+        return None;
+    }
+    let mut varbinds: Vec<(Vec<u32>, SnmpValue)> = vec![];
+    while !varbind_list.is_empty() {
+        let (_, varbind, rest) = decode_tlv(varbind_list)?;
+        let (tag, oid, value) = decode_tlv(varbind)?;
+        if tag != TAG_OBJECT_IDENTIFIER {
+            return None;
+        }
+        let (value_tag, value, _) = decode_tlv(value)?;
+        varbinds.push((decode_oid(oid)?, decode_value(value_tag, value)));
+        varbind_list = rest;
+    }
+    Some(SnmpMessage {
+        version,
+        community: community.to_vec(),
+        pdu_type,
+        request_id: decode_integer(request_id)? as i32,
+        error_status: decode_integer(error_status)?,
+        error_index: decode_integer(error_index)?,
+        varbinds,
+    })
+}
+
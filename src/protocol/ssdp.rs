@@ -0,0 +1,56 @@
+/// SSDP (Simple Service Discovery Protocol) message seen on the network
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SsdpMessage {
+    /// Start line (e.g. `NOTIFY * HTTP/1.1`, `HTTP/1.1 200 OK`)
+    pub start_line: String,
+    /// Headers with lowercase names
+    pub headers: Vec<(String, String)>,
+}
+
+impl SsdpMessage {
+    /// Get header value by lowercase name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+    /// Check if the message is a search request (M-SEARCH)
+    pub fn is_search(&self) -> bool {
+        self.start_line.starts_with("M-SEARCH")
+    }
+    /// Notification or search target (NT or ST header)
+    pub fn target(&self) -> Option<&str> {
+        self.header("nt").or_else(|| self.header("st"))
+    }
+    /// Port of the LOCATION URL
+    pub fn location_port(&self) -> Option<u16> {
+        let location = self.header("location")?;
+        let rest = location.split_once("://").map(|(_, rest)| rest)?;
+        let authority = rest.split('/').next()?;
+        match authority.rsplit_once(':') {
+            Some((_, port)) => port.parse().ok(),
+            None if location.starts_with("https") => Some(443),
+            None => Some(80),
+        }
+    }
+}
+
+/// Parse SSDP message
+pub(crate) fn parse_message(data: &[u8]) -> Option<SsdpMessage> {
+    let text = std::str::from_utf8(data).ok()?;
+    let mut lines = text.split("\r\n");
+    let start_line = lines.next()?.trim().to_string();
+    if !(start_line.starts_with("NOTIFY") || start_line.starts_with("M-SEARCH") || start_line.starts_with("HTTP/")) {
+        return None;
+    }
+    let headers: Vec<(String, String)> = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+    Some(SsdpMessage {
+        start_line,
+        headers,
+    })
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::time::SystemTime;
+use crate::host::{Host, Port, PortStatus};
+use crate::protocol::Protocol;
+use crate::scan::result::ScanResult;
+use crate::scan::setting::{HostScanSetting, HostScanType, PortScanSetting};
+use super::{add_port, format_ctime, from_unix_time, host_entry, parse_ctime, parse_port_status, update_start_time};
+
+/// Exporter of scan results in the grepable format of nmap (`-oG`).
+///
+/// Each host is written on one line with its status, ports and OS guess, so the output can be read back by `parse`.
+#[derive(Clone, Debug)]
+pub struct GrepableWriter {
+    /// Protocol name of the port entries
+    protocol: &'static str,
+    /// Command line for the header comment
+    args: String,
+    /// Start time of the scan. Taken from the result, or derived from its scan time, if not set
+    start_time: Option<SystemTime>,
+}
+
+impl Default for GrepableWriter {
+    fn default() -> Self {
+        GrepableWriter {
+            protocol: "tcp",
+            args: String::new(),
+            start_time: None,
+        }
+    }
+}
+
+impl GrepableWriter {
+    /// Create new GrepableWriter for TCP ports
+    pub fn new() -> Self {
+        GrepableWriter::default()
+    }
+    /// Create new GrepableWriter for the result of the port scan
+    pub fn from_port_scan_setting(setting: &PortScanSetting) -> Self {
+        GrepableWriter {
+            protocol: match setting.protocol {
+                Protocol::UDP => "udp",
+                _ => "tcp",
+            },
+            ..GrepableWriter::new()
+        }
+    }
+    /// Create new GrepableWriter for the result of the host scan
+    pub fn from_host_scan_setting(setting: &HostScanSetting) -> Self {
+        GrepableWriter {
+            protocol: match setting.scan_type {
+                HostScanType::UdpPingScan => "udp",
+                _ => "tcp",
+            },
+            ..GrepableWriter::new()
+        }
+    }
+    pub fn set_args(mut self, args: String) -> Self {
+        self.args = args;
+        self
+    }
+    pub fn set_start_time(mut self, start_time: SystemTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+    /// Render the scan result in grepable format
+    pub fn to_text(&self, result: &ScanResult) -> String {
+        let end_time: SystemTime = match self.start_time.or(result.start_time) {
+            Some(start_time) => start_time + result.scan_time,
+            None => SystemTime::now(),
+        };
+        let start_time: SystemTime = match self.start_time.or(result.start_time) {
+            Some(start_time) => start_time,
+            None => end_time.checked_sub(result.scan_time).unwrap_or(end_time),
+        };
+        let mut text = String::new();
+        let _ = write!(text, "# netscan {} scan initiated {}", env!("CARGO_PKG_VERSION"), format_ctime(start_time));
+        if !self.args.is_empty() {
+            let _ = write!(text, " as: {}", self.args);
+        }
+        text.push('\n');
+        for host in &result.hosts {
+            text.push_str(&self.host_line(host));
+            text.push('\n');
+        }
+        let _ = writeln!(
+            text,
+            "# netscan done at {} -- {} IP address{} ({} host{} up) scanned in {:.2} seconds",
+            format_ctime(end_time),
+            result.hosts.len(),
+            if result.hosts.len() == 1 { "" } else { "es" },
+            result.hosts.len(),
+            if result.hosts.len() == 1 { "" } else { "s" },
+            result.scan_time.as_secs_f64()
+        );
+        text
+    }
+    /// Write the scan result in grepable format
+    pub fn write<W: Write>(&self, result: &ScanResult, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.to_text(result).as_bytes())
+    }
+    fn host_line(&self, host: &Host) -> String {
+        let hostname = if host.hostname == host.ip_addr.to_string() { "" } else { host.hostname.as_str() };
+        let mut line = format!("Host: {} ({})\tStatus: Up", host.ip_addr, grepable_field(hostname));
+        if !host.ports.is_empty() {
+            let entries: Vec<String> = host
+                .ports
+                .iter()
+                .map(|port| {
+                    format!(
+                        "{}/{}/{}//{}//{}/",
+                        port.number,
+                        port.status.id(),
+                        self.protocol,
+                        grepable_field(&port.service_name),
+                        grepable_field(&port.service_version)
+                    )
+                })
+                .collect();
+            let _ = write!(line, "\tPorts: {}", entries.join(", "));
+        }
+        if !host.os_family.is_empty() {
+            let _ = write!(line, "\tOS: {}", grepable_field(&host.os_family));
+        }
+        line
+    }
+}
+
+/// Replace the characters that separate fields and port entries. `/` becomes `|` as in nmap
+fn grepable_field(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' => '|',
+            '\t' | '\r' | '\n' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+/// Split the `Ports:` field into port entries. Version strings may contain commas
+fn split_port_entries(ports: &str) -> Vec<String> {
+    let mut entries: Vec<String> = vec![];
+    for part in ports.split(',') {
+        let starts_entry = match part.trim_start().split_once('/') {
+            Some((number, _)) => !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()),
+            None => false,
+        };
+        match entries.last_mut() {
+            Some(last) if !starts_entry => {
+                last.push(',');
+                last.push_str(part);
+            }
+            _ => entries.push(part.trim_start().to_string()),
+        }
+    }
+    entries
+}
+
+/// Parse port entry `number/state/protocol/owner/service/rpc info/version/`
+fn parse_port_entry(entry: &str) -> Option<Port> {
+    let fields: Vec<&str> = entry.split('/').collect();
+    let number: u16 = fields.first()?.trim().parse().ok()?;
+    let status: PortStatus = parse_port_status(fields.get(1).copied().unwrap_or(""));
+    Some(Port {
+        number,
+        status,
+        service_name: fields.get(4).copied().unwrap_or("").to_string(),
+        service_version: fields.get(6).copied().unwrap_or("").trim().to_string(),
+        rtt: None,
+    })
+}
+
+/// Parse grepable output of nmap (`-oG`) or masscan (`-oG`).
+///
+/// Hosts with `Status: Down` and no ports are skipped.
+/// The start time is taken from the `scan initiated` comment or the earliest `Timestamp` field.
+pub fn parse(text: &str) -> Result<ScanResult, String> {
+    let mut result: ScanResult = ScanResult::new();
+    let mut index: HashMap<IpAddr, usize> = HashMap::new();
+    for line in text.lines() {
+        if line.starts_with('#') {
+            // e.g. `# Nmap 7.94 scan initiated Sun Oct 18 12:00:00 2026 as: nmap -oG - 192.168.1.0/24`
+            if let Some((_, initiated)) = line.split_once("scan initiated ") {
+                let time = initiated.split_once(" as: ").map(|(time, _)| time).unwrap_or(initiated);
+                if let Some(time) = parse_ctime(time) {
+                    update_start_time(&mut result, time);
+                }
+            }
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut ip_addr: Option<IpAddr> = None;
+        let mut hostname: String = String::new();
+        let mut up: bool = false;
+        let mut ports: Vec<Port> = vec![];
+        for field in line.split('\t') {
+            let (key, value) = match field.split_once(": ") {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            match key {
+                "Host" => {
+                    let (addr, name) = match value.split_once(' ') {
+                        Some((addr, name)) => (addr, name.trim().trim_start_matches('(').trim_end_matches(')')),
+                        None => (value, ""),
+                    };
+                    ip_addr = Some(addr.parse::<IpAddr>().map_err(|e| format!("Invalid host address {}: {}", addr, e))?);
+                    hostname = name.to_string();
+                }
+                "Status" => up = value.eq_ignore_ascii_case("up"),
+                "Timestamp" => {
+                    if let Some(time) = value.parse().ok().and_then(from_unix_time) {
+                        update_start_time(&mut result, time);
+                    }
+                }
+                "Ports" => {
+                    ports = split_port_entries(value)
+                        .iter()
+                        .filter_map(|entry| parse_port_entry(entry))
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        let ip_addr = match ip_addr {
+            Some(ip_addr) => ip_addr,
+            None => continue,
+        };
+        if !up && ports.is_empty() {
+            continue;
+        }
+        let host = host_entry(&mut result.hosts, &mut index, ip_addr);
+        if host.hostname.is_empty() {
+            host.hostname = hostname;
+        }
+        for port in ports {
+            add_port(host, port);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_nmap_grepable() {
+        let text = "# Nmap 7.94 scan initiated Sun Oct 18 12:00:00 2026 as: nmap -oG - 192.168.1.0/24\n\
+Host: 192.168.1.10 (server.local)\tStatus: Up\n\
+Host: 192.168.1.10 (server.local)\tPorts: 22/open/tcp//ssh//OpenSSH 8.9p1, Ubuntu/, 80/closed/tcp//http///\tOS: Linux\n\
+Host: 192.168.1.11 ()\tStatus: Down\n\
+Host: 192.168.1.12 ()\tStatus: Up\n\
+# Nmap done at Sun Oct 18 12:00:10 2026 -- 256 IP addresses (2 hosts up) scanned in 10.00 seconds\n";
+        let result = parse(text).unwrap();
+        assert_eq!(result.hosts.len(), 2);
+        let host = &result.hosts[0];
+        assert_eq!(host.hostname, "server.local");
+        assert_eq!(host.ports.len(), 2);
+        assert_eq!(host.ports[0].number, 22);
+        assert_eq!(host.ports[0].status, PortStatus::Open);
+        assert_eq!(host.ports[0].service_name, "ssh");
+        assert_eq!(host.ports[0].service_version, "OpenSSH 8.9p1, Ubuntu");
+        assert_eq!(host.ports[1].status, PortStatus::Closed);
+        assert!(result.hosts[1].ports.is_empty());
+        assert_eq!(result.start_time.map(format_ctime).as_deref(), Some("Sun Oct 18 12:00:00 2026"));
+    }
+
+    #[test]
+    fn parse_masscan_grepable() {
+        let text = "# Masscan 1.3.2 scan initiated\nTimestamp: 1700000000\tHost: 10.0.0.1 ()\tPorts: 443/open/tcp//https//\n";
+        let result = parse(text).unwrap();
+        assert_eq!(result.hosts.len(), 1);
+        assert_eq!(result.hosts[0].ports[0].number, 443);
+        assert_eq!(result.start_time, from_unix_time(1_700_000_000));
+    }
+
+    #[test]
+    fn reject_invalid_host_address() {
+        assert!(parse("Host: 10.0.0.256 ()\tStatus: Up\n").is_err());
+    }
+}
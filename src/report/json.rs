@@ -0,0 +1,273 @@
+/// JSON value
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Get member of the object by key
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    /// Number, or string containing a number
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            JsonValue::String(s) => s.trim().parse::<u64>().ok(),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Maximum nesting depth of arrays and objects
+const MAX_DEPTH: usize = 128;
+
+/// Lenient JSON parser. Accepts trailing commas and unquoted object keys
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// Nesting depth of the current value
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.input.len() && self.input[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+    fn expect(&mut self, b: u8) -> Result<(), String> {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' at {}", b as char, self.pos))
+        }
+    }
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        match self.peek() {
+            Some(b'{') | Some(b'[') => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(format!("Nesting too deep at {}", self.pos));
+                }
+                self.depth += 1;
+                let value = if self.input[self.pos] == b'{' { self.parse_object() } else { self.parse_array() };
+                self.depth -= 1;
+                value
+            }
+            Some(b'"') => self.parse_string().map(JsonValue::String),
+            Some(b't') | Some(b'f') | Some(b'n') => {
+                let word = self.parse_word();
+                match word.as_str() {
+                    "true" => Ok(JsonValue::Bool(true)),
+                    "false" => Ok(JsonValue::Bool(false)),
+                    "null" => Ok(JsonValue::Null),
+                    _ => Err(format!("Unexpected token: {}", word)),
+                }
+            }
+            Some(_) => {
+                let word = self.parse_word();
+                word.parse::<f64>()
+                    .map(JsonValue::Number)
+                    .map_err(|_| format!("Invalid number: {}", word))
+            }
+            None => Err("Unexpected end of input".to_string()),
+        }
+    }
+    /// Bare word (number, literal or unquoted key)
+    fn parse_word(&mut self) -> String {
+        let start = self.pos;
+        while self.pos < self.input.len() {
+            let b = self.input[self.pos];
+            if b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b == b'+' || b == b'_' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        String::from_utf8_lossy(&self.input[start..self.pos]).to_string()
+    }
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let hex = self.input.get(self.pos..self.pos + 4).ok_or("Invalid escape")?;
+        self.pos += 4;
+        u32::from_str_radix(&String::from_utf8_lossy(hex), 16).map_err(|e| e.to_string())
+    }
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes: Vec<u8> = vec![];
+        loop {
+            let b = *self.input.get(self.pos).ok_or("Unterminated string")?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let e = *self.input.get(self.pos).ok_or("Unterminated string")?;
+                    self.pos += 1;
+                    let c: char = match e {
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            // Surrogate pair
+                            if (0xd800..0xdc00).contains(&code) && self.input.get(self.pos..self.pos + 2) == Some(b"\\u") {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            char::from_u32(code).unwrap_or('\u{fffd}')
+                        }
+                        other => other as char,
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => bytes.push(b),
+            }
+        }
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'[')?;
+        let mut values: Vec<JsonValue> = vec![];
+        loop {
+            match self.peek() {
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                Some(b',') => self.pos += 1,
+                _ => values.push(self.parse_value()?),
+            }
+        }
+    }
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'{')?;
+        let mut members: Vec<(String, JsonValue)> = vec![];
+        loop {
+            match self.peek() {
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                Some(b',') => self.pos += 1,
+                Some(b'"') => {
+                    let key = self.parse_string()?;
+                    self.expect(b':')?;
+                    members.push((key, self.parse_value()?));
+                }
+                Some(_) => {
+                    let key = self.parse_word();
+                    if key.is_empty() {
+                        return Err(format!("Invalid object key at {}", self.pos));
+                    }
+                    self.expect(b':')?;
+                    members.push((key, self.parse_value()?));
+                }
+                None => return Err("Unterminated object".to_string()),
+            }
+        }
+    }
+}
+
+/// Parse JSON values. Multiple top-level values (NDJSON) are returned in order
+pub(crate) fn parse_values(input: &str) -> Result<Vec<JsonValue>, String> {
+    let mut parser = Parser {
+        input: input.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    let mut values: Vec<JsonValue> = vec![];
+    while parser.peek().is_some() {
+        values.push(parser.parse_value()?);
+    }
+    Ok(values)
+}
+
+/// Quote and escape the text as a JSON string
+pub(crate) fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ndjson_values() {
+        let values = parse_values("{\"ip\": \"10.0.0.1\", ports: [{port: 80,},]}\n[1, \"a\\u00e9\", true, null]").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].get("ip").and_then(|ip| ip.as_str()), Some("10.0.0.1"));
+        let ports = values[0].get("ports").and_then(|ports| ports.as_array()).unwrap();
+        assert_eq!(ports[0].get("port").and_then(|port| port.as_u64()), Some(80));
+        assert_eq!(
+            values[1],
+            JsonValue::Array(vec![
+                JsonValue::Number(1.0),
+                JsonValue::String("a\u{e9}".to_string()),
+                JsonValue::Bool(true),
+                JsonValue::Null,
+            ])
+        );
+    }
+
+    #[test]
+    fn reject_invalid_json() {
+        assert!(parse_values("{\"ip\": ").is_err());
+        assert!(parse_values("[\"unterminated").is_err());
+        assert!(parse_values("[nope]").is_err());
+    }
+
+    #[test]
+    fn reject_deep_nesting() {
+        let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(parse_values(&nested).is_err());
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse_values(&nested).is_ok());
+    }
+
+    #[test]
+    fn escape_json_string() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use crate::host::Port;
+use crate::scan::result::ScanResult;
+use super::json::{self, JsonValue};
+use super::{add_port, host_entry, parse_port_status, from_unix_time, update_start_time};
+
+/// First line of the banner. Line breaks are escaped (`\x0d\x0a`) in the list output
+fn banner_line(banner: &str) -> String {
+    let end = ["\r", "\n", "\\x0d", "\\x0a"]
+        .iter()
+        .filter_map(|line_break| banner.find(line_break))
+        .min()
+        .unwrap_or(banner.len());
+    banner[..end].trim().to_string()
+}
+
+fn add_record(result: &mut ScanResult, index: &mut HashMap<IpAddr, usize>, record: &JsonValue) -> Result<(), String> {
+    // Status record at the end of the output (e.g. `{finished: 1}`)
+    let ip_addr: IpAddr = match record.get("ip").and_then(|ip| ip.as_str()) {
+        Some(ip) => ip.parse().map_err(|e| format!("Invalid address {}: {}", ip, e))?,
+        None => return Ok(()),
+    };
+    if let Some(timestamp) = record.get("timestamp").and_then(|timestamp| timestamp.as_u64()).and_then(from_unix_time) {
+        update_start_time(result, timestamp);
+    }
+    let host = host_entry(&mut result.hosts, index, ip_addr);
+    let ports: &[JsonValue] = record.get("ports").and_then(|ports| ports.as_array()).unwrap_or(&[]);
+    for port in ports {
+        let number: u16 = match port.get("port").and_then(|number| number.as_u64()) {
+            Some(number) if number <= u16::MAX as u64 => number as u16,
+            _ => continue,
+        };
+        if let Some(ttl) = port.get("ttl").and_then(|ttl| ttl.as_u64()) {
+            host.ttl = ttl.min(u8::MAX as u64) as u8;
+        }
+        let service = port.get("service");
+        add_port(host, Port {
+            number,
+            status: parse_port_status(port.get("status").and_then(|status| status.as_str()).unwrap_or("open")),
+            service_name: service
+                .and_then(|service| service.get("name"))
+                .and_then(|name| name.as_str())
+                .unwrap_or("")
+                .to_string(),
+            service_version: service
+                .and_then(|service| service.get("banner"))
+                .and_then(|banner| banner.as_str())
+                .map(banner_line)
+                .unwrap_or_default(),
+            rtt: None,
+        });
+    }
+    Ok(())
+}
+
+/// Parse JSON (`-oJ`) or NDJSON (`--ndjson`) output of masscan.
+///
+/// Masscan writes one record per port or banner, so records of the same address are merged into one host.
+pub fn parse_json(text: &str) -> Result<ScanResult, String> {
+    let mut result: ScanResult = ScanResult::new();
+    let mut index: HashMap<IpAddr, usize> = HashMap::new();
+    for value in json::parse_values(text)? {
+        match value {
+            JsonValue::Array(records) => {
+                for record in &records {
+                    add_record(&mut result, &mut index, record)?;
+                }
+            }
+            record => add_record(&mut result, &mut index, &record)?,
+        }
+    }
+    Ok(result)
+}
+
+/// Parse list output (`-oL`) of masscan.
+///
+/// Lines are `<state> <protocol> <port> <address> <timestamp>` or `banner <protocol> <port> <address> <timestamp> <service> <banner>`.
+pub fn parse_list(text: &str) -> Result<ScanResult, String> {
+    let mut result: ScanResult = ScanResult::new();
+    let mut index: HashMap<IpAddr, usize> = HashMap::new();
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.splitn(7, ' ').collect();
+        if fields.len() < 5 {
+            return Err(format!("Invalid line: {}", line));
+        }
+        let number: u16 = fields[2].parse().map_err(|_| format!("Invalid port: {}", fields[2]))?;
+        let ip_addr: IpAddr = fields[3].parse().map_err(|_| format!("Invalid address: {}", fields[3]))?;
+        if let Some(timestamp) = fields[4].parse().ok().and_then(from_unix_time) {
+            update_start_time(&mut result, timestamp);
+        }
+        let host = host_entry(&mut result.hosts, &mut index, ip_addr);
+        let port = if fields[0] == "banner" {
+            Port {
+                number,
+                status: parse_port_status("open"),
+                service_name: fields.get(5).copied().unwrap_or("").to_string(),
+                service_version: banner_line(fields.get(6).copied().unwrap_or("")),
+                rtt: None,
+            }
+        } else {
+            Port {
+                number,
+                status: parse_port_status(fields[0]),
+                service_name: String::new(),
+                service_version: String::new(),
+                rtt: None,
+            }
+        };
+        add_port(host, port);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::PortStatus;
+
+    #[test]
+    fn parse_masscan_json() {
+        let text = r#"[
+{ "ip": "10.0.0.1", "timestamp": "1700000000", "ports": [ {"port": 80, "proto": "tcp", "status": "open", "reason": "syn-ack", "ttl": 64} ] },
+{ "ip": "10.0.0.1", "timestamp": "1700000001", "ports": [ {"port": 80, "proto": "tcp", "service": {"name": "http", "banner": "HTTP/1.1 200 OK\r\nServer: nginx"} } ] },
+{ "ip": "10.0.0.2", "timestamp": "1700000002", "ports": [ {"port": 443, "proto": "tcp", "status": "open"} ] },
+{finished: 1}
+]"#;
+        let result = parse_json(text).unwrap();
+        assert_eq!(result.hosts.len(), 2);
+        let host = &result.hosts[0];
+        assert_eq!(host.ip_addr, "10.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(host.ttl, 64);
+        assert_eq!(host.ports.len(), 1);
+        assert_eq!(host.ports[0].status, PortStatus::Open);
+        assert_eq!(host.ports[0].service_name, "http");
+        assert_eq!(host.ports[0].service_version, "HTTP/1.1 200 OK");
+        assert_eq!(result.hosts[1].ports[0].number, 443);
+        assert_eq!(result.start_time, from_unix_time(1_700_000_000));
+    }
+
+    #[test]
+    fn parse_masscan_ndjson() {
+        let text = "{\"ip\":\"10.0.0.1\",\"ports\":[{\"port\":22,\"status\":\"open\"}]}\n{\"ip\":\"10.0.0.1\",\"ports\":[{\"port\":23,\"status\":\"closed\"}]}\n";
+        let result = parse_json(text).unwrap();
+        assert_eq!(result.hosts.len(), 1);
+        assert_eq!(result.hosts[0].ports.len(), 2);
+        assert_eq!(result.hosts[0].ports[1].status, PortStatus::Closed);
+    }
+
+    #[test]
+    fn parse_masscan_list() {
+        let text = "#masscan\nopen tcp 22 10.0.0.1 1700000000\nopen tcp 80 10.0.0.2 1700000001\nbanner tcp 22 10.0.0.1 1700000002 ssh SSH-2.0-OpenSSH_8.9\\x0d\\x0a\n# end\n";
+        let result = parse_list(text).unwrap();
+        assert_eq!(result.hosts.len(), 2);
+        let host = &result.hosts[0];
+        assert_eq!(host.ports.len(), 1);
+        assert_eq!(host.ports[0].service_name, "ssh");
+        assert_eq!(host.ports[0].service_version, "SSH-2.0-OpenSSH_8.9");
+        assert_eq!(result.start_time, from_unix_time(1_700_000_000));
+    }
+
+    #[test]
+    fn reject_invalid_masscan_list() {
+        assert!(parse_list("open tcp 22\n").is_err());
+        assert!(parse_list("open tcp 70000 10.0.0.1 0\n").is_err());
+        assert!(parse_list("open tcp 22 10.0.0 0\n").is_err());
+        assert!(parse_json(r#"{"ip": "invalid"}"#).is_err());
+    }
+}
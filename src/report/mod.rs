@@ -0,0 +1,159 @@
+pub mod grepable;
+pub mod masscan;
+pub mod nmap;
+pub mod stream;
+pub mod table;
+pub(crate) mod json;
+pub(crate) mod xml;
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::host::{Host, Port, PortStatus};
+use crate::scan::result::ScanResult;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Seconds since the UNIX epoch
+pub(crate) fn unix_time(time: SystemTime) -> u64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+/// Convert days since the UNIX epoch to (year, month, day) in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Convert (year, month, day) in the proleptic Gregorian calendar to days since the UNIX epoch
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Time of the seconds since the UNIX epoch. None if out of range
+pub(crate) fn from_unix_time(secs: u64) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+}
+
+/// Keep the earliest of the parsed times as the start time of the result
+pub(crate) fn update_start_time(result: &mut ScanResult, time: SystemTime) {
+    if result.start_time.is_none_or(|start_time| time < start_time) {
+        result.start_time = Some(time);
+    }
+}
+
+/// Parse the time in the format of `format_ctime` (e.g. `Sun Oct 18 12:00:00 2026`).
+///
+/// The time is taken as UTC. nmap writes the local time of the scanning machine.
+pub(crate) fn parse_ctime(s: &str) -> Option<SystemTime> {
+    let fields: Vec<&str> = s.split_whitespace().collect();
+    if fields.len() != 5 {
+        return None;
+    }
+    let month: u32 = MONTHS.iter().position(|month| *month == fields[1])? as u32 + 1;
+    let day: u32 = fields[2].parse().ok().filter(|day| (1..=31).contains(day))?;
+    let year: i64 = fields[4].parse().ok().filter(|year| (1970..=9999).contains(year))?;
+    let hms: Vec<u64> = fields[3].split(':').map(|v| v.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    if hms.len() != 3 || hms[0] > 23 || hms[1] > 59 || hms[2] > 60 {
+        return None;
+    }
+    let days: u64 = days_from_civil(year, month, day) as u64;
+    UNIX_EPOCH.checked_add(Duration::from_secs(days * 86_400 + hms[0] * 3600 + hms[1] * 60 + hms[2]))
+}
+
+/// Format the time like ctime(3) in UTC (e.g. `Sun Oct 18 12:00:00 2026`)
+pub(crate) fn format_ctime(time: SystemTime) -> String {
+    let secs = unix_time(time) as i64;
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    // 1970-01-01 was a Thursday
+    let weekday = (days + 4).rem_euclid(7) as usize;
+    format!(
+        "{} {} {:>2} {:02}:{:02}:{:02} {}",
+        WEEKDAYS[weekday],
+        MONTHS[month as usize - 1],
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        year
+    )
+}
+
+/// Format the round-trip time in milliseconds (e.g. `0.35ms`)
+pub(crate) fn format_rtt(rtt: Duration) -> String {
+    format!("{:.2}ms", rtt.as_secs_f64() * 1000.0)
+}
+
+/// Escape the text for XML attribute values and character data
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if c.is_control() && c != '\t' && c != '\n' && c != '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Port status from the state name of nmap and masscan (e.g. `open`, `closed`, `open|filtered`)
+pub(crate) fn parse_port_status(state: &str) -> PortStatus {
+    match state.to_lowercase().as_str() {
+        "open" => PortStatus::Open,
+        "closed" => PortStatus::Closed,
+        "filtered" | "open|filtered" | "closed|filtered" => PortStatus::Filtered,
+        _ => PortStatus::Unknown,
+    }
+}
+
+/// Get the host with the address, or append a new one.
+///
+/// `index` maps the addresses to the positions in `hosts`, so that large imports are not quadratic.
+pub(crate) fn host_entry<'a>(hosts: &'a mut Vec<Host>, index: &mut HashMap<IpAddr, usize>, ip_addr: IpAddr) -> &'a mut Host {
+    let position = *index.entry(ip_addr).or_insert_with(|| {
+        hosts.push(Host::new(ip_addr, String::new()));
+        hosts.len() - 1
+    });
+    &mut hosts[position]
+}
+
+/// Add the port to the host. Service of the existing port with the same number is filled in
+pub(crate) fn add_port(host: &mut Host, port: Port) {
+    match host.ports.iter_mut().find(|p| p.number == port.number) {
+        Some(existing) => {
+            if existing.service_name.is_empty() {
+                existing.service_name = port.service_name;
+            }
+            if existing.service_version.is_empty() {
+                existing.service_version = port.service_version;
+            }
+        }
+        None => host.ports.push(port),
+    }
+}
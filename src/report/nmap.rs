@@ -0,0 +1,531 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, SystemTime};
+use netdev::mac::MacAddr;
+use crate::host::{self, Host, Port, PortStatus};
+use crate::protocol::Protocol;
+use crate::scan::result::{ScanResult, ScanStatus, ServiceProbeResult};
+use crate::scan::setting::{HostScanSetting, HostScanType, PortScanSetting, PortScanType};
+use super::xml::{self, XmlElement, XmlEvent};
+use super::{add_port, format_ctime, from_unix_time, parse_port_status, unix_time, xml_escape};
+
+/// Version of the nmap XML output format
+const XML_OUTPUT_VERSION: &str = "1.05";
+
+/// Scan that produced the result. Determines the reasons of the port and host states
+#[derive(Clone, Debug)]
+enum ScanKind {
+    Port(PortScanType, Protocol),
+    Host(HostScanType),
+}
+
+/// Exporter of scan results in nmap XML format
+#[derive(Clone, Debug)]
+pub struct NmapXmlWriter {
+    kind: ScanKind,
+    /// Scanned port numbers for `<scaninfo>`
+    ports: BTreeSet<u16>,
+    /// Command line for `<nmaprun args>`
+    args: String,
+    /// Start time of the scan. Taken from the result, or derived from its scan time, if not set
+    start_time: Option<SystemTime>,
+    /// Service probe results by host
+    service_results: HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>,
+}
+
+impl NmapXmlWriter {
+    /// Create new NmapXmlWriter for the result of the port scan
+    pub fn from_port_scan_setting(setting: &PortScanSetting) -> Self {
+        NmapXmlWriter {
+            kind: ScanKind::Port(setting.scan_type.clone(), setting.protocol.clone()),
+            ports: target_ports(&setting.targets),
+            args: String::new(),
+            start_time: None,
+            service_results: HashMap::new(),
+        }
+    }
+    /// Create new NmapXmlWriter for the result of the host scan
+    pub fn from_host_scan_setting(setting: &HostScanSetting) -> Self {
+        NmapXmlWriter {
+            kind: ScanKind::Host(setting.scan_type.clone()),
+            ports: target_ports(&setting.targets),
+            args: String::new(),
+            start_time: None,
+            service_results: HashMap::new(),
+        }
+    }
+    pub fn set_args(mut self, args: String) -> Self {
+        self.args = args;
+        self
+    }
+    pub fn set_start_time(mut self, start_time: SystemTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+    pub fn set_service_results(mut self, service_results: HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>) -> Self {
+        self.service_results = service_results;
+        self
+    }
+    /// Render the scan result as nmap XML
+    pub fn to_xml(&self, result: &ScanResult) -> String {
+        let end_time: SystemTime = match self.start_time.or(result.start_time) {
+            Some(start_time) => start_time + result.scan_time,
+            None => SystemTime::now(),
+        };
+        let start_time: SystemTime = match self.start_time.or(result.start_time) {
+            Some(start_time) => start_time,
+            None => end_time.checked_sub(result.scan_time).unwrap_or(end_time),
+        };
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<!DOCTYPE nmaprun>\n");
+        let _ = writeln!(
+            xml,
+            "<nmaprun scanner=\"netscan\" args=\"{}\" start=\"{}\" startstr=\"{}\" version=\"{}\" xmloutputversion=\"{}\">",
+            xml_escape(&self.args),
+            unix_time(start_time),
+            format_ctime(start_time),
+            env!("CARGO_PKG_VERSION"),
+            XML_OUTPUT_VERSION
+        );
+        if let Some(scan_type) = self.scaninfo_type() {
+            let _ = writeln!(
+                xml,
+                "<scaninfo type=\"{}\" protocol=\"{}\" numservices=\"{}\" services=\"{}\"/>",
+                scan_type,
+                self.protocol_name(),
+                self.ports.len(),
+                format_port_ranges(&self.ports)
+            );
+        }
+        xml.push_str("<verbose level=\"0\"/>\n");
+        xml.push_str("<debugging level=\"0\"/>\n");
+        for host in &result.hosts {
+            self.write_host(&mut xml, host, start_time, end_time);
+        }
+        let elapsed = result.scan_time.as_secs_f64();
+        let exit = match result.scan_status {
+            ScanStatus::Error(_) => "error",
+            _ => "success",
+        };
+        xml.push_str("<runstats>\n");
+        let _ = writeln!(
+            xml,
+            "<finished time=\"{}\" timestr=\"{}\" summary=\"netscan done; {} IP address{} ({} host{} up) scanned in {:.2} seconds\" elapsed=\"{:.2}\" exit=\"{}\"{}/>",
+            unix_time(end_time),
+            format_ctime(end_time),
+            result.hosts.len(),
+            if result.hosts.len() == 1 { "" } else { "es" },
+            result.hosts.len(),
+            if result.hosts.len() == 1 { "" } else { "s" },
+            elapsed,
+            elapsed,
+            exit,
+            match &result.scan_status {
+                ScanStatus::Error(message) => format!(" errormsg=\"{}\"", xml_escape(message)),
+                _ => String::new(),
+            }
+        );
+        let _ = writeln!(
+            xml,
+            "<hosts up=\"{}\" down=\"0\" total=\"{}\"/>",
+            result.hosts.len(),
+            result.hosts.len()
+        );
+        xml.push_str("</runstats>\n");
+        xml.push_str("</nmaprun>\n");
+        xml
+    }
+    /// Write the scan result as nmap XML
+    pub fn write<W: Write>(&self, result: &ScanResult, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.to_xml(result).as_bytes())
+    }
+    /// `<scaninfo type>`. Host discovery has no scaninfo
+    fn scaninfo_type(&self) -> Option<&'static str> {
+        match &self.kind {
+            ScanKind::Port(PortScanType::TcpSynScan, Protocol::UDP) => Some("udp"),
+            ScanKind::Port(PortScanType::TcpSynScan, _) => Some("syn"),
+            ScanKind::Port(PortScanType::TcpConnectScan, _) => Some("connect"),
+            ScanKind::Port(PortScanType::IdleScan, _) => Some("idle"),
+            ScanKind::Host(_) => None,
+        }
+    }
+    fn protocol_name(&self) -> &'static str {
+        match &self.kind {
+            ScanKind::Port(_, Protocol::UDP) | ScanKind::Host(HostScanType::UdpPingScan) => "udp",
+            _ => "tcp",
+        }
+    }
+    /// Reason of the port state
+    fn port_reason(&self, status: PortStatus) -> &'static str {
+        let udp = self.protocol_name() == "udp";
+        let connect = matches!(self.kind, ScanKind::Port(PortScanType::TcpConnectScan, _));
+        match status {
+            PortStatus::Open if udp => "udp-response",
+            PortStatus::Open => "syn-ack",
+            PortStatus::Closed if udp => "port-unreach",
+            PortStatus::Closed if connect => "conn-refused",
+            PortStatus::Closed => "reset",
+            PortStatus::Filtered => "no-response",
+            PortStatus::Unknown => "unknown",
+        }
+    }
+    /// Reason of the host state
+    fn host_reason(&self, host: &Host) -> &'static str {
+        match &self.kind {
+            ScanKind::Host(HostScanType::IcmpPingScan)
+            | ScanKind::Host(HostScanType::Icmpv6MulticastPingScan)
+            | ScanKind::Host(HostScanType::IcmpBroadcastPingScan) => "echo-reply",
+            ScanKind::Host(HostScanType::RouterSolicitationScan) => "router-advertisement",
+            ScanKind::Host(HostScanType::UdpPingScan) => "port-unreach",
+            _ => {
+                if host.ports.iter().any(|port| port.status == PortStatus::Open) {
+                    self.port_reason(PortStatus::Open)
+                } else if host.ports.iter().any(|port| port.status == PortStatus::Closed) {
+                    self.port_reason(PortStatus::Closed)
+                } else {
+                    "user-set"
+                }
+            }
+        }
+    }
+    fn write_host(&self, xml: &mut String, host: &Host, start_time: SystemTime, end_time: SystemTime) {
+        let _ = writeln!(xml, "<host starttime=\"{}\" endtime=\"{}\">", unix_time(start_time), unix_time(end_time));
+        let _ = writeln!(
+            xml,
+            "<status state=\"up\" reason=\"{}\" reason_ttl=\"{}\"/>",
+            self.host_reason(host),
+            host.ttl
+        );
+        let addrtype = if host.ip_addr.is_ipv4() { "ipv4" } else { "ipv6" };
+        let _ = writeln!(xml, "<address addr=\"{}\" addrtype=\"{}\"/>", host.ip_addr, addrtype);
+        if host.mac_addr != MacAddr::zero() {
+            let vendor = match host::lookup_vendor(&host.mac_addr) {
+                Some(vendor) => format!(" vendor=\"{}\"", xml_escape(vendor)),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                xml,
+                "<address addr=\"{}\" addrtype=\"mac\"{}/>",
+                host.mac_addr.to_string().to_uppercase(),
+                vendor
+            );
+        }
+        if host.hostname.is_empty() || host.hostname == host.ip_addr.to_string() {
+            xml.push_str("<hostnames>\n</hostnames>\n");
+        } else {
+            let _ = writeln!(
+                xml,
+                "<hostnames>\n<hostname name=\"{}\" type=\"PTR\"/>\n</hostnames>",
+                xml_escape(&host.hostname)
+            );
+        }
+        if !host.ports.is_empty() {
+            xml.push_str("<ports>\n");
+            let mut ports: Vec<&Port> = host.ports.iter().collect();
+            ports.sort_by_key(|port| port.number);
+            for port in ports {
+                self.write_port(xml, host, port);
+            }
+            xml.push_str("</ports>\n");
+        }
+        if !host.os_family.is_empty() {
+            let _ = writeln!(
+                xml,
+                "<os>\n<osmatch name=\"{}\" accuracy=\"{}\" line=\"0\">\n<osclass osfamily=\"{}\" accuracy=\"{}\"/>\n</osmatch>\n</os>",
+                xml_escape(&host.os_family),
+                host.os_confidence,
+                xml_escape(&host.os_family),
+                host.os_confidence
+            );
+        }
+        if let Some(uptime) = &host.uptime {
+            if let (Some(seconds), Some(last_boot)) = (uptime.uptime(), uptime.last_boot()) {
+                let _ = writeln!(
+                    xml,
+                    "<uptime seconds=\"{}\" lastboot=\"{}\"/>",
+                    seconds.as_secs(),
+                    format_ctime(last_boot)
+                );
+            }
+        }
+        xml.push_str("</host>\n");
+    }
+    fn write_port(&self, xml: &mut String, host: &Host, port: &Port) {
+        let reason_ttl = match port.status {
+            PortStatus::Open | PortStatus::Closed => host.ttl,
+            _ => 0,
+        };
+        let _ = writeln!(xml, "<port protocol=\"{}\" portid=\"{}\">", self.protocol_name(), port.number);
+        let _ = writeln!(
+            xml,
+            "<state state=\"{}\" reason=\"{}\" reason_ttl=\"{}\"/>",
+            port.status.id(),
+            self.port_reason(port.status),
+            reason_ttl
+        );
+        let probe_result = self
+            .service_results
+            .get(&host.ip_addr)
+            .and_then(|results| results.get(&port.number));
+        let service_name: &str = match probe_result {
+            Some(result) if !result.service_name.is_empty() => &result.service_name,
+            _ => &port.service_name,
+        };
+        if !service_name.is_empty() {
+            let mut attrs = format!("name=\"{}\"", xml_escape(service_name));
+            let product: Option<String> = match probe_result.and_then(|result| result.service_version()) {
+                Some(version) => Some(version),
+                None if !port.service_version.is_empty() => Some(port.service_version.clone()),
+                None => None,
+            };
+            if let Some(product) = product {
+                let _ = write!(attrs, " product=\"{}\"", xml_escape(&product));
+            }
+            if let Some(result) = probe_result {
+                if let Some(title) = result.http_info.as_ref().and_then(|info| info.title.as_ref()) {
+                    let _ = write!(attrs, " extrainfo=\"{}\"", xml_escape(title));
+                }
+                if result.tls_info.is_some() {
+                    attrs.push_str(" tunnel=\"ssl\"");
+                }
+            }
+            // Probed services are identified from the response, others only by the port number
+            let (method, conf) = match probe_result {
+                Some(result) if result.error.is_none() => ("probed", 10),
+                _ => ("table", 3),
+            };
+            let _ = writeln!(xml, "<service {} method=\"{}\" conf=\"{}\"/>", attrs, method, conf);
+        }
+        xml.push_str("</port>\n");
+    }
+}
+
+/// Union of the target ports
+pub(crate) fn target_ports(targets: &[Host]) -> BTreeSet<u16> {
+    targets
+        .iter()
+        .flat_map(|target| target.ports.iter().map(|port| port.number))
+        .collect()
+}
+
+/// Format sorted ports as ranges (e.g. `22,80-90,443`)
+pub(crate) fn format_port_ranges(ports: &BTreeSet<u16>) -> String {
+    let mut ranges: Vec<String> = vec![];
+    let mut iter = ports.iter().copied().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while iter.peek() == Some(&end.wrapping_add(1)) && end != u16::MAX {
+            end = iter.next().unwrap_or(end);
+        }
+        if start == end {
+            ranges.push(start.to_string());
+        } else {
+            ranges.push(format!("{}-{}", start, end));
+        }
+    }
+    ranges.join(",")
+}
+
+/// Render the scan result of the port scan as nmap XML
+pub fn port_scan_to_xml(result: &ScanResult, setting: &PortScanSetting, service_results: &HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>) -> String {
+    NmapXmlWriter::from_port_scan_setting(setting)
+        .set_service_results(service_results.clone())
+        .to_xml(result)
+}
+
+/// Render the scan result of the host scan as nmap XML
+pub fn host_scan_to_xml(result: &ScanResult, setting: &HostScanSetting) -> String {
+    NmapXmlWriter::from_host_scan_setting(setting).to_xml(result)
+}
+
+/// Service version in the form nmap prints (e.g. `OpenSSH 8.9p1 (Ubuntu Linux; protocol 2.0)`)
+fn service_version(element: &XmlElement) -> String {
+    let mut parts: Vec<String> = vec![];
+    for name in ["product", "version"] {
+        if let Some(value) = element.attr(name).filter(|value| !value.is_empty()) {
+            parts.push(value.to_string());
+        }
+    }
+    if let Some(extrainfo) = element.attr("extrainfo").filter(|value| !value.is_empty()) {
+        parts.push(format!("({})", extrainfo));
+    }
+    parts.join(" ")
+}
+
+/// Parse nmap XML output (`-oX`).
+///
+/// Only hosts with `<status state="up">` are included. Ports of all protocols are added to the host.
+pub fn parse_xml(xml: &str) -> Result<ScanResult, String> {
+    let mut result: ScanResult = ScanResult::new();
+    let mut host: Option<(Host, bool)> = None;
+    let mut port: Option<Port> = None;
+    let mut in_osmatch: bool = false;
+    for event in xml::parse_events(xml)? {
+        match event {
+            XmlEvent::Start(element) => match element.name.as_str() {
+                "nmaprun" => {
+                    result.start_time = element.attr("start").and_then(|start| start.parse().ok()).and_then(from_unix_time);
+                }
+                "host" => host = Some((Host::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), String::new()), false)),
+                "status" => {
+                    if let Some((_, up)) = host.as_mut() {
+                        *up = element.attr("state") == Some("up");
+                    }
+                }
+                "address" => {
+                    let (host, _) = match host.as_mut() {
+                        Some(host) => host,
+                        None => continue,
+                    };
+                    let addr = element.attr("addr").unwrap_or("");
+                    match element.attr("addrtype") {
+                        Some("mac") => {
+                            host.mac_addr = addr.parse::<MacAddr>().map_err(|e| format!("Invalid MAC address {}: {}", addr, e))?;
+                        }
+                        _ => {
+                            host.ip_addr = addr.parse::<IpAddr>().map_err(|e| format!("Invalid address {}: {}", addr, e))?;
+                        }
+                    }
+                }
+                "hostname" => {
+                    if let Some((host, _)) = host.as_mut() {
+                        if host.hostname.is_empty() {
+                            host.hostname = element.attr("name").unwrap_or("").to_string();
+                        }
+                    }
+                }
+                "port" => {
+                    let number: u16 = element
+                        .attr("portid")
+                        .and_then(|portid| portid.parse().ok())
+                        .ok_or("Invalid portid")?;
+                    port = Some(Port::new(number));
+                }
+                "state" => {
+                    if let Some(port) = port.as_mut() {
+                        port.status = parse_port_status(element.attr("state").unwrap_or(""));
+                        let reason_ttl: u8 = element.attr("reason_ttl").and_then(|ttl| ttl.parse().ok()).unwrap_or(0);
+                        if let Some((host, _)) = host.as_mut() {
+                            if reason_ttl > 0 {
+                                host.ttl = reason_ttl;
+                            }
+                        }
+                    }
+                }
+                "service" => {
+                    if let Some(port) = port.as_mut() {
+                        port.service_name = element.attr("name").unwrap_or("").to_string();
+                        port.service_version = service_version(&element);
+                    }
+                }
+                "osmatch" => {
+                    if let Some((host, _)) = host.as_mut() {
+                        // The first match has the highest accuracy
+                        if host.os_family.is_empty() {
+                            host.os_family = element.attr("name").unwrap_or("").to_string();
+                            host.os_confidence = element.attr("accuracy").and_then(|a| a.parse().ok()).unwrap_or(0);
+                            in_osmatch = true;
+                        }
+                    }
+                }
+                "osclass" if in_osmatch => {
+                    if let (Some((host, _)), Some(family)) = (host.as_mut(), element.attr("osfamily")) {
+                        host.os_family = family.to_string();
+                    }
+                    in_osmatch = false;
+                }
+                "finished" => {
+                    // Negative, infinite and out of range values are ignored
+                    if let Some(elapsed) = element
+                        .attr("elapsed")
+                        .and_then(|e| e.parse::<f64>().ok())
+                        .and_then(|e| Duration::try_from_secs_f64(e).ok())
+                    {
+                        result.scan_time = elapsed;
+                    }
+                    if element.attr("exit") == Some("error") {
+                        result.scan_status = ScanStatus::Error(element.attr("errormsg").unwrap_or("").to_string());
+                    }
+                }
+                _ => {}
+            },
+            XmlEvent::End(name) => match name.as_str() {
+                "port" => {
+                    if let (Some((host, _)), Some(port)) = (host.as_mut(), port.take()) {
+                        add_port(host, port);
+                    }
+                }
+                "osmatch" => in_osmatch = false,
+                "host" => {
+                    if let Some((host, up)) = host.take() {
+                        if up {
+                            result.hosts.push(host);
+                        }
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::PortStatus;
+
+    const NMAP_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<nmaprun scanner="nmap" start="1700000000">
+<host><status state="up" reason="syn-ack"/>
+<address addr="192.168.1.10" addrtype="ipv4"/>
+<address addr="00:11:22:33:44:55" addrtype="mac" vendor="Test"/>
+<hostnames><hostname name="server.local" type="PTR"/></hostnames>
+<ports>
+<port protocol="tcp" portid="22"><state state="open" reason="syn-ack" reason_ttl="64"/><service name="ssh" product="OpenSSH" version="8.9p1" extrainfo="Ubuntu Linux; protocol 2.0"/></port>
+<port protocol="tcp" portid="80"><state state="closed" reason="reset" reason_ttl="64"/></port>
+</ports>
+<os><osmatch name="Linux 5.X" accuracy="95"><osclass osfamily="Linux"/></osmatch></os>
+</host>
+<host><status state="down"/><address addr="192.168.1.11" addrtype="ipv4"/></host>
+<runstats><finished time="1700000012" elapsed="12.50" exit="success"/></runstats>
+</nmaprun>"#;
+
+    #[test]
+    fn parse_nmap_xml() {
+        let result = parse_xml(NMAP_XML).unwrap();
+        assert_eq!(result.hosts.len(), 1);
+        let host = &result.hosts[0];
+        assert_eq!(host.ip_addr, "192.168.1.10".parse::<IpAddr>().unwrap());
+        assert_eq!(host.mac_addr, "00:11:22:33:44:55".parse::<MacAddr>().unwrap());
+        assert_eq!(host.hostname, "server.local");
+        assert_eq!(host.ttl, 64);
+        assert_eq!(host.os_family, "Linux");
+        assert_eq!(host.os_confidence, 95);
+        assert_eq!(host.ports.len(), 2);
+        assert_eq!(host.ports[0].status, PortStatus::Open);
+        assert_eq!(host.ports[0].service_name, "ssh");
+        assert_eq!(host.ports[0].service_version, "OpenSSH 8.9p1 (Ubuntu Linux; protocol 2.0)");
+        assert_eq!(host.ports[1].status, PortStatus::Closed);
+        assert_eq!(result.scan_time, Duration::from_millis(12_500));
+        assert_eq!(result.start_time, from_unix_time(1_700_000_000));
+        assert_eq!(result.scan_status, ScanStatus::Done);
+    }
+
+    #[test]
+    fn ignore_invalid_elapsed() {
+        for elapsed in ["inf", "NaN", "1e20", "-1"] {
+            let xml = format!(r#"<nmaprun><runstats><finished elapsed="{}"/></runstats></nmaprun>"#, elapsed);
+            let result = parse_xml(&xml).unwrap();
+            assert_eq!(result.scan_time, Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_port() {
+        assert!(parse_xml(r#"<host><ports><port portid="70000"/></ports></host>"#).is_err());
+    }
+}
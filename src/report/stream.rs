@@ -0,0 +1,215 @@
+use std::io::{self, Write};
+use std::sync::mpsc::Receiver;
+use netdev::mac::MacAddr;
+use crate::host::{Host, Port};
+use crate::scan::result::ScanResult;
+use super::json::json_string;
+
+/// Columns of the CSV output
+const CSV_HEADER: &str = "ip_addr,hostname,mac_addr,ttl,os_family,port,status,service_name,service_version,rtt_us";
+
+/// Writer that appends one record per host or port as results arrive.
+///
+/// Records are written as they are passed, so the finished hosts from the result receiver of
+/// `HostScanner` or `PortScanner` can be streamed to the output while the scan is running (see `write_received`).
+pub trait RecordWriter {
+    /// Write one record of the host. `port` is None for a host without ports (e.g. from a host scan)
+    fn write_record(&mut self, host: &Host, port: Option<&Port>) -> io::Result<()>;
+    /// Flush the underlying writer
+    fn flush(&mut self) -> io::Result<()>;
+    /// Write a record for each port of the host, or a single record if it has no ports
+    fn write_host(&mut self, host: &Host) -> io::Result<()> {
+        if host.ports.is_empty() {
+            return self.write_record(host, None);
+        }
+        for port in &host.ports {
+            self.write_record(host, Some(port))?;
+        }
+        Ok(())
+    }
+    /// Write the hosts from the result receiver until the channel is closed.
+    ///
+    /// `HostScanner` and `PortScanner` close the channel when `scan` returns, so call this on another thread
+    /// while the scan is running. It blocks until then; a receiver whose scan is never run does not return.
+    /// The output is flushed after each host.
+    fn write_received(&mut self, rx: &Receiver<Host>) -> io::Result<()> {
+        for host in rx.iter() {
+            self.write_host(&host)?;
+            self.flush()?;
+        }
+        Ok(())
+    }
+    /// Write the records of all hosts in the scan result
+    fn write_result(&mut self, result: &ScanResult) -> io::Result<()> {
+        for host in &result.hosts {
+            self.write_host(host)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fields of the record. RTT is of the port, or the average of the host for a host record
+struct Record {
+    ip_addr: String,
+    hostname: String,
+    mac_addr: Option<String>,
+    ttl: Option<u8>,
+    os_family: String,
+    port: Option<u16>,
+    status: String,
+    service_name: String,
+    service_version: String,
+    rtt_us: Option<u128>,
+}
+
+impl Record {
+    fn new(host: &Host, port: Option<&Port>) -> Record {
+        Record {
+            ip_addr: host.ip_addr.to_string(),
+            hostname: host.hostname.clone(),
+            mac_addr: if host.mac_addr == MacAddr::zero() { None } else { Some(host.mac_addr.to_string()) },
+            ttl: if host.ttl == 0 { None } else { Some(host.ttl) },
+            os_family: host.os_family.clone(),
+            port: port.map(|port| port.number),
+            status: match port {
+                Some(port) => port.status.id(),
+                None => String::from("up"),
+            },
+            service_name: port.map(|port| port.service_name.clone()).unwrap_or_default(),
+            service_version: port.map(|port| port.service_version.clone()).unwrap_or_default(),
+            rtt_us: match port {
+                Some(port) => port.rtt.map(|rtt| rtt.as_micros()),
+                None => host.rtt.map(|rtt| rtt.avg.as_micros()),
+            },
+        }
+    }
+}
+
+/// Quote the CSV field if it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Streaming CSV writer. The header is written before the first record
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    /// Write the header line
+    header: bool,
+    header_written: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(writer: W) -> Self {
+        CsvWriter {
+            writer,
+            header: true,
+            header_written: false,
+        }
+    }
+    /// Write the header line or not (e.g. when appending to an existing file)
+    pub fn set_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+    /// Get the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> RecordWriter for CsvWriter<W> {
+    fn write_record(&mut self, host: &Host, port: Option<&Port>) -> io::Result<()> {
+        if self.header && !self.header_written {
+            writeln!(self.writer, "{}", CSV_HEADER)?;
+            self.header_written = true;
+        }
+        let record = Record::new(host, port);
+        let fields: [String; 10] = [
+            csv_field(&record.ip_addr),
+            csv_field(&record.hostname),
+            record.mac_addr.unwrap_or_default(),
+            record.ttl.map(|ttl| ttl.to_string()).unwrap_or_default(),
+            csv_field(&record.os_family),
+            record.port.map(|port| port.to_string()).unwrap_or_default(),
+            record.status,
+            csv_field(&record.service_name),
+            csv_field(&record.service_version),
+            record.rtt_us.map(|rtt| rtt.to_string()).unwrap_or_default(),
+        ];
+        writeln!(self.writer, "{}", fields.join(","))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Streaming NDJSON writer. Each record is written as one JSON object per line
+pub struct NdjsonWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        NdjsonWriter { writer }
+    }
+    /// Get the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> RecordWriter for NdjsonWriter<W> {
+    fn write_record(&mut self, host: &Host, port: Option<&Port>) -> io::Result<()> {
+        let record = Record::new(host, port);
+        let null = || String::from("null");
+        writeln!(
+            self.writer,
+            "{{\"ip_addr\":{},\"hostname\":{},\"mac_addr\":{},\"ttl\":{},\"os_family\":{},\"port\":{},\"status\":{},\"service_name\":{},\"service_version\":{},\"rtt_us\":{}}}",
+            json_string(&record.ip_addr),
+            json_string(&record.hostname),
+            record.mac_addr.map(|mac_addr| json_string(&mac_addr)).unwrap_or_else(null),
+            record.ttl.map(|ttl| ttl.to_string()).unwrap_or_else(null),
+            json_string(&record.os_family),
+            record.port.map(|port| port.to_string()).unwrap_or_else(null),
+            json_string(&record.status),
+            json_string(&record.service_name),
+            json_string(&record.service_version),
+            record.rtt_us.map(|rtt| rtt.to_string()).unwrap_or_else(null)
+        )
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+    use std::sync::mpsc::channel;
+    use std::thread;
+    use crate::host::PortStatus;
+
+    #[test]
+    fn write_received_hosts() {
+        let (tx, rx) = channel();
+        let sender = thread::spawn(move || {
+            let mut host = Host::new("10.0.0.1".parse::<IpAddr>().unwrap(), "a,b".to_string());
+            let mut port = Port::new(22);
+            port.status = PortStatus::Open;
+            host.ports.push(port);
+            tx.send(host).unwrap();
+            tx.send(Host::new("10.0.0.2".parse::<IpAddr>().unwrap(), String::new())).unwrap();
+        });
+        let mut writer = CsvWriter::new(vec![]);
+        writer.write_received(&rx).unwrap();
+        sender.join().unwrap();
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines, vec![CSV_HEADER, "10.0.0.1,\"a,b\",,,,22,open,,,", "10.0.0.2,,,,,,up,,,"]);
+    }
+}
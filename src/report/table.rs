@@ -0,0 +1,139 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use crate::host::{Host, Port, PortStatus};
+use crate::scan::result::{ScanResult, ScanStatus};
+use super::format_rtt;
+
+const HEADERS: [&str; 6] = ["HOST", "PORT", "STATE", "SERVICE", "VERSION", "RTT"];
+/// Spaces between columns
+const COLUMN_GAP: usize = 2;
+
+/// Renderer of scan results as an aligned text table.
+///
+/// One row per port with the columns host, port, state, service, version and RTT.
+/// Hosts without ports (e.g. from a host scan), or without open ports when only open ports are shown,
+/// are shown as a single `up` row with the average RTT of the host.
+#[derive(Clone, Debug, Default)]
+pub struct TableWriter {
+    /// Show only open ports
+    open_only: bool,
+    /// Append the status and scan time of the result
+    summary: bool,
+}
+
+impl TableWriter {
+    pub fn new() -> Self {
+        TableWriter::default()
+    }
+    pub fn set_open_only(mut self, open_only: bool) -> Self {
+        self.open_only = open_only;
+        self
+    }
+    pub fn set_summary(mut self, summary: bool) -> Self {
+        self.summary = summary;
+        self
+    }
+    /// Render the scan result as a table
+    pub fn to_text(&self, result: &ScanResult) -> String {
+        let mut rows: Vec<[String; 6]> = vec![HEADERS.map(|header| header.to_string())];
+        for host in &result.hosts {
+            self.push_host(&mut rows, host);
+        }
+        let mut widths: [usize; 6] = [0; 6];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut text = String::new();
+        for row in &rows {
+            let mut line = String::new();
+            for (i, cell) in row.iter().enumerate() {
+                if i + 1 < row.len() {
+                    let _ = write!(line, "{:width$}", cell, width = widths[i] + COLUMN_GAP);
+                } else {
+                    line.push_str(cell);
+                }
+            }
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        if self.summary {
+            let status = match &result.scan_status {
+                ScanStatus::Done => String::from("Done"),
+                ScanStatus::Timeout => String::from("Timeout"),
+                ScanStatus::Error(message) => format!("Error: {}", message),
+            };
+            text.push_str(&format!(
+                "\n{} host{} scanned in {:.2} seconds ({})\n",
+                result.hosts.len(),
+                if result.hosts.len() == 1 { "" } else { "s" },
+                result.scan_time.as_secs_f64(),
+                status
+            ));
+        }
+        text
+    }
+    /// Write the scan result as a table
+    pub fn write<W: Write>(&self, result: &ScanResult, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.to_text(result).as_bytes())
+    }
+    /// Add the rows of the host. Host name is shown only on the first row
+    fn push_host(&self, rows: &mut Vec<[String; 6]>, host: &Host) {
+        let name = if host.hostname.is_empty() || host.hostname == host.ip_addr.to_string() {
+            host.ip_addr.to_string()
+        } else {
+            format!("{} ({})", host.ip_addr, host.hostname)
+        };
+        let ports: Vec<&Port> = host
+            .ports
+            .iter()
+            .filter(|port| !self.open_only || port.status == PortStatus::Open)
+            .collect();
+        if ports.is_empty() {
+            rows.push([
+                name,
+                String::from("-"),
+                String::from("up"),
+                String::new(),
+                String::new(),
+                host.rtt.map(|rtt| format_rtt(rtt.avg)).unwrap_or_default(),
+            ]);
+            return;
+        }
+        for (i, port) in ports.into_iter().enumerate() {
+            rows.push([
+                if i == 0 { name.clone() } else { String::new() },
+                port.number.to_string(),
+                port.status.id(),
+                port.service_name.clone(),
+                port.service_version.clone(),
+                port.rtt.map(format_rtt).unwrap_or_default(),
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+
+    #[test]
+    fn open_only_keeps_hosts_without_open_ports() {
+        let mut host = Host::new("10.0.0.1".parse::<IpAddr>().unwrap(), String::new());
+        let mut port = Port::new(22);
+        port.status = PortStatus::Closed;
+        host.ports.push(port);
+        let mut result = ScanResult::new();
+        result.hosts.push(host);
+        assert_eq!(
+            TableWriter::new().to_text(&result),
+            "HOST      PORT  STATE   SERVICE  VERSION  RTT\n10.0.0.1  22    closed\n"
+        );
+        assert_eq!(
+            TableWriter::new().set_open_only(true).to_text(&result),
+            "HOST      PORT  STATE  SERVICE  VERSION  RTT\n10.0.0.1  -     up\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+/// Start tag with the attributes
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl XmlElement {
+    /// Get attribute value by name
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Markup event. Character data is skipped
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum XmlEvent {
+    Start(XmlElement),
+    End(String),
+}
+
+/// Decode the predefined entities and character references
+pub(crate) fn decode_entities(s: &str) -> String {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(pos) = rest.find('&') {
+        decoded.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let c: Option<char> = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => {
+                if let Some(hex) = entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse::<u32>().ok().and_then(char::from_u32)
+                } else {
+                    None
+                }
+            }
+        };
+        match c {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn parse_attributes(mut s: &str) -> Result<Vec<(String, String)>, String> {
+    let mut attributes: Vec<(String, String)> = vec![];
+    loop {
+        s = s.trim_start();
+        if s.is_empty() {
+            return Ok(attributes);
+        }
+        let eq = s.find('=').ok_or_else(|| format!("Invalid attribute: {}", s))?;
+        let name = s[..eq].trim().to_string();
+        s = s[eq + 1..].trim_start();
+        let quote = match s.chars().next() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Err(format!("Unquoted attribute value: {}", name)),
+        };
+        let end = s[1..].find(quote).ok_or_else(|| format!("Unterminated attribute value: {}", name))?;
+        attributes.push((name, decode_entities(&s[1..end + 1])));
+        s = &s[end + 2..];
+    }
+}
+
+/// Parse the markup of the XML document into start and end tag events.
+///
+/// Declarations, processing instructions, comments, CDATA sections and character data are skipped.
+/// Self-closing tags produce both start and end events.
+pub(crate) fn parse_events(xml: &str) -> Result<Vec<XmlEvent>, String> {
+    let mut events: Vec<XmlEvent> = vec![];
+    let mut rest = xml;
+    while let Some(pos) = rest.find('<') {
+        rest = &rest[pos..];
+        let skip_until = if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<![CDATA[") {
+            Some("]]>")
+        } else if rest.starts_with("<?") {
+            Some("?>")
+        } else if rest.starts_with("<!") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(terminator) = skip_until {
+            let end = rest.find(terminator).ok_or("Unterminated markup")?;
+            rest = &rest[end + terminator.len()..];
+            continue;
+        }
+        let end = rest.find('>').ok_or("Unterminated tag")?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if let Some(name) = tag.strip_prefix('/') {
+            events.push(XmlEvent::End(name.trim().to_string()));
+            continue;
+        }
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let name_end = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+        let name = tag[..name_end].to_string();
+        if name.is_empty() {
+            return Err("Empty tag name".to_string());
+        }
+        events.push(XmlEvent::Start(XmlElement {
+            name: name.clone(),
+            attributes: parse_attributes(&tag[name_end..])?,
+        }));
+        if self_closing {
+            events.push(XmlEvent::End(name));
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tags_and_attributes() {
+        let xml = r#"<?xml version="1.0"?><!DOCTYPE nmaprun><!-- comment --><run a="1" b='x &amp; &#x41;&#66;'><item/></run>"#;
+        let events = parse_events(xml).unwrap();
+        assert_eq!(events.len(), 4);
+        match &events[0] {
+            XmlEvent::Start(element) => {
+                assert_eq!(element.name, "run");
+                assert_eq!(element.attr("a"), Some("1"));
+                assert_eq!(element.attr("b"), Some("x & AB"));
+                assert_eq!(element.attr("c"), None);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        assert!(matches!(&events[1], XmlEvent::Start(element) if element.name == "item"));
+        assert_eq!(events[2], XmlEvent::End("item".to_string()));
+        assert_eq!(events[3], XmlEvent::End("run".to_string()));
+    }
+
+    #[test]
+    fn reject_malformed_markup() {
+        assert!(parse_events("<run a=1>").is_err());
+        assert!(parse_events("<run a=\"1>").is_err());
+        assert!(parse_events("<run").is_err());
+        assert!(parse_events("<!-- unterminated").is_err());
+    }
+
+    #[test]
+    fn keep_unknown_entities() {
+        assert_eq!(decode_entities("a &unknown; &lt;b&gt; &"), "a &unknown; <b> &");
+    }
+}
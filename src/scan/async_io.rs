@@ -1,12 +1,12 @@
 use std::net::{IpAddr, SocketAddr};
 use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use futures::stream::{self, StreamExt};
 use netdev::Interface;
 use nex::socket::{AsyncSocket, IpVersion, SocketOption, SocketType};
 
-use crate::host::{Host, Port, PortStatus};
+use crate::host::{Host, Port, PortStatus, RttStats};
 
 use super::result::ScanResult;
 use super::setting::{HostScanSetting, PortScanSetting};
@@ -18,11 +18,13 @@ use nex::packet::ip::IpNextLevelProtocol;
 use crate::config::PCAP_WAIT_TIME_MILLIS;
 use crate::packet::frame::PacketFrame;
 use crate::pcap::PacketCaptureOptions;
+use crate::pcap::dump::PacketDump;
 
-use super::result::{ScanStatus, parse_hostscan_result, parse_portscan_result};
-use super::setting::{HostScanType, PortScanType};
+use super::result::{ProbeTimes, ScanStatus, parse_hostscan_result, parse_portscan_result, send_results};
+use super::setting::HostScanType;
+use super::blocking::{set_hostscan_filter, set_portscan_filter};
 
-pub (crate) async fn send_portscan_packets(interface: &Interface, socket: &AsyncSocket, scan_setting: &PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>) {
+pub (crate) async fn send_portscan_packets(interface: &Interface, socket: &AsyncSocket, scan_setting: &PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>, probe_times: &Mutex<ProbeTimes>) {
     let fut_host = stream::iter(scan_setting.targets.clone()).for_each_concurrent(
         scan_setting.concurrency,
         |dst| async move {
@@ -33,9 +35,10 @@ pub (crate) async fn send_portscan_packets(interface: &Interface, socket: &Async
                     let dst_socket_addr: SocketAddr = SocketAddr::new(target.ip_addr, port);
                     async move {
                         let packet_bytes: Vec<u8> = build_portscan_ip_next_packet(&interface, target.ip_addr, port);
-                        match socket.send_to(&packet_bytes, dst_socket_addr).await {
-                            Ok(_) => {}
-                            Err(_) => {}
+                        if socket.send_to(&packet_bytes, dst_socket_addr).await.is_ok() {
+                            if let Ok(mut probe_times) = probe_times.lock() {
+                                probe_times.record(dst_socket_addr, SystemTime::now());
+                            }
                         }
                         match ptx.lock() {
                             Ok(lr) => match lr.send(dst_socket_addr) {
@@ -54,12 +57,15 @@ pub (crate) async fn send_portscan_packets(interface: &Interface, socket: &Async
     fut_host.await;
 }
 
-pub (crate) async fn send_hostscan_packets(interface: &Interface, scan_setting: &HostScanSetting, ptx: &Arc<Mutex<Sender<Host>>>) {
+pub (crate) async fn send_hostscan_packets(interface: &Interface, scan_setting: &HostScanSetting, ptx: &Arc<Mutex<Sender<Host>>>, probe_times: &Mutex<ProbeTimes>) {
     let fut_host = stream::iter(scan_setting.targets.clone()).for_each_concurrent(
         scan_setting.concurrency,
         |dst| async move {
             let socket: AsyncSocket = match scan_setting.scan_type {
-                HostScanType::IcmpPingScan => match dst.ip_addr {
+                HostScanType::IcmpPingScan
+                | HostScanType::Icmpv6MulticastPingScan
+                | HostScanType::RouterSolicitationScan
+                | HostScanType::IcmpBroadcastPingScan => match dst.ip_addr {
                     IpAddr::V4(_) => {
                         let socket_option = SocketOption {
                             ip_version: IpVersion::V4,
@@ -108,9 +114,10 @@ pub (crate) async fn send_hostscan_packets(interface: &Interface, scan_setting:
             };
             let dst_socket_addr: SocketAddr = SocketAddr::new(dst.ip_addr, 0);
             let packet_bytes = build_hostscan_ip_next_packet(&interface, &dst, &scan_setting.scan_type);
-            match socket.send_to(&packet_bytes, dst_socket_addr).await {
-                Ok(_) => {}
-                Err(_) => {}
+            if socket.send_to(&packet_bytes, dst_socket_addr).await.is_ok() {
+                if let Ok(mut probe_times) = probe_times.lock() {
+                    probe_times.record(dst_socket_addr, SystemTime::now());
+                }
             }
             match ptx.lock() {
                 Ok(lr) => match lr.send(dst) {
@@ -138,10 +145,12 @@ pub async fn try_connect_ports(
             let channel_tx = channel_tx.clone();
             async move {
                 let socket_addr: SocketAddr = SocketAddr::new(target.ip_addr, port);
+                let start_time = Instant::now();
                 match AsyncSocket::new_with_async_connect_timeout(&socket_addr, timeout).await
                 {
                     Ok(async_socket) => {
-                        let _ = channel_tx.send(port);
+                        // Connect time is the RTT of the handshake
+                        let _ = channel_tx.send((port, start_time.elapsed()));
                         match async_socket.shutdown(std::net::Shutdown::Both).await {
                             Ok(_) => {}
                             Err(_) => {}
@@ -163,12 +172,13 @@ pub async fn try_connect_ports(
     let mut open_ports: Vec<Port> = vec![];
     loop {
         match channel_rx.recv() {
-            Ok(port) => {
+            Ok((port, rtt)) => {
                 open_ports.push(Port {
                     number: port,
                     status: PortStatus::Open,
                     service_name: String::new(),
                     service_version: String::new(),
+                    rtt: Some(rtt),
                 });
             }
             Err(_) => {
@@ -176,24 +186,35 @@ pub async fn try_connect_ports(
             }
         }
     }
+    let rtt_samples: Vec<Duration> = open_ports.iter().filter_map(|port| port.rtt).collect();
     Host {
         ip_addr: target.ip_addr,
         hostname: target.hostname,
         ports: open_ports,
         mac_addr: target.mac_addr,
         ttl: target.ttl,
+        os_family: target.os_family,
+        os_confidence: target.os_confidence,
+        uptime: target.uptime,
+        rtt: RttStats::from_samples(&rtt_samples),
+        details: target.details,
     }
 }
 
-pub fn run_connect_scan(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>) -> ScanResult {
+/// Run TCP connect scan. Each host is sent to `result_tx` as soon as its ports are done
+pub fn run_connect_scan(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>, result_tx: Option<&Arc<Mutex<Sender<Host>>>>) -> ScanResult {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let result = rt.block_on(async {
         let start_time = std::time::Instant::now();
         let mut tasks = vec![];
         for target in scan_setting.targets {
             let ptx = ptx.clone();
+            let result_tx = result_tx.cloned();
             tasks.push(tokio::spawn(async move {
                 let host = try_connect_ports(target, scan_setting.concurrency, scan_setting.timeout, &ptx).await;
+                if let Some(result_tx) = result_tx {
+                    send_results(&result_tx, std::slice::from_ref(&host));
+                }
                 host
             }));
         }
@@ -217,7 +238,8 @@ pub fn run_connect_scan(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<So
     result
 }
 
-pub (crate) async fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<Host>>>) -> ScanResult {
+/// Scan the hosts. The hosts are sent to `result_tx` as soon as the responses are parsed
+pub (crate) async fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<Host>>>, result_tx: Option<&Arc<Mutex<Sender<Host>>>>, dump: Option<&PacketDump>) -> ScanResult {
     let interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
         Some(interface) => interface,
         None => return ScanResult::new(),
@@ -252,41 +274,10 @@ pub (crate) async fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<S
         receive_undefined: false,
         tunnel: interface.is_tun(),
         loopback: interface.is_loopback(),
+        // Probes sent through raw sockets are recorded from the capture
+        dump: dump.cloned(),
     };
-    for target in scan_setting.targets.clone() {
-        capture_options.src_ips.insert(target.ip_addr);
-    }
-    match scan_setting.scan_type {
-        HostScanType::IcmpPingScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmp);
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmpv6);
-        }
-        HostScanType::TcpPingScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Tcp);
-            for target in scan_setting.targets.clone() {
-                for port in target.get_ports() {
-                    capture_options.src_ports.insert(port);
-                }
-            }
-        }
-        HostScanType::UdpPingScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Udp);
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmp);
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmpv6);
-        }
-    }
+    set_hostscan_filter(&mut capture_options, &scan_setting);
     let stop: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     let stop_handle = Arc::clone(&stop);
     let packets: Arc<Mutex<Vec<PacketFrame>>> = Arc::new(Mutex::new(vec![]));
@@ -310,7 +301,8 @@ pub (crate) async fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<S
     thread::sleep(Duration::from_millis(PCAP_WAIT_TIME_MILLIS));
     let start_time = std::time::Instant::now();
     // Send probe packets
-    send_hostscan_packets(&interface, &scan_setting, ptx).await;
+    let probe_times: Mutex<ProbeTimes> = Mutex::new(ProbeTimes::new());
+    send_hostscan_packets(&interface, &scan_setting, ptx, &probe_times).await;
     thread::sleep(scan_setting.wait_time);
     // Stop pcap
     match stop.lock() {
@@ -332,7 +324,7 @@ pub (crate) async fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<S
     let mut scan_result: ScanResult = ScanResult::new();
     match packets.lock() {
         Ok(packets) => {
-            scan_result = parse_hostscan_result(packets.clone(), scan_setting);
+            scan_result = parse_hostscan_result(packets.clone(), scan_setting, &probe_times.lock().map(|probe_times| probe_times.clone()).unwrap_or_default());
         }
         Err(e) => {
             eprintln!("Failed to lock packets: {}", e);
@@ -340,10 +332,14 @@ pub (crate) async fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<S
     }
     scan_result.scan_time = start_time.elapsed();
     scan_result.scan_status = ScanStatus::Done;
+    if let Some(result_tx) = result_tx {
+        send_results(result_tx, &scan_result.hosts);
+    }
     scan_result
 }
 
-pub (crate) async fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>) -> ScanResult {
+/// Scan the ports. The hosts are sent to `result_tx` as soon as the responses are parsed
+pub (crate) async fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>, result_tx: Option<&Arc<Mutex<Sender<Host>>>>, dump: Option<&PacketDump>) -> ScanResult {
     let interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
         Some(interface) => interface,
         None => return ScanResult::new(),
@@ -393,23 +389,10 @@ pub (crate) async fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<S
         receive_undefined: false,
         tunnel: interface.is_tun(),
         loopback: interface.is_loopback(),
+        // Probes sent through raw sockets are recorded from the capture
+        dump: dump.cloned(),
     };
-    for target in scan_setting.targets.clone() {
-        capture_options.src_ips.insert(target.ip_addr);
-        capture_options.src_ports.extend(target.get_ports());
-    }
-    match scan_setting.scan_type {
-        PortScanType::TcpSynScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Tcp);
-        }
-        PortScanType::TcpConnectScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Tcp);
-        }
-    }
+    set_portscan_filter(&mut capture_options, &scan_setting);
     let stop: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     let stop_handle = Arc::clone(&stop);
     let packets: Arc<Mutex<Vec<PacketFrame>>> = Arc::new(Mutex::new(vec![]));
@@ -432,7 +415,8 @@ pub (crate) async fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<S
     thread::sleep(Duration::from_millis(PCAP_WAIT_TIME_MILLIS));
     let start_time = std::time::Instant::now();
     // Send probe packets
-    send_portscan_packets(&interface, &socket, &scan_setting, ptx).await;
+    let probe_times: Mutex<ProbeTimes> = Mutex::new(ProbeTimes::new());
+    send_portscan_packets(&interface, &socket, &scan_setting, ptx, &probe_times).await;
     thread::sleep(scan_setting.wait_time);
     // Stop pcap
     match stop.lock() {
@@ -453,7 +437,7 @@ pub (crate) async fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<S
     let mut scan_result: ScanResult = ScanResult::new();
     match packets.lock() {
         Ok(packets) => {
-        scan_result = parse_portscan_result(packets.clone(), scan_setting);
+        scan_result = parse_portscan_result(packets.clone(), scan_setting, &probe_times.lock().map(|probe_times| probe_times.clone()).unwrap_or_default());
         }
         Err(e) => {
         eprintln!("Failed to lock packets: {}", e);
@@ -461,5 +445,8 @@ pub (crate) async fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<S
     }
     scan_result.scan_time = start_time.elapsed();
     scan_result.scan_status = ScanStatus::Done;
+    if let Some(result_tx) = result_tx {
+        send_results(result_tx, &scan_result.hosts);
+    }
     scan_result
 }
@@ -1,23 +1,37 @@
 use std::collections::HashSet;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use netdev::Interface;
 use nex::datalink::FrameSender;
 use nex::packet::ip::IpNextLevelProtocol;
-use crate::config::PCAP_WAIT_TIME_MILLIS;
+use crate::config::{PCAP_WAIT_TIME_MILLIS, UPTIME_PROBE_BASE_PORT, UPTIME_PROBE_COUNT, UPTIME_PROBE_INTERVAL_MILLIS};
 use crate::packet::frame::PacketFrame;
 use crate::pcap::PacketCaptureOptions;
+use crate::pcap::dump::PacketDump;
 use crate::scan::setting::{PortScanSetting, HostScanSetting};
-use crate::host::Host;
+use crate::host::{Host, PortStatus};
+use crate::os::probe::{match_probe_fingerprint, parse_probe_responses, OsProbe};
+use crate::os::uptime::{estimate_uptime, timestamp_samples};
 
-use super::result::{ScanResult, ScanStatus, parse_hostscan_result, parse_portscan_result};
+use super::result::{ProbeTimes, ScanResult, ScanStatus, parse_hostscan_result, parse_portscan_result, send_results};
 use super::setting::{HostScanType, PortScanType};
-use super::packet::{build_hostscan_packet, build_portscan_packet};
+use super::packet::{build_discovery_packet, build_hostscan_packet, build_os_probe_packet, build_portscan_packet, build_timestamp_probe_packet};
 
-pub (crate) fn send_hostscan_packets(tx: &mut Box<dyn FrameSender>, interface: &Interface, targets: Vec<Host>, ptx: &Arc<Mutex<Sender<Host>>>, scan_type: HostScanType) {
+/// Send the frame and record it to the capture file
+fn send_frame(tx: &mut Box<dyn FrameSender>, packet: &[u8], dump: Option<&PacketDump>) -> Option<std::io::Result<()>> {
+    let result = tx.send(packet);
+    if result.is_some() {
+        if let Some(dump) = dump {
+            dump.record_sent(packet);
+        }
+    }
+    result
+}
+
+pub (crate) fn send_hostscan_packets(tx: &mut Box<dyn FrameSender>, interface: &Interface, targets: Vec<Host>, ptx: &Arc<Mutex<Sender<Host>>>, scan_type: HostScanType, dump: Option<&PacketDump>, probe_times: &mut ProbeTimes) {
     // Acquire message sender lock
     let ptx_lock = match ptx.lock() {
         Ok(ptx) => ptx,
@@ -28,8 +42,9 @@ pub (crate) fn send_hostscan_packets(tx: &mut Box<dyn FrameSender>, interface: &
     };
     for target in targets {
         let packet = build_hostscan_packet(&interface, &target, &scan_type, false);
-        match tx.send(&packet) {
+        match send_frame(tx, &packet, dump) {
             Some(_) => {
+                probe_times.record(SocketAddr::new(target.ip_addr, 0), SystemTime::now());
                 // Notify packet sent
                 match ptx_lock.send(target) {
                     Ok(_) => {},
@@ -47,7 +62,7 @@ pub (crate) fn send_hostscan_packets(tx: &mut Box<dyn FrameSender>, interface: &
     drop(ptx_lock);
 }
 
-pub (crate) fn send_portscan_packets(tx: &mut Box<dyn FrameSender>, interface: &Interface, targets: Vec<Host>, ptx: &Arc<Mutex<Sender<SocketAddr>>>, scan_type: PortScanType) {
+pub (crate) fn send_portscan_packets(tx: &mut Box<dyn FrameSender>, interface: &Interface, targets: Vec<Host>, ptx: &Arc<Mutex<Sender<SocketAddr>>>, scan_type: PortScanType, dump: Option<&PacketDump>, probe_times: &mut ProbeTimes) {
     // Acquire message sender lock
     let ptx_lock = match ptx.lock() {
         Ok(ptx) => ptx,
@@ -61,8 +76,9 @@ pub (crate) fn send_portscan_packets(tx: &mut Box<dyn FrameSender>, interface: &
             PortScanType::TcpSynScan => {
                 for port in target.ports {
                     let packet = build_portscan_packet(&interface, target.ip_addr, port.number, false);
-                    match tx.send(&packet) {
+                    match send_frame(tx, &packet, dump) {
                         Some(_) => {
+                            probe_times.record(SocketAddr::new(target.ip_addr, port.number), SystemTime::now());
                             // Notify packet sent
                             match ptx_lock.send(SocketAddr::new(target.ip_addr, port.number)) {
                                 Ok(_) => {},
@@ -80,13 +96,88 @@ pub (crate) fn send_portscan_packets(tx: &mut Box<dyn FrameSender>, interface: &
             PortScanType::TcpConnectScan => {
                 // TODO
             },
+            PortScanType::IdleScan => {
+                // Sent by idle scan
+            },
         }
     }
     // Drop message sender lock
     drop(ptx_lock);
 }
 
-pub (crate) fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<Host>>>) -> ScanResult {
+/// Set the capture filters for the responses to the host scan probes
+pub (crate) fn set_hostscan_filter(capture_options: &mut PacketCaptureOptions, scan_setting: &HostScanSetting) {
+    // Responders of the discovery scan are unknown in advance
+    if !scan_setting.scan_type.is_discovery() {
+        for target in &scan_setting.targets {
+            capture_options.src_ips.insert(target.ip_addr);
+        }
+    }
+    match scan_setting.scan_type {
+        HostScanType::IcmpPingScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Icmp);
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Icmpv6);
+        }
+        HostScanType::TcpPingScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Tcp);
+            for target in &scan_setting.targets {
+                for port in &target.ports {
+                    capture_options.src_ports.insert(port.number);
+                }
+            }
+        }
+        HostScanType::UdpPingScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Udp);
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Icmp);
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Icmpv6);
+        }
+        HostScanType::Icmpv6MulticastPingScan | HostScanType::RouterSolicitationScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Icmpv6);
+        }
+        HostScanType::IcmpBroadcastPingScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Icmp);
+        }
+    }
+}
+
+/// Set the capture filters for the responses to the port scan probes
+pub (crate) fn set_portscan_filter(capture_options: &mut PacketCaptureOptions, scan_setting: &PortScanSetting) {
+    for target in &scan_setting.targets {
+        capture_options.src_ips.insert(target.ip_addr);
+        capture_options.src_ports.extend(target.get_ports());
+    }
+    match scan_setting.scan_type {
+        PortScanType::TcpSynScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Tcp);
+        }
+        PortScanType::TcpConnectScan | PortScanType::IdleScan => {
+            capture_options
+                .ip_protocols
+                .insert(IpNextLevelProtocol::Tcp);
+        }
+    }
+}
+
+/// Scan the hosts. The hosts are sent to `result_tx` as soon as the responses are parsed
+pub (crate) fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<Host>>>, result_tx: Option<&Arc<Mutex<Sender<Host>>>>, dump: Option<&PacketDump>) -> ScanResult {
     let interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
         Some(interface) => interface,
         None => return ScanResult::new(),
@@ -121,41 +212,18 @@ pub (crate) fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<
         receive_undefined: false,
         tunnel: interface.is_tun(),
         loopback: interface.is_loopback(),
+        dump: dump.cloned(),
     };
-    for target in scan_setting.targets.clone() {
-        capture_options.src_ips.insert(target.ip_addr);
-    }
-    match scan_setting.scan_type {
-        HostScanType::IcmpPingScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmp);
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmpv6);
-        }
-        HostScanType::TcpPingScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Tcp);
-            for target in scan_setting.targets.clone() {
-                for port in target.ports {
-                    capture_options.src_ports.insert(port.number);
-                }
-            }
+    // Build the discovery probe before starting capture
+    let discovery_packet: Option<Vec<u8>> = if scan_setting.scan_type.is_discovery() {
+        match build_discovery_packet(&interface, &scan_setting.scan_type) {
+            Ok(packet) => Some(packet),
+            Err(e) => return ScanResult::error(e),
         }
-        HostScanType::UdpPingScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Udp);
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmp);
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Icmpv6);
-        }
-    }
+    } else {
+        None
+    };
+    set_hostscan_filter(&mut capture_options, &scan_setting);
     let stop: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     let stop_handle = Arc::clone(&stop);
     let packets: Arc<Mutex<Vec<PacketFrame>>> = Arc::new(Mutex::new(vec![]));
@@ -178,7 +246,18 @@ pub (crate) fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<
     thread::sleep(Duration::from_millis(PCAP_WAIT_TIME_MILLIS));
     let start_time = std::time::Instant::now();
     // Send probe packets
-    send_hostscan_packets(&mut tx, &interface, scan_setting.targets.clone(), ptx, scan_setting.scan_type.clone());
+    let mut probe_times: ProbeTimes = ProbeTimes::new();
+    match discovery_packet {
+        Some(packet) => {
+            if send_frame(&mut tx, &packet, dump).is_none() {
+                eprintln!("Failed to send packet");
+            }
+            probe_times.record_discovery(SystemTime::now());
+        }
+        None => {
+            send_hostscan_packets(&mut tx, &interface, scan_setting.targets.clone(), ptx, scan_setting.scan_type.clone(), dump, &mut probe_times);
+        }
+    }
     thread::sleep(scan_setting.wait_time);
     // Stop pcap
     match stop.lock() {
@@ -199,7 +278,7 @@ pub (crate) fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<
     let mut scan_result: ScanResult = ScanResult::new();
     match packets.lock() {
         Ok(packets) => {
-            scan_result = parse_hostscan_result(packets.clone(), scan_setting);
+            scan_result = parse_hostscan_result(packets.clone(), scan_setting, &probe_times);
         }
         Err(e) => {
             eprintln!("Failed to lock packets: {}", e);
@@ -207,10 +286,14 @@ pub (crate) fn scan_hosts(scan_setting: HostScanSetting, ptx: &Arc<Mutex<Sender<
     }
     scan_result.scan_time = start_time.elapsed();
     scan_result.scan_status = ScanStatus::Done;
+    if let Some(result_tx) = result_tx {
+        send_results(result_tx, &scan_result.hosts);
+    }
     scan_result
 }
 
-pub (crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>) -> ScanResult {
+/// Scan the ports. The hosts are sent to `result_tx` as soon as the responses are parsed
+pub (crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>, result_tx: Option<&Arc<Mutex<Sender<Host>>>>, dump: Option<&PacketDump>) -> ScanResult {
     let interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
         Some(interface) => interface,
         None => return ScanResult::new(),
@@ -245,23 +328,9 @@ pub (crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<
         receive_undefined: false,
         tunnel: interface.is_tun(),
         loopback: interface.is_loopback(),
+        dump: dump.cloned(),
     };
-    for target in scan_setting.targets.clone() {
-        capture_options.src_ips.insert(target.ip_addr);
-        capture_options.src_ports.extend(target.get_ports());
-    }
-    match scan_setting.scan_type {
-        PortScanType::TcpSynScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Tcp);
-        }
-        PortScanType::TcpConnectScan => {
-            capture_options
-                .ip_protocols
-                .insert(IpNextLevelProtocol::Tcp);
-        }
-    }
+    set_portscan_filter(&mut capture_options, &scan_setting);
     let stop: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     let stop_handle = Arc::clone(&stop);
     let packets: Arc<Mutex<Vec<PacketFrame>>> = Arc::new(Mutex::new(vec![]));
@@ -284,7 +353,8 @@ pub (crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<
     thread::sleep(Duration::from_millis(PCAP_WAIT_TIME_MILLIS));
     let start_time = std::time::Instant::now();
     // Send probe packets
-    send_portscan_packets(&mut tx, &interface, scan_setting.targets.clone(), ptx, scan_setting.scan_type.clone());
+    let mut probe_times: ProbeTimes = ProbeTimes::new();
+    send_portscan_packets(&mut tx, &interface, scan_setting.targets.clone(), ptx, scan_setting.scan_type.clone(), dump, &mut probe_times);
     thread::sleep(scan_setting.wait_time);
     // Stop pcap
     match stop.lock() {
@@ -305,7 +375,7 @@ pub (crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<
     let mut scan_result: ScanResult = ScanResult::new();
     match packets.lock() {
         Ok(packets) => {
-        scan_result = parse_portscan_result(packets.clone(), scan_setting);
+        scan_result = parse_portscan_result(packets.clone(), scan_setting, &probe_times);
         }
         Err(e) => {
         eprintln!("Failed to lock packets: {}", e);
@@ -313,5 +383,207 @@ pub (crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<
     }
     scan_result.scan_time = start_time.elapsed();
     scan_result.scan_status = ScanStatus::Done;
+    if let Some(result_tx) = result_tx {
+        send_results(result_tx, &scan_result.hosts);
+    }
     scan_result
 }
+
+/// Pick open and closed ports for OS detection probes.
+///
+/// If no closed port was found in the scan, a high port that was not scanned is used.
+fn select_os_probe_ports(host: &Host) -> Option<(u16, u16)> {
+    let open_port: u16 = host.ports.iter().find(|port| port.status == PortStatus::Open)?.number;
+    let closed_port: u16 = match host.ports.iter().find(|port| port.status == PortStatus::Closed) {
+        Some(port) => port.number,
+        None => (40000..=u16::MAX).find(|number| !host.ports.iter().any(|port| port.number == *number))?,
+    };
+    Some((open_port, closed_port))
+}
+
+/// Capture options for the replies to the probes sent from the interface. Filters are left empty
+fn probe_capture_options(interface: &Interface, capture_timeout: Duration, read_timeout: Duration, dump: Option<&PacketDump>) -> PacketCaptureOptions {
+    PacketCaptureOptions {
+        interface_index: interface.index,
+        src_ips: HashSet::new(),
+        dst_ips: HashSet::new(),
+        src_ports: HashSet::new(),
+        dst_ports: HashSet::new(),
+        ether_types: HashSet::new(),
+        ip_protocols: HashSet::new(),
+        capture_timeout,
+        read_timeout,
+        promiscuous: false,
+        receive_undefined: false,
+        tunnel: interface.is_tun(),
+        loopback: interface.is_loopback(),
+        dump: dump.cloned(),
+    }
+}
+
+/// Send probe packets with `send` while capturing the replies on the interface.
+///
+/// Capture stops `wait_time` after `send` returns. Returns None if the channel could not be opened.
+fn send_and_capture<F>(interface: &Interface, wait_time: Duration, capture_options: PacketCaptureOptions, send: F) -> Option<Vec<PacketFrame>>
+where
+    F: FnOnce(&mut Box<dyn FrameSender>),
+{
+    let config = nex::datalink::Config {
+        write_buffer_size: 4096,
+        read_buffer_size: 4096,
+        read_timeout: Some(wait_time),
+        write_timeout: None,
+        channel_type: nex::datalink::ChannelType::Layer2,
+        bpf_fd_attempts: 1000,
+        linux_fanout: None,
+        promiscuous: false,
+    };
+    let (mut tx, mut rx) = match nex::datalink::channel(interface, config) {
+        Ok(nex::datalink::Channel::Ethernet(tx, rx)) => (tx, rx),
+        Ok(_) => {
+            eprintln!("Unhandled channel type");
+            return None;
+        }
+        Err(e) => {
+            eprintln!("Failed to create channel: {}", e);
+            return None;
+        }
+    };
+    let stop: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    let stop_handle = Arc::clone(&stop);
+    // Spawn pcap thread
+    let pcap_handler = thread::spawn(move || {
+        crate::pcap::start_capture(&mut rx, capture_options, &stop_handle)
+    });
+    // Wait for listener to start (need fix for better way)
+    thread::sleep(Duration::from_millis(PCAP_WAIT_TIME_MILLIS));
+    send(&mut tx);
+    thread::sleep(wait_time);
+    // Stop pcap
+    match stop.lock() {
+        Ok(mut stop) => {
+            *stop = true;
+        }
+        Err(e) => {
+            eprintln!("Failed to lock stop: {}", e);
+        }
+    }
+    // Wait for listener to stop
+    match pcap_handler.join() {
+        Ok(packets) => Some(packets),
+        Err(e) => {
+            eprintln!("Failed to join pcap_handler: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Run active OS detection against the hosts with open ports in the scan result.
+///
+/// Matched OS overrides the passive guess of the host.
+pub (crate) fn detect_os(scan_setting: &PortScanSetting, scan_result: &mut ScanResult, dump: Option<&PacketDump>) {
+    let interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
+        Some(interface) => interface,
+        None => return,
+    };
+    let targets: Vec<(IpAddr, u16, u16)> = scan_result
+        .hosts
+        .iter()
+        .filter_map(|host| select_os_probe_ports(host).map(|(open_port, closed_port)| (host.ip_addr, open_port, closed_port)))
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+    let mut capture_options = probe_capture_options(&interface, scan_setting.timeout, scan_setting.wait_time, dump);
+    for (ip_addr, _, _) in &targets {
+        capture_options.src_ips.insert(*ip_addr);
+    }
+    capture_options.ip_protocols.insert(IpNextLevelProtocol::Tcp);
+    capture_options.ip_protocols.insert(IpNextLevelProtocol::Icmp);
+    let packets: Vec<PacketFrame> = match send_and_capture(&interface, scan_setting.wait_time, capture_options, |tx| {
+        for (ip_addr, open_port, closed_port) in &targets {
+            for probe in OsProbe::all() {
+                // ICMP and UDP probes are IPv4 only
+                if !probe.is_tcp() && ip_addr.is_ipv6() {
+                    continue;
+                }
+                let packet = build_os_probe_packet(&interface, *ip_addr, probe, *open_port, *closed_port);
+                match send_frame(tx, &packet, dump) {
+                    Some(_) => {},
+                    None => {
+                        eprintln!("Failed to send packet");
+                    }
+                }
+                thread::sleep(scan_setting.send_rate);
+            }
+        }
+    }) {
+        Some(packets) => packets,
+        None => return,
+    };
+    for (ip_addr, open_port, closed_port) in targets {
+        let fingerprint = parse_probe_responses(ip_addr, &packets, open_port, closed_port);
+        if let Some(os_match) = match_probe_fingerprint(&fingerprint) {
+            for host in scan_result.hosts.iter_mut().filter(|host| host.ip_addr == ip_addr) {
+                host.os_family = os_match.family.clone();
+                host.os_confidence = os_match.confidence;
+            }
+        }
+    }
+}
+
+/// Estimate uptime of the hosts with open ports in the scan result from TCP timestamps.
+///
+/// Several SYN probes are sent to the first open port with an interval, each from its own source port.
+pub (crate) fn detect_uptime(scan_setting: &PortScanSetting, scan_result: &mut ScanResult, dump: Option<&PacketDump>) {
+    let interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
+        Some(interface) => interface,
+        None => return,
+    };
+    let targets: Vec<(IpAddr, u16)> = scan_result
+        .hosts
+        .iter()
+        .filter_map(|host| host.ports.iter().find(|port| port.status == PortStatus::Open).map(|port| (host.ip_addr, port.number)))
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+    let capture_timeout = scan_setting.timeout + Duration::from_millis(UPTIME_PROBE_INTERVAL_MILLIS * UPTIME_PROBE_COUNT as u64);
+    let mut capture_options = probe_capture_options(&interface, capture_timeout, scan_setting.wait_time, dump);
+    for (ip_addr, port) in &targets {
+        capture_options.src_ips.insert(*ip_addr);
+        capture_options.src_ports.insert(*port);
+    }
+    capture_options.ip_protocols.insert(IpNextLevelProtocol::Tcp);
+    // (source port, elapsed time) of each probe
+    let mut sent: Vec<(u16, Duration)> = vec![];
+    let packets: Vec<PacketFrame> = match send_and_capture(&interface, scan_setting.wait_time, capture_options, |tx| {
+        let start_time = std::time::Instant::now();
+        for i in 0..UPTIME_PROBE_COUNT {
+            if i > 0 {
+                thread::sleep(Duration::from_millis(UPTIME_PROBE_INTERVAL_MILLIS));
+            }
+            let src_port: u16 = UPTIME_PROBE_BASE_PORT + i as u16;
+            sent.push((src_port, start_time.elapsed()));
+            for (ip_addr, port) in &targets {
+                let packet = build_timestamp_probe_packet(&interface, *ip_addr, *port, src_port);
+                match send_frame(tx, &packet, dump) {
+                    Some(_) => {},
+                    None => {
+                        eprintln!("Failed to send packet");
+                    }
+                }
+            }
+        }
+    }) {
+        Some(packets) => packets,
+        None => return,
+    };
+    for (ip_addr, _) in targets {
+        let samples = timestamp_samples(ip_addr, &packets, &sent);
+        let estimate = estimate_uptime(&samples);
+        for host in scan_result.hosts.iter_mut().filter(|host| host.ip_addr == ip_addr) {
+            host.uptime = estimate.clone();
+        }
+    }
+}
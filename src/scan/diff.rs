@@ -0,0 +1,385 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::net::IpAddr;
+use netdev::mac::MacAddr;
+use crate::host::{Host, Port, PortStatus};
+use super::result::ScanResult;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Change between two scan results
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ScanChange {
+    /// Host found only in the new result
+    HostAdded {
+        ip_addr: IpAddr,
+        hostname: String,
+    },
+    /// Host found only in the old result
+    HostVanished {
+        ip_addr: IpAddr,
+        hostname: String,
+    },
+    /// Port is open in the new result but was not open in the old result
+    PortOpened {
+        ip_addr: IpAddr,
+        port: u16,
+        service_name: String,
+    },
+    /// Port was open in the old result but is not open in the new result
+    PortClosed {
+        ip_addr: IpAddr,
+        port: u16,
+        /// Status in the new result. Unknown if the port was not scanned
+        status: PortStatus,
+    },
+    /// Service name or version of the open port changed
+    ServiceChanged {
+        ip_addr: IpAddr,
+        port: u16,
+        old_service: String,
+        old_version: String,
+        new_service: String,
+        new_version: String,
+    },
+    /// MAC address changed. Possible IP address reuse or spoofing
+    MacChanged {
+        ip_addr: IpAddr,
+        old_mac_addr: MacAddr,
+        new_mac_addr: MacAddr,
+    },
+    /// TTL of the responses changed. Possible route or OS change
+    TtlChanged {
+        ip_addr: IpAddr,
+        old_ttl: u8,
+        new_ttl: u8,
+    },
+}
+
+impl ScanChange {
+    /// Address of the host the change belongs to
+    pub fn ip_addr(&self) -> IpAddr {
+        match *self {
+            ScanChange::HostAdded { ip_addr, .. }
+            | ScanChange::HostVanished { ip_addr, .. }
+            | ScanChange::PortOpened { ip_addr, .. }
+            | ScanChange::PortClosed { ip_addr, .. }
+            | ScanChange::ServiceChanged { ip_addr, .. }
+            | ScanChange::MacChanged { ip_addr, .. }
+            | ScanChange::TtlChanged { ip_addr, .. } => ip_addr,
+        }
+    }
+}
+
+fn format_host(ip_addr: &IpAddr, hostname: &str) -> String {
+    if hostname.is_empty() {
+        ip_addr.to_string()
+    } else {
+        format!("{} ({})", ip_addr, hostname)
+    }
+}
+
+fn format_service(service_name: &str, version: &str) -> String {
+    match (service_name.is_empty(), version.is_empty()) {
+        (true, true) => String::from("unknown"),
+        (false, true) => service_name.to_string(),
+        (true, false) => version.to_string(),
+        (false, false) => format!("{} {}", service_name, version),
+    }
+}
+
+impl fmt::Display for ScanChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanChange::HostAdded { ip_addr, hostname } => write!(f, "+ host {}", format_host(ip_addr, hostname)),
+            ScanChange::HostVanished { ip_addr, hostname } => write!(f, "- host {}", format_host(ip_addr, hostname)),
+            ScanChange::PortOpened { ip_addr, port, service_name } => {
+                write!(f, "+ port {} {} open {}", ip_addr, port, format_service(service_name, ""))
+            }
+            ScanChange::PortClosed { ip_addr, port, status } => write!(f, "- port {} {} {}", ip_addr, port, status.id()),
+            ScanChange::ServiceChanged {
+                ip_addr,
+                port,
+                old_service,
+                old_version,
+                new_service,
+                new_version,
+            } => write!(
+                f,
+                "~ service {} {} {} -> {}",
+                ip_addr,
+                port,
+                format_service(old_service, old_version),
+                format_service(new_service, new_version)
+            ),
+            ScanChange::MacChanged {
+                ip_addr,
+                old_mac_addr,
+                new_mac_addr,
+            } => write!(f, "~ mac {} {} -> {}", ip_addr, old_mac_addr, new_mac_addr),
+            ScanChange::TtlChanged { ip_addr, old_ttl, new_ttl } => write!(f, "~ ttl {} {} -> {}", ip_addr, old_ttl, new_ttl),
+        }
+    }
+}
+
+/// Changes between two scan results, ordered by host address
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanDiff {
+    pub changes: Vec<ScanChange>,
+}
+
+impl ScanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    /// Changes of the host
+    pub fn host_changes(&self, ip_addr: IpAddr) -> Vec<&ScanChange> {
+        self.changes.iter().filter(|change| change.ip_addr() == ip_addr).collect()
+    }
+}
+
+impl fmt::Display for ScanDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+fn open_ports(host: &Host) -> BTreeMap<u16, &Port> {
+    host.ports
+        .iter()
+        .filter(|port| port.status == PortStatus::Open)
+        .map(|port| (port.number, port))
+        .collect()
+}
+
+fn diff_host(old: &Host, new: &Host, changes: &mut Vec<ScanChange>) {
+    let ip_addr = new.ip_addr;
+    // Unknown MAC address (e.g. routed host) and TTL are not compared
+    if old.mac_addr != MacAddr::zero() && new.mac_addr != MacAddr::zero() && old.mac_addr != new.mac_addr {
+        changes.push(ScanChange::MacChanged {
+            ip_addr,
+            old_mac_addr: old.mac_addr,
+            new_mac_addr: new.mac_addr,
+        });
+    }
+    if old.ttl != 0 && new.ttl != 0 && old.ttl != new.ttl {
+        changes.push(ScanChange::TtlChanged {
+            ip_addr,
+            old_ttl: old.ttl,
+            new_ttl: new.ttl,
+        });
+    }
+    let old_ports = open_ports(old);
+    let new_ports = open_ports(new);
+    let numbers: BTreeSet<u16> = old_ports.keys().chain(new_ports.keys()).copied().collect();
+    for number in numbers {
+        match (old_ports.get(&number), new_ports.get(&number)) {
+            (None, Some(new_port)) => changes.push(ScanChange::PortOpened {
+                ip_addr,
+                port: number,
+                service_name: new_port.service_name.clone(),
+            }),
+            (Some(_), None) => changes.push(ScanChange::PortClosed {
+                ip_addr,
+                port: number,
+                status: match new.ports.iter().find(|port| port.number == number) {
+                    Some(port) => port.status,
+                    None => PortStatus::Unknown,
+                },
+            }),
+            (Some(old_port), Some(new_port)) => {
+                // Services are compared only when detected in both scans
+                let service_changed = !old_port.service_name.is_empty()
+                    && !new_port.service_name.is_empty()
+                    && old_port.service_name != new_port.service_name;
+                let version_changed = !old_port.service_version.is_empty()
+                    && !new_port.service_version.is_empty()
+                    && old_port.service_version != new_port.service_version;
+                if service_changed || version_changed {
+                    changes.push(ScanChange::ServiceChanged {
+                        ip_addr,
+                        port: number,
+                        old_service: old_port.service_name.clone(),
+                        old_version: old_port.service_version.clone(),
+                        new_service: new_port.service_name.clone(),
+                        new_version: new_port.service_version.clone(),
+                    });
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Compare two scan results.
+///
+/// Open ports of new hosts are reported as opened. Ports of vanished hosts are not reported.
+pub fn diff(old: &ScanResult, new: &ScanResult) -> ScanDiff {
+    let old_hosts: BTreeMap<IpAddr, &Host> = old.hosts.iter().map(|host| (host.ip_addr, host)).collect();
+    let new_hosts: BTreeMap<IpAddr, &Host> = new.hosts.iter().map(|host| (host.ip_addr, host)).collect();
+    let ip_addrs: BTreeSet<IpAddr> = old_hosts.keys().chain(new_hosts.keys()).copied().collect();
+    let mut changes: Vec<ScanChange> = vec![];
+    for ip_addr in ip_addrs {
+        match (old_hosts.get(&ip_addr), new_hosts.get(&ip_addr)) {
+            (None, Some(new_host)) => {
+                changes.push(ScanChange::HostAdded {
+                    ip_addr,
+                    hostname: new_host.hostname.clone(),
+                });
+                for port in open_ports(new_host).values() {
+                    changes.push(ScanChange::PortOpened {
+                        ip_addr,
+                        port: port.number,
+                        service_name: port.service_name.clone(),
+                    });
+                }
+            }
+            (Some(old_host), None) => changes.push(ScanChange::HostVanished {
+                ip_addr,
+                hostname: old_host.hostname.clone(),
+            }),
+            (Some(old_host), Some(new_host)) => diff_host(old_host, new_host, &mut changes),
+            (None, None) => {}
+        }
+    }
+    ScanDiff { changes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn port(number: u16, status: PortStatus, service_name: &str, service_version: &str) -> Port {
+        let mut port = Port::new(number);
+        port.status = status;
+        port.service_name = service_name.to_string();
+        port.service_version = service_version.to_string();
+        port
+    }
+
+    fn host(addr: &str, ports: Vec<Port>) -> Host {
+        let mut host = Host::new(ip(addr), String::new());
+        host.ports = ports;
+        host
+    }
+
+    fn result(hosts: Vec<Host>) -> ScanResult {
+        let mut result = ScanResult::new();
+        result.hosts = hosts;
+        result
+    }
+
+    #[test]
+    fn host_added() {
+        let old = result(vec![]);
+        let new = result(vec![host("10.0.0.1", vec![port(22, PortStatus::Open, "ssh", ""), port(23, PortStatus::Closed, "", "")])]);
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![
+                ScanChange::HostAdded { ip_addr: ip("10.0.0.1"), hostname: String::new() },
+                ScanChange::PortOpened { ip_addr: ip("10.0.0.1"), port: 22, service_name: "ssh".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn host_vanished() {
+        let old = result(vec![host("10.0.0.1", vec![port(22, PortStatus::Open, "ssh", "")])]);
+        let new = result(vec![]);
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![ScanChange::HostVanished { ip_addr: ip("10.0.0.1"), hostname: String::new() }]
+        );
+    }
+
+    #[test]
+    fn port_opened() {
+        let old = result(vec![host("10.0.0.1", vec![port(80, PortStatus::Closed, "", "")])]);
+        let new = result(vec![host("10.0.0.1", vec![port(80, PortStatus::Open, "http", "")])]);
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![ScanChange::PortOpened { ip_addr: ip("10.0.0.1"), port: 80, service_name: "http".to_string() }]
+        );
+    }
+
+    #[test]
+    fn port_closed() {
+        let old = result(vec![host("10.0.0.1", vec![port(80, PortStatus::Open, "http", ""), port(443, PortStatus::Open, "https", "")])]);
+        let new = result(vec![host("10.0.0.1", vec![port(80, PortStatus::Filtered, "", "")])]);
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![
+                ScanChange::PortClosed { ip_addr: ip("10.0.0.1"), port: 80, status: PortStatus::Filtered },
+                // Not scanned in the new result
+                ScanChange::PortClosed { ip_addr: ip("10.0.0.1"), port: 443, status: PortStatus::Unknown },
+            ]
+        );
+    }
+
+    #[test]
+    fn service_changed() {
+        let old = result(vec![host("10.0.0.1", vec![port(22, PortStatus::Open, "ssh", "OpenSSH 8.9")])]);
+        let new = result(vec![host("10.0.0.1", vec![port(22, PortStatus::Open, "ssh", "OpenSSH 9.6")])]);
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![ScanChange::ServiceChanged {
+                ip_addr: ip("10.0.0.1"),
+                port: 22,
+                old_service: "ssh".to_string(),
+                old_version: "OpenSSH 8.9".to_string(),
+                new_service: "ssh".to_string(),
+                new_version: "OpenSSH 9.6".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn mac_changed() {
+        let mut old_host = host("10.0.0.1", vec![]);
+        old_host.mac_addr = MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55);
+        let mut new_host = host("10.0.0.1", vec![]);
+        new_host.mac_addr = MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x66);
+        assert_eq!(
+            diff(&result(vec![old_host]), &result(vec![new_host])).changes,
+            vec![ScanChange::MacChanged {
+                ip_addr: ip("10.0.0.1"),
+                old_mac_addr: MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55),
+                new_mac_addr: MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x66),
+            }]
+        );
+    }
+
+    #[test]
+    fn ttl_changed() {
+        let mut old_host = host("10.0.0.1", vec![]);
+        old_host.ttl = 64;
+        let mut new_host = host("10.0.0.1", vec![]);
+        new_host.ttl = 128;
+        assert_eq!(
+            diff(&result(vec![old_host]), &result(vec![new_host])).changes,
+            vec![ScanChange::TtlChanged { ip_addr: ip("10.0.0.1"), old_ttl: 64, new_ttl: 128 }]
+        );
+    }
+
+    #[test]
+    fn unknown_values_are_not_compared() {
+        let mut old_host = host("10.0.0.1", vec![port(22, PortStatus::Open, "ssh", "OpenSSH 8.9"), port(80, PortStatus::Open, "", "")]);
+        old_host.mac_addr = MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55);
+        old_host.ttl = 0;
+        // Zero MAC address and TTL, and services detected only in one scan
+        let mut new_host = host("10.0.0.1", vec![port(22, PortStatus::Open, "", ""), port(80, PortStatus::Open, "http", "nginx")]);
+        new_host.mac_addr = MacAddr::zero();
+        new_host.ttl = 64;
+        let diff = diff(&result(vec![old_host]), &result(vec![new_host]));
+        assert!(diff.is_empty(), "{}", diff);
+    }
+}
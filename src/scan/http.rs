@@ -0,0 +1,539 @@
+use std::net::IpAddr;
+use super::service::{fetch_response, tls_connect_config};
+use super::setting::ServiceProbeSetting;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of bytes of favicon to be hashed
+const MAX_FAVICON_SIZE: usize = 1024 * 1024;
+
+/// Technologies identified by response header name
+static HEADER_TECHNOLOGIES: &[(&str, &str)] = &[
+    ("x-aspnet-version", "ASP.NET"),
+    ("x-aspnetmvc-version", "ASP.NET MVC"),
+    ("x-drupal-cache", "Drupal"),
+    ("x-drupal-dynamic-cache", "Drupal"),
+    ("x-jenkins", "Jenkins"),
+    ("x-confluence-request-time", "Confluence"),
+    ("x-varnish", "Varnish"),
+    ("cf-ray", "Cloudflare"),
+    ("x-amz-cf-id", "Amazon CloudFront"),
+    ("kbn-name", "Kibana"),
+    ("microsoftsharepointteamservices", "SharePoint"),
+    ("x-owa-version", "Outlook Web App"),
+    ("x-gitlab-meta", "GitLab"),
+    ("x-jfrog-version", "Artifactory"),
+    ("x-influxdb-version", "InfluxDB"),
+    ("x-elastic-product", "Elasticsearch"),
+];
+
+/// Technologies identified by cookie name prefix
+static COOKIE_TECHNOLOGIES: &[(&str, &str)] = &[
+    ("PHPSESSID", "PHP"),
+    ("JSESSIONID", "Java"),
+    ("ASP.NET_SessionId", "ASP.NET"),
+    ("ASPSESSIONID", "ASP"),
+    ("laravel_session", "Laravel"),
+    ("ci_session", "CodeIgniter"),
+    ("csrftoken", "Django"),
+    ("wordpress_", "WordPress"),
+    ("wp-settings", "WordPress"),
+    ("grafana_session", "Grafana"),
+    ("connect.sid", "Express"),
+    ("BIGipServer", "F5 BIG-IP"),
+    ("MRHSession", "F5 BIG-IP APM"),
+    ("AWSALB", "AWS Elastic Load Balancing"),
+    ("__cf_bm", "Cloudflare"),
+    ("PVEAuthCookie", "Proxmox VE"),
+    ("CFID", "ColdFusion"),
+    ("_gitlab_session", "GitLab"),
+    ("i_like_gitea", "Gitea"),
+    ("rack.session", "Ruby Rack"),
+];
+
+/// HTTP fingerprint information
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HttpInfo {
+    /// HTTP version of the final response (e.g. HTTP/1.1)
+    pub version: String,
+    /// Status code of the final response
+    pub status_code: u16,
+    /// Reason phrase of the final response
+    pub reason: String,
+    /// Headers of the final response, in received order
+    pub headers: Vec<(String, String)>,
+    /// HTML title
+    pub title: Option<String>,
+    /// URLs followed by redirects, in order
+    pub redirects: Vec<String>,
+    /// Technologies detected from headers and cookies
+    pub technologies: Vec<String>,
+    /// Favicon hash. MurmurHash3 of the base64 encoded favicon (Shodan compatible)
+    pub favicon_hash: Option<i32>,
+}
+
+impl HttpInfo {
+    /// Get the first header value with the specified name (case-insensitive)
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        get_header(&self.headers, name)
+    }
+    /// Get the Server header value
+    pub fn server(&self) -> Option<&str> {
+        self.get_header("Server")
+    }
+}
+
+/// Parsed HTTP response
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct HttpResponse {
+    pub version: String,
+    pub status_code: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn is_redirect(&self) -> bool {
+        matches!(self.status_code, 301 | 302 | 303 | 307 | 308)
+    }
+}
+
+/// Target of HTTP request
+#[derive(Clone, Debug, PartialEq)]
+struct HttpTarget {
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl HttpTarget {
+    fn default_port(&self) -> u16 {
+        if self.tls { 443 } else { 80 }
+    }
+    /// Host header value
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') { format!("[{}]", self.host) } else { self.host.clone() };
+        if self.port == self.default_port() {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+    fn url(&self) -> String {
+        format!("{}://{}{}", if self.tls { "https" } else { "http" }, self.authority(), self.path)
+    }
+    /// Resolve the Location header value against this target
+    fn join(&self, location: &str) -> Option<HttpTarget> {
+        let location = location.trim();
+        let (tls, rest) = if let Some(rest) = strip_prefix_ignore_case(location, "https://") {
+            (true, rest)
+        } else if let Some(rest) = strip_prefix_ignore_case(location, "http://") {
+            (false, rest)
+        } else if let Some(rest) = location.strip_prefix("//") {
+            (self.tls, rest)
+        } else if location.starts_with('/') {
+            return Some(HttpTarget { path: location.to_string(), ..self.clone() });
+        } else if location.is_empty() {
+            return None;
+        } else {
+            let base = match self.path.rfind('/') {
+                Some(pos) => &self.path[..pos + 1],
+                None => "/",
+            };
+            return Some(HttpTarget { path: format!("{}{}", base, location), ..self.clone() });
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => (&rest[..pos], rest[pos..].to_string()),
+            None => (rest, String::from("/")),
+        };
+        let default_port: u16 = if tls { 443 } else { 80 };
+        let (host, port) = if let Some(end) = authority.strip_prefix('[').and_then(|a| a.find(']')) {
+            // IPv6 literal
+            let host = authority[1..end + 1].to_string();
+            let port = authority[end + 2..].strip_prefix(':').and_then(|p| p.parse().ok()).unwrap_or(default_port);
+            (host, port)
+        } else {
+            match authority.rsplit_once(':') {
+                Some((host, port)) => (host.to_string(), port.parse().ok()?),
+                None => (authority.to_string(), default_port),
+            }
+        };
+        if host.is_empty() {
+            return None;
+        }
+        Some(HttpTarget { tls, host, port, path })
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    if s.len() >= prefix.len() && s.is_char_boundary(prefix.len()) && s[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+pub(crate) fn get_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Decode chunked transfer encoding. Returns the data decoded so far if the body is truncated.
+fn decode_chunked(body: &[u8]) -> Vec<u8> {
+    let mut decoded: Vec<u8> = Vec::new();
+    let mut pos: usize = 0;
+    while pos < body.len() {
+        let line_end = match find_subslice(&body[pos..], b"\r\n") {
+            Some(end) => pos + end,
+            None => break,
+        };
+        let size_line = String::from_utf8_lossy(&body[pos..line_end]);
+        let size_str = size_line.split(';').next().unwrap_or("").trim();
+        let size = match usize::from_str_radix(size_str, 16) {
+            Ok(size) => size,
+            Err(_) => break,
+        };
+        if size == 0 {
+            break;
+        }
+        let start = line_end + 2;
+        let end = match start.checked_add(size) {
+            Some(end) if end <= body.len() => end,
+            Some(_) => {
+                decoded.extend_from_slice(&body[start..]);
+                break;
+            }
+            None => break,
+        };
+        decoded.extend_from_slice(&body[start..end]);
+        pos = match end.checked_add(2) {
+            Some(next) if next <= body.len() => next,
+            _ => break,
+        };
+    }
+    decoded
+}
+
+/// Parse HTTP response bytes
+pub(crate) fn parse_http_response(res_bytes: &[u8]) -> Option<HttpResponse> {
+    if !res_bytes.starts_with(b"HTTP/") {
+        return None;
+    }
+    let (head, body) = match find_subslice(res_bytes, b"\r\n\r\n") {
+        Some(pos) => (&res_bytes[..pos], &res_bytes[pos + 4..]),
+        None => (res_bytes, &res_bytes[res_bytes.len()..]),
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next()?;
+    let mut status_fields = status_line.splitn(3, ' ');
+    let version = status_fields.next()?.to_string();
+    let status_code: u16 = status_fields.next()?.trim().parse().ok()?;
+    let reason = status_fields.next().unwrap_or("").trim().to_string();
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let chunked = get_header(&headers, "Transfer-Encoding")
+        .map(|v| v.to_ascii_lowercase().contains("chunked"))
+        .unwrap_or(false);
+    let body = if chunked { decode_chunked(body) } else { body.to_vec() };
+    Some(HttpResponse {
+        version,
+        status_code,
+        reason,
+        headers,
+        body,
+    })
+}
+
+/// Find the inner text of the first element with the specified tag name
+fn find_element_text(html: &str, tag: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find(&format!("<{}", tag))?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find(&format!("</{}", tag))?;
+    let text: String = html[start..end].split_whitespace().collect::<Vec<&str>>().join(" ");
+    Some(decode_html_entities(&text))
+}
+
+fn decode_html_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Extract HTML title from response body
+pub(crate) fn extract_title(body: &[u8]) -> Option<String> {
+    let html = String::from_utf8_lossy(body);
+    match find_element_text(&html, "title") {
+        Some(title) if !title.is_empty() => Some(title),
+        _ => None,
+    }
+}
+
+/// Extract favicon path from `<link rel="icon">` in HTML body
+fn extract_favicon_path(body: &[u8]) -> Option<String> {
+    let html = String::from_utf8_lossy(body);
+    let lower = html.to_ascii_lowercase();
+    let mut pos: usize = 0;
+    while let Some(offset) = lower[pos..].find("<link") {
+        let start = pos + offset;
+        let end = start + lower[start..].find('>')?;
+        let tag = &lower[start..end];
+        if tag.contains("rel=\"icon\"") || tag.contains("rel=\"shortcut icon\"") || tag.contains("rel='icon'") || tag.contains("rel='shortcut icon'") {
+            let href_pos = tag.find("href=")? + 5;
+            let quote = tag[href_pos..].chars().next()?;
+            if quote == '"' || quote == '\'' {
+                let value_start = start + href_pos + 1;
+                let value_end = value_start + html[value_start..].find(quote)?;
+                return Some(html[value_start..value_end].to_string());
+            }
+        }
+        pos = end;
+    }
+    None
+}
+
+/// Detect technologies from response headers and cookies
+pub(crate) fn detect_technologies(headers: &[(String, String)]) -> Vec<String> {
+    let mut technologies: Vec<String> = vec![];
+    let mut add = |tech: String| {
+        if !tech.is_empty() && !technologies.contains(&tech) {
+            technologies.push(tech);
+        }
+    };
+    for (name, value) in headers {
+        let name_lower = name.to_ascii_lowercase();
+        match name_lower.as_str() {
+            "server" | "x-powered-by" | "x-generator" => {
+                add(value.clone());
+            }
+            "set-cookie" => {
+                let cookie_name = value.split('=').next().unwrap_or("").trim();
+                for (prefix, tech) in COOKIE_TECHNOLOGIES {
+                    if cookie_name.starts_with(prefix) {
+                        add(tech.to_string());
+                    }
+                }
+            }
+            _ => {
+                for (header, tech) in HEADER_TECHNOLOGIES {
+                    if name_lower == *header {
+                        add(tech.to_string());
+                    }
+                }
+            }
+        }
+    }
+    technologies
+}
+
+/// 32-bit MurmurHash3 (x86)
+fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mut h: u32 = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k: u32 = 0;
+        for (i, b) in tail.iter().enumerate() {
+            k |= (*b as u32) << (8 * i);
+        }
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+    }
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
+
+/// Base64 encode with a line break every 76 characters (MIME style)
+fn base64_mime(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    let mut line_len: usize = 0;
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        encoded.push(TABLE[(n >> 18) as usize & 0x3f] as char);
+        encoded.push(TABLE[(n >> 12) as usize & 0x3f] as char);
+        encoded.push(if chunk.len() > 1 { TABLE[(n >> 6) as usize & 0x3f] as char } else { '=' });
+        encoded.push(if chunk.len() > 2 { TABLE[n as usize & 0x3f] as char } else { '=' });
+        line_len += 4;
+        if line_len == 76 {
+            encoded.push('\n');
+            line_len = 0;
+        }
+    }
+    if line_len > 0 {
+        encoded.push('\n');
+    }
+    encoded
+}
+
+/// Calculate favicon hash (Shodan compatible)
+pub fn favicon_hash(favicon: &[u8]) -> i32 {
+    murmur3_32(base64_mime(favicon).as_bytes(), 0) as i32
+}
+
+fn build_get_request(target: &HttpTarget) -> Vec<u8> {
+    format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: Mozilla/5.0\r\nAccept: */*\r\nConnection: close\r\nAccept-Encoding: identity\r\n\r\n",
+        target.path,
+        target.authority()
+    )
+    .into_bytes()
+}
+
+/// Check if the host of the target is the scanned host
+fn is_same_host(ip_addr: IpAddr, original_host: &str, host: &str) -> bool {
+    host.eq_ignore_ascii_case(original_host) || host.parse::<IpAddr>().map(|ip| ip == ip_addr).unwrap_or(false)
+}
+
+/// Send GET request to the target and parse response
+///
+/// Targets on other hosts are skipped unless `follow_offsite_redirects` is enabled.
+async fn get(ip_addr: IpAddr, original_host: &str, target: &HttpTarget, setting: &ServiceProbeSetting) -> Option<HttpResponse> {
+    let dst_ip: IpAddr = if is_same_host(ip_addr, original_host, &target.host) {
+        ip_addr
+    } else if !setting.follow_offsite_redirects {
+        return None;
+    } else if let Ok(ip) = target.host.parse::<IpAddr>() {
+        ip
+    } else {
+        crate::dns::lookup_host_name_async(target.host.clone()).await?
+    };
+    // Certificate of the scanned host is already recorded in TlsInfo
+    let tls_config = if target.tls {
+        Some(tls_connect_config(dst_ip == ip_addr || setting.accept_invalid_certs))
+    } else {
+        None
+    };
+    let res = fetch_response(dst_ip, &target.host, target.port, &build_get_request(target), tls_config, setting).await.ok()?;
+    parse_http_response(&res)
+}
+
+/// Build HTTP fingerprint from the response to the probe payload.
+///
+/// If the payload was not an HTTP request, the page is fetched with GET request first.
+/// Follows redirects up to `max_redirects` hops, fetches the page for the title if the first response has no body,
+/// and fetches the favicon.
+pub(crate) async fn probe_http_info(ip_addr: IpAddr, hostname: &str, port: u16, tls: bool, http_request: bool, res_bytes: &[u8], setting: &ServiceProbeSetting) -> Option<HttpInfo> {
+    let host: String = if hostname.is_empty() { ip_addr.to_string() } else { hostname.to_string() };
+    let mut response: HttpResponse = parse_http_response(res_bytes)?;
+    let mut target = HttpTarget {
+        tls,
+        host: host.clone(),
+        port,
+        path: String::from("/"),
+    };
+    let mut fetched: bool = false;
+    // The response to a non-HTTP payload is usually an error page (e.g. 400 Bad Request). Fetch the page instead.
+    if !http_request {
+        if let Some(get_response) = get(ip_addr, &host, &target, setting).await {
+            response = get_response;
+            fetched = true;
+        }
+    }
+    let mut redirects: Vec<String> = vec![];
+    let mut header_history: Vec<(String, String)> = response.headers.clone();
+    while response.is_redirect() && redirects.len() < setting.max_redirects {
+        let next = match get_header(&response.headers, "Location").and_then(|location| target.join(location)) {
+            Some(next) => next,
+            None => break,
+        };
+        redirects.push(next.url());
+        match get(ip_addr, &host, &next, setting).await {
+            Some(next_response) => {
+                header_history.extend(next_response.headers.clone());
+                response = next_response;
+                target = next;
+                fetched = true;
+            }
+            None => break,
+        }
+    }
+    // The probe payload might be HEAD request. Fetch the page to get the title.
+    if !fetched && response.body.is_empty() && response.status_code < 300 {
+        if let Some(get_response) = get(ip_addr, &host, &target, setting).await {
+            header_history.extend(get_response.headers.clone());
+            response = get_response;
+        }
+    }
+    let title = extract_title(&response.body);
+    let favicon_path = extract_favicon_path(&response.body).unwrap_or(String::from("/favicon.ico"));
+    let mut favicon_hash_value: Option<i32> = None;
+    if let Some(favicon_target) = target.join(&favicon_path) {
+        if let Some(favicon_response) = get(ip_addr, &host, &favicon_target, setting).await {
+            if favicon_response.status_code == 200 && !favicon_response.body.is_empty() && favicon_response.body.len() <= MAX_FAVICON_SIZE {
+                favicon_hash_value = Some(favicon_hash(&favicon_response.body));
+            }
+        }
+    }
+    Some(HttpInfo {
+        version: response.version,
+        status_code: response.status_code,
+        reason: response.reason,
+        headers: response.headers,
+        title,
+        redirects,
+        technologies: detect_technologies(&header_history),
+        favicon_hash: favicon_hash_value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_chunked_body() {
+        assert_eq!(decode_chunked(b"4\r\nWiki\r\n5;ext=1\r\npedia\r\n0\r\n\r\n"), b"Wikipedia");
+        let res = parse_http_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n").unwrap();
+        assert_eq!(res.body, b"abc");
+    }
+
+    #[test]
+    fn decode_truncated_chunked_body() {
+        // Chunk data cut off
+        assert_eq!(decode_chunked(b"4\r\nWiki\r\n5\r\npe"), b"Wikipe");
+        // Trailing CRLF of the chunk cut off
+        assert_eq!(decode_chunked(b"4\r\nWiki"), b"Wiki");
+        // Size line cut off
+        assert_eq!(decode_chunked(b"4\r\nWiki\r\n5"), b"Wiki");
+    }
+
+    #[test]
+    fn decode_hostile_chunked_body() {
+        assert_eq!(decode_chunked(b"ffffffffffffffff\r\nabc\r\n"), b"");
+        assert_eq!(decode_chunked(b"3\r\nabc\r\nfffffffffffffffe\r\nabc\r\n"), b"abc");
+        assert_eq!(decode_chunked(b"10000000000000000\r\nabc\r\n"), b"");
+        assert_eq!(decode_chunked(b"zz\r\nabc\r\n"), b"");
+    }
+}
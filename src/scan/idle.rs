@@ -0,0 +1,282 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use netdev::Interface;
+use nex::datalink::{FrameReceiver, FrameSender};
+use nex::packet::frame::{Frame, ParseOption};
+use nex::packet::tcp::TcpFlags;
+use crate::config::{IDLE_PROBE_PORT, IDLE_PROBE_TIMEOUT_MILLIS, IDLE_SCAN_RETRIES, IDLE_ZOMBIE_PROBE_COUNT};
+use crate::host::{Host, PortStatus};
+use crate::os::IpIdBehavior;
+use crate::packet::frame::PacketFrame;
+use crate::pcap::dump::PacketDump;
+use super::packet::build_spoofed_tcp_packet;
+use super::result::{ScanResult, ScanStatus};
+use super::setting::PortScanSetting;
+
+/// Channel for sending probes and receiving responses one by one
+struct ProbeChannel {
+    interface: Interface,
+    tx: Box<dyn FrameSender>,
+    rx: Box<dyn FrameReceiver>,
+    src_ip: Ipv4Addr,
+    probe_count: u16,
+    dump: Option<PacketDump>,
+}
+
+impl ProbeChannel {
+    fn open(if_index: u32) -> Result<ProbeChannel, String> {
+        let interface = match crate::interface::get_interface_by_index(if_index) {
+            Some(interface) => interface,
+            None => return Err("Interface not found".to_string()),
+        };
+        let src_ip: Ipv4Addr = match interface.ipv4.first() {
+            Some(ipv4) => ipv4.addr,
+            None => return Err("No IPv4 address on the interface".to_string()),
+        };
+        let config = nex::datalink::Config {
+            write_buffer_size: 4096,
+            read_buffer_size: 4096,
+            read_timeout: Some(Duration::from_millis(100)),
+            write_timeout: None,
+            channel_type: nex::datalink::ChannelType::Layer2,
+            bpf_fd_attempts: 1000,
+            linux_fanout: None,
+            promiscuous: false,
+        };
+        let (tx, rx) = match nex::datalink::channel(&interface, config) {
+            Ok(nex::datalink::Channel::Ethernet(tx, rx)) => (tx, rx),
+            Ok(_) => return Err("Unhandled channel type".to_string()),
+            Err(e) => return Err(format!("Failed to create channel: {}", e)),
+        };
+        Ok(ProbeChannel {
+            interface,
+            tx,
+            rx,
+            src_ip,
+            probe_count: 0,
+            dump: None,
+        })
+    }
+    fn send(&mut self, src_addr: SocketAddr, dst_addr: SocketAddr, tcp_flags: u8) {
+        let packet = build_spoofed_tcp_packet(&self.interface, src_addr, dst_addr, tcp_flags);
+        match self.tx.send(&packet) {
+            Some(_) => {
+                if let Some(dump) = &self.dump {
+                    dump.record_sent(&packet);
+                }
+            },
+            None => {
+                eprintln!("Failed to send packet");
+            }
+        }
+    }
+    fn parse(&self, packet: &[u8]) -> PacketFrame {
+        let mut parse_option: ParseOption = ParseOption::default();
+        if self.interface.is_tun() || (cfg!(any(target_os = "macos", target_os = "ios")) && self.interface.is_loopback()) {
+            parse_option.from_ip_packet = true;
+            parse_option.offset = if self.interface.is_loopback() { 14 } else { 0 };
+        }
+        PacketFrame::from_nex_frame(&Frame::from_bytes(packet, parse_option))
+    }
+    /// Send SYN/ACK to the host and get IP ID of the RST response.
+    ///
+    /// Source port is rotated so that a late response to the previous probe is not taken.
+    fn probe_ip_id(&mut self, addr: SocketAddr) -> Option<u16> {
+        let src_port: u16 = IDLE_PROBE_PORT + self.probe_count % 16;
+        self.probe_count = self.probe_count.wrapping_add(1);
+        let src_addr = SocketAddr::new(IpAddr::V4(self.src_ip), src_port);
+        self.send(src_addr, addr, TcpFlags::SYN | TcpFlags::ACK);
+        let start_time = Instant::now();
+        while start_time.elapsed() < Duration::from_millis(IDLE_PROBE_TIMEOUT_MILLIS) {
+            let packet = match self.rx.next() {
+                Ok(packet) => packet.to_vec(),
+                Err(_) => continue,
+            };
+            if let Some(dump) = &self.dump {
+                dump.record_received(&packet);
+            }
+            let frame = self.parse(&packet);
+            let (ipv4_header, tcp_header) = match (&frame.ipv4_header, &frame.tcp_header) {
+                (Some(ipv4_header), Some(tcp_header)) => (ipv4_header, tcp_header),
+                _ => continue,
+            };
+            if IpAddr::V4(ipv4_header.source) == addr.ip()
+                && tcp_header.source == addr.port()
+                && tcp_header.destination == src_port
+                && tcp_header.flags & TcpFlags::RST != 0
+            {
+                return Some(ipv4_header.identification);
+            }
+        }
+        None
+    }
+}
+
+/// Probe IP IDs of the host repeatedly. IDs are in received order
+fn probe_ip_ids(channel: &mut ProbeChannel, addr: SocketAddr, count: usize) -> Vec<u16> {
+    let mut ids: Vec<u16> = vec![];
+    for _ in 0..count {
+        if let Some(id) = channel.probe_ip_id(addr) {
+            ids.push(id);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    ids
+}
+
+/// Classify IP ID sequence of the host (IPv4 address and port) from repeated probes.
+///
+/// If `other` is set, a SYN/ACK spoofed from `other` is sent to the host to check whether the counter is shared between destinations.
+fn analyze_ip_id_with_channel(channel: &mut ProbeChannel, addr: SocketAddr, other: Option<IpAddr>) -> IpIdBehavior {
+    let ids = probe_ip_ids(channel, addr, IDLE_ZOMBIE_PROBE_COUNT);
+    let other = match other {
+        Some(other) if IpIdBehavior::classify(&ids) == IpIdBehavior::Incremental => other,
+        _ => return IpIdBehavior::classify(&ids),
+    };
+    let mut ids = ids;
+    // Probe again right before sending the spoofed packet to reduce noise
+    if let Some(id) = channel.probe_ip_id(addr) {
+        ids.push(id);
+    }
+    channel.send(SocketAddr::new(other, IDLE_PROBE_PORT), addr, TcpFlags::SYN | TcpFlags::ACK);
+    thread::sleep(Duration::from_millis(50));
+    let id_after_other = channel.probe_ip_id(addr);
+    IpIdBehavior::classify_with_other(&ids, id_after_other)
+}
+
+/// Classify IP ID sequence of the host (IPv4 address and port) from repeated probes.
+///
+/// If `other` is set, a SYN/ACK spoofed from `other` is sent to the host to detect per-host counters.
+pub fn analyze_ip_id(if_index: u32, addr: SocketAddr, other: Option<IpAddr>) -> Result<IpIdBehavior, String> {
+    if !addr.is_ipv4() {
+        return Err("IP ID analysis supports IPv4 only".to_string());
+    }
+    let mut channel = ProbeChannel::open(if_index)?;
+    Ok(analyze_ip_id_with_channel(&mut channel, addr, other))
+}
+
+/// Infer port status from IP ID increment of the zombie. None if the increment is noisy.
+///
+/// Closed and filtered ports cannot be told apart, so both are reported as filtered (closed|filtered in nmap).
+fn port_status_from_increment(increment: u16) -> Option<PortStatus> {
+    match increment {
+        // Zombie sent only our RST. Target sent RST or nothing (closed or filtered)
+        1 => Some(PortStatus::Filtered),
+        // Zombie also sent RST for the SYN/ACK from the target
+        2 => Some(PortStatus::Open),
+        _ => None,
+    }
+}
+
+/// Idle scan the port of the target through the zombie
+fn scan_port(channel: &mut ProbeChannel, zombie: SocketAddr, target: SocketAddr, wait_time: Duration) -> PortStatus {
+    for _ in 0..IDLE_SCAN_RETRIES {
+        let before = match channel.probe_ip_id(zombie) {
+            Some(id) => id,
+            None => continue,
+        };
+        channel.send(zombie, target, TcpFlags::SYN);
+        thread::sleep(wait_time);
+        let after = match channel.probe_ip_id(zombie) {
+            Some(id) => id,
+            None => continue,
+        };
+        if let Some(status) = port_status_from_increment(after.wrapping_sub(before)) {
+            return status;
+        }
+    }
+    PortStatus::Unknown
+}
+
+pub(crate) fn scan_ports(scan_setting: PortScanSetting, ptx: &Arc<Mutex<Sender<SocketAddr>>>, dump: Option<&PacketDump>) -> ScanResult {
+    let zombie: SocketAddr = match scan_setting.zombie {
+        Some(zombie) if zombie.is_ipv4() => zombie,
+        Some(_) => return ScanResult::error("Idle scan supports IPv4 zombie only".to_string()),
+        None => return ScanResult::error("Zombie host is not set".to_string()),
+    };
+    let mut channel = match ProbeChannel::open(scan_setting.if_index) {
+        Ok(channel) => channel,
+        Err(e) => return ScanResult::error(e),
+    };
+    channel.dump = dump.cloned();
+    let start_time = Instant::now();
+    let other: Option<IpAddr> = scan_setting.targets.first().map(|target| target.ip_addr);
+    match analyze_ip_id_with_channel(&mut channel, zombie, other) {
+        IpIdBehavior::Incremental => {},
+        behavior => return ScanResult::error(format!("Zombie IP ID sequence is not usable: {:?}", behavior)),
+    }
+    let mut scan_result: ScanResult = ScanResult::new();
+    for target in &scan_setting.targets {
+        if !target.ip_addr.is_ipv4() {
+            continue;
+        }
+        let mut host: Host = Host::new(
+            target.ip_addr,
+            scan_setting.dns_map.get(&target.ip_addr).cloned().unwrap_or(target.hostname.clone()),
+        );
+        for port in &target.ports {
+            let target_addr = SocketAddr::new(target.ip_addr, port.number);
+            let mut port = port.clone();
+            port.status = scan_port(&mut channel, zombie, target_addr, scan_setting.wait_time);
+            host.ports.push(port);
+            // Notify port scanned
+            if let Ok(lr) = ptx.lock() {
+                if let Err(e) = lr.send(target_addr) {
+                    eprintln!("Failed to send message: {}", e);
+                }
+            }
+            thread::sleep(scan_setting.send_rate);
+        }
+        scan_result.hosts.push(host);
+    }
+    scan_result.scan_time = start_time.elapsed();
+    scan_result.scan_status = ScanStatus::Done;
+    scan_result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_status_of_zombie_increment() {
+        assert_eq!(port_status_from_increment(1), Some(PortStatus::Filtered));
+        assert_eq!(port_status_from_increment(2), Some(PortStatus::Open));
+        // No response to our probe or other traffic of the zombie
+        assert_eq!(port_status_from_increment(0), None);
+        assert_eq!(port_status_from_increment(3), None);
+        assert_eq!(port_status_from_increment(u16::MAX), None);
+    }
+
+    #[test]
+    fn port_status_of_wrapped_zombie_ids() {
+        // Simulated zombie with the counter wrapping around between the probes
+        let before: u16 = u16::MAX;
+        assert_eq!(port_status_from_increment(before.wrapping_add(2).wrapping_sub(before)), Some(PortStatus::Open));
+        assert_eq!(port_status_from_increment(before.wrapping_add(1).wrapping_sub(before)), Some(PortStatus::Filtered));
+    }
+
+    #[test]
+    fn classify_ip_id_sequence() {
+        assert_eq!(IpIdBehavior::classify(&[]), IpIdBehavior::Unknown);
+        assert_eq!(IpIdBehavior::classify(&[0, 0, 0]), IpIdBehavior::Zero);
+        assert_eq!(IpIdBehavior::classify(&[100]), IpIdBehavior::Unknown);
+        assert_eq!(IpIdBehavior::classify(&[100, 101, 103, 104]), IpIdBehavior::Incremental);
+        assert_eq!(IpIdBehavior::classify(&[65534, 65535, 0, 1]), IpIdBehavior::Incremental);
+        assert_eq!(IpIdBehavior::classify(&[100, 40000, 1234, 52000]), IpIdBehavior::Random);
+    }
+
+    #[test]
+    fn classify_ip_id_sequence_with_other() {
+        // Shared counter: the reply to the other host takes one ID in between
+        assert_eq!(IpIdBehavior::classify_with_other(&[100, 101, 102], Some(104)), IpIdBehavior::Incremental);
+        // Per-destination counter: the reply to the other host does not affect our IDs
+        assert_eq!(IpIdBehavior::classify_with_other(&[100, 101, 102], Some(103)), IpIdBehavior::PerHost);
+        assert_eq!(IpIdBehavior::classify_with_other(&[100, 101, 102], None), IpIdBehavior::Incremental);
+        assert_eq!(IpIdBehavior::classify_with_other(&[100, 40000, 1234], Some(1235)), IpIdBehavior::Random);
+        assert_eq!(IpIdBehavior::classify_with_other(&[0, 0], Some(1)), IpIdBehavior::Zero);
+    }
+}
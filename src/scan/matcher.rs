@@ -0,0 +1,115 @@
+/// Service identified from a probe response
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ServiceMatch {
+    pub service_name: String,
+    pub detail: Option<String>,
+}
+
+impl ServiceMatch {
+    fn new(service_name: &str, detail: Option<String>) -> ServiceMatch {
+        ServiceMatch {
+            service_name: service_name.to_string(),
+            detail,
+        }
+    }
+}
+
+/// Get the first line of the response without line break
+fn first_line(res: &[u8]) -> String {
+    let end = res.iter().position(|&b| b == b'\n').unwrap_or(res.len());
+    String::from_utf8_lossy(&res[..end]).trim_end().to_string()
+}
+
+/// Get HTTP header value from the response
+fn http_header(res: &[u8], name: &str) -> Option<String> {
+    let text = String::from_utf8_lossy(res);
+    for line in text.split("\r\n").skip(1) {
+        if line.is_empty() {
+            break;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            if k.trim().eq_ignore_ascii_case(name) {
+                return Some(v.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Parse MySQL initial handshake packet and return server version
+fn mysql_version(res: &[u8]) -> Option<String> {
+    // Packet length (3 bytes), sequence id, protocol version 10
+    if res.len() < 6 || res[3] != 0x00 || res[4] != 0x0a {
+        return None;
+    }
+    let payload_len = u32::from_le_bytes([res[0], res[1], res[2], 0]) as usize;
+    if payload_len + 4 > res.len() {
+        return None;
+    }
+    let end = res[5..].iter().position(|&b| b == 0x00)? + 5;
+    let version = std::str::from_utf8(&res[5..end]).ok()?;
+    if version.is_empty() || !version.chars().all(|c| c.is_ascii_graphic()) {
+        return None;
+    }
+    Some(version.to_string())
+}
+
+/// Identify service from the probe response.
+///
+/// Returns None if the response does not match any known signature.
+pub(crate) fn match_response(res: &[u8]) -> Option<ServiceMatch> {
+    if res.is_empty() {
+        return None;
+    }
+    let line = first_line(res);
+    let upper = line.to_ascii_uppercase();
+    if line.starts_with("SSH-") {
+        return Some(ServiceMatch::new("ssh", Some(line)));
+    }
+    if line.starts_with("HTTP/1.") || line.starts_with("HTTP/2") {
+        return Some(ServiceMatch::new("http", http_header(res, "Server")));
+    }
+    if line.starts_with("RTSP/1.0") {
+        return Some(ServiceMatch::new("rtsp", http_header(res, "Server")));
+    }
+    if line.starts_with("RFB 00") {
+        return Some(ServiceMatch::new("vnc", Some(line)));
+    }
+    if line.starts_with("220") {
+        if upper.contains("FTP") {
+            return Some(ServiceMatch::new("ftp", Some(line)));
+        }
+        if upper.contains("SMTP") || upper.contains("MAIL") {
+            return Some(ServiceMatch::new("smtp", Some(line)));
+        }
+    }
+    if line.starts_with("+OK") {
+        return Some(ServiceMatch::new("pop3", Some(line)));
+    }
+    if line.starts_with("* OK") || line.starts_with("* PREAUTH") {
+        return Some(ServiceMatch::new("imap", Some(line)));
+    }
+    if line.starts_with("-ERR") || line.starts_with("-NOAUTH") || line.starts_with("-DENIED") {
+        return Some(ServiceMatch::new("redis", Some(line)));
+    }
+    if res.len() >= 3 && res[0] == 0xff && (0xfb..=0xfe).contains(&res[1]) {
+        return Some(ServiceMatch::new("telnet", None));
+    }
+    if let Some(version) = mysql_version(res) {
+        return Some(ServiceMatch::new("mysql", Some(version)));
+    }
+    None
+}
+
+/// Service name when the service is running over TLS
+pub(crate) fn tls_service_name(service_name: &str) -> String {
+    match service_name {
+        "http" => "https",
+        "ftp" => "ftps",
+        "smtp" => "smtps",
+        "pop3" => "pop3s",
+        "imap" => "imaps",
+        _ => service_name,
+    }
+    .to_string()
+}
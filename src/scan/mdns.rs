@@ -0,0 +1,398 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use hickory_resolver::proto::op::Message;
+use hickory_resolver::proto::rr::{DNSClass, Name, RData, RecordType};
+use crate::host::{Host, Port, PortStatus};
+use crate::protocol::dns;
+use super::result::{ScanResult, ScanStatus};
+use super::setting::ServiceBrowseSetting;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const MDNS_TTL: u32 = 255;
+/// DNS-SD meta query for enumerating service types
+const SERVICES_QUERY_NAME: &str = "_services._dns-sd._udp.local";
+
+/// Service instance found by DNS-SD
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MdnsService {
+    /// Instance name (e.g. `Office Printer._ipp._tcp.local`)
+    pub instance: String,
+    /// Service type (e.g. `_ipp._tcp`)
+    pub service_type: String,
+    /// Target host name of the SRV record without `.local`
+    pub hostname: String,
+    /// Port of the SRV record
+    pub port: u16,
+    /// Addresses of the target host
+    pub addresses: Vec<IpAddr>,
+    /// TXT record strings (e.g. `md=Chromecast`)
+    pub txt: Vec<String>,
+}
+
+impl MdnsService {
+    /// Service name without the underscore and protocol (e.g. `ipp`)
+    pub fn service_name(&self) -> &str {
+        self.service_type.split('.').next().unwrap_or("").trim_start_matches('_')
+    }
+    /// Get TXT value by key
+    pub fn txt_value(&self, key: &str) -> Option<&str> {
+        self.txt
+            .iter()
+            .filter_map(|txt| txt.split_once('='))
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+}
+
+/// Result of mDNS/DNS-SD service browsing
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServiceBrowseResult {
+    /// Service instances with resolved SRV records
+    pub services: Vec<MdnsService>,
+    /// Time taken to browse
+    pub scan_time: Duration,
+    /// Status of the browse task
+    pub scan_status: ScanStatus,
+}
+
+impl ServiceBrowseResult {
+    fn error(message: String) -> ServiceBrowseResult {
+        ServiceBrowseResult {
+            services: vec![],
+            scan_time: Duration::from_millis(0),
+            scan_status: ScanStatus::Error(message),
+        }
+    }
+    /// Hosts with the advertised services as open ports
+    pub fn hosts(&self) -> Vec<Host> {
+        let mut hosts: BTreeMap<IpAddr, Host> = BTreeMap::new();
+        for service in &self.services {
+            for ip_addr in &service.addresses {
+                let host = hosts
+                    .entry(*ip_addr)
+                    .or_insert_with(|| Host::new(*ip_addr, service.hostname.clone()));
+                if host.ports.iter().any(|port| port.number == service.port) {
+                    continue;
+                }
+                host.ports.push(Port {
+                    number: service.port,
+                    status: PortStatus::Open,
+                    service_name: service.service_name().to_string(),
+                    service_version: String::new(),
+                    rtt: None,
+                });
+            }
+        }
+        hosts.into_values().collect()
+    }
+    /// Resolved host names by address.
+    ///
+    /// Scans do not use the browse result by themselves. Pass the map to `set_dns_map` of the scan setting
+    /// so that the names are used for the hosts of the scan result (see `examples/mdns_browse.rs`).
+    pub fn dns_map(&self) -> HashMap<IpAddr, String> {
+        let mut dns_map: HashMap<IpAddr, String> = HashMap::new();
+        for service in &self.services {
+            if service.hostname.is_empty() {
+                continue;
+            }
+            for ip_addr in &service.addresses {
+                dns_map.entry(*ip_addr).or_insert_with(|| service.hostname.clone());
+            }
+        }
+        dns_map
+    }
+    /// Convert to scan result of the hosts
+    pub fn to_scan_result(&self) -> ScanResult {
+        let mut result: ScanResult = ScanResult::new();
+        result.hosts = self.hosts();
+        result.scan_time = self.scan_time;
+        result.scan_status = self.scan_status.clone();
+        result
+    }
+}
+
+/// Key of the name in the records. DNS names are case-insensitive
+fn key(name: &Name) -> String {
+    dns::name_to_string(name).to_lowercase()
+}
+
+/// Records collected from the responses, keyed by lowercase name
+#[derive(Default)]
+struct Records {
+    ptr: BTreeMap<String, BTreeSet<Name>>,
+    srv: BTreeMap<String, (Name, u16)>,
+    txt: BTreeMap<String, Vec<String>>,
+    addrs: BTreeMap<String, BTreeSet<IpAddr>>,
+    /// Responder of the SRV record
+    sources: BTreeMap<String, IpAddr>,
+}
+
+impl Records {
+    fn add_message(&mut self, message: &Message, src_ip: IpAddr) {
+        for record in message.answers().iter().chain(message.additionals().iter()) {
+            let name = key(record.name());
+            match record.data() {
+                Some(RData::PTR(ptr)) => {
+                    self.ptr.entry(name).or_default().insert(ptr.0.clone());
+                }
+                Some(RData::SRV(srv)) => {
+                    self.srv.insert(name.clone(), (srv.target().clone(), srv.port()));
+                    self.sources.insert(name, src_ip);
+                }
+                Some(RData::TXT(_)) => {
+                    let txt: Vec<String> = dns::txt_strings(record).into_iter().filter(|s| !s.is_empty()).collect();
+                    self.txt.insert(name, txt);
+                }
+                Some(RData::A(a)) => {
+                    self.addrs.entry(name).or_default().insert(IpAddr::V4(a.0));
+                }
+                Some(RData::AAAA(aaaa)) => {
+                    self.addrs.entry(name).or_default().insert(IpAddr::V6(aaaa.0));
+                }
+                _ => {}
+            }
+        }
+    }
+    fn ptr_targets(&self, name: &Name) -> Vec<Name> {
+        match self.ptr.get(&key(name)) {
+            Some(targets) => targets.iter().cloned().collect(),
+            None => vec![],
+        }
+    }
+    /// Instances of the service types. Returns (service type, instance)
+    fn instances(&self, service_types: &BTreeSet<Name>) -> Vec<(Name, Name)> {
+        service_types
+            .iter()
+            .flat_map(|service_type| {
+                self.ptr_targets(service_type)
+                    .into_iter()
+                    .map(move |instance| (service_type.clone(), instance))
+            })
+            .collect()
+    }
+    /// Service instances with SRV records. The responder is used as the address if the target has no address records
+    fn services(&self, instances: Vec<(Name, Name)>) -> Vec<MdnsService> {
+        let mut services: Vec<MdnsService> = vec![];
+        for (service_type, instance) in instances {
+            let (target, port) = match self.srv.get(&key(&instance)) {
+                Some(srv) => srv.clone(),
+                None => continue,
+            };
+            let addresses: Vec<IpAddr> = match self.addrs.get(&key(&target)) {
+                Some(addrs) => addrs.iter().copied().collect(),
+                None => self.sources.get(&key(&instance)).copied().into_iter().collect(),
+            };
+            services.push(MdnsService {
+                instance: dns::name_to_display_string(&instance),
+                service_type: strip_local(dns::name_to_string(&service_type)),
+                hostname: strip_local(dns::name_to_string(&target)),
+                port,
+                addresses,
+                txt: self.txt.get(&key(&instance)).cloned().unwrap_or_default(),
+            });
+        }
+        services
+    }
+}
+
+/// Parse the service type (e.g. `_ipp._tcp`) and append `.local` if the domain is omitted
+fn service_type_name(service_type: &str) -> Option<Name> {
+    let service_type = service_type.trim_end_matches('.');
+    let name = if service_type.ends_with(".local") {
+        format!("{}.", service_type)
+    } else {
+        format!("{}.local.", service_type)
+    };
+    Name::from_ascii(name).ok()
+}
+
+/// Strip `.local` from the name
+fn strip_local(name: String) -> String {
+    name.trim_end_matches(".local").to_string()
+}
+
+/// Send the queries and collect responses for the wait time
+fn query(socket: &UdpSocket, queries: &[(Name, RecordType)], wait_time: Duration, records: &mut Records) {
+    if queries.is_empty() {
+        return;
+    }
+    let dst_addr: SocketAddr = SocketAddr::new(IpAddr::V4(MDNS_ADDR), MDNS_PORT);
+    for (name, record_type) in queries {
+        let packet = dns::build_name_query(0, name.clone(), *record_type, DNSClass::IN, false);
+        if let Err(e) = socket.send_to(&packet, dst_addr) {
+            eprintln!("Failed to send mDNS query: {}", e);
+        }
+    }
+    let mut buf: Vec<u8> = vec![0; 9000];
+    let start_time = Instant::now();
+    while start_time.elapsed() < wait_time {
+        match socket.recv_from(&mut buf) {
+            Ok((len, src_addr)) => {
+                if let Some(message) = dns::parse_response(&buf[..len]) {
+                    records.add_message(&message, src_addr.ip());
+                }
+            }
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Browse DNS-SD services on the local link.
+///
+/// Queries are sent from an ephemeral port, so responders reply by unicast (legacy unicast, RFC 6762 section 6.7).
+pub(crate) fn browse(setting: &ServiceBrowseSetting) -> ServiceBrowseResult {
+    let interface = match crate::interface::get_interface_by_index(setting.if_index) {
+        Some(interface) => interface,
+        None => return ServiceBrowseResult::error("Interface not found".to_string()),
+    };
+    let src_ip: Ipv4Addr = match interface.ipv4.first() {
+        Some(ipv4) => ipv4.addr,
+        None => return ServiceBrowseResult::error("No IPv4 address on the interface".to_string()),
+    };
+    // Multicast is sent from the interface that owns the bound address
+    let socket = match UdpSocket::bind(SocketAddr::new(IpAddr::V4(src_ip), 0)) {
+        Ok(socket) => socket,
+        Err(e) => return ServiceBrowseResult::error(format!("Failed to bind socket: {}", e)),
+    };
+    if let Err(e) = socket.set_read_timeout(Some(Duration::from_millis(100))) {
+        return ServiceBrowseResult::error(format!("Failed to set read timeout: {}", e));
+    }
+    if let Err(e) = socket.set_multicast_ttl_v4(MDNS_TTL) {
+        return ServiceBrowseResult::error(format!("Failed to set multicast TTL: {}", e));
+    }
+    let start_time = Instant::now();
+    let mut records: Records = Records::default();
+    // Enumerate service types
+    let mut service_types: BTreeSet<Name> = setting
+        .service_types
+        .iter()
+        .filter_map(|service_type| service_type_name(service_type))
+        .collect();
+    if service_types.is_empty() {
+        let services_name: Name = match Name::from_ascii(SERVICES_QUERY_NAME) {
+            Ok(name) => name,
+            Err(e) => return ServiceBrowseResult::error(format!("Invalid query name: {}", e)),
+        };
+        query(&socket, &[(services_name.clone(), RecordType::PTR)], setting.wait_time, &mut records);
+        service_types.extend(records.ptr_targets(&services_name));
+    }
+    // Enumerate instances of each service type. Responders usually add SRV, TXT and addresses
+    let queries: Vec<(Name, RecordType)> = service_types
+        .iter()
+        .map(|service_type| (service_type.clone(), RecordType::PTR))
+        .collect();
+    query(&socket, &queries, setting.wait_time, &mut records);
+    let instances: Vec<(Name, Name)> = records.instances(&service_types);
+    // Resolve missing SRV and TXT records
+    let mut queries: Vec<(Name, RecordType)> = vec![];
+    for (_, instance) in &instances {
+        if !records.srv.contains_key(&key(instance)) {
+            queries.push((instance.clone(), RecordType::SRV));
+        }
+        if !records.txt.contains_key(&key(instance)) {
+            queries.push((instance.clone(), RecordType::TXT));
+        }
+    }
+    query(&socket, &queries, setting.wait_time, &mut records);
+    // Resolve missing addresses of the targets
+    let mut queries: Vec<(Name, RecordType)> = vec![];
+    let mut targets: BTreeSet<String> = BTreeSet::new();
+    for (target, _) in records.srv.values() {
+        if records.addrs.contains_key(&key(target)) || !targets.insert(key(target)) {
+            continue;
+        }
+        queries.push((target.clone(), RecordType::A));
+        queries.push((target.clone(), RecordType::AAAA));
+    }
+    query(&socket, &queries, setting.wait_time, &mut records);
+    ServiceBrowseResult {
+        services: records.services(instances),
+        scan_time: start_time.elapsed(),
+        scan_status: ScanStatus::Done,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::op::MessageType;
+    use hickory_resolver::proto::rr::rdata::{A, PTR, SRV, TXT};
+    use hickory_resolver::proto::rr::Record;
+
+    fn name(name: &str) -> Name {
+        Name::from_ascii(name).unwrap()
+    }
+
+    /// Response of a printer to the PTR query of `_ipp._tcp.local`
+    fn printer_response() -> Message {
+        let instance = Name::from_labels(vec!["Office Printer".as_bytes(), b"_ipp", b"_tcp", b"local"]).unwrap();
+        let mut message = Message::new();
+        message.set_message_type(MessageType::Response);
+        message.add_answer(Record::from_rdata(name("_ipp._tcp.local."), 4500, RData::PTR(PTR(instance.clone()))));
+        message.add_additional(Record::from_rdata(instance.clone(), 120, RData::SRV(SRV::new(0, 0, 631, name("Printer.local.")))));
+        message.add_additional(Record::from_rdata(instance, 4500, RData::TXT(TXT::new(vec!["rp=ipp/print".to_string(), "ty=Office Printer".to_string()]))));
+        // Name case differs from the SRV target
+        message.add_additional(Record::from_rdata(name("printer.local."), 120, RData::A(A(Ipv4Addr::new(192, 168, 1, 50)))));
+        message
+    }
+
+    fn browse_result(records: &Records) -> ServiceBrowseResult {
+        let service_types: BTreeSet<Name> = [name("_ipp._tcp.local."), name("_http._tcp.local.")].into_iter().collect();
+        ServiceBrowseResult {
+            services: records.services(records.instances(&service_types)),
+            scan_time: Duration::from_millis(0),
+            scan_status: ScanStatus::Done,
+        }
+    }
+
+    #[test]
+    fn add_records_of_response() {
+        let mut records = Records::default();
+        records.add_message(&printer_response(), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)));
+        let instances = records.ptr_targets(&name("_IPP._tcp.local."));
+        assert_eq!(instances.len(), 1);
+        assert_eq!(dns::name_to_display_string(&instances[0]), "Office Printer._ipp._tcp.local");
+        assert_eq!(records.srv.get(&key(&instances[0])).map(|(_, port)| *port), Some(631));
+        assert_eq!(records.txt.get(&key(&instances[0])).map(|txt| txt.len()), Some(2));
+        assert!(records.ptr_targets(&name("_http._tcp.local.")).is_empty());
+    }
+
+    #[test]
+    fn services_to_hosts() {
+        let mut records = Records::default();
+        records.add_message(&printer_response(), IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50)));
+        let result = browse_result(&records);
+        assert_eq!(result.services.len(), 1);
+        let service = &result.services[0];
+        assert_eq!(service.instance, "Office Printer._ipp._tcp.local");
+        assert_eq!(service.service_type, "_ipp._tcp");
+        assert_eq!(service.service_name(), "ipp");
+        assert_eq!(service.hostname, "Printer");
+        assert_eq!(service.addresses, vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50))]);
+        assert_eq!(service.txt_value("TY"), Some("Office Printer"));
+        let hosts = result.hosts();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].hostname, "Printer");
+        assert_eq!(hosts[0].get_open_port_numbers(), vec![631]);
+        let dns_map = result.dns_map();
+        assert_eq!(dns_map.get(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 50))).map(|s| s.as_str()), Some("Printer"));
+    }
+
+    #[test]
+    fn responder_address_without_address_records() {
+        let mut message = printer_response();
+        let additionals: Vec<Record> = message.take_additionals().into_iter().filter(|record| record.record_type() != RecordType::A).collect();
+        message.insert_additionals(additionals);
+        let mut records = Records::default();
+        records.add_message(&message, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 51)));
+        let result = browse_result(&records);
+        assert_eq!(result.services[0].addresses, vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 51))]);
+        assert_eq!(result.dns_map().get(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 51))).map(|s| s.as_str()), Some("Printer"));
+    }
+}
@@ -6,3 +6,14 @@ pub mod async_io;
 pub mod result;
 pub mod payload;
 pub mod service;
+pub mod http;
+pub mod tls;
+pub mod idle;
+pub mod passive;
+pub mod mdns;
+pub mod smb;
+pub mod snmp;
+pub mod diff;
+pub mod offline;
+pub(crate) mod udp_probe;
+pub(crate) mod matcher;
@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use netdev::mac::MacAddr;
+use nex::packet::icmp::IcmpType;
+use nex::packet::icmpv6::Icmpv6Type;
+use nex::packet::tcp::TcpFlags;
+use crate::packet::frame::PacketFrame;
+use crate::pcap::dump::{self, CapturedPacket, LinkType, PacketDirection};
+use crate::pcap::PacketCaptureOptions;
+use super::blocking::{set_hostscan_filter, set_portscan_filter};
+use super::result::{parse_hostscan_frames, parse_portscan_frames, ProbeTimes, ScanResult, ScanStatus};
+use super::setting::{HostScanSetting, PortScanSetting};
+
+/// Capture options without filters for the link type of the capture file
+fn capture_options(link_type: LinkType) -> PacketCaptureOptions {
+    PacketCaptureOptions {
+        interface_index: 0,
+        src_ips: HashSet::new(),
+        dst_ips: HashSet::new(),
+        src_ports: HashSet::new(),
+        dst_ports: HashSet::new(),
+        ether_types: HashSet::new(),
+        ip_protocols: HashSet::new(),
+        capture_timeout: Duration::MAX,
+        read_timeout: Duration::ZERO,
+        promiscuous: false,
+        receive_undefined: false,
+        tunnel: link_type == LinkType::RawIp,
+        loopback: false,
+        dump: None,
+    }
+}
+
+fn destination_ip(frame: &PacketFrame) -> Option<IpAddr> {
+    if let Some(ipv4_header) = &frame.ipv4_header {
+        Some(IpAddr::V4(ipv4_header.destination))
+    } else {
+        frame.ipv6_header.as_ref().map(|ipv6_header| IpAddr::V6(ipv6_header.destination))
+    }
+}
+
+fn source_ip(frame: &PacketFrame) -> Option<IpAddr> {
+    if let Some(ipv4_header) = &frame.ipv4_header {
+        Some(IpAddr::V4(ipv4_header.source))
+    } else {
+        frame.ipv6_header.as_ref().map(|ipv6_header| IpAddr::V6(ipv6_header.source))
+    }
+}
+
+/// Check if the frame looks like a probe (ICMP echo request, Router Solicitation or TCP SYN)
+fn is_probe(frame: &PacketFrame) -> bool {
+    match (&frame.icmp_header, &frame.icmpv6_header, &frame.tcp_header) {
+        (Some(icmp_header), _, _) => icmp_header.icmp_type == IcmpType::EchoRequest,
+        (_, Some(icmpv6_header), _) => {
+            icmpv6_header.icmpv6_type == Icmpv6Type::EchoRequest || icmpv6_header.icmpv6_type == Icmpv6Type::RouterSolicitation
+        }
+        (_, _, Some(tcp_header)) => tcp_header.flags == TcpFlags::SYN,
+        _ => false,
+    }
+}
+
+/// Which frames are taken as outbound when looking for the local addresses
+#[derive(Clone, Copy, PartialEq)]
+enum OutboundRule {
+    /// pcapng direction flags
+    Direction,
+    /// Frames sent to the targets
+    Target,
+    /// Frames that look like probes
+    Probe,
+}
+
+/// MAC and IP addresses of the capturing host from the outbound frames.
+///
+/// Rules are tried in order until one of them finds outbound frames.
+fn local_addrs(frames: &[(PacketDirection, PacketFrame)], targets: &HashSet<IpAddr>) -> (Option<MacAddr>, HashSet<IpAddr>) {
+    for rule in [OutboundRule::Direction, OutboundRule::Target, OutboundRule::Probe] {
+        let mut mac_addr: Option<MacAddr> = None;
+        let mut ip_addrs: HashSet<IpAddr> = HashSet::new();
+        for (direction, frame) in frames {
+            let outbound: bool = match (rule, direction) {
+                (_, PacketDirection::Inbound) => false,
+                (OutboundRule::Direction, direction) => *direction == PacketDirection::Outbound,
+                (OutboundRule::Target, _) => matches!(destination_ip(frame), Some(ip_addr) if targets.contains(&ip_addr)),
+                (OutboundRule::Probe, _) => is_probe(frame),
+            };
+            if !outbound {
+                continue;
+            }
+            if mac_addr.is_none() {
+                mac_addr = frame.ethernet_header.as_ref().map(|ethernet_header| ethernet_header.source);
+            }
+            if let Some(ip_addr) = source_ip(frame) {
+                ip_addrs.insert(ip_addr);
+            }
+        }
+        if mac_addr.is_some() || !ip_addrs.is_empty() {
+            return (mac_addr, ip_addrs);
+        }
+    }
+    (None, HashSet::new())
+}
+
+/// Send times of the outbound probes in the capture
+fn probe_times(frames: &[(PacketDirection, PacketFrame)], mac_addr: Option<MacAddr>, ip_addrs: &HashSet<IpAddr>) -> ProbeTimes {
+    let mut probe_times: ProbeTimes = ProbeTimes::new();
+    for (direction, frame) in frames {
+        let outbound: bool = match (direction, mac_addr, &frame.ethernet_header) {
+            (PacketDirection::Inbound, _, _) => false,
+            (PacketDirection::Outbound, _, _) => true,
+            (_, Some(mac_addr), Some(ethernet_header)) => ethernet_header.source == mac_addr,
+            _ => matches!(source_ip(frame), Some(ip_addr) if ip_addrs.contains(&ip_addr)),
+        };
+        let (dst_ip, time) = match (outbound, destination_ip(frame), frame.timestamp) {
+            (true, Some(dst_ip), Some(time)) => (dst_ip, time),
+            _ => continue,
+        };
+        match &frame.ethernet_header {
+            Some(ethernet_header) if ethernet_header.destination.octets()[0] & 0x01 == 0x01 => {
+                probe_times.record_discovery(time);
+            }
+            _ => {
+                // Probes other than TCP are recorded without port like a live scan
+                let dst_port: u16 = match &frame.tcp_header {
+                    Some(tcp_header) => tcp_header.destination,
+                    None => 0,
+                };
+                probe_times.record(SocketAddr::new(dst_ip, dst_port), time);
+            }
+        }
+    }
+    probe_times
+}
+
+/// Capture file with the local addresses and probe send times found in it
+struct Capture {
+    packets: Vec<CapturedPacket>,
+    link_type: LinkType,
+    /// Local MAC address. Zero on raw IP links
+    mac_addr: MacAddr,
+    ip_addrs: HashSet<IpAddr>,
+    probe_times: ProbeTimes,
+}
+
+impl Capture {
+    fn read(capture: &[u8], targets: &HashSet<IpAddr>) -> Result<Capture, String> {
+        let (link_type, packets) = dump::read_capture(capture)?;
+        let frames: Vec<(PacketDirection, PacketFrame)> = parse_frames(&packets, &capture_options(link_type))
+            .into_iter()
+            .map(|(packet, frame)| (packet.direction, frame))
+            .collect();
+        let (mac_addr, ip_addrs) = local_addrs(&frames, targets);
+        let probe_times = probe_times(&frames, mac_addr, &ip_addrs);
+        let mac_addr: MacAddr = match (link_type, mac_addr) {
+            (LinkType::Ethernet, Some(mac_addr)) => mac_addr,
+            (LinkType::Ethernet, None) => return Err("Local address not found in the capture".to_string()),
+            (LinkType::RawIp, _) => MacAddr::zero(),
+        };
+        Ok(Capture {
+            packets,
+            link_type,
+            mac_addr,
+            ip_addrs,
+            probe_times,
+        })
+    }
+    /// Time between the first and the last packet
+    fn duration(&self) -> Duration {
+        match (self.packets.first(), self.packets.last()) {
+            (Some(first), Some(last)) => last.time.duration_since(first.time).unwrap_or(Duration::ZERO),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Parse the packets with the filters of the capture options. Capture time is set to the frames
+fn parse_frames<'a>(packets: &'a [CapturedPacket], options: &PacketCaptureOptions) -> Vec<(&'a CapturedPacket, PacketFrame)> {
+    packets
+        .iter()
+        .filter_map(|packet| {
+            let mut frame = crate::pcap::parse_packet(&packet.data, options)?;
+            frame.timestamp = Some(packet.time);
+            Some((packet, frame))
+        })
+        .collect()
+}
+
+/// Parse the pcap or pcapng capture of a host scan with the setting.
+///
+/// Frames go through the same filters and result parsing as a live scan.
+/// Interface of the setting is not used. Targets are only used for filtering, so they can be empty for post-hoc analysis.
+pub fn parse_hostscan_capture(capture: &[u8], scan_setting: HostScanSetting) -> ScanResult {
+    let targets: HashSet<IpAddr> = scan_setting.targets.iter().map(|target| target.ip_addr).collect();
+    let capture = match Capture::read(capture, &targets) {
+        Ok(capture) => capture,
+        Err(e) => return ScanResult::error(e),
+    };
+    let mut options = capture_options(capture.link_type);
+    set_hostscan_filter(&mut options, &scan_setting);
+    let frames: Vec<PacketFrame> = parse_frames(&capture.packets, &options).into_iter().map(|(_, frame)| frame).collect();
+    let mut scan_result = parse_hostscan_frames(frames, scan_setting, capture.mac_addr, &capture.ip_addrs, &capture.probe_times);
+    scan_result.scan_time = capture.duration();
+    scan_result.scan_status = ScanStatus::Done;
+    scan_result
+}
+
+/// Parse the pcap or pcapng capture of a TCP SYN port scan with the setting.
+///
+/// Frames go through the same filters and result parsing as a live scan.
+/// Interface of the setting is not used. Ports of the targets are only used for filtering.
+pub fn parse_portscan_capture(capture: &[u8], scan_setting: PortScanSetting) -> ScanResult {
+    let targets: HashSet<IpAddr> = scan_setting.targets.iter().map(|target| target.ip_addr).collect();
+    let capture = match Capture::read(capture, &targets) {
+        Ok(capture) => capture,
+        Err(e) => return ScanResult::error(e),
+    };
+    let mut options = capture_options(capture.link_type);
+    set_portscan_filter(&mut options, &scan_setting);
+    let frames: Vec<PacketFrame> = parse_frames(&capture.packets, &options).into_iter().map(|(_, frame)| frame).collect();
+    let mut scan_result = parse_portscan_frames(frames, scan_setting, capture.mac_addr, &capture.probe_times);
+    scan_result.scan_time = capture.duration();
+    scan_result.scan_status = ScanStatus::Done;
+    scan_result
+}
@@ -1,12 +1,15 @@
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use netdev::Interface;
+use netdev::mac::MacAddr;
 use nex::net::ip::is_global_ipv6;
 use crate::config::{DEFAULT_HOP_LIMIT, DEFAULT_LOCAL_TCP_PORT, DEFAULT_LOCAL_UDP_PORT};
 use crate::packet::setting::PacketBuildSetting;
 use crate::host::Host;
+use crate::os::probe::{OsProbe, PROBE_ICMP_CODE, PROBE_ICMP_ID, PROBE_ICMP_SEQ, PROBE_SEQ, PROBE_ACK};
 use super::setting::HostScanType;
 
-pub (crate) fn build_hostscan_packet(interface: &Interface, target_host: &Host, scan_type: &HostScanType, ip_packet: bool) -> Vec<u8> {
+/// Build setting with the MAC addresses of the interface and its gateway, and the source address of the interface for the destination
+fn new_build_setting(interface: &Interface, dst_ip: IpAddr) -> PacketBuildSetting {
     let mut build_setting = PacketBuildSetting::new();
     if let Some(mac_addr) = &interface.mac_addr {
         build_setting.src_mac = *mac_addr;
@@ -14,7 +17,7 @@ pub (crate) fn build_hostscan_packet(interface: &Interface, target_host: &Host,
     if let Some(gateway) = &interface.gateway {
         build_setting.dst_mac = gateway.mac_addr;
     }
-    match target_host.ip_addr {
+    match dst_ip {
         IpAddr::V4(ipv4_addr) => {
             interface.ipv4.iter().for_each(|ipv4| {
                 build_setting.src_ip = IpAddr::V4(ipv4.addr);
@@ -36,6 +39,11 @@ pub (crate) fn build_hostscan_packet(interface: &Interface, target_host: &Host,
             build_setting.dst_ip = IpAddr::V6(ipv6_addr);
         },
     }
+    build_setting
+}
+
+pub (crate) fn build_hostscan_packet(interface: &Interface, target_host: &Host, scan_type: &HostScanType, ip_packet: bool) -> Vec<u8> {
+    let mut build_setting = new_build_setting(interface, target_host.ip_addr);
     if target_host.ports.len() > 0 {
         build_setting.dst_port = target_host.ports[0].number;
     }
@@ -44,7 +52,10 @@ pub (crate) fn build_hostscan_packet(interface: &Interface, target_host: &Host,
         build_setting.ip_packet = true;
     }
     match scan_type {
-        HostScanType::IcmpPingScan => {
+        HostScanType::IcmpPingScan
+        | HostScanType::Icmpv6MulticastPingScan
+        | HostScanType::RouterSolicitationScan
+        | HostScanType::IcmpBroadcastPingScan => {
             crate::packet::icmp::build_icmp_packet(build_setting)
         },
         HostScanType::TcpPingScan => {
@@ -58,36 +69,54 @@ pub (crate) fn build_hostscan_packet(interface: &Interface, target_host: &Host,
     }
 }
 
-pub (crate) fn build_hostscan_ip_next_packet(interface: &Interface, target_host: &Host, scan_type: &HostScanType) -> Vec<u8> {
+/// Build the single multicast or broadcast probe of the discovery host scan type
+pub (crate) fn build_discovery_packet(interface: &Interface, scan_type: &HostScanType) -> Result<Vec<u8>, String> {
+    if interface.is_tun() || interface.is_loopback() {
+        return Err("Discovery scan requires an Ethernet interface".to_string());
+    }
     let mut build_setting = PacketBuildSetting::new();
     if let Some(mac_addr) = &interface.mac_addr {
         build_setting.src_mac = *mac_addr;
     }
-    if let Some(gateway) = &interface.gateway {
-        build_setting.dst_mac = gateway.mac_addr;
-    }
-    match target_host.ip_addr {
-        IpAddr::V4(ipv4_addr) => {
-            interface.ipv4.iter().for_each(|ipv4| {
-                build_setting.src_ip = IpAddr::V4(ipv4.addr);
-            });
-            build_setting.dst_ip = IpAddr::V4(ipv4_addr);
-        },
-        IpAddr::V6(ipv6_addr) => {
-            if is_global_ipv6(&ipv6_addr) {
-                interface.ipv6.iter().for_each(|ipv6| {
-                    if is_global_ipv6(&ipv6.addr) {
-                        build_setting.src_ip = IpAddr::V6(ipv6.addr);
-                    }
-                });
-            }else {
-                interface.ipv6.iter().for_each(|ipv6| {
-                    build_setting.src_ip = IpAddr::V6(ipv6.addr);
-                });
+    match scan_type {
+        HostScanType::Icmpv6MulticastPingScan | HostScanType::RouterSolicitationScan => {
+            // Link-local source address (fe80::/10)
+            let src_ipv6: Ipv6Addr = match interface.ipv6.iter().find(|ipv6| ipv6.addr.segments()[0] & 0xffc0 == 0xfe80) {
+                Some(ipv6) => ipv6.addr,
+                None => return Err("No link-local IPv6 address on the interface".to_string()),
+            };
+            build_setting.src_ip = IpAddr::V6(src_ipv6);
+            if let HostScanType::RouterSolicitationScan = scan_type {
+                // All-routers
+                build_setting.dst_ip = IpAddr::V6(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2));
+                build_setting.dst_mac = MacAddr::new(0x33, 0x33, 0, 0, 0, 2);
+                build_setting.hop_limit = 255;
+                Ok(crate::packet::icmp::build_router_solicitation_packet(build_setting))
+            } else {
+                // All-nodes
+                build_setting.dst_ip = IpAddr::V6(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1));
+                build_setting.dst_mac = MacAddr::new(0x33, 0x33, 0, 0, 0, 1);
+                build_setting.hop_limit = 1;
+                Ok(crate::packet::icmp::build_icmp_packet(build_setting))
             }
-            build_setting.dst_ip = IpAddr::V6(ipv6_addr);
         },
+        HostScanType::IcmpBroadcastPingScan => {
+            let ipv4 = match interface.ipv4.first() {
+                Some(ipv4) => ipv4,
+                None => return Err("No IPv4 address on the interface".to_string()),
+            };
+            build_setting.src_ip = IpAddr::V4(ipv4.addr);
+            build_setting.dst_ip = IpAddr::V4(ipv4.broadcast());
+            build_setting.dst_mac = MacAddr::broadcast();
+            build_setting.hop_limit = DEFAULT_HOP_LIMIT;
+            Ok(crate::packet::icmp::build_icmp_packet(build_setting))
+        },
+        _ => Err(format!("{} is not a discovery scan type", scan_type.to_str())),
     }
+}
+
+pub (crate) fn build_hostscan_ip_next_packet(interface: &Interface, target_host: &Host, scan_type: &HostScanType) -> Vec<u8> {
+    let mut build_setting = new_build_setting(interface, target_host.ip_addr);
     if target_host.ports.len() > 0 {
         build_setting.dst_port = target_host.ports[0].number;
     }
@@ -96,7 +125,10 @@ pub (crate) fn build_hostscan_ip_next_packet(interface: &Interface, target_host:
         build_setting.ip_packet = true;
     }
     match scan_type {
-        HostScanType::IcmpPingScan => {
+        HostScanType::IcmpPingScan
+        | HostScanType::Icmpv6MulticastPingScan
+        | HostScanType::RouterSolicitationScan
+        | HostScanType::IcmpBroadcastPingScan => {
             crate::packet::icmp::build_ip_next_icmp_packet(build_setting)
         },
         HostScanType::TcpPingScan => {
@@ -111,45 +143,69 @@ pub (crate) fn build_hostscan_ip_next_packet(interface: &Interface, target_host:
 }
 
 pub (crate) fn build_portscan_packet(interface: &Interface, target_ip_addr: IpAddr, target_port: u16, ip_packet: bool) -> Vec<u8> {
-    let mut build_setting = PacketBuildSetting::new();
-    if let Some(mac_addr) = &interface.mac_addr {
-        build_setting.src_mac = *mac_addr;
+    let mut build_setting = new_build_setting(interface, target_ip_addr);
+    build_setting.dst_port = target_port;
+    build_setting.hop_limit = DEFAULT_HOP_LIMIT;
+    if ip_packet || interface.is_tun() || interface.is_loopback() {
+        build_setting.ip_packet = true;
     }
-    if let Some(gateway) = &interface.gateway {
-        build_setting.dst_mac = gateway.mac_addr;
+    build_setting.src_port = DEFAULT_LOCAL_TCP_PORT;
+    crate::packet::tcp::build_tcp_syn_packet(build_setting)
+}
+
+pub (crate) fn build_portscan_ip_next_packet(interface: &Interface, target_ip_addr: IpAddr, target_port: u16) -> Vec<u8> {
+    let mut build_setting = new_build_setting(interface, target_ip_addr);
+    build_setting.dst_port = target_port;
+    build_setting.hop_limit = DEFAULT_HOP_LIMIT;
+    if interface.is_tun() || interface.is_loopback() {
+        build_setting.ip_packet = true;
     }
-    match target_ip_addr {
-        IpAddr::V4(ipv4_addr) => {
-            interface.ipv4.iter().for_each(|ipv4| {
-                build_setting.src_ip = IpAddr::V4(ipv4.addr);
-            });
-            build_setting.dst_ip = IpAddr::V4(ipv4_addr);
+    build_setting.src_port = DEFAULT_LOCAL_TCP_PORT;
+    crate::packet::tcp::build_ip_next_tcp_syn_packet(build_setting)
+}
+pub (crate) fn build_os_probe_packet(interface: &Interface, target_ip_addr: IpAddr, probe: OsProbe, open_port: u16, closed_port: u16) -> Vec<u8> {
+    let mut build_setting = new_build_setting(interface, target_ip_addr);
+    build_setting.dst_port = if probe.targets_open_port() { open_port } else { closed_port };
+    build_setting.src_port = probe.src_port();
+    build_setting.hop_limit = DEFAULT_HOP_LIMIT;
+    if interface.is_tun() || interface.is_loopback() {
+        build_setting.ip_packet = true;
+    }
+    build_setting.dont_fragment = probe.dont_fragment();
+    build_setting.payload = probe.payload();
+    match probe {
+        OsProbe::IE => {
+            build_setting.icmp_code = PROBE_ICMP_CODE;
+            build_setting.icmp_identifier = PROBE_ICMP_ID;
+            build_setting.icmp_sequence = PROBE_ICMP_SEQ;
+            crate::packet::icmp::build_icmp_echo_packet(build_setting)
         },
-        IpAddr::V6(ipv6_addr) => {
-            if is_global_ipv6(&ipv6_addr) {
-                interface.ipv6.iter().for_each(|ipv6| {
-                    if is_global_ipv6(&ipv6.addr) {
-                        build_setting.src_ip = IpAddr::V6(ipv6.addr);
-                    }
-                });
-            }else {
-                interface.ipv6.iter().for_each(|ipv6| {
-                    build_setting.src_ip = IpAddr::V6(ipv6.addr);
-                });
-            }
-            build_setting.dst_ip = IpAddr::V6(ipv6_addr);
+        OsProbe::U1 => {
+            crate::packet::udp::build_udp_packet(build_setting)
+        },
+        _ => {
+            build_setting.tcp_flags = probe.tcp_flags();
+            build_setting.tcp_window = probe.tcp_window();
+            build_setting.tcp_options = probe.tcp_options();
+            build_setting.tcp_seq = PROBE_SEQ;
+            build_setting.tcp_ack = PROBE_ACK;
+            crate::packet::tcp::build_tcp_packet(build_setting)
         },
     }
+}
+
+pub (crate) fn build_timestamp_probe_packet(interface: &Interface, target_ip_addr: IpAddr, target_port: u16, src_port: u16) -> Vec<u8> {
+    let mut build_setting = new_build_setting(interface, target_ip_addr);
     build_setting.dst_port = target_port;
     build_setting.hop_limit = DEFAULT_HOP_LIMIT;
-    if ip_packet || interface.is_tun() || interface.is_loopback() {
+    if interface.is_tun() || interface.is_loopback() {
         build_setting.ip_packet = true;
     }
-    build_setting.src_port = DEFAULT_LOCAL_TCP_PORT;
+    build_setting.src_port = src_port;
     crate::packet::tcp::build_tcp_syn_packet(build_setting)
 }
 
-pub (crate) fn build_portscan_ip_next_packet(interface: &Interface, target_ip_addr: IpAddr, target_port: u16) -> Vec<u8> {
+pub (crate) fn build_spoofed_tcp_packet(interface: &Interface, src_addr: SocketAddr, dst_addr: SocketAddr, tcp_flags: u8) -> Vec<u8> {
     let mut build_setting = PacketBuildSetting::new();
     if let Some(mac_addr) = &interface.mac_addr {
         build_setting.src_mac = *mac_addr;
@@ -157,33 +213,15 @@ pub (crate) fn build_portscan_ip_next_packet(interface: &Interface, target_ip_ad
     if let Some(gateway) = &interface.gateway {
         build_setting.dst_mac = gateway.mac_addr;
     }
-    match target_ip_addr {
-        IpAddr::V4(ipv4_addr) => {
-            interface.ipv4.iter().for_each(|ipv4| {
-                build_setting.src_ip = IpAddr::V4(ipv4.addr);
-            });
-            build_setting.dst_ip = IpAddr::V4(ipv4_addr);
-        },
-        IpAddr::V6(ipv6_addr) => {
-            if is_global_ipv6(&ipv6_addr) {
-                interface.ipv6.iter().for_each(|ipv6| {
-                    if is_global_ipv6(&ipv6.addr) {
-                        build_setting.src_ip = IpAddr::V6(ipv6.addr);
-                    }
-                });
-            }else {
-                interface.ipv6.iter().for_each(|ipv6| {
-                    build_setting.src_ip = IpAddr::V6(ipv6.addr);
-                });
-            }
-            build_setting.dst_ip = IpAddr::V6(ipv6_addr);
-        },
-    }
-    build_setting.dst_port = target_port;
+    build_setting.src_ip = src_addr.ip();
+    build_setting.src_port = src_addr.port();
+    build_setting.dst_ip = dst_addr.ip();
+    build_setting.dst_port = dst_addr.port();
     build_setting.hop_limit = DEFAULT_HOP_LIMIT;
     if interface.is_tun() || interface.is_loopback() {
         build_setting.ip_packet = true;
     }
-    build_setting.src_port = DEFAULT_LOCAL_TCP_PORT;
-    crate::packet::tcp::build_ip_next_tcp_syn_packet(build_setting)
-}
\ No newline at end of file
+    build_setting.tcp_flags = tcp_flags;
+    build_setting.tcp_seq = rand::random::<u32>();
+    crate::packet::tcp::build_tcp_packet(build_setting)
+}
@@ -0,0 +1,484 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use hickory_resolver::proto::rr::RData;
+use netdev::mac::MacAddr;
+use nex::packet::ethernet::EtherType;
+use nex::packet::icmpv6::Icmpv6Type;
+use nex::packet::tcp::TcpFlags;
+use crate::host::{Host, Port, PortStatus};
+use crate::packet::frame::PacketFrame;
+use crate::pcap::PacketCaptureOptions;
+use crate::protocol::{dhcp, dns, lldp, netbios, ssdp};
+use crate::os::frame_source_ip;
+use super::result::{ScanResult, ScanStatus};
+use super::setting::PassiveScanSetting;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const NETBIOS_NS_PORT: u16 = 137;
+const NETBIOS_DGM_PORT: u16 = 138;
+const SSDP_PORT: u16 = 1900;
+const MDNS_PORT: u16 = 5353;
+const LLMNR_PORT: u16 = 5355;
+/// SYN/ACK frames kept per host for the passive OS guess
+const MAX_FINGERPRINT_FRAMES: usize = 8;
+
+/// Protocol the host was observed with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DiscoveryProtocol {
+    Arp,
+    Ndp,
+    Dhcp,
+    Mdns,
+    Llmnr,
+    Netbios,
+    Ssdp,
+    Lldp,
+    Cdp,
+}
+
+impl DiscoveryProtocol {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DiscoveryProtocol::Arp => "ARP",
+            DiscoveryProtocol::Ndp => "NDP",
+            DiscoveryProtocol::Dhcp => "DHCP",
+            DiscoveryProtocol::Mdns => "mDNS",
+            DiscoveryProtocol::Llmnr => "LLMNR",
+            DiscoveryProtocol::Netbios => "NetBIOS",
+            DiscoveryProtocol::Ssdp => "SSDP",
+            DiscoveryProtocol::Lldp => "LLDP",
+            DiscoveryProtocol::Cdp => "CDP",
+        }
+    }
+}
+
+/// Protocols and description of the host observed by passive discovery
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PassiveInfo {
+    /// Protocols the host was observed with
+    pub protocols: Vec<DiscoveryProtocol>,
+    /// Description announced by the host
+    pub description: String,
+}
+
+/// Host observed by passive discovery
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ObservedHost {
+    pub ip_addr: IpAddr,
+    pub mac_addr: MacAddr,
+    pub hostname: String,
+    /// Advertised services
+    pub services: Vec<Port>,
+    /// Protocols the host was observed with
+    pub protocols: Vec<DiscoveryProtocol>,
+    /// Description announced by the host (e.g. LLDP system description, SSDP server, DHCP vendor class)
+    pub description: String,
+}
+
+impl ObservedHost {
+    fn new(ip_addr: IpAddr) -> ObservedHost {
+        ObservedHost {
+            ip_addr,
+            mac_addr: MacAddr::zero(),
+            hostname: String::new(),
+            services: vec![],
+            protocols: vec![],
+            description: String::new(),
+        }
+    }
+    fn to_host(&self) -> Host {
+        let mut host = Host::new(self.ip_addr, self.hostname.clone());
+        host.mac_addr = self.mac_addr;
+        host.ports = self.services.clone();
+        host.details.passive = Some(PassiveInfo {
+            protocols: self.protocols.clone(),
+            description: self.description.clone(),
+        });
+        host
+    }
+}
+
+/// Collects observations from the captured frames
+#[derive(Default)]
+struct Observations {
+    hosts: BTreeMap<IpAddr, ObservedHost>,
+}
+
+impl Observations {
+    fn host(&mut self, ip_addr: IpAddr, protocol: DiscoveryProtocol) -> Option<&mut ObservedHost> {
+        if ip_addr.is_unspecified() || ip_addr.is_multicast() || ip_addr == IpAddr::V4(std::net::Ipv4Addr::BROADCAST) {
+            return None;
+        }
+        let host = self.hosts.entry(ip_addr).or_insert_with(|| ObservedHost::new(ip_addr));
+        if !host.protocols.contains(&protocol) {
+            host.protocols.push(protocol);
+        }
+        Some(host)
+    }
+    fn add(&mut self, ip_addr: IpAddr, protocol: DiscoveryProtocol, mac_addr: Option<MacAddr>, hostname: Option<&str>) {
+        if let Some(host) = self.host(ip_addr, protocol) {
+            if let Some(mac_addr) = mac_addr {
+                if mac_addr != MacAddr::zero() && host.mac_addr == MacAddr::zero() {
+                    host.mac_addr = mac_addr;
+                }
+            }
+            if let Some(hostname) = hostname {
+                if !hostname.is_empty() && host.hostname.is_empty() {
+                    host.hostname = hostname.to_string();
+                }
+            }
+        }
+    }
+    fn add_service(&mut self, ip_addr: IpAddr, protocol: DiscoveryProtocol, port: u16, service_name: &str, service_version: &str) {
+        if let Some(host) = self.host(ip_addr, protocol) {
+            if host.services.iter().any(|p| p.number == port) {
+                return;
+            }
+            host.services.push(Port {
+                number: port,
+                status: PortStatus::Open,
+                service_name: service_name.to_string(),
+                service_version: service_version.to_string(),
+                rtt: None,
+            });
+        }
+    }
+    fn add_description(&mut self, ip_addr: IpAddr, protocol: DiscoveryProtocol, description: &str) {
+        if let Some(host) = self.host(ip_addr, protocol) {
+            if host.description.is_empty() {
+                host.description = description.to_string();
+            }
+        }
+    }
+}
+
+fn source_mac(frame: &PacketFrame) -> Option<MacAddr> {
+    frame.ethernet_header.as_ref().map(|ethernet_header| ethernet_header.source)
+}
+
+fn observe_arp(frame: &PacketFrame, observations: &mut Observations) {
+    if let Some(arp_header) = &frame.arp_header {
+        observations.add(IpAddr::V4(arp_header.sender_proto_addr), DiscoveryProtocol::Arp, Some(arp_header.sender_hw_addr), None);
+    }
+}
+
+fn observe_ndp(frame: &PacketFrame, observations: &mut Observations) {
+    let (icmpv6_header, ipv6_header) = match (&frame.icmpv6_header, &frame.ipv6_header) {
+        (Some(icmpv6_header), Some(ipv6_header)) => (icmpv6_header, ipv6_header),
+        _ => return,
+    };
+    match icmpv6_header.icmpv6_type {
+        Icmpv6Type::RouterSolicitation
+        | Icmpv6Type::RouterAdvertisement
+        | Icmpv6Type::NeighborSolicitation
+        | Icmpv6Type::NeighborAdvertisement => {
+            observations.add(IpAddr::V6(ipv6_header.source), DiscoveryProtocol::Ndp, source_mac(frame), None);
+        }
+        _ => {}
+    }
+}
+
+fn observe_dhcp(frame: &PacketFrame, observations: &mut Observations) {
+    let message = match dhcp::parse_message(&frame.payload) {
+        Some(message) => message,
+        None => return,
+    };
+    // BOOTREQUEST from the client, or BOOTREPLY from the server with the assigned address
+    if let Some(client_ip) = message.client_addr() {
+        observations.add(IpAddr::V4(client_ip), DiscoveryProtocol::Dhcp, Some(message.client_mac), message.hostname.as_deref());
+        if let Some(vendor_class) = &message.vendor_class {
+            observations.add_description(IpAddr::V4(client_ip), DiscoveryProtocol::Dhcp, vendor_class);
+        }
+    }
+    if message.op == 2 {
+        if let Some(server_ip) = frame_source_ip(frame) {
+            observations.add(server_ip, DiscoveryProtocol::Dhcp, source_mac(frame), None);
+            observations.add_service(server_ip, DiscoveryProtocol::Dhcp, DHCP_SERVER_PORT, "dhcps", "");
+        }
+    }
+}
+
+/// Service type (e.g. `http`) and protocol from DNS-SD instance name (e.g. `Printer._http._tcp.local`)
+fn dns_sd_service(name: &str) -> Option<(String, String)> {
+    let labels: Vec<&str> = name.split('.').collect();
+    let proto_index = labels.iter().position(|label| *label == "_tcp" || *label == "_udp")?;
+    let service = labels.get(proto_index.checked_sub(1)?)?.trim_start_matches('_');
+    Some((service.to_string(), labels[proto_index].trim_start_matches('_').to_string()))
+}
+
+fn observe_dns(frame: &PacketFrame, protocol: DiscoveryProtocol, observations: &mut Observations) {
+    let src_ip = match frame_source_ip(frame) {
+        Some(ip) => ip,
+        None => return,
+    };
+    observations.add(src_ip, protocol, source_mac(frame), None);
+    let message = match dns::parse_message(&frame.payload) {
+        Some(message) => message,
+        None => return,
+    };
+    let records = message.answers().iter().chain(message.additionals().iter());
+    // Host name to address in the message
+    let mut addresses: Vec<(String, IpAddr)> = vec![];
+    for record in records.clone() {
+        let name = dns::name_to_string(record.name());
+        let hostname = name.trim_end_matches(".local").to_string();
+        match record.data() {
+            Some(RData::A(a)) => {
+                let ip_addr = IpAddr::V4(a.0);
+                let mac_addr = if ip_addr == src_ip { source_mac(frame) } else { None };
+                observations.add(ip_addr, protocol, mac_addr, Some(&hostname));
+                addresses.push((name, ip_addr));
+            }
+            Some(RData::AAAA(aaaa)) => {
+                let ip_addr = IpAddr::V6(aaaa.0);
+                let mac_addr = if ip_addr == src_ip { source_mac(frame) } else { None };
+                observations.add(ip_addr, protocol, mac_addr, Some(&hostname));
+                addresses.push((name, ip_addr));
+            }
+            _ => {}
+        }
+    }
+    for record in records {
+        if let Some(RData::SRV(srv)) = record.data() {
+            let (service, _) = match dns_sd_service(&dns::name_to_string(record.name())) {
+                Some(service) => service,
+                None => continue,
+            };
+            let target = dns::name_to_string(srv.target());
+            let ip_addrs: Vec<IpAddr> = match addresses.iter().filter(|(name, _)| *name == target).map(|(_, ip)| *ip).collect::<Vec<IpAddr>>() {
+                ip_addrs if !ip_addrs.is_empty() => ip_addrs,
+                _ => vec![src_ip],
+            };
+            for ip_addr in ip_addrs {
+                observations.add_service(ip_addr, protocol, srv.port(), &service, "");
+            }
+        }
+    }
+}
+
+fn observe_netbios(frame: &PacketFrame, observations: &mut Observations) {
+    let src_ip = match frame_source_ip(frame) {
+        Some(ip) => ip,
+        None => return,
+    };
+    let udp_header = match &frame.udp_header {
+        Some(udp_header) => udp_header,
+        None => return,
+    };
+    let name = if udp_header.destination == NETBIOS_NS_PORT {
+        netbios::parse_name_registration(&frame.payload)
+    } else {
+        netbios::parse_datagram_source_name(&frame.payload)
+    };
+    // Workstation (0x00) or server (0x20) name
+    let hostname = match name {
+        Some((name, 0x00)) | Some((name, 0x20)) => Some(name),
+        _ => None,
+    };
+    observations.add(src_ip, DiscoveryProtocol::Netbios, source_mac(frame), hostname.as_deref());
+}
+
+fn observe_ssdp(frame: &PacketFrame, observations: &mut Observations) {
+    let src_ip = match frame_source_ip(frame) {
+        Some(ip) => ip,
+        None => return,
+    };
+    observations.add(src_ip, DiscoveryProtocol::Ssdp, source_mac(frame), None);
+    let message = match ssdp::parse_message(&frame.payload) {
+        Some(message) => message,
+        None => return,
+    };
+    if message.is_search() {
+        return;
+    }
+    let server = message.header("server").unwrap_or("");
+    // Fall back to the device or service type (e.g. urn:schemas-upnp-org:device:MediaRenderer:1)
+    let description = if server.is_empty() { message.target().unwrap_or("") } else { server };
+    if !description.is_empty() {
+        observations.add_description(src_ip, DiscoveryProtocol::Ssdp, description);
+    }
+    if let Some(port) = message.location_port() {
+        observations.add_service(src_ip, DiscoveryProtocol::Ssdp, port, "upnp", server);
+    }
+}
+
+fn observe_neighbor(frame: &PacketFrame, info: lldp::NeighborInfo, protocol: DiscoveryProtocol, observations: &mut Observations) {
+    for ip_addr in &info.addresses {
+        observations.add(*ip_addr, protocol, source_mac(frame), info.system_name.as_deref());
+        let description: Vec<&str> = [info.platform.as_deref(), info.description.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        if !description.is_empty() {
+            observations.add_description(*ip_addr, protocol, &description.join(" "));
+        }
+    }
+}
+
+fn observe_frame(frame: &PacketFrame, observations: &mut Observations) {
+    if frame.arp_header.is_some() {
+        observe_arp(frame, observations);
+        return;
+    }
+    if frame.icmpv6_header.is_some() {
+        observe_ndp(frame, observations);
+        return;
+    }
+    if let Some(udp_header) = &frame.udp_header {
+        let ports = [udp_header.source, udp_header.destination];
+        if ports.contains(&DHCP_SERVER_PORT) && ports.contains(&DHCP_CLIENT_PORT) {
+            observe_dhcp(frame, observations);
+        } else if ports.contains(&MDNS_PORT) {
+            observe_dns(frame, DiscoveryProtocol::Mdns, observations);
+        } else if ports.contains(&LLMNR_PORT) {
+            observe_dns(frame, DiscoveryProtocol::Llmnr, observations);
+        } else if udp_header.destination == NETBIOS_NS_PORT || udp_header.destination == NETBIOS_DGM_PORT {
+            observe_netbios(frame, observations);
+        } else if ports.contains(&SSDP_PORT) {
+            observe_ssdp(frame, observations);
+        }
+        return;
+    }
+    let ethernet_header = match &frame.ethernet_header {
+        Some(ethernet_header) => ethernet_header,
+        None => return,
+    };
+    if ethernet_header.ethertype == EtherType::Lldp {
+        if let Some(info) = lldp::parse_lldp(&frame.payload) {
+            observe_neighbor(frame, info, DiscoveryProtocol::Lldp, observations);
+        }
+    } else if ethernet_header.destination.octets() == lldp::CDP_MAC_ADDR {
+        if let Some(info) = lldp::parse_cdp(&frame.payload) {
+            observe_neighbor(frame, info, DiscoveryProtocol::Cdp, observations);
+        }
+    }
+}
+
+/// Collects observations and SYN/ACK fingerprints frame by frame, so a long listening period does not keep every frame
+#[derive(Default)]
+struct PassiveCollector {
+    observations: Observations,
+    /// First SYN/ACK frames of each source address
+    fingerprints: HashMap<IpAddr, Vec<PacketFrame>>,
+}
+
+impl PassiveCollector {
+    fn add_frame(&mut self, frame: &PacketFrame) {
+        observe_frame(frame, &mut self.observations);
+        let syn_ack = match &frame.tcp_header {
+            Some(tcp_header) => tcp_header.flags == TcpFlags::SYN | TcpFlags::ACK,
+            None => false,
+        };
+        if let (true, Some(src_ip)) = (syn_ack, frame_source_ip(frame)) {
+            let frames = self.fingerprints.entry(src_ip).or_default();
+            if frames.len() < MAX_FINGERPRINT_FRAMES {
+                frames.push(frame.clone());
+            }
+        }
+    }
+    fn into_observations(self) -> Vec<ObservedHost> {
+        self.observations.hosts.into_values().collect()
+    }
+    fn into_result(mut self) -> ScanResult {
+        let mut result: ScanResult = ScanResult::new();
+        for observed in self.observations.hosts.values() {
+            result.hosts.push(observed.to_host());
+            // SYN/ACK frames of the observed hosts only
+            if let Some(frames) = self.fingerprints.remove(&observed.ip_addr) {
+                result.fingerprints.extend(frames);
+            }
+        }
+        result.guess_os();
+        result
+    }
+}
+
+/// Build observed hosts from the captured frames
+pub fn parse_observations(frames: &[PacketFrame]) -> Vec<ObservedHost> {
+    let mut collector = PassiveCollector::default();
+    for frame in frames {
+        collector.add_frame(frame);
+    }
+    collector.into_observations()
+}
+
+/// Build scan result from the captured frames.
+///
+/// SYN/ACK frames seen on the wire are kept as fingerprints for passive OS guess.
+pub fn parse_passive_result(frames: &[PacketFrame]) -> ScanResult {
+    let mut collector = PassiveCollector::default();
+    for frame in frames {
+        collector.add_frame(frame);
+    }
+    collector.into_result()
+}
+
+/// Listen on the interface for the duration and pass each frame to the collector. Nothing is sent.
+fn listen(setting: &PassiveScanSetting, collector: &mut PassiveCollector) -> Result<(), String> {
+    let interface = match crate::interface::get_interface_by_index(setting.if_index) {
+        Some(interface) => interface,
+        None => return Err("Interface not found".to_string()),
+    };
+    let config = nex::datalink::Config {
+        write_buffer_size: 4096,
+        read_buffer_size: 4096,
+        read_timeout: Some(Duration::from_millis(100)),
+        write_timeout: None,
+        channel_type: nex::datalink::ChannelType::Layer2,
+        bpf_fd_attempts: 1000,
+        linux_fanout: None,
+        promiscuous: setting.promiscuous,
+    };
+    let mut rx = match nex::datalink::channel(&interface, config) {
+        Ok(nex::datalink::Channel::Ethernet(_tx, rx)) => rx,
+        Ok(_) => return Err("Unhandled channel type".to_string()),
+        Err(e) => return Err(format!("Failed to create channel: {}", e)),
+    };
+    let capture_options: PacketCaptureOptions = PacketCaptureOptions {
+        interface_index: interface.index,
+        src_ips: HashSet::new(),
+        dst_ips: HashSet::new(),
+        src_ports: HashSet::new(),
+        dst_ports: HashSet::new(),
+        ether_types: HashSet::new(),
+        ip_protocols: HashSet::new(),
+        capture_timeout: setting.duration,
+        read_timeout: Duration::from_millis(100),
+        promiscuous: setting.promiscuous,
+        receive_undefined: true,
+        tunnel: interface.is_tun(),
+        loopback: interface.is_loopback(),
+        dump: None,
+    };
+    let stop: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    crate::pcap::capture_each(&mut rx, capture_options, &stop, |frame| collector.add_frame(&frame));
+    Ok(())
+}
+
+/// Listen on the interface for the duration and return the observed hosts. Nothing is sent.
+pub(crate) fn observe(setting: &PassiveScanSetting) -> Result<Vec<ObservedHost>, String> {
+    let mut collector = PassiveCollector::default();
+    listen(setting, &mut collector)?;
+    Ok(collector.into_observations())
+}
+
+/// Listen on the interface for the duration and build scan result of the observed hosts. Nothing is sent.
+pub(crate) fn scan(setting: &PassiveScanSetting) -> ScanResult {
+    let start_time = Instant::now();
+    let mut collector = PassiveCollector::default();
+    if let Err(e) = listen(setting, &mut collector) {
+        return ScanResult::error(e);
+    }
+    let mut result: ScanResult = collector.into_result();
+    result.scan_time = start_time.elapsed();
+    result.scan_status = ScanStatus::Done;
+    result
+}
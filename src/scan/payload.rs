@@ -1,5 +1,9 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Payloads for service detection
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PayloadType {
     /// No payload. Just open TCP connection and read response.
     Null,
@@ -15,6 +19,7 @@ pub enum PayloadType {
 
 /// Payload information for service detection
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PayloadInfo {
     pub payload: Vec<u8>,
     pub payload_type: PayloadType,
@@ -22,6 +27,7 @@ pub struct PayloadInfo {
 
 /// Payload builder for service detection
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PayloadBuilder {
     payload_info: PayloadInfo,
 }
@@ -77,12 +83,13 @@ impl PayloadBuilder {
     pub fn payload(self) -> PayloadInfo {
         self.payload_info
     }
-    /* pub fn null() -> PayloadInfo {
+    /// Create a new PayloadInfo for the null probe (no payload)
+    pub fn null() -> PayloadInfo {
         PayloadInfo {
-            payload: vec![0x00],
+            payload: vec![],
             payload_type: PayloadType::Null,
         }
-    } */
+    }
     /// Create a new PayloadInfo with a generic line
     pub fn generic_line() -> PayloadInfo {
         PayloadInfo {
@@ -1,17 +1,27 @@
 use netdev::mac::MacAddr;
 use netdev::Interface;
+use nex::packet::icmp::IcmpType;
+use nex::packet::icmpv6::Icmpv6Type;
 use nex::packet::tcp::TcpFlags;
 
 use crate::packet::frame::PacketFrame;
-use crate::host::{Host, Port, PortStatus};
-use std::collections::HashSet;
+use crate::host::{Host, HostDetails, Port, PortStatus, RttStats};
+use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, SocketAddr};
-use std::time::Duration;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 
+use super::diff::ScanDiff;
+use super::http::HttpInfo;
+use super::tls::TlsInfo;
 use super::setting::{HostScanSetting, HostScanType, PortScanSetting};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 /// Status of scan task
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ScanStatus {
     Done,
     Timeout,
@@ -20,11 +30,14 @@ pub enum ScanStatus {
 
 /// Result of scan
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ScanResult {
     /// List of scanned Host info and their respective ports
     pub hosts: Vec<Host>,
     /// Time taken to scan
     pub scan_time: Duration,
+    /// Start time of the scan, if known. Set by the importers from the parsed output
+    pub start_time: Option<SystemTime>,
     /// Status of the scan task
     pub scan_status: ScanStatus,
     /// Captured packet fingerprints
@@ -36,6 +49,7 @@ impl ScanResult {
         ScanResult {
             hosts: vec![],
             scan_time: Duration::from_millis(0),
+            start_time: None,
             scan_status: ScanStatus::Done,
             fingerprints: vec![],
         }
@@ -44,10 +58,49 @@ impl ScanResult {
         ScanResult {
             hosts: vec![],
             scan_time: Duration::from_millis(0),
+            start_time: None,
             scan_status: ScanStatus::Error(message),
             fingerprints: vec![],
         }
     }
+    /// Write service detection results back into the ports of each host
+    pub fn set_service_results(&mut self, service_results: &HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>) {
+        for host in &mut self.hosts {
+            let results = match service_results.get(&host.ip_addr) {
+                Some(results) => results,
+                None => continue,
+            };
+            for port in &mut host.ports {
+                let result = match results.get(&port.number) {
+                    Some(result) => result,
+                    None => continue,
+                };
+                if !result.service_name.is_empty() {
+                    port.service_name = result.service_name.clone();
+                }
+                if let Some(version) = result.service_version() {
+                    port.service_version = version;
+                }
+            }
+        }
+    }
+    /// Guess OS of each host from the captured SYN/ACK fingerprints
+    pub fn guess_os(&mut self) {
+        for host in &mut self.hosts {
+            let fingerprint = match crate::os::fingerprint_host(host.ip_addr, &self.fingerprints) {
+                Some(fingerprint) => fingerprint,
+                None => continue,
+            };
+            if let Some(os_match) = crate::os::match_fingerprint(&fingerprint) {
+                host.os_family = os_match.family;
+                host.os_confidence = os_match.confidence;
+            }
+        }
+    }
+    /// Compare with the newer scan result
+    pub fn diff(&self, new: &ScanResult) -> ScanDiff {
+        super::diff::diff(self, new)
+    }
     /// Returns IP addresses from the scan result
     pub fn get_hosts(&self) -> Vec<IpAddr> {
         let mut hosts: Vec<IpAddr> = vec![];
@@ -117,11 +170,16 @@ impl ScanResult {
 
 /// Result of a service probe
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ServiceProbeResult {
     pub port: u16,
     pub service_name: String,
     pub service_detail: Option<String>,
     pub response: Vec<u8>,
+    /// HTTP fingerprint. Set when the port is probed with HTTP or HTTPS payload
+    pub http_info: Option<HttpInfo>,
+    /// TLS session information. Set when TLS is detected on the port automatically
+    pub tls_info: Option<TlsInfo>,
     pub error: Option<ServiceProbeError>,
 }
 
@@ -133,6 +191,8 @@ impl ServiceProbeResult {
             service_name,
             service_detail: None,
             response,
+            http_info: None,
+            tls_info: None,
             error: None,
         }
     }
@@ -144,6 +204,8 @@ impl ServiceProbeResult {
             service_name,
             service_detail: None,
             response: Vec::new(),
+            http_info: None,
+            tls_info: None,
             error: Some(error),
         }
     }
@@ -162,9 +224,27 @@ impl ServiceProbeResult {
     pub fn into_error(self) -> Option<ServiceProbeError> {
         self.error
     }
+
+    /// Get service version (product banner) from the result
+    ///
+    /// HTTP Server header is preferred if available.
+    pub fn service_version(&self) -> Option<String> {
+        if let Some(server) = self.http_info.as_ref().and_then(|info| info.server()) {
+            return Some(server.to_string());
+        }
+        let detail: &str = self.service_detail.as_deref()?;
+        let line: &str = detail.lines().next().unwrap_or("").trim();
+        let line: &str = line.strip_prefix("Server:").unwrap_or(line).trim();
+        if line.is_empty() {
+            None
+        } else {
+            Some(line.to_string())
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ServiceProbeError {
     ConnectionError(String),
     WriteError(String),
@@ -173,17 +253,85 @@ pub enum ServiceProbeError {
     CustomError(String),
 }
 
-pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: HostScanSetting) -> ScanResult {
-    let mut result: ScanResult = ScanResult::new();
+/// Send times of the probes for round-trip time
+#[derive(Clone, Debug, Default)]
+pub (crate) struct ProbeTimes {
+    /// Send time by destination. Port is zero for ICMP and UDP ping probes
+    sent: HashMap<SocketAddr, SystemTime>,
+    /// Send time of the multicast or broadcast probe of the discovery scan
+    discovery: Option<SystemTime>,
+}
+
+impl ProbeTimes {
+    pub fn new() -> ProbeTimes {
+        ProbeTimes::default()
+    }
+    /// Record the send time of the probe to the destination
+    pub fn record(&mut self, dst: SocketAddr, time: SystemTime) {
+        self.sent.insert(dst, time);
+    }
+    /// Record the send time of the discovery probe
+    pub fn record_discovery(&mut self, time: SystemTime) {
+        self.discovery = Some(time);
+    }
+    /// Round-trip time of the response from the source received at the time
+    pub fn rtt(&self, src: SocketAddr, received: Option<SystemTime>) -> Option<Duration> {
+        let sent: SystemTime = match self.sent.get(&src) {
+            Some(sent) => *sent,
+            None => match self.sent.get(&SocketAddr::new(src.ip(), 0)) {
+                Some(sent) => *sent,
+                None => self.discovery?,
+            },
+        };
+        received?.duration_since(sent).ok()
+    }
+}
+
+/// Set RTT statistics of the hosts from the samples
+fn set_rtt_stats(hosts: &mut [Host], samples: &HashMap<IpAddr, Vec<Duration>>) {
+    for host in hosts.iter_mut() {
+        if let Some(samples) = samples.get(&host.ip_addr) {
+            host.rtt = RttStats::from_samples(samples);
+        }
+    }
+}
+
+/// Send the finished hosts to the result receiver. Nothing is sent if the receiver is dropped
+pub (crate) fn send_results(result_tx: &Arc<Mutex<Sender<Host>>>, hosts: &[Host]) {
+    match result_tx.lock() {
+        Ok(result_tx) => {
+            for host in hosts {
+                if result_tx.send(host.clone()).is_err() {
+                    break;
+                }
+            }
+        }
+        Err(e) => eprintln!("Failed to lock result sender: {}", e),
+    }
+}
+
+pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: HostScanSetting, probe_times: &ProbeTimes) -> ScanResult {
     let iface: Interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
         Some(iface) => iface,
         None => return ScanResult::error("Interface not found".to_string()),
     };
     let iface_ips: HashSet<IpAddr> = crate::interface::get_local_ips(scan_setting.if_index);
-    for p in packets {
+    parse_hostscan_frames(packets, scan_setting, iface.mac_addr.unwrap_or(MacAddr::zero()), &iface_ips, probe_times)
+}
+
+/// Parse host scan responses to the interface with the MAC and IP addresses
+pub (crate) fn parse_hostscan_frames(packets: Vec<PacketFrame>, scan_setting: HostScanSetting, iface_mac_addr: MacAddr, iface_ips: &HashSet<IpAddr>, probe_times: &ProbeTimes) -> ScanResult {
+    let mut result: ScanResult = ScanResult::new();
+    // Responses without RTT, for skipping duplicates
+    let mut seen: Vec<Host> = vec![];
+    let mut rtt_samples: HashMap<IpAddr, Vec<Duration>> = HashMap::new();
+    for mut p in packets {
         let mac_addr: MacAddr;
         if let Some(ethernet_frame) = &p.ethernet_header {
-            if ethernet_frame.destination != iface.mac_addr.unwrap_or(MacAddr::zero()) {
+            // Router Advertisement is usually sent to all-nodes multicast address
+            let multicast: bool = ethernet_frame.destination.octets()[0] & 0x01 == 0x01;
+            let accept_multicast: bool = matches!(scan_setting.scan_type, HostScanType::RouterSolicitationScan);
+            if ethernet_frame.destination != iface_mac_addr && !(multicast && accept_multicast) {
                 continue;
             }
             mac_addr = ethernet_frame.source;
@@ -208,6 +356,7 @@ pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: Ho
                             status: PortStatus::Open,
                             service_name: String::new(),
                             service_version: String::new(),
+                            rtt: None,
                         };
                         ports.push(port_info);
                     } else if tcp_packet.flags == TcpFlags::RST | TcpFlags::ACK {
@@ -216,6 +365,7 @@ pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: Ho
                             status: PortStatus::Closed,
                             service_name: String::new(),
                             service_version: String::new(),
+                            rtt: None,
                         };
                         ports.push(port_info);
                     } else {
@@ -230,6 +380,24 @@ pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: Ho
                     continue;
                 }
             }
+            HostScanType::Icmpv6MulticastPingScan => {
+                match &p.icmpv6_header {
+                    Some(icmpv6_header) if icmpv6_header.icmpv6_type == Icmpv6Type::EchoReply => {},
+                    _ => continue,
+                }
+            }
+            HostScanType::RouterSolicitationScan => {
+                match &p.icmpv6_header {
+                    Some(icmpv6_header) if icmpv6_header.icmpv6_type == Icmpv6Type::RouterAdvertisement => {},
+                    _ => continue,
+                }
+            }
+            HostScanType::IcmpBroadcastPingScan => {
+                match &p.icmp_header {
+                    Some(icmp_header) if icmp_header.icmp_type == IcmpType::EchoReply => {},
+                    _ => continue,
+                }
+            }
         }
         let host_info: Host = if let Some(ipv4_packet) = &p.ipv4_header {
             Host {
@@ -240,8 +408,13 @@ pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: Ho
                     .unwrap_or(&String::new())
                     .clone(),
                 ports: ports,
-                mac_addr: if iface_ips.contains(&IpAddr::V4(ipv4_packet.source)) {iface.mac_addr.unwrap_or(MacAddr::zero())} else { mac_addr },
+                mac_addr: if iface_ips.contains(&IpAddr::V4(ipv4_packet.source)) {iface_mac_addr} else { mac_addr },
                 ttl: ipv4_packet.ttl,
+                os_family: String::new(),
+                os_confidence: 0,
+                uptime: None,
+                rtt: None,
+                details: HostDetails::default(),
             }
         } else if let Some(ipv6_packet) = &p.ipv6_header {
             Host {
@@ -252,34 +425,63 @@ pub (crate) fn parse_hostscan_result(packets: Vec<PacketFrame>, scan_setting: Ho
                     .unwrap_or(&String::new())
                     .clone(),
                 ports: ports,
-                mac_addr: if iface_ips.contains(&IpAddr::V6(ipv6_packet.source)) {iface.mac_addr.unwrap_or(MacAddr::zero())} else { mac_addr },
+                mac_addr: if iface_ips.contains(&IpAddr::V6(ipv6_packet.source)) {iface_mac_addr} else { mac_addr },
                 ttl: ipv6_packet.hop_limit,
+                os_family: String::new(),
+                os_confidence: 0,
+                uptime: None,
+                rtt: None,
+                details: HostDetails::default(),
             }
         } else {
             continue;
         };
-        if !result.hosts.contains(&host_info) {
+        // Source port of TCP ping responses. ICMP and UDP ping probes are recorded without port
+        let src_port: u16 = match &p.tcp_header {
+            Some(tcp_packet) => tcp_packet.source,
+            None => 0,
+        };
+        // Only the first reply to the probe is measured. Retransmitted replies would be measured from the original send time
+        if !seen.contains(&host_info) {
+            let rtt: Option<Duration> = probe_times.rtt(SocketAddr::new(host_info.ip_addr, src_port), p.timestamp);
+            if let Some(rtt) = rtt {
+                rtt_samples.entry(host_info.ip_addr).or_default().push(rtt);
+            }
+            seen.push(host_info.clone());
+            let mut host_info = host_info;
+            for port in host_info.ports.iter_mut() {
+                port.rtt = rtt;
+            }
             result.hosts.push(host_info);
+            p.rtt = rtt;
             result.fingerprints.push(p.clone());
         }
     }
+    set_rtt_stats(&mut result.hosts, &rtt_samples);
+    result.guess_os();
     return result;
 }
 
-pub (crate) fn parse_portscan_result(packets: Vec<PacketFrame>, scan_setting: PortScanSetting) -> ScanResult {
-    let mut result: ScanResult = ScanResult::new();
-    let mut socket_set: HashSet<SocketAddr> = HashSet::new();
+pub (crate) fn parse_portscan_result(packets: Vec<PacketFrame>, scan_setting: PortScanSetting, probe_times: &ProbeTimes) -> ScanResult {
     let iface: Interface = match crate::interface::get_interface_by_index(scan_setting.if_index) {
         Some(iface) => iface,
         None => return ScanResult::error("Interface not found".to_string()),
     };
-    for p in packets {
+    parse_portscan_frames(packets, scan_setting, iface.mac_addr.unwrap_or(MacAddr::zero()), probe_times)
+}
+
+/// Parse port scan responses to the interface with the MAC address
+pub (crate) fn parse_portscan_frames(packets: Vec<PacketFrame>, scan_setting: PortScanSetting, iface_mac_addr: MacAddr, probe_times: &ProbeTimes) -> ScanResult {
+    let mut result: ScanResult = ScanResult::new();
+    let mut socket_set: HashSet<SocketAddr> = HashSet::new();
+    let mut rtt_samples: HashMap<IpAddr, Vec<Duration>> = HashMap::new();
+    for mut p in packets {
         if p.ipv4_header.is_none() && p.ipv6_header.is_none() {
             continue;
         }
         let mac_addr: MacAddr;
         if let Some(ethernet_frame) = &p.ethernet_header {
-            if ethernet_frame.destination != iface.mac_addr.unwrap_or(MacAddr::zero()) {
+            if ethernet_frame.destination != iface_mac_addr {
                 continue;
             }
             mac_addr = ethernet_frame.source;
@@ -322,13 +524,14 @@ pub (crate) fn parse_portscan_result(packets: Vec<PacketFrame>, scan_setting: Po
         } else {
             0
         };
-        let port_info: Port = if let Some(tcp_packet) = &p.tcp_header {
+        let mut port_info: Port = if let Some(tcp_packet) = &p.tcp_header {
             if tcp_packet.flags == TcpFlags::SYN | TcpFlags::ACK {
                 Port {
                     number: tcp_packet.source,
                     status: PortStatus::Open,
                     service_name: String::new(),
                     service_version: String::new(),
+                    rtt: None,
                 }
             } else if tcp_packet.flags == TcpFlags::RST | TcpFlags::ACK {
                 Port {
@@ -336,6 +539,7 @@ pub (crate) fn parse_portscan_result(packets: Vec<PacketFrame>, scan_setting: Po
                     status: PortStatus::Closed,
                     service_name: String::new(),
                     service_version: String::new(),
+                    rtt: None,
                 }
             } else {
                 continue;
@@ -343,6 +547,11 @@ pub (crate) fn parse_portscan_result(packets: Vec<PacketFrame>, scan_setting: Po
         } else {
             continue;
         };
+        port_info.rtt = probe_times.rtt(SocketAddr::new(ip_addr, port_info.number), p.timestamp);
+        if let Some(rtt) = port_info.rtt {
+            rtt_samples.entry(ip_addr).or_default().push(rtt);
+        }
+        p.rtt = port_info.rtt;
         let mut exists: bool = false;
         for host in result.hosts.iter_mut() {
             if host.ip_addr == ip_addr {
@@ -361,11 +570,108 @@ pub (crate) fn parse_portscan_result(packets: Vec<PacketFrame>, scan_setting: Po
                 ports: vec![port_info.clone()],
                 mac_addr: mac_addr,
                 ttl: ttl,
+                os_family: String::new(),
+                os_confidence: 0,
+                uptime: None,
+                rtt: None,
+                details: HostDetails::default(),
             };
             result.hosts.push(host_info);
         }
         result.fingerprints.push(p.clone());
         socket_set.insert(SocketAddr::new(ip_addr, port_info.number));
     }
+    set_rtt_stats(&mut result.hosts, &rtt_samples);
+    result.guess_os();
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use nex::packet::ip::IpNextLevelProtocol;
+    use nex::packet::ipv4::Ipv4Header;
+    use nex::packet::tcp::TcpHeader;
+
+    /// SYN/ACK from the address and port received at the time
+    fn syn_ack_frame(ip_addr: Ipv4Addr, port: u16, timestamp: SystemTime) -> PacketFrame {
+        let mut frame = PacketFrame::new();
+        frame.ipv4_header = Some(Ipv4Header {
+            version: 4,
+            header_length: 5,
+            dscp: 0,
+            ecn: 0,
+            total_length: 44,
+            identification: 0,
+            flags: 0,
+            fragment_offset: 0,
+            ttl: 64,
+            next_level_protocol: IpNextLevelProtocol::Tcp,
+            checksum: 0,
+            source: ip_addr,
+            destination: Ipv4Addr::new(192, 168, 1, 2),
+            options: vec![],
+        });
+        frame.tcp_header = Some(TcpHeader {
+            source: port,
+            destination: 44322,
+            sequence: 0,
+            acknowledgement: 1,
+            data_offset: 5,
+            reserved: 0,
+            flags: TcpFlags::SYN | TcpFlags::ACK,
+            window: 64240,
+            checksum: 0,
+            urgent_ptr: 0,
+            options: vec![],
+        });
+        frame.timestamp = Some(timestamp);
+        frame
+    }
+
+    #[test]
+    fn retransmitted_reply_not_sampled() {
+        let ip_addr = Ipv4Addr::new(192, 168, 1, 10);
+        let sent = SystemTime::now();
+        let mut probe_times = ProbeTimes::new();
+        probe_times.record(SocketAddr::new(IpAddr::V4(ip_addr), 80), sent);
+        let frames = vec![
+            syn_ack_frame(ip_addr, 80, sent + Duration::from_millis(10)),
+            // SYN/ACK retransmits after 1s and 3s
+            syn_ack_frame(ip_addr, 80, sent + Duration::from_millis(1010)),
+            syn_ack_frame(ip_addr, 80, sent + Duration::from_millis(3010)),
+        ];
+        let scan_setting = HostScanSetting::default().set_scan_type(HostScanType::TcpPingScan);
+        let result = parse_hostscan_frames(frames, scan_setting, MacAddr::zero(), &HashSet::new(), &probe_times);
+        assert_eq!(result.hosts.len(), 1);
+        let rtt = result.hosts[0].rtt.unwrap();
+        assert_eq!(rtt.count, 1);
+        assert_eq!(rtt.max, Duration::from_millis(10));
+        assert_eq!(result.hosts[0].ports[0].rtt, Some(Duration::from_millis(10)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn scan_result_round_trip() {
+        let ip_addr = Ipv4Addr::new(192, 168, 1, 10);
+        let mut host = Host::new(IpAddr::V4(ip_addr), String::from("router")).with_ports(vec![22, 80]);
+        host.ports[0].status = PortStatus::Open;
+        host.ports[1].status = PortStatus::Closed;
+        host.details.smb = Some(Default::default());
+        let mut result = ScanResult::new();
+        result.hosts.push(host);
+        result.scan_time = Duration::from_millis(1520);
+        result.start_time = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        result.scan_status = ScanStatus::Timeout;
+        result.fingerprints.push(syn_ack_frame(ip_addr, 22, SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_001)));
+        let json = serde_json::to_string(&result).unwrap();
+        let decoded: ScanResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.hosts, result.hosts);
+        assert_eq!(decoded.scan_time, result.scan_time);
+        assert_eq!(decoded.start_time, result.start_time);
+        assert_eq!(decoded.scan_status, result.scan_status);
+        // PacketFrame does not implement PartialEq
+        assert_eq!(format!("{:?}", decoded.fingerprints), format!("{:?}", result.fingerprints));
+    }
+}
@@ -1,15 +1,72 @@
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
-use crate::scan::setting::{PortScanSetting, HostScanSetting};
+use crate::scan::setting::{PortScanSetting, HostScanSetting, HostScanType, PassiveScanSetting, ServiceBrowseSetting};
 use crate::host::Host;
+use crate::protocol::Protocol;
+use crate::pcap::dump::{PacketDump, PcapFormat};
 
 use super::async_io;
 use super::blocking;
-use super::result::{ScanResult, ServiceProbeResult};
+use super::idle;
+use super::smb;
+use super::snmp;
+use super::mdns::{self, ServiceBrowseResult};
+use super::offline;
+use super::passive::{self, ObservedHost};
+use super::result::{send_results, ScanResult, ServiceProbeResult};
 use super::setting::ServiceProbeSetting;
 
+/// Create the capture file if set
+fn create_dump(if_index: u32, pcap_file: &Option<PathBuf>, pcap_format: PcapFormat) -> Result<Option<PacketDump>, String> {
+    let pcap_file = match pcap_file {
+        Some(pcap_file) => pcap_file,
+        None => return Ok(None),
+    };
+    let interface = match crate::interface::get_interface_by_index(if_index) {
+        Some(interface) => interface,
+        None => return Err("Interface not found".to_string()),
+    };
+    PacketDump::create(pcap_file, pcap_format, &interface).map(Some)
+}
+
+/// Close the result channel by replacing the sender, so the receiver ends after the last host
+fn close_results(result_tx: &Arc<Mutex<Sender<Host>>>) {
+    match result_tx.lock() {
+        Ok(mut result_tx) => *result_tx = channel().0,
+        Err(e) => eprintln!("Failed to lock result sender: {}", e),
+    }
+}
+
+/// Probe SNMP agents in the scan result and scan the neighbours in their ARP tables with `scan_neighbors`.
+///
+/// Hosts of the scan result are sent first. Neighbours are sent after their own SNMP probes and added to the scan result.
+fn scan_snmp_neighbors<F>(scan_result: &mut ScanResult, communities: &[String], result_tx: &Arc<Mutex<Sender<Host>>>, scan_neighbors: F)
+where
+    F: FnOnce(&[IpAddr]) -> ScanResult,
+{
+    let neighbors: Vec<IpAddr> = snmp::probe_hosts(scan_result, communities);
+    send_results(result_tx, &scan_result.hosts);
+    if neighbors.is_empty() {
+        return;
+    }
+    let mut neighbor_result = scan_neighbors(&neighbors);
+    snmp::probe_hosts(&mut neighbor_result, communities);
+    send_results(result_tx, &neighbor_result.hosts);
+    scan_result.hosts.extend(neighbor_result.hosts);
+}
+
+/// Flush the capture file at the end of the scan
+fn close_dump(dump: Option<PacketDump>) {
+    if let Some(dump) = dump {
+        if let Err(e) = dump.flush() {
+            eprintln!("{}", e);
+        }
+    }
+}
+
 /// Host Scanner
 #[derive(Clone, Debug)]
 pub struct HostScanner {
@@ -19,30 +76,96 @@ pub struct HostScanner {
     pub tx: Arc<Mutex<Sender<Host>>>,
     /// Receiver for progress messaging
     pub rx: Arc<Mutex<Receiver<Host>>>,
+    /// Sender for finished hosts
+    pub result_tx: Arc<Mutex<Sender<Host>>>,
+    /// Receiver for finished hosts
+    pub result_rx: Arc<Mutex<Receiver<Host>>>,
 }
 
 impl HostScanner {
     /// Create new HostScanner
     pub fn new(scan_setting: HostScanSetting) -> Self {
         let (tx, rx) = channel();
+        let (result_tx, result_rx) = channel();
         Self {
             scan_setting,
             tx: Arc::new(Mutex::new(tx)),
             rx: Arc::new(Mutex::new(rx)),
+            result_tx: Arc::new(Mutex::new(result_tx)),
+            result_rx: Arc::new(Mutex::new(result_rx)),
         }
     }
     /// Get progress receiver
     pub fn get_progress_receiver(&self) -> Arc<Mutex<Receiver<Host>>> {
         self.rx.clone()
     }
+    /// Get receiver of the finished hosts.
+    ///
+    /// Each host is sent once, as soon as it is final. Without SMB or SNMP discovery that is when
+    /// the responses are parsed; otherwise after its SMB and SNMP probes, before the SNMP neighbours are scanned.
+    /// The sender is closed when `scan` returns, so the receiver ends after the last host.
+    /// Hosts are streamed only from the first scan of the scanner.
+    pub fn get_result_receiver(&self) -> Arc<Mutex<Receiver<Host>>> {
+        self.result_rx.clone()
+    }
     // Scan hosts
     pub fn scan(&self) -> ScanResult {
-        if self.scan_setting.async_scan {
+        let dump = match create_dump(self.scan_setting.if_index, &self.scan_setting.pcap_file, self.scan_setting.pcap_format) {
+            Ok(dump) => dump,
+            Err(e) => {
+                close_results(&self.result_tx);
+                return ScanResult::error(e);
+            }
+        };
+        let scan_result = self.scan_with_dump(dump.as_ref());
+        close_dump(dump);
+        close_results(&self.result_tx);
+        scan_result
+    }
+    /// Parse the pcap or pcapng capture file of a host scan with the scan setting instead of scanning
+    pub fn scan_pcap(&self, path: &Path) -> ScanResult {
+        match std::fs::read(path) {
+            Ok(capture) => offline::parse_hostscan_capture(&capture, self.scan_setting.clone()),
+            Err(e) => ScanResult::error(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+    fn scan_with_dump(&self, dump: Option<&PacketDump>) -> ScanResult {
+        let setting = &self.scan_setting;
+        // Hosts are final after the scan unless other probes follow
+        let detection = setting.smb_discovery || setting.snmp_discovery;
+        let result_tx = if detection { None } else { Some(&self.result_tx) };
+        // Discovery probes need Ethernet frames to multicast or broadcast MAC address
+        let mut scan_result: ScanResult = if setting.async_scan && !setting.scan_type.is_discovery() {
             let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async_io::scan_hosts(self.scan_setting.clone(), &self.tx))
+            rt.block_on(async_io::scan_hosts(setting.clone(), &self.tx, result_tx, dump))
         } else {
-            blocking::scan_hosts(self.scan_setting.clone(), &self.tx)
+            blocking::scan_hosts(setting.clone(), &self.tx, result_tx, dump)
+        };
+        if setting.smb_discovery {
+            smb::probe_hosts(&mut scan_result);
         }
+        if !setting.snmp_discovery {
+            if detection {
+                send_results(&self.result_tx, &scan_result.hosts);
+            }
+            return scan_result;
+        }
+        scan_snmp_neighbors(&mut scan_result, &setting.snmp_communities, &self.result_tx, |neighbors| {
+            let mut scan_setting = setting.clone();
+            scan_setting.targets = snmp::neighbor_targets(&setting.targets, neighbors);
+            // Neighbours are scanned once, without following their ARP tables
+            scan_setting.snmp_discovery = false;
+            // Discovery probes do not use the targets
+            if scan_setting.scan_type.is_discovery() {
+                scan_setting.scan_type = HostScanType::IcmpPingScan;
+                scan_setting.protocol = Protocol::ICMP;
+            }
+            let mut scanner = HostScanner::new(scan_setting);
+            scanner.tx = self.tx.clone();
+            scanner.rx = self.rx.clone();
+            scanner.scan_with_dump(dump)
+        });
+        scan_result
     }
 }
 
@@ -55,37 +178,111 @@ pub struct PortScanner {
     pub tx: Arc<Mutex<Sender<SocketAddr>>>,
     /// Receiver for progress messaging
     pub rx: Arc<Mutex<Receiver<SocketAddr>>>,
+    /// Sender for finished hosts
+    pub result_tx: Arc<Mutex<Sender<Host>>>,
+    /// Receiver for finished hosts
+    pub result_rx: Arc<Mutex<Receiver<Host>>>,
 }
 
 impl PortScanner {
     /// Create new PortScanner
     pub fn new(scan_setting: PortScanSetting) -> Self {
         let (tx, rx) = channel();
+        let (result_tx, result_rx) = channel();
         Self {
             scan_setting,
             tx: Arc::new(Mutex::new(tx)),
             rx: Arc::new(Mutex::new(rx)),
+            result_tx: Arc::new(Mutex::new(result_tx)),
+            result_rx: Arc::new(Mutex::new(result_rx)),
         }
     }
     /// Get progress receiver
     pub fn get_progress_receiver(&self) -> Arc<Mutex<Receiver<SocketAddr>>> {
         self.rx.clone()
     }
+    /// Get receiver of the finished hosts.
+    ///
+    /// Each host is sent once, as soon as it is final. Without OS, uptime, SMB or SNMP detection that is
+    /// when its ports are done (each host of a TCP connect scan is sent on its own); otherwise after the
+    /// detections, before the SNMP neighbours are scanned.
+    /// The sender is closed when `scan` returns, so the receiver ends after the last host.
+    /// Hosts are streamed only from the first scan of the scanner.
+    pub fn get_result_receiver(&self) -> Arc<Mutex<Receiver<Host>>> {
+        self.result_rx.clone()
+    }
     /// Scan ports
     pub fn scan(&self) -> ScanResult {
-        match self.scan_setting.scan_type {
+        let dump = match create_dump(self.scan_setting.if_index, &self.scan_setting.pcap_file, self.scan_setting.pcap_format) {
+            Ok(dump) => dump,
+            Err(e) => {
+                close_results(&self.result_tx);
+                return ScanResult::error(e);
+            }
+        };
+        let scan_result = self.scan_with_dump(dump.as_ref());
+        close_dump(dump);
+        close_results(&self.result_tx);
+        scan_result
+    }
+    /// Parse the pcap or pcapng capture file of a TCP SYN port scan with the scan setting instead of scanning
+    pub fn scan_pcap(&self, path: &Path) -> ScanResult {
+        match std::fs::read(path) {
+            Ok(capture) => offline::parse_portscan_capture(&capture, self.scan_setting.clone()),
+            Err(e) => ScanResult::error(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+    fn scan_with_dump(&self, dump: Option<&PacketDump>) -> ScanResult {
+        let setting = &self.scan_setting;
+        // Hosts are final after the scan unless other detections follow
+        let detection = setting.os_detection || setting.uptime_detection || setting.smb_discovery || setting.snmp_discovery;
+        let result_tx = if detection { None } else { Some(&self.result_tx) };
+        let mut scan_result: ScanResult = match setting.scan_type {
             crate::scan::setting::PortScanType::TcpSynScan => {
-                if self.scan_setting.async_scan {
+                if setting.async_scan {
                     let rt = tokio::runtime::Runtime::new().unwrap();
-                    rt.block_on(async_io::scan_ports(self.scan_setting.clone(), &self.tx))
+                    rt.block_on(async_io::scan_ports(setting.clone(), &self.tx, result_tx, dump))
                 } else {
-                    blocking::scan_ports(self.scan_setting.clone(), &self.tx)
+                    blocking::scan_ports(setting.clone(), &self.tx, result_tx, dump)
                 }
             }
             crate::scan::setting::PortScanType::TcpConnectScan => {
-                async_io::run_connect_scan(self.scan_setting.clone(), &self.tx)
+                async_io::run_connect_scan(setting.clone(), &self.tx, result_tx)
+            }
+            crate::scan::setting::PortScanType::IdleScan => {
+                let scan_result = idle::scan_ports(setting.clone(), &self.tx, dump);
+                if let Some(result_tx) = result_tx {
+                    send_results(result_tx, &scan_result.hosts);
+                }
+                scan_result
             }
+        };
+        if setting.os_detection {
+            blocking::detect_os(setting, &mut scan_result, dump);
         }
+        if setting.uptime_detection {
+            blocking::detect_uptime(setting, &mut scan_result, dump);
+        }
+        if setting.smb_discovery {
+            smb::probe_hosts(&mut scan_result);
+        }
+        if !setting.snmp_discovery {
+            if detection {
+                send_results(&self.result_tx, &scan_result.hosts);
+            }
+            return scan_result;
+        }
+        scan_snmp_neighbors(&mut scan_result, &setting.snmp_communities, &self.result_tx, |neighbors| {
+            let mut scan_setting = setting.clone();
+            scan_setting.targets = snmp::neighbor_targets(&setting.targets, neighbors);
+            // Neighbours are scanned once, without following their ARP tables
+            scan_setting.snmp_discovery = false;
+            let mut scanner = PortScanner::new(scan_setting);
+            scanner.tx = self.tx.clone();
+            scanner.rx = self.rx.clone();
+            scanner.scan_with_dump(dump)
+        });
+        scan_result
     }
 }
 
@@ -119,4 +316,108 @@ impl ServiceDetector {
         let rt = tokio::runtime::Runtime::new().unwrap();
         rt.block_on(super::service::run_service_probe(&self.setting, &self.tx))
     }
+    /// Run service detection for open ports of all hosts in the scan result.
+    ///
+    /// The setting is used as a template for every host. Detected service name and version are written back into the ports of `scan_result`.
+    pub fn run_with_scan_result(&self, scan_result: &mut ScanResult) -> HashMap<IpAddr, HashMap<u16, ServiceProbeResult>> {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let service_results = rt.block_on(super::service::run_service_probe_hosts(&self.setting, &scan_result.hosts, &self.tx));
+        scan_result.set_service_results(&service_results);
+        service_results
+    }
+}
+
+/// Passive discovery. Listens for ARP, NDP, DHCP, mDNS, LLMNR, NetBIOS, SSDP and LLDP/CDP without sending anything
+#[derive(Clone, Debug)]
+pub struct PassiveScanner {
+    /// Passive scan setting
+    pub scan_setting: PassiveScanSetting,
+}
+
+impl PassiveScanner {
+    /// Create new PassiveScanner
+    pub fn new(scan_setting: PassiveScanSetting) -> Self {
+        Self { scan_setting }
+    }
+    /// Listen for the duration and build scan result of the observed hosts
+    pub fn scan(&self) -> ScanResult {
+        passive::scan(&self.scan_setting)
+    }
+    /// Listen for the duration and return the observed hosts with protocols and announced descriptions
+    pub fn observe(&self) -> Result<Vec<ObservedHost>, String> {
+        passive::observe(&self.scan_setting)
+    }
+}
+
+/// mDNS/DNS-SD service browser
+#[derive(Clone, Debug)]
+pub struct ServiceBrowser {
+    /// Browse setting
+    pub setting: ServiceBrowseSetting,
+}
+
+impl ServiceBrowser {
+    /// Create new ServiceBrowser
+    pub fn new(setting: ServiceBrowseSetting) -> Self {
+        Self { setting }
+    }
+    /// Enumerate service types and resolve PTR, SRV, TXT and address records of each instance
+    pub fn browse(&self) -> ServiceBrowseResult {
+        mdns::browse(&self.setting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+    use nex::packet::ip::IpNextLevelProtocol;
+    use nex::socket::{IpVersion, Socket, SocketOption, SocketType};
+    use crate::scan::setting::PortScanType;
+
+    #[test]
+    fn stream_host_before_scan_completes() {
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let open = TcpListener::bind((localhost, 0)).unwrap();
+        let open_port = open.local_addr().unwrap().port();
+        let stalled_port = TcpListener::bind((localhost, 0)).unwrap().local_addr().unwrap().port();
+        // Listener with a full accept queue drops SYNs, so the connection lasts until the timeout
+        let stalled = Socket::new(SocketOption {
+            ip_version: IpVersion::V4,
+            socket_type: SocketType::Stream,
+            protocol: Some(IpNextLevelProtocol::Tcp),
+            non_blocking: false,
+        })
+        .unwrap();
+        stalled.bind(SocketAddr::new(localhost, stalled_port)).unwrap();
+        stalled.listen(0).unwrap();
+        let _queued: Vec<TcpStream> = (0..2)
+            .filter_map(|_| TcpStream::connect_timeout(&SocketAddr::new(localhost, stalled_port), Duration::from_millis(200)).ok())
+            .collect();
+        let scan_setting = PortScanSetting::default()
+            .add_target(Host::new(localhost, String::from("open")).with_ports(vec![open_port]))
+            .add_target(Host::new(localhost, String::from("stalled")).with_ports(vec![stalled_port]))
+            .set_scan_type(PortScanType::TcpConnectScan)
+            .set_timeout(Duration::from_secs(2));
+        let scanner = PortScanner::new(scan_setting);
+        let result_rx = scanner.get_result_receiver();
+        let scan_handle = {
+            let scanner = scanner.clone();
+            thread::spawn(move || scanner.scan())
+        };
+        let host = result_rx.lock().unwrap().recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(host.hostname, "open");
+        assert_eq!(host.ports[0].number, open_port);
+        assert!(!scan_handle.is_finished());
+        let scan_result = scan_handle.join().unwrap();
+        assert_eq!(scan_result.hosts.len(), 2);
+        // The receiver ends when the scan returns, even though the scanner is alive
+        let hosts: Vec<Host> = result_rx.lock().unwrap().iter().collect();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].hostname, "stalled");
+        assert!(hosts[0].ports.is_empty());
+        drop(scanner);
+    }
 }
@@ -1,19 +1,28 @@
 use std::net::{IpAddr, SocketAddr, TcpStream};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::io::{Read, Write};
 use async_io::{Async, Timer};
 use futures_lite::{AsyncWriteExt, AsyncReadExt};
 use futures_lite::future::FutureExt;
 use futures::stream::{self, StreamExt};
 use nex::socket::tls::socket::rustls;
-use nex::socket::tls::TlsClient;
-use super::payload::{PayloadInfo, PayloadType};
+use nex::socket::tls::socket::pki_types::ServerName;
+use super::http;
+use super::tls::{self, TlsInfo};
+use super::udp_probe;
+use super::matcher::{self, ServiceMatch};
+use super::payload::{PayloadBuilder, PayloadInfo, PayloadType};
 use super::result::{ServiceProbeError, ServiceProbeResult};
 use super::setting::ServiceProbeSetting;
 use std::collections::HashMap;
 use crate::db::tcp_service::PORT_SERVICE_MAP;
+use crate::protocol::Protocol;
+use crate::host::Host;
+
+/// Maximum number of bytes read from a response. Larger than the favicon limit of HTTP fingerprinting.
+const MAX_RESPONSE_SIZE: usize = 2 * 1024 * 1024;
 
 /// Parse HTTP header and return server name
 ///
@@ -37,14 +46,14 @@ fn parse_http_header(res_bytes: &Vec<u8>) -> Option<String> {
     None
 }
 
-/// Read to end and return response as Vec<u8>
+/// Read to end (up to `MAX_RESPONSE_SIZE` bytes) and return response as Vec<u8>
 /// This ignore io::Error on read_to_end because it is expected when reading response.
 /// If no response is received, and io::Error is occurred, return Err.
 async fn read_response_timeout(tcp_stream: &mut Async<TcpStream>, timeout: Duration) -> std::io::Result<Vec<u8>> {
     let mut io_error: std::io::Error =
         std::io::Error::new(std::io::ErrorKind::Other, "No response");
     let mut response: Vec<u8> = Vec::new();
-    match tcp_stream.read_to_end(&mut response).or(async {
+    match tcp_stream.take(MAX_RESPONSE_SIZE as u64).read_to_end(&mut response).or(async {
         Timer::after(timeout).await;
         Err(std::io::ErrorKind::TimedOut.into())
     }).await {
@@ -73,169 +82,283 @@ pub async fn async_tcp_connect_timeout(
     Ok(stream)
 }
 
-async fn probe_port(ip_addr:IpAddr, hostname: String, port: u16, payload_info: Option<PayloadInfo>, timeout: Duration) -> ServiceProbeResult {
-    let service_name: String = match PORT_SERVICE_MAP.get(&port) {
-        Some(name) => name.to_string(),
-        None => String::new(),
+/// Create TLS client config
+pub(crate) fn tls_connect_config(accept_invalid_certs: bool) -> rustls::ClientConfig {
+    let native_certs = nex::socket::tls::certs::get_native_certs().unwrap_or(rustls::RootCertStore::empty());
+    let mut config = rustls::ClientConfig::builder()
+        .with_root_certificates(native_certs)
+        .with_no_client_auth();
+    if accept_invalid_certs {
+        nex::socket::tls::danger::disable_certificate_verification(&mut config, rustls::crypto::aws_lc_rs::default_provider());
+    }
+    config
+}
+
+/// Server name of the TLS session. If the hostname is empty, IP address is used
+pub(crate) fn tls_server_name(ip_addr: IpAddr, hostname: &str) -> std::io::Result<ServerName<'static>> {
+    let server_name: String = if hostname.is_empty() { ip_addr.to_string() } else { hostname.to_string() };
+    ServerName::try_from(server_name).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+/// Start TLS session over the connected TCP stream.
+///
+/// If the hostname is empty, IP address is used as server name.
+pub(crate) fn tls_connect(tcp_stream: Async<TcpStream>, ip_addr: IpAddr, hostname: &str, config: rustls::ClientConfig) -> std::io::Result<rustls::StreamOwned<rustls::ClientConnection, TcpStream>> {
+    let server_name = tls_server_name(ip_addr, hostname)?;
+    let tcp_stream_inner = tcp_stream.into_inner()?;
+    tcp_stream_inner.set_nonblocking(false)?;
+    let tls_connection = rustls::ClientConnection::new(Arc::new(config), server_name)
+        .map_err(std::io::Error::other)?;
+    Ok(rustls::StreamOwned::new(tls_connection, tcp_stream_inner))
+}
+
+/// Read TLS stream until EOF, timeout or `MAX_RESPONSE_SIZE` bytes and return response as Vec<u8>
+/// Like `read_response_timeout`, this ignores io::Error if some response is received.
+fn read_tls_response(tls_stream: &mut rustls::StreamOwned<rustls::ClientConnection, TcpStream>, timeout: Duration) -> std::io::Result<Vec<u8>> {
+    let start_time = Instant::now();
+    let mut response: Vec<u8> = Vec::new();
+    let mut buf: [u8; 4096] = [0; 4096];
+    while response.len() < MAX_RESPONSE_SIZE && start_time.elapsed() < timeout {
+        let max_len: usize = std::cmp::min(buf.len(), MAX_RESPONSE_SIZE - response.len());
+        match tls_stream.read(&mut buf[..max_len]) {
+            Ok(0) => break,
+            Ok(n) => response.extend_from_slice(&buf[..n]),
+            Err(e) => {
+                if response.is_empty() {
+                    return Err(e);
+                }
+                break;
+            }
+        }
+    }
+    if response.is_empty() {
+        return Err(std::io::Error::other("No response"));
+    }
+    Ok(response)
+}
+
+/// Start TLS session over the connected TCP stream, write payload (if not empty) and read response.
+fn fetch_tls_response(tcp_stream: Async<TcpStream>, ip_addr: IpAddr, hostname: &str, payload: &[u8], config: rustls::ClientConfig, timeout: Duration) -> Result<Vec<u8>, ServiceProbeError> {
+    let mut tls_stream = match tls_connect(tcp_stream, ip_addr, hostname, config) {
+        Ok(tls_stream) => tls_stream,
+        Err(e) => return Err(ServiceProbeError::TlsError(e.to_string())),
     };
+    if !payload.is_empty() {
+        match tls_stream.write_all(payload) {
+            Ok(_) => {},
+            Err(e) => return Err(ServiceProbeError::WriteError(e.to_string())),
+        }
+        match tls_stream.flush() {
+            Ok(_) => {},
+            Err(e) => return Err(ServiceProbeError::WriteError(e.to_string())),
+        }
+    }
+    read_tls_response(&mut tls_stream, timeout).map_err(|e| ServiceProbeError::ReadError(e.to_string()))
+}
+
+/// Connect to the port, write payload (if not empty) and read response.
+///
+/// If `tls_config` is set, payload is sent over TLS.
+pub(crate) async fn fetch_response(ip_addr: IpAddr, hostname: &str, port: u16, payload: &[u8], tls_config: Option<rustls::ClientConfig>, setting: &ServiceProbeSetting) -> Result<Vec<u8>, ServiceProbeError> {
     let socket_addr: SocketAddr = SocketAddr::new(ip_addr, port);
-    let mut tcp_stream = async_tcp_connect_timeout(&socket_addr, timeout).await.unwrap();
+    let mut tcp_stream = match async_tcp_connect_timeout(&socket_addr, setting.connect_timeout).await {
+        Ok(tcp_stream) => tcp_stream,
+        Err(e) => return Err(ServiceProbeError::ConnectionError(e.to_string())),
+    };
+    let timeout: Duration = setting.read_timeout;
     match tcp_stream.write_with(|inner| inner.set_read_timeout(Some(timeout))).await {
         Ok(_) => {},
-        Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
+        Err(e) => return Err(ServiceProbeError::ConnectionError(e.to_string())),
     }
-    if let Some(payload) = payload_info {
-        match payload.payload_type {
-            PayloadType::Http => {
-                match tcp_stream.write_all(&payload.payload).await {
-                    Ok(_) => {
-                        match tcp_stream.flush().await {
-                            Ok(_) => {},
-                            Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                        }
-                        match read_response_timeout(&mut tcp_stream, timeout).await {
-                            Ok(res) => {
-                                let mut result = ServiceProbeResult::new(port, service_name, res.clone());
-                                result.service_detail = parse_http_header(&res);
-                                return result;
-                            },
-                            Err(e) => {
-                                return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ReadError(e.to_string()))
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                    }
-                }
-            },
-            PayloadType::Https => {
-                let native_certs = nex::socket::tls::certs::get_native_certs().unwrap();
-                let config = rustls::ClientConfig::builder()
-                    .with_root_certificates(native_certs)
-                    .with_no_client_auth();
-                let tcp_stream_inner = match tcp_stream.into_inner() {
-                    Ok(tcp_stream_inner) => tcp_stream_inner,
-                    Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
-                };
-                match tcp_stream_inner.set_nonblocking(false) {
+    match tls_config {
+        Some(config) => {
+            let hostname: String = hostname.to_string();
+            let payload: Vec<u8> = payload.to_vec();
+            // rustls stream is blocking. Keep it off the async worker threads.
+            match tokio::task::spawn_blocking(move || fetch_tls_response(tcp_stream, ip_addr, &hostname, &payload, config, timeout)).await {
+                Ok(res) => res,
+                Err(e) => Err(ServiceProbeError::TlsError(e.to_string())),
+            }
+        },
+        None => {
+            if !payload.is_empty() {
+                match tcp_stream.write_all(payload).await {
                     Ok(_) => {},
-                    Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
-                }
-                let mut tls_client = match TlsClient::new(hostname.to_string(), tcp_stream_inner, config) {
-                    Ok(tls_client) => tls_client,
-                    Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
-                };
-                match tls_client.write_all(&payload.payload) {
-                    Ok(_) => {
-                        match tls_client.flush() {
-                            Ok(_) => {},
-                            Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                        }
-                        let mut buf: Vec<u8> = Vec::new();
-                        match tls_client.read_to_end(&mut buf) {
-                            Ok(_) => {
-                                let mut result = ServiceProbeResult::new(port, service_name, buf.clone());
-                                result.service_detail = parse_http_header(&buf);
-                                return result;
-                            },
-                            Err(e) => {
-                                return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ReadError(e.to_string()))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                    }
+                    Err(e) => return Err(ServiceProbeError::WriteError(e.to_string())),
                 }
-            },
-            PayloadType::Common => {
-                match tcp_stream.write_all(&payload.payload).await {
-                    Ok(_) => {
-                        match tcp_stream.flush().await {
-                            Ok(_) => {},
-                            Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                        }
-                        match read_response_timeout(&mut tcp_stream, timeout).await {
-                            Ok(res) => {
-                                let mut result = ServiceProbeResult::new(port, service_name, res.clone());
-                                result.service_detail = Some(String::from_utf8(res).unwrap_or(String::new()).replace("\r\n", ""));
-                                return result;
-                            },
-                            Err(e) => {
-                                return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ReadError(e.to_string()))
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                    }
-                }
-            },
-            PayloadType::CommonTls => {
-                let native_certs = nex::socket::tls::certs::get_native_certs().unwrap();
-                let config = rustls::ClientConfig::builder()
-                    .with_root_certificates(native_certs)
-                    .with_no_client_auth();
-                let tcp_stream_inner = match tcp_stream.into_inner() {
-                    Ok(tcp_stream_inner) => tcp_stream_inner,
-                    Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
-                };
-                match tcp_stream_inner.set_nonblocking(false) {
+                match tcp_stream.flush().await {
                     Ok(_) => {},
-                    Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
+                    Err(e) => return Err(ServiceProbeError::WriteError(e.to_string())),
                 }
-                let mut tls_client = match TlsClient::new(hostname.to_string(), tcp_stream_inner, config) {
-                    Ok(tls_client) => tls_client,
-                    Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ConnectionError(e.to_string()))
-                };
-                match tls_client.write_all(&payload.payload) {
-                    Ok(_) => {
-                        match tls_client.flush() {
-                            Ok(_) => {},
-                            Err(e) => return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                        }
-                        let mut buf: Vec<u8> = Vec::new();
-                        match tls_client.read_to_end(&mut buf) {
-                            Ok(_) => {
-                                let mut result = ServiceProbeResult::new(port, service_name, buf.clone());
-                                result.service_detail = Some(String::from_utf8(buf).unwrap_or(String::new()).to_string());
-                                return result;
-                            },
-                            Err(e) => {
-                                return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ReadError(e.to_string()))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::WriteError(e.to_string()))
-                    }
-                }
-            },
-            PayloadType::Null => {
-                match read_response_timeout(&mut tcp_stream, timeout).await {
-                    Ok(res) => {
-                        let mut result = ServiceProbeResult::new(port, service_name, res.clone());                        
-                        result.service_detail = Some(String::from_utf8(res).unwrap_or(String::new()).replace("\r\n", ""));
-                        return result;
-                    },
-                    Err(e) => {
-                        return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ReadError(e.to_string()))
+            }
+            read_response_timeout(&mut tcp_stream, timeout).await.map_err(|e| ServiceProbeError::ReadError(e.to_string()))
+        },
+    }
+}
+
+/// Build probe result from the response of the payload
+async fn build_probe_result(setting: &ServiceProbeSetting, port: u16, service_name: String, payload_info: &PayloadInfo, res: Vec<u8>) -> ServiceProbeResult {
+    let tls: bool = matches!(payload_info.payload_type, PayloadType::Https | PayloadType::CommonTls);
+    let service_match: Option<ServiceMatch> = matcher::match_response(&res);
+    let service_name: String = match &service_match {
+        Some(m) if tls => matcher::tls_service_name(&m.service_name),
+        Some(m) => m.service_name.clone(),
+        None => service_name,
+    };
+    let is_http: bool = match &service_match {
+        Some(m) => m.service_name == "http",
+        None => matches!(payload_info.payload_type, PayloadType::Http | PayloadType::Https),
+    };
+    let mut result = ServiceProbeResult::new(port, service_name, res.clone());
+    match payload_info.payload_type {
+        PayloadType::Http | PayloadType::Https => {
+            result.service_detail = parse_http_header(&res);
+        },
+        PayloadType::CommonTls => {
+            result.service_detail = match &service_match {
+                Some(_) if is_http => parse_http_header(&res),
+                Some(ServiceMatch { detail: Some(detail), .. }) => Some(detail.clone()),
+                _ => Some(String::from_utf8(res.clone()).unwrap_or(String::new())),
+            };
+        },
+        PayloadType::Common | PayloadType::Null => {
+            result.service_detail = match &service_match {
+                Some(_) if is_http => parse_http_header(&res),
+                Some(ServiceMatch { detail: Some(detail), .. }) => Some(detail.clone()),
+                _ => Some(String::from_utf8(res.clone()).unwrap_or(String::new()).replace("\r\n", "")),
+            };
+        },
+    }
+    if is_http {
+        let http_request: bool = matches!(payload_info.payload_type, PayloadType::Http | PayloadType::Https);
+        result.http_info = http::probe_http_info(setting.ip_addr, &setting.hostname, port, tls, http_request, &res, setting).await;
+    }
+    result
+}
+
+/// Outcome of the probe chain
+enum ProbeChainOutcome {
+    /// Response with a confident match
+    Matched(PayloadInfo, Vec<u8>),
+    /// First response without a confident match
+    Unmatched(PayloadInfo, Vec<u8>),
+    /// No response
+    Failed(ServiceProbeError),
+}
+
+/// Try payloads in order and stop at the first confident match.
+///
+/// Certificate verification is skipped for payloads sent over TLS. Validity of the certificate is recorded in `TlsInfo`.
+async fn run_probe_chain(setting: &ServiceProbeSetting, port: u16, payloads: &[PayloadInfo]) -> ProbeChainOutcome {
+    let mut first_response: Option<(PayloadInfo, Vec<u8>)> = None;
+    let mut last_error: Option<ServiceProbeError> = None;
+    for payload_info in payloads {
+        let tls_config = match payload_info.payload_type {
+            PayloadType::Https | PayloadType::CommonTls => Some(tls_connect_config(true)),
+            _ => None,
+        };
+        let res: Vec<u8> = match fetch_response(setting.ip_addr, &setting.hostname, port, &payload_info.payload, tls_config, setting).await {
+            Ok(res) => res,
+            Err(e) => {
+                // Closed or filtered port. The rest of the chain would fail in the same way.
+                if let ServiceProbeError::ConnectionError(_) = e {
+                    if first_response.is_none() {
+                        return ProbeChainOutcome::Failed(e);
                     }
+                    break;
                 }
-            },
-        }
-    }else{
-        match read_response_timeout(&mut tcp_stream, timeout).await {
-            Ok(res) => {
-                let mut result = ServiceProbeResult::new(port, service_name, res.clone());
-                result.service_detail = Some(String::from_utf8(res).unwrap_or(String::new()).replace("\r\n", ""));
-                return result;
-            },
-            Err(e) => {
-                return ServiceProbeResult::with_error(port, service_name, ServiceProbeError::ReadError(e.to_string()))
+                last_error = Some(e);
+                continue;
             }
+        };
+        if matcher::match_response(&res).is_some() {
+            return ProbeChainOutcome::Matched(payload_info.clone(), res);
         }
+        if first_response.is_none() {
+            first_response = Some((payload_info.clone(), res));
+        }
+    }
+    match first_response {
+        Some((payload_info, res)) => ProbeChainOutcome::Unmatched(payload_info, res),
+        None => ProbeChainOutcome::Failed(last_error.unwrap_or(ServiceProbeError::ReadError("No response".to_string()))),
+    }
+}
+
+/// Convert plaintext payload to the one sent inside TLS tunnel
+fn to_tls_payload(payload_info: &PayloadInfo) -> PayloadInfo {
+    let payload_type: PayloadType = match payload_info.payload_type {
+        PayloadType::Http | PayloadType::Https => PayloadType::Https,
+        PayloadType::Null | PayloadType::Common | PayloadType::CommonTls => PayloadType::CommonTls,
+    };
+    PayloadInfo {
+        payload: payload_info.payload.clone(),
+        payload_type,
+    }
+}
+
+/// Retry the probe chain inside TLS tunnel.
+///
+/// Returns None if TLS handshake fails.
+async fn probe_port_tls(setting: &ServiceProbeSetting, port: u16, service_name: &str, payloads: &[PayloadInfo]) -> Option<ServiceProbeResult> {
+    let tls_info: TlsInfo = match tls::probe_tls_info(setting.ip_addr, &setting.hostname, port, setting).await {
+        Ok(tls_info) => tls_info,
+        Err(_) => return None,
+    };
+    // Service name used when the tunneled probes are not identified
+    let tls_service_name: String = match tls_info.alpn_protocol.as_deref().and_then(tls::alpn_service_name) {
+        Some(name) => name.to_string(),
+        None if service_name.is_empty() => String::from("ssl"),
+        None => matcher::tls_service_name(service_name),
+    };
+    let tls_payloads: Vec<PayloadInfo> = payloads.iter().map(to_tls_payload).collect();
+    let mut result = match run_probe_chain(setting, port, &tls_payloads).await {
+        ProbeChainOutcome::Matched(payload_info, res) => build_probe_result(setting, port, tls_service_name, &payload_info, res).await,
+        ProbeChainOutcome::Unmatched(payload_info, res) => build_probe_result(setting, port, tls_service_name, &payload_info, res).await,
+        ProbeChainOutcome::Failed(_) => ServiceProbeResult::new(port, tls_service_name, vec![]),
+    };
+    result.tls_info = Some(tls_info);
+    Some(result)
+}
+
+/// Probe the port with its probe chain.
+///
+/// Payloads are tried in order and the chain stops at the first confident match.
+/// If no payload gets a confident match, the first response is used.
+/// If TLS detection is enabled and the plaintext probes get no response or a TLS alert, the chain is retried inside TLS tunnel.
+async fn probe_port(setting: &ServiceProbeSetting, port: u16) -> ServiceProbeResult {
+    let service_name: String = match PORT_SERVICE_MAP.get(&port) {
+        Some(name) => name.to_string(),
+        None => String::new(),
+    };
+    let payloads: Vec<PayloadInfo> = match setting.payloads(port) {
+        [] => vec![PayloadBuilder::null()],
+        payloads => payloads.to_vec(),
+    };
+    let outcome = run_probe_chain(setting, port, &payloads).await;
+    let has_plaintext: bool = payloads
+        .iter()
+        .any(|p| matches!(p.payload_type, PayloadType::Null | PayloadType::Common | PayloadType::Http));
+    let retry_tls: bool = setting.tls_detection && has_plaintext && match &outcome {
+        ProbeChainOutcome::Matched(_, _) => false,
+        ProbeChainOutcome::Unmatched(_, res) => tls::is_tls_alert(res),
+        ProbeChainOutcome::Failed(ServiceProbeError::ConnectionError(_)) => false,
+        ProbeChainOutcome::Failed(_) => true,
+    };
+    if retry_tls {
+        if let Some(result) = probe_port_tls(setting, port, &service_name, &payloads).await {
+            return result;
+        }
+    }
+    match outcome {
+        ProbeChainOutcome::Matched(payload_info, res) | ProbeChainOutcome::Unmatched(payload_info, res) => {
+            let tls: bool = matches!(payload_info.payload_type, PayloadType::Https | PayloadType::CommonTls);
+            let mut result = build_probe_result(setting, port, service_name, &payload_info, res).await;
+            if tls {
+                result.tls_info = tls::probe_tls_info(setting.ip_addr, &setting.hostname, port, setting).await.ok();
+            }
+            result
+        },
+        ProbeChainOutcome::Failed(e) => ServiceProbeResult::with_error(port, service_name, e),
     }
 }
 
@@ -249,8 +372,10 @@ pub async fn run_service_probe(setting: &ServiceProbeSetting, ptx: &Arc<Mutex<Se
                 Arc::clone(&service_map);
             async move {
                 let ip_addr = setting.ip_addr;
-                let hostname = setting.hostname.clone();
-                let probe_result: ServiceProbeResult = probe_port(ip_addr, hostname, port, setting.payload_map.get(&port).cloned(), setting.read_timeout).await;
+                let probe_result: ServiceProbeResult = match setting.protocol {
+                    Protocol::UDP => udp_probe::probe_udp_port(setting, port).await,
+                    _ => probe_port(setting, port).await,
+                };
                 c_service_map.lock().unwrap().insert(port, probe_result);
                 match ptx.lock() {
                     Ok(lr) => match lr.send(SocketAddr::new(ip_addr, port)) {
@@ -266,3 +391,48 @@ pub async fn run_service_probe(setting: &ServiceProbeSetting, ptx: &Arc<Mutex<Se
     let result_map: HashMap<u16, ServiceProbeResult> = service_map.lock().unwrap().clone();
     result_map
 }
+
+/// Run service detection for open ports of all hosts concurrently.
+///
+/// `setting` is used as a template. Target IP address, hostname and ports are taken from each host.
+pub async fn run_service_probe_hosts(setting: &ServiceProbeSetting, hosts: &[Host], ptx: &Arc<Mutex<Sender<SocketAddr>>>) -> HashMap<IpAddr, HashMap<u16, ServiceProbeResult>> {
+    let mut host_settings: Vec<ServiceProbeSetting> = vec![];
+    for host in hosts {
+        let ports: Vec<u16> = host.get_open_port_numbers();
+        if ports.is_empty() {
+            continue;
+        }
+        let mut host_setting: ServiceProbeSetting = setting.clone();
+        host_setting.ip_addr = host.ip_addr;
+        host_setting.hostname = host.hostname.clone();
+        host_setting.ports = ports;
+        host_settings.push(host_setting);
+    }
+    let targets: Vec<(&ServiceProbeSetting, u16)> = host_settings
+        .iter()
+        .flat_map(|host_setting| host_setting.ports.iter().map(move |port| (host_setting, *port)))
+        .collect();
+    let service_map: Arc<Mutex<HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+    let fut_port = stream::iter(targets).for_each_concurrent(
+        setting.concurrent_limit,
+        |(host_setting, port)| {
+            let c_service_map: Arc<Mutex<HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>>> =
+                Arc::clone(&service_map);
+            async move {
+                let ip_addr = host_setting.ip_addr;
+                let probe_result: ServiceProbeResult = match host_setting.protocol {
+                    Protocol::UDP => udp_probe::probe_udp_port(host_setting, port).await,
+                    _ => probe_port(host_setting, port).await,
+                };
+                c_service_map.lock().unwrap().entry(ip_addr).or_default().insert(port, probe_result);
+                if let Ok(lr) = ptx.lock() {
+                    let _ = lr.send(SocketAddr::new(ip_addr, port));
+                }
+            }
+        },
+    );
+    fut_port.await;
+    let result_map: HashMap<IpAddr, HashMap<u16, ServiceProbeResult>> = service_map.lock().unwrap().clone();
+    result_map
+}
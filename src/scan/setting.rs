@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::time::Duration;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 use rand::seq::SliceRandom;
 use crate::scan::payload::PayloadBuilder;
 use crate::host::Host;
@@ -9,6 +10,11 @@ use crate::protocol::Protocol;
 use crate::config::{DEFAULT_HOSTS_CONCURRENCY, DEFAULT_PORTS_CONCURRENCY};
 
 use super::payload::PayloadInfo;
+use super::snmp;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub use crate::pcap::dump::PcapFormat;
 
 /* /// Scan Type
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -22,6 +28,7 @@ pub enum ScanType {
 
 /// Port Scan Type
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PortScanType {
     /// Default fast port scan type.
     ///
@@ -31,6 +38,12 @@ pub enum PortScanType {
     ///
     /// Slow but can be run without administrator privileges.
     TcpConnectScan,
+    /// Send TCP SYN with the source address spoofed as the zombie host and infer port status
+    /// from the IP ID increments of the zombie.
+    ///
+    /// Requires a zombie host set by `PortScanSetting::set_zombie`. IPv4 only.
+    /// Closed and filtered ports cannot be told apart and are reported as filtered.
+    IdleScan,
 }
 
 impl PortScanType {
@@ -38,6 +51,7 @@ impl PortScanType {
         match scan_type {
             "SYN" | "TCP-SYN" | "TCP_SYN" => PortScanType::TcpSynScan,
             "CONNECT" | "TCP-CONNECT" | "TCP_CONNECT" => PortScanType::TcpConnectScan,
+            "IDLE" | "TCP-IDLE" | "TCP_IDLE" => PortScanType::IdleScan,
             _ => PortScanType::TcpSynScan,
         }
     }
@@ -45,11 +59,13 @@ impl PortScanType {
         match self {
             PortScanType::TcpSynScan => "TCP-SYN",
             PortScanType::TcpConnectScan => "TCP-CONNECT",
+            PortScanType::IdleScan => "TCP-IDLE",
         }
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PortScanSetting {
     pub if_index: u32,
     pub targets: Vec<Host>,
@@ -63,6 +79,24 @@ pub struct PortScanSetting {
     pub minimize_packet: bool,
     pub dns_map: HashMap<IpAddr, String>,
     pub async_scan: bool,
+    /// Run active OS detection after the port scan
+    pub os_detection: bool,
+    /// Estimate uptime from TCP timestamps after the port scan
+    pub uptime_detection: bool,
+    /// Zombie host (IPv4 address and port) for idle scan
+    pub zombie: Option<SocketAddr>,
+    /// Query NetBIOS node status and probe SMB on open port 445 after the port scan
+    pub smb_discovery: bool,
+    /// Probe SNMP agents after the port scan and scan the neighbours in their ARP tables
+    pub snmp_discovery: bool,
+    /// Community strings for SNMP discovery
+    pub snmp_communities: Vec<String>,
+    /// Record sent probes and received frames to the capture file.
+    ///
+    /// TCP connect scan is not recorded
+    pub pcap_file: Option<PathBuf>,
+    /// Format of the capture file
+    pub pcap_format: PcapFormat,
 }
 
 impl Default for PortScanSetting {
@@ -80,6 +114,14 @@ impl Default for PortScanSetting {
             minimize_packet: false,
             dns_map: HashMap::new(),
             async_scan: false,
+            os_detection: false,
+            uptime_detection: false,
+            zombie: None,
+            smb_discovery: false,
+            snmp_discovery: false,
+            snmp_communities: snmp::default_communities(),
+            pcap_file: None,
+            pcap_format: PcapFormat::PcapNg,
         }
     }
 }
@@ -138,6 +180,38 @@ impl PortScanSetting {
         self.async_scan = async_scan;
         self
     }
+    pub fn set_os_detection(mut self, os_detection: bool) -> Self {
+        self.os_detection = os_detection;
+        self
+    }
+    pub fn set_uptime_detection(mut self, uptime_detection: bool) -> Self {
+        self.uptime_detection = uptime_detection;
+        self
+    }
+    pub fn set_zombie(mut self, zombie: SocketAddr) -> Self {
+        self.zombie = Some(zombie);
+        self
+    }
+    pub fn set_smb_discovery(mut self, smb_discovery: bool) -> Self {
+        self.smb_discovery = smb_discovery;
+        self
+    }
+    pub fn set_snmp_discovery(mut self, snmp_discovery: bool) -> Self {
+        self.snmp_discovery = snmp_discovery;
+        self
+    }
+    pub fn set_snmp_communities(mut self, snmp_communities: Vec<String>) -> Self {
+        self.snmp_communities = snmp_communities;
+        self
+    }
+    pub fn set_pcap_file(mut self, pcap_file: PathBuf) -> Self {
+        self.pcap_file = Some(pcap_file);
+        self
+    }
+    pub fn set_pcap_format(mut self, pcap_format: PcapFormat) -> Self {
+        self.pcap_format = pcap_format;
+        self
+    }
     pub fn randomize_hosts(&mut self) {
         let mut rng = rand::thread_rng();
         self.targets.shuffle(&mut rng);
@@ -151,6 +225,7 @@ impl PortScanSetting {
 
 /// Host Scan Type
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum HostScanType {
     /// Default host scan type.
     ///
@@ -163,6 +238,15 @@ pub enum HostScanType {
     /// Send UDP packets to a probably closed port and check response.
     /// This expects ICMP port unreachable message.
     UdpPingScan,
+    /// Send ICMPv6 echo request to all-nodes multicast address (ff02::1) and collect every responder.
+    /// Targets are not needed.
+    Icmpv6MulticastPingScan,
+    /// Send ICMPv6 Router Solicitation to all-routers multicast address (ff02::2) and collect Router Advertisements.
+    /// Targets are not needed.
+    RouterSolicitationScan,
+    /// Send ICMP echo request to the directed broadcast address of the interface network and collect every responder.
+    /// Targets are not needed. Many hosts ignore broadcast echo requests.
+    IcmpBroadcastPingScan,
 }
 
 impl HostScanType {
@@ -171,6 +255,9 @@ impl HostScanType {
             "ICMP" | "ICMP-PING" | "ICMP_PING" => HostScanType::IcmpPingScan,
             "TCP" | "TCP-PING" | "TCP_PING" => HostScanType::TcpPingScan,
             "UDP" | "UDP-PING" | "UDP_PING" => HostScanType::UdpPingScan,
+            "ICMPV6-MULTICAST" | "ICMPV6_MULTICAST" | "ALL-NODES" => HostScanType::Icmpv6MulticastPingScan,
+            "RS" | "ROUTER-SOLICITATION" | "ROUTER_SOLICITATION" => HostScanType::RouterSolicitationScan,
+            "ICMP-BROADCAST" | "ICMP_BROADCAST" => HostScanType::IcmpBroadcastPingScan,
             _ => HostScanType::IcmpPingScan,
         }
     }
@@ -179,11 +266,22 @@ impl HostScanType {
             HostScanType::IcmpPingScan => "ICMP-PING",
             HostScanType::TcpPingScan => "TCP-PING",
             HostScanType::UdpPingScan => "UDP-PING",
+            HostScanType::Icmpv6MulticastPingScan => "ICMPV6-MULTICAST",
+            HostScanType::RouterSolicitationScan => "ROUTER-SOLICITATION",
+            HostScanType::IcmpBroadcastPingScan => "ICMP-BROADCAST",
         }
     }
+    /// Check if the scan type discovers hosts on the segment with a single multicast or broadcast probe
+    pub fn is_discovery(&self) -> bool {
+        matches!(
+            self,
+            HostScanType::Icmpv6MulticastPingScan | HostScanType::RouterSolicitationScan | HostScanType::IcmpBroadcastPingScan
+        )
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HostScanSetting {
     pub if_index: u32,
     pub targets: Vec<Host>,
@@ -197,6 +295,18 @@ pub struct HostScanSetting {
     pub minimize_packet: bool,
     pub dns_map: HashMap<IpAddr, String>,
     pub async_scan: bool,
+    /// Query NetBIOS node status of the found hosts
+    pub smb_discovery: bool,
+    /// Probe SNMP agents of the found hosts and scan the neighbours in their ARP tables
+    pub snmp_discovery: bool,
+    /// Community strings for SNMP discovery
+    pub snmp_communities: Vec<String>,
+    /// Record sent probes and received frames to the capture file.
+    ///
+    /// NetBIOS, SMB and SNMP discovery traffic is not recorded
+    pub pcap_file: Option<PathBuf>,
+    /// Format of the capture file
+    pub pcap_format: PcapFormat,
 }
 
 impl Default for HostScanSetting {
@@ -214,6 +324,11 @@ impl Default for HostScanSetting {
             minimize_packet: false,
             dns_map: HashMap::new(),
             async_scan: false,
+            smb_discovery: false,
+            snmp_discovery: false,
+            snmp_communities: snmp::default_communities(),
+            pcap_file: None,
+            pcap_format: PcapFormat::PcapNg,
         }
     }
 }
@@ -271,6 +386,26 @@ impl HostScanSetting {
         self.async_scan = async_scan;
         self
     }
+    pub fn set_smb_discovery(mut self, smb_discovery: bool) -> Self {
+        self.smb_discovery = smb_discovery;
+        self
+    }
+    pub fn set_snmp_discovery(mut self, snmp_discovery: bool) -> Self {
+        self.snmp_discovery = snmp_discovery;
+        self
+    }
+    pub fn set_snmp_communities(mut self, snmp_communities: Vec<String>) -> Self {
+        self.snmp_communities = snmp_communities;
+        self
+    }
+    pub fn set_pcap_file(mut self, pcap_file: PathBuf) -> Self {
+        self.pcap_file = Some(pcap_file);
+        self
+    }
+    pub fn set_pcap_format(mut self, pcap_format: PcapFormat) -> Self {
+        self.pcap_format = pcap_format;
+        self
+    }
     pub fn randomize_hosts(&mut self) {
         let mut rng = rand::thread_rng();
         self.targets.shuffle(&mut rng);
@@ -282,8 +417,84 @@ impl HostScanSetting {
     }
 }
 
+/// Setting for passive discovery. Nothing is sent
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PassiveScanSetting {
+    pub if_index: u32,
+    /// Listening duration
+    pub duration: Duration,
+    pub promiscuous: bool,
+}
+
+impl Default for PassiveScanSetting {
+    fn default() -> Self {
+        Self {
+            if_index: 0,
+            duration: Duration::from_secs(60),
+            promiscuous: false,
+        }
+    }
+}
+
+impl PassiveScanSetting {
+    pub fn set_if_index(mut self, if_index: u32) -> Self {
+        self.if_index = if_index;
+        self
+    }
+    pub fn set_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+    pub fn set_promiscuous(mut self, promiscuous: bool) -> Self {
+        self.promiscuous = promiscuous;
+        self
+    }
+}
+
+/// Setting for mDNS/DNS-SD service browsing
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ServiceBrowseSetting {
+    pub if_index: u32,
+    /// Service types to browse (e.g. `_ipp._tcp`). If empty, service types are enumerated with `_services._dns-sd._udp.local`
+    pub service_types: Vec<String>,
+    /// Time to wait for responses to each round of queries
+    pub wait_time: Duration,
+}
+
+impl Default for ServiceBrowseSetting {
+    fn default() -> Self {
+        Self {
+            if_index: 0,
+            service_types: Vec::new(),
+            wait_time: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ServiceBrowseSetting {
+    pub fn set_if_index(mut self, if_index: u32) -> Self {
+        self.if_index = if_index;
+        self
+    }
+    pub fn set_service_types(mut self, service_types: Vec<String>) -> Self {
+        self.service_types = service_types;
+        self
+    }
+    pub fn add_service_type(mut self, service_type: String) -> Self {
+        self.service_types.push(service_type);
+        self
+    }
+    pub fn set_wait_time(mut self, wait_time: Duration) -> Self {
+        self.wait_time = wait_time;
+        self
+    }
+}
+
 /// Probe setting for service detection
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ServiceProbeSetting {
     /// Destination IP address
     pub ip_addr: IpAddr,
@@ -291,20 +502,40 @@ pub struct ServiceProbeSetting {
     pub hostname: String,
     /// Target ports for service detection
     pub ports: Vec<u16>,
+    /// Transport protocol of the target ports. TCP or UDP.
+    ///
+    /// For UDP, protocol-specific probes (DNS, NTP, SNMP, SSDP, NetBIOS, mDNS, memcached) are sent to their well-known ports.
+    pub protocol: Protocol,
     /// TCP connect (open) timeout
     pub connect_timeout: Duration,
     /// TCP read timeout
     pub read_timeout: Duration,
-    /// SSL/TLS certificate validation when detecting HTTPS services.  
+    /// Accept invalid certificates of other hosts reached by HTTP redirects or favicon links.
     ///
     /// Default value is false, which means validation is enabled.
+    /// Probes to the target host always skip certificate verification and record the validity in `TlsInfo`.
     pub accept_invalid_certs: bool,
-    /// Payloads for specified ports.
+    /// Ordered probe chains for specified ports.
+    ///
+    /// Payloads are tried in order until one of them gets a confident match.
+    /// For UDP, each payload is sent as a datagram instead of the default probes.
+    pub payload_map: HashMap<u16, Vec<PayloadInfo>>,
+    /// Probe chain for ports not in `payload_map`.
     ///
-    /// If not set, default null probe will be used. (No payload, just open TCP connection and read response)
-    pub payload_map: HashMap<u16, PayloadInfo>,
+    /// Default chain is null probe, generic line and HTTP GET request.
+    pub fallback_payloads: Vec<PayloadInfo>,
     /// Concurrent connection limit for service detection
     pub concurrent_limit: usize,
+    /// Maximum number of HTTP redirects to follow when fingerprinting HTTP services
+    pub max_redirects: usize,
+    /// Follow HTTP redirects and favicon links that point to other hosts.
+    ///
+    /// Default value is false, which means only the target host is contacted. Off-host redirects are still recorded.
+    pub follow_offsite_redirects: bool,
+    /// Retry the probe chain inside TLS tunnel if the plaintext probes get no response or a TLS alert.
+    ///
+    /// Default value is true.
+    pub tls_detection: bool,
 }
 
 impl ServiceProbeSetting {
@@ -314,30 +545,40 @@ impl ServiceProbeSetting {
             ip_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
             hostname: String::new(),
             ports: vec![],
+            protocol: Protocol::TCP,
             connect_timeout: Duration::from_millis(200),
             read_timeout: Duration::from_secs(5),
             accept_invalid_certs: false,
             payload_map: HashMap::new(),
+            fallback_payloads: default_fallback_payloads(),
             concurrent_limit: 10,
+            max_redirects: 5,
+            follow_offsite_redirects: false,
+            tls_detection: true,
         }
     }
     pub fn default(ip_addr: IpAddr, hostname: String, ports: Vec<u16>) -> ServiceProbeSetting {
-        let mut payload_map: HashMap<u16, PayloadInfo> = HashMap::new();
+        let mut payload_map: HashMap<u16, Vec<PayloadInfo>> = HashMap::new();
         let http_head = PayloadBuilder::http_head();
         let https_head = PayloadBuilder::https_head(&hostname);
-        payload_map.insert(80, http_head.clone());
-        payload_map.insert(443, https_head.clone());
-        payload_map.insert(8080, http_head);
-        payload_map.insert(8443, https_head);
+        payload_map.insert(80, vec![http_head.clone()]);
+        payload_map.insert(443, vec![https_head.clone()]);
+        payload_map.insert(8080, vec![http_head]);
+        payload_map.insert(8443, vec![https_head]);
         ServiceProbeSetting {
             ip_addr: ip_addr,
             hostname: hostname,
             ports: ports,
+            protocol: Protocol::TCP,
             connect_timeout: Duration::from_secs(1),
             read_timeout: Duration::from_secs(5),
             accept_invalid_certs: false,
             payload_map: payload_map,
+            fallback_payloads: default_fallback_payloads(),
             concurrent_limit: 10,
+            max_redirects: 5,
+            follow_offsite_redirects: false,
+            tls_detection: true,
         }
     }
     /// Set Destination IP address
@@ -360,6 +601,10 @@ impl ServiceProbeSetting {
     pub fn add_port(&mut self, port: u16) {
         self.ports.push(port);
     }
+    /// Set transport protocol of the target ports
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+    }
     /// Set connect (open) timeout in milliseconds
     pub fn set_connect_timeout_millis(&mut self, connect_timeout_millis: u64) {
         self.connect_timeout = Duration::from_millis(connect_timeout_millis);
@@ -368,4 +613,44 @@ impl ServiceProbeSetting {
     pub fn set_read_timeout_millis(&mut self, read_timeout_millis: u64) {
         self.read_timeout = Duration::from_millis(read_timeout_millis);
     }
+    /// Set maximum number of HTTP redirects to follow
+    pub fn set_max_redirects(&mut self, max_redirects: usize) {
+        self.max_redirects = max_redirects;
+    }
+    /// Enable/Disable following HTTP redirects to other hosts
+    pub fn set_follow_offsite_redirects(&mut self, follow_offsite_redirects: bool) {
+        self.follow_offsite_redirects = follow_offsite_redirects;
+    }
+    /// Enable/Disable automatic TLS detection
+    pub fn set_tls_detection(&mut self, tls_detection: bool) {
+        self.tls_detection = tls_detection;
+    }
+    /// Set probe chain for the port
+    pub fn set_payloads(&mut self, port: u16, payloads: Vec<PayloadInfo>) {
+        self.payload_map.insert(port, payloads);
+    }
+    /// Append payload to the probe chain for the port
+    pub fn add_payload(&mut self, port: u16, payload: PayloadInfo) {
+        self.payload_map.entry(port).or_default().push(payload);
+    }
+    /// Set probe chain for ports not in `payload_map`
+    pub fn set_fallback_payloads(&mut self, payloads: Vec<PayloadInfo>) {
+        self.fallback_payloads = payloads;
+    }
+    /// Get probe chain for the port
+    pub fn payloads(&self, port: u16) -> &[PayloadInfo] {
+        match self.payload_map.get(&port) {
+            Some(payloads) => payloads,
+            None => &self.fallback_payloads,
+        }
+    }
+}
+
+/// Default probe chain for ports without specific payloads
+fn default_fallback_payloads() -> Vec<PayloadInfo> {
+    vec![
+        PayloadBuilder::null(),
+        PayloadBuilder::generic_line(),
+        PayloadBuilder::http_get("/"),
+    ]
 }
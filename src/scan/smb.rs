@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use async_io::{Async, Timer};
+use futures::stream::{self, StreamExt};
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use futures_lite::future::FutureExt;
+use netdev::mac::MacAddr;
+use crate::config::{NETBIOS_WAIT_TIME_MILLIS, SMB_CONCURRENT_LIMIT, SMB_TIMEOUT_MILLIS};
+use crate::host::PortStatus;
+use crate::protocol::{netbios, smb};
+use super::result::ScanResult;
+use super::service::async_tcp_connect_timeout;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const NETBIOS_NS_PORT: u16 = 137;
+const SMB_PORT: u16 = 445;
+/// Upper limit of the SMB message length. NEGOTIATE and SESSION_SETUP responses are a few KB
+const MAX_MESSAGE_LEN: usize = 0x10000;
+/// FILETIME (100ns intervals since 1601-01-01) of the UNIX epoch
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+/// SMB dialect
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SmbDialect {
+    /// SMB1 (NT LM 0.12)
+    Smb1,
+    Smb202,
+    Smb21,
+    Smb30,
+    Smb302,
+    Smb311,
+}
+
+impl SmbDialect {
+    /// SMB2 dialects in ascending order
+    pub fn smb2_dialects() -> Vec<SmbDialect> {
+        vec![
+            SmbDialect::Smb202,
+            SmbDialect::Smb21,
+            SmbDialect::Smb30,
+            SmbDialect::Smb302,
+            SmbDialect::Smb311,
+        ]
+    }
+    /// SMB2 dialect revision number. None for SMB1
+    pub fn revision(&self) -> Option<u16> {
+        match *self {
+            SmbDialect::Smb1 => None,
+            SmbDialect::Smb202 => Some(0x0202),
+            SmbDialect::Smb21 => Some(0x0210),
+            SmbDialect::Smb30 => Some(0x0300),
+            SmbDialect::Smb302 => Some(0x0302),
+            SmbDialect::Smb311 => Some(0x0311),
+        }
+    }
+    pub fn from_revision(revision: u16) -> Option<SmbDialect> {
+        SmbDialect::smb2_dialects()
+            .into_iter()
+            .find(|dialect| dialect.revision() == Some(revision))
+    }
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SmbDialect::Smb1 => "NT LM 0.12",
+            SmbDialect::Smb202 => "2.0.2",
+            SmbDialect::Smb21 => "2.1",
+            SmbDialect::Smb30 => "3.0",
+            SmbDialect::Smb302 => "3.0.2",
+            SmbDialect::Smb311 => "3.1.1",
+        }
+    }
+}
+
+/// OS version reported in NTLMSSP CHALLENGE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NtlmVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub build: u16,
+}
+
+impl fmt::Display for NtlmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Windows {}.{} Build {}", self.major, self.minor, self.build)
+    }
+}
+
+/// NetBIOS name registered on the host
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NetbiosName {
+    pub name: String,
+    /// Suffix (type) of the name (e.g. 0x00 workstation, 0x20 file server, 0x1c domain controllers)
+    pub suffix: u8,
+    /// Group name
+    pub group: bool,
+}
+
+/// NetBIOS node status of the host
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NetbiosInfo {
+    /// Workstation name
+    pub hostname: Option<String>,
+    /// Domain or workgroup name
+    pub workgroup: Option<String>,
+    /// All registered names
+    pub names: Vec<NetbiosName>,
+    /// MAC address reported by the host. Zero for Samba
+    pub mac_addr: MacAddr,
+}
+
+/// SMB negotiate and NTLM challenge information of the host
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SmbInfo {
+    /// Supported dialects in ascending order
+    pub dialects: Vec<SmbDialect>,
+    /// Message signing is enabled
+    pub signing_enabled: bool,
+    /// Message signing is required
+    pub signing_required: bool,
+    /// OS version
+    pub os_version: Option<NtlmVersion>,
+    pub netbios_computer_name: Option<String>,
+    /// NetBIOS domain or workgroup name
+    pub netbios_domain_name: Option<String>,
+    pub dns_computer_name: Option<String>,
+    pub dns_domain_name: Option<String>,
+    /// DNS forest name
+    pub dns_tree_name: Option<String>,
+    /// Current time of the server
+    pub server_time: Option<SystemTime>,
+}
+
+impl SmbInfo {
+    /// Host name of the server. DNS name is preferred
+    pub fn hostname(&self) -> Option<String> {
+        self.dns_computer_name.clone().or_else(|| self.netbios_computer_name.clone())
+    }
+}
+
+fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    if filetime <= FILETIME_UNIX_EPOCH {
+        return None;
+    }
+    let nanos = (filetime - FILETIME_UNIX_EPOCH).checked_mul(100)?;
+    UNIX_EPOCH.checked_add(Duration::from_nanos(nanos))
+}
+
+impl NetbiosInfo {
+    fn from_node_status(node_status: netbios::NodeStatus) -> NetbiosInfo {
+        NetbiosInfo {
+            hostname: node_status.hostname(),
+            workgroup: node_status.workgroup(),
+            names: node_status
+                .names
+                .into_iter()
+                .map(|entry| NetbiosName {
+                    name: entry.name,
+                    suffix: entry.suffix,
+                    group: entry.group,
+                })
+                .collect(),
+            mac_addr: node_status.mac_addr,
+        }
+    }
+}
+
+/// Send NetBIOS name query (NBSTAT) to the hosts and collect node status for the wait time. IPv4 only
+pub fn query_netbios(ip_addrs: &[IpAddr], wait_time: Duration) -> HashMap<IpAddr, NetbiosInfo> {
+    let mut results: HashMap<IpAddr, NetbiosInfo> = HashMap::new();
+    let socket = match UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Failed to bind socket: {}", e);
+            return results;
+        }
+    };
+    if let Err(e) = socket.set_read_timeout(Some(Duration::from_millis(100))) {
+        eprintln!("Failed to set read timeout: {}", e);
+        return results;
+    }
+    // Transaction ID of the request sent to each host
+    let mut transaction_ids: HashMap<IpAddr, u16> = HashMap::new();
+    for (i, ip_addr) in ip_addrs.iter().enumerate() {
+        if !ip_addr.is_ipv4() {
+            continue;
+        }
+        let request = netbios::build_nbstat_request(i as u16);
+        match socket.send_to(&request, SocketAddr::new(*ip_addr, NETBIOS_NS_PORT)) {
+            Ok(_) => {
+                transaction_ids.insert(*ip_addr, i as u16);
+            }
+            Err(e) => eprintln!("Failed to send NBSTAT request: {}", e),
+        }
+    }
+    let sent: usize = transaction_ids.len();
+    if sent == 0 {
+        return results;
+    }
+    let mut buf: Vec<u8> = vec![0; 2048];
+    let start_time = Instant::now();
+    while start_time.elapsed() < wait_time && results.len() < sent {
+        match socket.recv_from(&mut buf) {
+            Ok((len, src_addr)) => {
+                // Reply to our request to the host
+                match (transaction_ids.get(&src_addr.ip()), netbios::transaction_id(&buf[..len])) {
+                    (Some(expected), Some(id)) if *expected == id => {}
+                    _ => continue,
+                }
+                if let Some(node_status) = netbios::parse_nbstat_response(&buf[..len]) {
+                    results.insert(src_addr.ip(), NetbiosInfo::from_node_status(node_status));
+                }
+            }
+            Err(_) => continue,
+        }
+    }
+    results
+}
+
+async fn connect(addr: SocketAddr, timeout: Duration) -> Result<Async<TcpStream>, String> {
+    async_tcp_connect_timeout(&addr, timeout).await.map_err(|e| format!("Failed to connect: {}", e))
+}
+
+/// Send the request and read one SMB message
+async fn exchange(stream: &mut Async<TcpStream>, request: &[u8], timeout: Duration) -> Result<Vec<u8>, String> {
+    async {
+        stream.write_all(request).await.map_err(|e| format!("Failed to send request: {}", e))?;
+        let mut header: [u8; 4] = [0; 4];
+        stream.read_exact(&mut header).await.map_err(|e| format!("Failed to read response: {}", e))?;
+        let len = match smb::session_message_len(&header) {
+            Some(len) if len <= MAX_MESSAGE_LEN => len,
+            Some(len) => return Err(format!("SMB message too large: {} bytes", len)),
+            None => return Err("Invalid NetBIOS session header".to_string()),
+        };
+        let mut message: Vec<u8> = vec![0; len];
+        stream.read_exact(&mut message).await.map_err(|e| format!("Failed to read response: {}", e))?;
+        Ok(message)
+    }
+    .or(async {
+        Timer::after(timeout).await;
+        Err("Timed out".to_string())
+    })
+    .await
+}
+
+/// Check if the server accepts the dialect on a new connection
+async fn supports_dialect(addr: SocketAddr, timeout: Duration, dialect: SmbDialect) -> bool {
+    let mut stream = match connect(addr, timeout).await {
+        Ok(stream) => stream,
+        Err(_) => return false,
+    };
+    match dialect.revision() {
+        Some(revision) => match exchange(&mut stream, &smb::build_smb2_negotiate_request(&[revision]), timeout).await {
+            Ok(response) => smb::parse_smb2_negotiate_response(&response).map(|r| r.dialect) == Some(revision),
+            Err(_) => false,
+        },
+        None => match exchange(&mut stream, &smb::build_smb1_negotiate_request(), timeout).await {
+            Ok(response) => smb::parse_smb1_negotiate_response(&response),
+            Err(_) => false,
+        },
+    }
+}
+
+/// Probe SMB server with NEGOTIATE and anonymous NTLMSSP SESSION_SETUP.
+///
+/// No credentials are sent. Each dialect below the negotiated one is checked concurrently on a separate connection.
+pub async fn probe_smb(addr: SocketAddr, timeout: Duration) -> Result<SmbInfo, String> {
+    let mut info = SmbInfo::default();
+    let revisions: Vec<u16> = SmbDialect::smb2_dialects().iter().filter_map(|d| d.revision()).collect();
+    let mut stream = connect(addr, timeout).await?;
+    let negotiate = match exchange(&mut stream, &smb::build_smb2_negotiate_request(&revisions), timeout).await {
+        Ok(response) => smb::parse_smb2_negotiate_response(&response),
+        Err(_) => None,
+    };
+    let negotiate = match negotiate {
+        Some(negotiate) => negotiate,
+        None => {
+            // SMB1 only server
+            if supports_dialect(addr, timeout, SmbDialect::Smb1).await {
+                info.dialects.push(SmbDialect::Smb1);
+                return Ok(info);
+            }
+            return Err("SMB negotiation failed".to_string());
+        }
+    };
+    info.signing_enabled = negotiate.security_mode & smb::SMB2_SIGNING_ENABLED != 0;
+    info.signing_required = negotiate.security_mode & smb::SMB2_SIGNING_REQUIRED != 0;
+    info.server_time = filetime_to_system_time(negotiate.system_time);
+    let request = smb::build_smb2_session_setup_request(1, &smb::build_spnego_init(&smb::build_ntlm_negotiate()));
+    if let Ok(response) = exchange(&mut stream, &request, timeout).await {
+        if smb::is_session_setup_challenge(&response) {
+            if let Some(challenge) = smb::parse_ntlm_challenge(&response) {
+                info.os_version = challenge.version.map(|(major, minor, build)| NtlmVersion { major, minor, build });
+                info.netbios_computer_name = challenge.netbios_computer_name;
+                info.netbios_domain_name = challenge.netbios_domain_name.or(challenge.target_name);
+                info.dns_computer_name = challenge.dns_computer_name;
+                info.dns_domain_name = challenge.dns_domain_name;
+                info.dns_tree_name = challenge.dns_tree_name;
+                if info.server_time.is_none() {
+                    info.server_time = challenge.timestamp.and_then(filetime_to_system_time);
+                }
+            }
+        }
+    }
+    drop(stream);
+    // The server selects the highest common dialect, so only the lower ones need to be checked
+    let mut candidates: Vec<SmbDialect> = vec![SmbDialect::Smb1];
+    for dialect in SmbDialect::smb2_dialects() {
+        match dialect.revision() {
+            Some(revision) if revision == negotiate.dialect => info.dialects.push(dialect),
+            Some(revision) if revision < negotiate.dialect => candidates.push(dialect),
+            _ => {}
+        }
+    }
+    let supported = futures::future::join_all(candidates.iter().map(|dialect| supports_dialect(addr, timeout, *dialect))).await;
+    for (dialect, supported) in candidates.into_iter().zip(supported) {
+        if supported {
+            info.dialects.push(dialect);
+        }
+    }
+    info.dialects.sort();
+    Ok(info)
+}
+
+/// Query NetBIOS node status of all hosts and probe SMB concurrently on hosts with port 445 open.
+///
+/// Results are set to `details` of each host. Empty host names are filled with the NetBIOS or SMB server name.
+pub fn probe_hosts(scan_result: &mut ScanResult) {
+    let ip_addrs: Vec<IpAddr> = scan_result.hosts.iter().map(|host| host.ip_addr).collect();
+    let mut netbios_results = query_netbios(&ip_addrs, Duration::from_millis(NETBIOS_WAIT_TIME_MILLIS));
+    for host in &mut scan_result.hosts {
+        if let Some(netbios_info) = netbios_results.remove(&host.ip_addr) {
+            if host.hostname.is_empty() {
+                host.hostname = netbios_info.hostname.clone().unwrap_or_default();
+            }
+            host.details.netbios = Some(netbios_info);
+        }
+    }
+    let targets: Vec<IpAddr> = scan_result
+        .hosts
+        .iter()
+        .filter(|host| host.ports.iter().any(|port| port.number == SMB_PORT && port.status == PortStatus::Open))
+        .map(|host| host.ip_addr)
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut smb_results: HashMap<IpAddr, Result<SmbInfo, String>> = rt.block_on(
+        stream::iter(targets)
+            .map(|ip_addr| async move {
+                (ip_addr, probe_smb(SocketAddr::new(ip_addr, SMB_PORT), Duration::from_millis(SMB_TIMEOUT_MILLIS)).await)
+            })
+            .buffer_unordered(SMB_CONCURRENT_LIMIT)
+            .collect(),
+    );
+    for host in &mut scan_result.hosts {
+        match smb_results.remove(&host.ip_addr) {
+            Some(Ok(smb_info)) => {
+                if host.hostname.is_empty() {
+                    host.hostname = smb_info.hostname().unwrap_or_default();
+                }
+                host.details.smb = Some(smb_info);
+            }
+            Some(Err(e)) => {
+                eprintln!("SMB probe failed for {}: {}", host.ip_addr, e);
+            }
+            None => {}
+        }
+    }
+}
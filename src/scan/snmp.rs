@@ -0,0 +1,576 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use netdev::mac::MacAddr;
+use crate::config::{SNMP_MAX_WALK_ENTRIES, SNMP_RETRIES, SNMP_TIMEOUT_MILLIS, SNMP_WAIT_TIME_MILLIS};
+use crate::host::Host;
+use crate::protocol::snmp::{self, SnmpMessage, SnmpValue};
+use super::result::ScanResult;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub use crate::protocol::snmp::SnmpVersion;
+
+const SNMP_PORT: u16 = 161;
+const SYS_DESCR_OID: &str = "1.3.6.1.2.1.1.1.0";
+const SYS_OBJECT_ID_OID: &str = "1.3.6.1.2.1.1.2.0";
+const SYS_UPTIME_OID: &str = "1.3.6.1.2.1.1.3.0";
+const SYS_CONTACT_OID: &str = "1.3.6.1.2.1.1.4.0";
+const SYS_NAME_OID: &str = "1.3.6.1.2.1.1.5.0";
+const SYS_LOCATION_OID: &str = "1.3.6.1.2.1.1.6.0";
+/// ifTable columns, indexed by ifIndex
+const IF_DESCR_OID: &str = "1.3.6.1.2.1.2.2.1.2";
+const IF_TYPE_OID: &str = "1.3.6.1.2.1.2.2.1.3";
+const IF_MTU_OID: &str = "1.3.6.1.2.1.2.2.1.4";
+const IF_SPEED_OID: &str = "1.3.6.1.2.1.2.2.1.5";
+const IF_PHYS_ADDRESS_OID: &str = "1.3.6.1.2.1.2.2.1.6";
+const IF_ADMIN_STATUS_OID: &str = "1.3.6.1.2.1.2.2.1.7";
+const IF_OPER_STATUS_OID: &str = "1.3.6.1.2.1.2.2.1.8";
+/// ipAdEntIfIndex, indexed by IPv4 address
+const IP_AD_ENT_IF_INDEX_OID: &str = "1.3.6.1.2.1.4.20.1.2";
+/// ipNetToMediaPhysAddress, indexed by ifIndex and IPv4 address
+const IP_NET_TO_MEDIA_PHYS_ADDRESS_OID: &str = "1.3.6.1.2.1.4.22.1.2";
+/// ifAdminStatus and ifOperStatus value of up
+const IF_STATUS_UP: i64 = 1;
+
+/// Default community strings for probing
+pub fn default_communities() -> Vec<String> {
+    vec![String::from("public"), String::from("private")]
+}
+
+/// Network interface from the ifTable
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnmpInterface {
+    /// ifIndex
+    pub index: u32,
+    /// ifDescr (e.g. `GigabitEthernet0/1`)
+    pub description: String,
+    /// IANAifType (e.g. 6 for ethernetCsmacd)
+    pub if_type: u32,
+    pub mtu: u32,
+    /// Speed in bits per second
+    pub speed: u32,
+    pub mac_addr: MacAddr,
+    /// Addresses from the ipAddrTable
+    pub ip_addrs: Vec<Ipv4Addr>,
+    pub admin_up: bool,
+    pub oper_up: bool,
+}
+
+/// Entry of the ipNetToMediaTable (ARP table)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnmpArpEntry {
+    /// ifIndex of the interface the neighbour was learned on
+    pub if_index: u32,
+    pub ip_addr: Ipv4Addr,
+    pub mac_addr: MacAddr,
+}
+
+/// SNMP agent information
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnmpInfo {
+    /// Version the agent answered to
+    pub version: SnmpVersion,
+    /// Accepted community string
+    pub community: String,
+    pub sys_descr: Option<String>,
+    /// sysObjectID as dotted string (e.g. `1.3.6.1.4.1.9.1.1208`)
+    pub sys_object_id: Option<String>,
+    pub sys_uptime: Option<Duration>,
+    pub sys_contact: Option<String>,
+    pub sys_name: Option<String>,
+    pub sys_location: Option<String>,
+    pub interfaces: Vec<SnmpInterface>,
+    pub arp_table: Vec<SnmpArpEntry>,
+}
+
+impl SnmpInfo {
+    fn new(version: SnmpVersion, community: &str) -> SnmpInfo {
+        SnmpInfo {
+            version,
+            community: community.to_string(),
+            sys_descr: None,
+            sys_object_id: None,
+            sys_uptime: None,
+            sys_contact: None,
+            sys_name: None,
+            sys_location: None,
+            interfaces: vec![],
+            arp_table: vec![],
+        }
+    }
+    /// Unicast addresses in the ARP table that are not assigned to the agent itself
+    pub fn neighbor_addrs(&self) -> Vec<IpAddr> {
+        let own_addrs: HashSet<Ipv4Addr> = self
+            .interfaces
+            .iter()
+            .flat_map(|interface| interface.ip_addrs.iter().copied())
+            .collect();
+        let mut seen: HashSet<Ipv4Addr> = HashSet::new();
+        let mut neighbors: Vec<IpAddr> = vec![];
+        for entry in &self.arp_table {
+            if own_addrs.contains(&entry.ip_addr)
+                || entry.ip_addr.is_unspecified()
+                || entry.ip_addr.is_multicast()
+                || entry.ip_addr.is_broadcast()
+                || entry.mac_addr == MacAddr::zero()
+                || entry.mac_addr == MacAddr::broadcast()
+            {
+                continue;
+            }
+            if seen.insert(entry.ip_addr) {
+                neighbors.push(IpAddr::V4(entry.ip_addr));
+            }
+        }
+        neighbors
+    }
+}
+
+fn bind_socket(ip_addr: &IpAddr) -> std::io::Result<UdpSocket> {
+    let src_ip: IpAddr = match ip_addr {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    UdpSocket::bind(SocketAddr::new(src_ip, 0))
+}
+
+/// Send GetRequest for sysDescr.0 with each community and version, and collect the agents that respond
+fn discover(ip_addrs: &[IpAddr], communities: &[String], wait_time: Duration, results: &mut HashMap<IpAddr, (SnmpVersion, String)>) {
+    let socket = match ip_addrs.first().map(bind_socket) {
+        Some(Ok(socket)) => socket,
+        Some(Err(e)) => {
+            eprintln!("Failed to bind socket: {}", e);
+            return;
+        }
+        None => return,
+    };
+    if let Err(e) = socket.set_read_timeout(Some(Duration::from_millis(100))) {
+        eprintln!("Failed to set read timeout: {}", e);
+        return;
+    }
+    let versions: [SnmpVersion; 2] = [SnmpVersion::V1, SnmpVersion::V2c];
+    let oids: Vec<Vec<u32>> = vec![snmp::parse_oid(SYS_DESCR_OID)];
+    // Request ID identifies the host, community and version of the request
+    let mut sent: usize = 0;
+    for (i, ip_addr) in ip_addrs.iter().enumerate() {
+        for (j, community) in communities.iter().enumerate() {
+            for (k, version) in versions.iter().enumerate() {
+                let request_id = ((i * communities.len() + j) * versions.len() + k) as i32;
+                let request = snmp::build_get_request(*version, community, request_id, &oids);
+                match socket.send_to(&request, SocketAddr::new(*ip_addr, SNMP_PORT)) {
+                    Ok(_) => sent += 1,
+                    Err(e) => eprintln!("Failed to send SNMP request: {}", e),
+                }
+            }
+        }
+    }
+    if sent == 0 {
+        return;
+    }
+    let mut buf: Vec<u8> = vec![0; 65535];
+    let start_time = Instant::now();
+    while start_time.elapsed() < wait_time {
+        match socket.recv_from(&mut buf) {
+            Ok((len, src_addr)) => {
+                let message = match snmp::parse_message(&buf[..len]) {
+                    Some(message) if message.pdu_type == snmp::PDU_GET_RESPONSE && message.request_id >= 0 => message,
+                    _ => continue,
+                };
+                let request_id = message.request_id as usize;
+                let i = request_id / versions.len() / communities.len();
+                let j = request_id / versions.len() % communities.len();
+                if ip_addrs.get(i) != Some(&src_addr.ip()) {
+                    continue;
+                }
+                // Prefer v2c for GetNext exceptions instead of noSuchName errors
+                match results.get(&src_addr.ip()) {
+                    Some((SnmpVersion::V2c, _)) => {}
+                    Some(_) if message.version == SnmpVersion::V1 => {}
+                    _ => {
+                        results.insert(src_addr.ip(), (message.version, communities[j].clone()));
+                    }
+                }
+            }
+            Err(_) => continue,
+        }
+    }
+}
+
+/// Find the version and community string each host answers to on UDP/161.
+///
+/// All requests are sent at once and responses are collected for the wait time.
+pub fn find_communities(ip_addrs: &[IpAddr], communities: &[String], wait_time: Duration) -> HashMap<IpAddr, (SnmpVersion, String)> {
+    let mut results: HashMap<IpAddr, (SnmpVersion, String)> = HashMap::new();
+    if communities.is_empty() {
+        return results;
+    }
+    let (ipv4_addrs, ipv6_addrs): (Vec<IpAddr>, Vec<IpAddr>) = ip_addrs.iter().partition(|ip_addr| ip_addr.is_ipv4());
+    discover(&ipv4_addrs, communities, wait_time, &mut results);
+    discover(&ipv6_addrs, communities, wait_time, &mut results);
+    results
+}
+
+/// SNMP session with a single agent
+struct Agent {
+    socket: UdpSocket,
+    version: SnmpVersion,
+    community: String,
+    request_id: i32,
+    buf: Vec<u8>,
+}
+
+impl Agent {
+    fn connect(ip_addr: IpAddr, version: SnmpVersion, community: &str, timeout: Duration) -> Result<Agent, String> {
+        let socket = bind_socket(&ip_addr).map_err(|e| format!("Failed to bind socket: {}", e))?;
+        socket
+            .connect(SocketAddr::new(ip_addr, SNMP_PORT))
+            .map_err(|e| format!("Failed to connect: {}", e))?;
+        socket.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+        Ok(Agent {
+            socket,
+            version,
+            community: community.to_string(),
+            request_id: (rand::random::<u32>() >> 1) as i32,
+            buf: vec![0; 65535],
+        })
+    }
+    /// Send the request and wait for the matching response. Retransmitted on timeout
+    fn request(&mut self, pdu_type: u8, oids: &[Vec<u32>]) -> Option<SnmpMessage> {
+        for _ in 0..=SNMP_RETRIES {
+            self.request_id = self.request_id.wrapping_add(1) & i32::MAX;
+            let request = snmp::build_request(self.version, &self.community, pdu_type, self.request_id, oids);
+            if self.socket.send(&request).is_err() {
+                return None;
+            }
+            // Skip late responses of the previous requests
+            while let Ok(len) = self.socket.recv(&mut self.buf) {
+                match snmp::parse_message(&self.buf[..len]) {
+                    Some(message) if message.pdu_type == snmp::PDU_GET_RESPONSE && message.request_id == self.request_id => {
+                        return Some(message);
+                    }
+                    _ => continue,
+                }
+            }
+        }
+        None
+    }
+    /// Get the values of the OIDs. Missing objects are omitted
+    fn get(&mut self, oids: &[Vec<u32>]) -> Vec<(Vec<u32>, SnmpValue)> {
+        let message = match self.request(snmp::PDU_GET_REQUEST, oids) {
+            Some(message) => message,
+            None => return vec![],
+        };
+        if message.error_status == 0 {
+            return message.varbinds.into_iter().filter(|(_, value)| !value.is_exception()).collect();
+        }
+        // SNMPv1 fails the whole request with noSuchName if one of the objects is missing
+        if oids.len() == 1 {
+            return vec![];
+        }
+        oids.iter().flat_map(|oid| self.get(std::slice::from_ref(oid))).collect()
+    }
+    /// Walk the subtree with GetNextRequest
+    fn walk(&mut self, root: &[u32]) -> Vec<(Vec<u32>, SnmpValue)> {
+        let mut results: Vec<(Vec<u32>, SnmpValue)> = vec![];
+        let mut oid: Vec<u32> = root.to_vec();
+        while results.len() < SNMP_MAX_WALK_ENTRIES {
+            let message = match self.request(snmp::PDU_GET_NEXT_REQUEST, &[oid.clone()]) {
+                Some(message) => message,
+                None => break,
+            };
+            let (next_oid, value) = match next_walk_entry(root, &oid, message) {
+                Some(entry) => entry,
+                None => break,
+            };
+            oid = next_oid.clone();
+            results.push((next_oid, value));
+        }
+        results
+    }
+    /// Walk the table column and return values by the index (OID suffix)
+    fn walk_column(&mut self, column: &str) -> Vec<(Vec<u32>, SnmpValue)> {
+        let root: Vec<u32> = snmp::parse_oid(column);
+        self.walk(&root)
+            .into_iter()
+            .map(|(oid, value)| (oid[root.len()..].to_vec(), value))
+            .collect()
+    }
+}
+
+/// Next entry of the walk in the GetNextResponse. None at the end of the subtree
+fn next_walk_entry(root: &[u32], oid: &[u32], message: SnmpMessage) -> Option<(Vec<u32>, SnmpValue)> {
+    // noSuchName at the end of the MIB view in SNMPv1
+    if message.error_status != 0 {
+        return None;
+    }
+    let (next_oid, value) = message.varbinds.into_iter().next()?;
+    // Stop at the end of the subtree. OIDs must increase to avoid loops on broken agents
+    if value.is_exception() || !next_oid.starts_with(root) || next_oid.as_slice() <= oid {
+        return None;
+    }
+    Some((next_oid, value))
+}
+
+fn value_to_string(value: &SnmpValue) -> Option<String> {
+    let s = value.to_string_lossy().trim().to_string();
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+fn value_to_u32(value: &SnmpValue) -> Option<u32> {
+    match value {
+        SnmpValue::Integer(v) => u32::try_from(*v).ok(),
+        SnmpValue::Counter32(v) | SnmpValue::Gauge32(v) | SnmpValue::TimeTicks(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn value_to_mac_addr(value: &SnmpValue) -> Option<MacAddr> {
+    match value {
+        SnmpValue::OctetString(v) if v.len() == 6 => Some(MacAddr::new(v[0], v[1], v[2], v[3], v[4], v[5])),
+        _ => None,
+    }
+}
+
+fn index_to_ipv4(index: &[u32]) -> Option<Ipv4Addr> {
+    if index.len() != 4 || index.iter().any(|n| *n > 255) {
+        return None;
+    }
+    Some(Ipv4Addr::new(index[0] as u8, index[1] as u8, index[2] as u8, index[3] as u8))
+}
+
+/// Walk the interfaces and the ipAddrTable
+fn walk_interfaces(agent: &mut Agent) -> Vec<SnmpInterface> {
+    let mut interfaces: BTreeMap<u32, SnmpInterface> = BTreeMap::new();
+    for (index, value) in agent.walk_column(IF_DESCR_OID) {
+        if let Some(if_index) = index.first() {
+            interfaces.insert(*if_index, SnmpInterface {
+                index: *if_index,
+                description: value.to_string_lossy(),
+                ..Default::default()
+            });
+        }
+    }
+    if interfaces.is_empty() {
+        return vec![];
+    }
+    let columns: [&str; 6] = [
+        IF_TYPE_OID,
+        IF_MTU_OID,
+        IF_SPEED_OID,
+        IF_PHYS_ADDRESS_OID,
+        IF_ADMIN_STATUS_OID,
+        IF_OPER_STATUS_OID,
+    ];
+    for column in columns {
+        for (index, value) in agent.walk_column(column) {
+            let interface = match index.first().and_then(|if_index| interfaces.get_mut(if_index)) {
+                Some(interface) => interface,
+                None => continue,
+            };
+            match column {
+                IF_TYPE_OID => interface.if_type = value_to_u32(&value).unwrap_or(0),
+                IF_MTU_OID => interface.mtu = value_to_u32(&value).unwrap_or(0),
+                IF_SPEED_OID => interface.speed = value_to_u32(&value).unwrap_or(0),
+                IF_PHYS_ADDRESS_OID => interface.mac_addr = value_to_mac_addr(&value).unwrap_or(MacAddr::zero()),
+                IF_ADMIN_STATUS_OID => interface.admin_up = value == SnmpValue::Integer(IF_STATUS_UP),
+                _ => interface.oper_up = value == SnmpValue::Integer(IF_STATUS_UP),
+            }
+        }
+    }
+    for (index, value) in agent.walk_column(IP_AD_ENT_IF_INDEX_OID) {
+        let ip_addr = match index_to_ipv4(&index) {
+            Some(ip_addr) => ip_addr,
+            None => continue,
+        };
+        if let Some(interface) = value_to_u32(&value).and_then(|if_index| interfaces.get_mut(&if_index)) {
+            interface.ip_addrs.push(ip_addr);
+        }
+    }
+    interfaces.into_values().collect()
+}
+
+/// Walk the ipNetToMediaTable
+fn walk_arp_table(agent: &mut Agent) -> Vec<SnmpArpEntry> {
+    let mut arp_table: Vec<SnmpArpEntry> = vec![];
+    for (index, value) in agent.walk_column(IP_NET_TO_MEDIA_PHYS_ADDRESS_OID) {
+        if index.len() != 5 {
+            continue;
+        }
+        let ip_addr = match index_to_ipv4(&index[1..]) {
+            Some(ip_addr) => ip_addr,
+            None => continue,
+        };
+        if let Some(mac_addr) = value_to_mac_addr(&value) {
+            arp_table.push(SnmpArpEntry {
+                if_index: index[0],
+                ip_addr,
+                mac_addr,
+            });
+        }
+    }
+    arp_table
+}
+
+/// Get the system group and walk the interfaces and the ARP table of the agent
+pub fn probe_snmp(ip_addr: IpAddr, version: SnmpVersion, community: &str, timeout: Duration) -> Result<SnmpInfo, String> {
+    let mut agent = Agent::connect(ip_addr, version, community, timeout)?;
+    let oids: Vec<Vec<u32>> = [
+        SYS_DESCR_OID,
+        SYS_OBJECT_ID_OID,
+        SYS_UPTIME_OID,
+        SYS_CONTACT_OID,
+        SYS_NAME_OID,
+        SYS_LOCATION_OID,
+    ]
+    .iter()
+    .map(|oid| snmp::parse_oid(oid))
+    .collect();
+    let system = agent.get(&oids);
+    if system.is_empty() {
+        return Err("No response from the agent".to_string());
+    }
+    let mut info = SnmpInfo::new(version, community);
+    for (oid, value) in system {
+        match snmp::format_oid(&oid).as_str() {
+            SYS_DESCR_OID => info.sys_descr = value_to_string(&value),
+            SYS_OBJECT_ID_OID => info.sys_object_id = value_to_string(&value),
+            SYS_UPTIME_OID => {
+                // TimeTicks are hundredths of a second
+                info.sys_uptime = value_to_u32(&value).map(|ticks| Duration::from_millis(ticks as u64 * 10));
+            }
+            SYS_CONTACT_OID => info.sys_contact = value_to_string(&value),
+            SYS_NAME_OID => info.sys_name = value_to_string(&value),
+            SYS_LOCATION_OID => info.sys_location = value_to_string(&value),
+            _ => {}
+        }
+    }
+    info.interfaces = walk_interfaces(&mut agent);
+    info.arp_table = walk_arp_table(&mut agent);
+    Ok(info)
+}
+
+/// Probe the community strings on all hosts and walk the agents that respond.
+///
+/// Results are set to `details` of each host and empty host names are filled with sysName.
+/// Returns the neighbour addresses from the ARP tables that are not in the scan result.
+pub fn probe_hosts(scan_result: &mut ScanResult, communities: &[String]) -> Vec<IpAddr> {
+    let ip_addrs: Vec<IpAddr> = scan_result.hosts.iter().map(|host| host.ip_addr).collect();
+    let agents = find_communities(&ip_addrs, communities, Duration::from_millis(SNMP_WAIT_TIME_MILLIS));
+    // Scanned hosts and the neighbours found so far
+    let mut seen: HashSet<IpAddr> = ip_addrs.iter().copied().collect();
+    let mut neighbors: Vec<IpAddr> = vec![];
+    for host in &mut scan_result.hosts {
+        let (version, community) = match agents.get(&host.ip_addr) {
+            Some(agent) => agent,
+            None => continue,
+        };
+        match probe_snmp(host.ip_addr, *version, community, Duration::from_millis(SNMP_TIMEOUT_MILLIS)) {
+            Ok(snmp_info) => {
+                if host.hostname.is_empty() {
+                    host.hostname = snmp_info.sys_name.clone().unwrap_or_default();
+                }
+                for ip_addr in snmp_info.neighbor_addrs() {
+                    if seen.insert(ip_addr) {
+                        neighbors.push(ip_addr);
+                    }
+                }
+                host.details.snmp = Some(snmp_info);
+            }
+            Err(e) => {
+                eprintln!("SNMP probe failed for {}: {}", host.ip_addr, e);
+            }
+        }
+    }
+    neighbors
+}
+
+/// New targets for the neighbour addresses with the ports of the first target
+pub(crate) fn neighbor_targets(targets: &[Host], ip_addrs: &[IpAddr]) -> Vec<Host> {
+    let ports: Vec<u16> = match targets.first() {
+        Some(target) => target.ports.iter().map(|port| port.number).collect(),
+        None => vec![],
+    };
+    ip_addrs
+        .iter()
+        .map(|ip_addr| Host::new(*ip_addr, String::new()).with_ports(ports.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(error_status: i64, varbinds: Vec<(Vec<u32>, SnmpValue)>) -> SnmpMessage {
+        SnmpMessage {
+            version: SnmpVersion::V2c,
+            community: b"public".to_vec(),
+            pdu_type: snmp::PDU_GET_RESPONSE,
+            request_id: 1,
+            error_status,
+            error_index: 0,
+            varbinds,
+        }
+    }
+
+    #[test]
+    fn parse_ipv4_index() {
+        assert_eq!(index_to_ipv4(&[192, 168, 1, 10]), Some(Ipv4Addr::new(192, 168, 1, 10)));
+        assert_eq!(index_to_ipv4(&[192, 168, 1]), None);
+        assert_eq!(index_to_ipv4(&[192, 168, 1, 10, 0]), None);
+        assert_eq!(index_to_ipv4(&[192, 168, 256, 10]), None);
+    }
+
+    #[test]
+    fn walk_stops_at_end_of_subtree() {
+        let root: Vec<u32> = snmp::parse_oid(IF_DESCR_OID);
+        let oid: Vec<u32> = [root.as_slice(), &[1]].concat();
+        let next: Vec<u32> = [root.as_slice(), &[2]].concat();
+        let value = SnmpValue::OctetString(b"eth0".to_vec());
+        assert_eq!(
+            next_walk_entry(&root, &oid, response(0, vec![(next.clone(), value.clone())])),
+            Some((next.clone(), value.clone()))
+        );
+        // Next column
+        let outside: Vec<u32> = snmp::parse_oid(IF_TYPE_OID).into_iter().chain([1]).collect();
+        assert_eq!(next_walk_entry(&root, &oid, response(0, vec![(outside, SnmpValue::Integer(6))])), None);
+        // OID not increasing
+        assert_eq!(next_walk_entry(&root, &next, response(0, vec![(oid.clone(), value.clone())])), None);
+        assert_eq!(next_walk_entry(&root, &oid, response(0, vec![(oid.clone(), value.clone())])), None);
+        // End of the MIB view in SNMPv2c and SNMPv1
+        assert_eq!(next_walk_entry(&root, &oid, response(0, vec![(next.clone(), SnmpValue::EndOfMibView)])), None);
+        assert_eq!(next_walk_entry(&root, &oid, response(2, vec![(next, value)])), None);
+        assert_eq!(next_walk_entry(&root, &oid, response(0, vec![])), None);
+    }
+
+    #[test]
+    fn neighbor_addrs_skip_own_and_duplicate_entries() {
+        let mut info = SnmpInfo::new(SnmpVersion::V2c, "public");
+        info.interfaces.push(SnmpInterface {
+            index: 1,
+            ip_addrs: vec![Ipv4Addr::new(10, 0, 0, 1)],
+            ..Default::default()
+        });
+        let mac_addr = MacAddr::new(0x00, 0x11, 0x22, 0x33, 0x44, 0x55);
+        for (ip_addr, mac_addr) in [
+            (Ipv4Addr::new(10, 0, 0, 1), mac_addr),
+            (Ipv4Addr::new(10, 0, 0, 2), mac_addr),
+            (Ipv4Addr::new(10, 0, 0, 2), mac_addr),
+            (Ipv4Addr::new(10, 0, 0, 3), MacAddr::zero()),
+            (Ipv4Addr::new(10, 0, 0, 255), MacAddr::broadcast()),
+            (Ipv4Addr::new(10, 0, 0, 4), mac_addr),
+        ] {
+            info.arp_table.push(SnmpArpEntry { if_index: 1, ip_addr, mac_addr });
+        }
+        assert_eq!(
+            info.neighbor_addrs(),
+            vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 4))]
+        );
+    }
+}
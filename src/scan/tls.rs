@@ -0,0 +1,126 @@
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use async_io::Async;
+use nex::socket::tls::socket::rustls;
+use nex::socket::tls::socket::rustls::client::danger::ServerCertVerifier;
+use nex::socket::tls::socket::rustls::client::WebPkiServerVerifier;
+use nex::socket::tls::socket::pki_types::{CertificateDer, ServerName, UnixTime};
+use super::result::ServiceProbeError;
+use super::service::{async_tcp_connect_timeout, tls_connect, tls_connect_config, tls_server_name};
+use super::setting::ServiceProbeSetting;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// ALPN protocols offered when probing TLS
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+/// TLS session information
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TlsInfo {
+    /// Negotiated protocol version (e.g. TLSv1_3)
+    pub version: String,
+    /// Negotiated cipher suite (e.g. TLS13_AES_256_GCM_SHA384)
+    pub cipher_suite: String,
+    /// Negotiated ALPN protocol
+    pub alpn_protocol: Option<String>,
+    /// Certificate chain is trusted by the native root certificates and valid for the server name
+    pub certificate_valid: bool,
+    /// Reason the certificate was not accepted (e.g. self-signed, expired, name mismatch)
+    pub certificate_error: Option<String>,
+}
+
+/// Check if the response is a TLS alert record
+pub(crate) fn is_tls_alert(res: &[u8]) -> bool {
+    res.len() >= 5 && res[0] == 0x15 && res[1] == 0x03
+}
+
+/// Service name guessed from the negotiated ALPN protocol
+pub(crate) fn alpn_service_name(alpn_protocol: &str) -> Option<&'static str> {
+    match alpn_protocol {
+        "h2" => Some("http2"),
+        "http/1.1" | "http/1.0" => Some("https"),
+        _ => None,
+    }
+}
+
+/// Verify the certificate chain of the server against the native root certificates
+fn verify_certificate(certs: Option<&[CertificateDer<'_>]>, server_name: &ServerName<'_>) -> Result<(), String> {
+    let (end_entity, intermediates) = match certs {
+        Some([end_entity, intermediates @ ..]) => (end_entity, intermediates),
+        _ => return Err("No certificate".to_string()),
+    };
+    let roots = nex::socket::tls::certs::get_native_certs().unwrap_or(rustls::RootCertStore::empty());
+    let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::new(rustls::crypto::aws_lc_rs::default_provider()))
+        .build()
+        .map_err(|e| e.to_string())?;
+    verifier
+        .verify_server_cert(end_entity, intermediates, server_name, &[], UnixTime::now())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Connect to the port and complete TLS handshake.
+///
+/// Certificate verification is skipped so that self-signed and internal CA services are detected.
+/// Validity of the certificate is checked after the handshake and recorded in the returned session information.
+pub(crate) async fn probe_tls_info(ip_addr: IpAddr, hostname: &str, port: u16, setting: &ServiceProbeSetting) -> Result<TlsInfo, ServiceProbeError> {
+    let socket_addr: SocketAddr = SocketAddr::new(ip_addr, port);
+    let tcp_stream = match async_tcp_connect_timeout(&socket_addr, setting.connect_timeout).await {
+        Ok(tcp_stream) => tcp_stream,
+        Err(e) => return Err(ServiceProbeError::ConnectionError(e.to_string())),
+    };
+    let timeout: Duration = setting.read_timeout;
+    match tcp_stream.write_with(|inner| inner.set_read_timeout(Some(timeout))).await {
+        Ok(_) => {},
+        Err(e) => return Err(ServiceProbeError::ConnectionError(e.to_string())),
+    }
+    let hostname: String = hostname.to_string();
+    // rustls stream is blocking. Keep it off the async worker threads.
+    match tokio::task::spawn_blocking(move || handshake(tcp_stream, ip_addr, &hostname)).await {
+        Ok(tls_info) => tls_info,
+        Err(e) => Err(ServiceProbeError::TlsError(e.to_string())),
+    }
+}
+
+/// Complete TLS handshake over the connected TCP stream and check the certificate
+fn handshake(tcp_stream: Async<TcpStream>, ip_addr: IpAddr, hostname: &str) -> Result<TlsInfo, ServiceProbeError> {
+    let server_name = tls_server_name(ip_addr, hostname).map_err(|e| ServiceProbeError::TlsError(e.to_string()))?;
+    let mut config = tls_connect_config(true);
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    let mut tls_stream = match tls_connect(tcp_stream, ip_addr, hostname, config) {
+        Ok(tls_stream) => tls_stream,
+        Err(e) => return Err(ServiceProbeError::TlsError(e.to_string())),
+    };
+    while tls_stream.conn.is_handshaking() {
+        match tls_stream.conn.complete_io(&mut tls_stream.sock) {
+            Ok((0, 0)) => return Err(ServiceProbeError::TlsError("Connection closed during handshake".to_string())),
+            Ok(_) => {},
+            Err(e) => return Err(ServiceProbeError::TlsError(e.to_string())),
+        }
+    }
+    let version: String = match tls_stream.conn.protocol_version() {
+        Some(version) => format!("{:?}", version),
+        None => String::new(),
+    };
+    let cipher_suite: String = match tls_stream.conn.negotiated_cipher_suite() {
+        Some(suite) => format!("{:?}", suite.suite()),
+        None => String::new(),
+    };
+    let alpn_protocol: Option<String> = tls_stream
+        .conn
+        .alpn_protocol()
+        .map(|p| String::from_utf8_lossy(p).to_string());
+    let certificate_error: Option<String> = verify_certificate(tls_stream.conn.peer_certificates(), &server_name).err();
+    tls_stream.conn.send_close_notify();
+    let _ = tls_stream.conn.complete_io(&mut tls_stream.sock);
+    let _ = tls_stream.sock.shutdown(std::net::Shutdown::Both);
+    Ok(TlsInfo {
+        version,
+        cipher_suite,
+        alpn_protocol,
+        certificate_valid: certificate_error.is_none(),
+        certificate_error,
+    })
+}
@@ -0,0 +1,355 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+use async_io::{Async, Timer};
+use futures_lite::future::FutureExt;
+use hickory_resolver::proto::rr::{DNSClass, RData, RecordType};
+use crate::protocol::{dns, netbios, ntp, snmp};
+use super::http::{get_header, parse_http_response};
+use super::result::{ServiceProbeError, ServiceProbeResult};
+use super::setting::ServiceProbeSetting;
+
+/// OID of sysDescr.0
+pub(crate) const SYS_DESCR_OID: &str = "1.3.6.1.2.1.1.1.0";
+
+/// Protocol-specific UDP probes for service detection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UdpProbe {
+    /// DNS CHAOS TXT query for version.bind
+    DnsVersionBind,
+    /// NTP mode 6 READVAR request
+    NtpReadVar,
+    /// NTP mode 7 MON_GETLIST_1 (monlist) request
+    NtpMonlist,
+    /// SNMP v1 GetRequest for sysDescr with community "public"
+    SnmpV1,
+    /// SNMP v2c GetRequest for sysDescr with community "public"
+    SnmpV2c,
+    /// SSDP M-SEARCH request
+    Ssdp,
+    /// NetBIOS node status (NBSTAT) request
+    NetbiosNbstat,
+    /// mDNS query for DNS-SD service types
+    Mdns,
+    /// memcached stats command
+    MemcachedStats,
+}
+
+impl UdpProbe {
+    /// All UDP probes
+    pub fn all() -> Vec<UdpProbe> {
+        vec![
+            UdpProbe::DnsVersionBind,
+            UdpProbe::NtpReadVar,
+            UdpProbe::NtpMonlist,
+            UdpProbe::SnmpV1,
+            UdpProbe::SnmpV2c,
+            UdpProbe::Ssdp,
+            UdpProbe::NetbiosNbstat,
+            UdpProbe::Mdns,
+            UdpProbe::MemcachedStats,
+        ]
+    }
+    /// Default probes for the port
+    pub fn for_port(port: u16) -> Vec<UdpProbe> {
+        UdpProbe::all()
+            .into_iter()
+            .filter(|probe| probe.default_port() == port)
+            .collect()
+    }
+    /// Well-known port of the service
+    pub fn default_port(&self) -> u16 {
+        match self {
+            UdpProbe::DnsVersionBind => 53,
+            UdpProbe::NtpReadVar | UdpProbe::NtpMonlist => 123,
+            UdpProbe::SnmpV1 | UdpProbe::SnmpV2c => 161,
+            UdpProbe::Ssdp => 1900,
+            UdpProbe::NetbiosNbstat => 137,
+            UdpProbe::Mdns => 5353,
+            UdpProbe::MemcachedStats => 11211,
+        }
+    }
+    /// Service name
+    pub fn service_name(&self) -> &'static str {
+        match self {
+            UdpProbe::DnsVersionBind => "domain",
+            UdpProbe::NtpReadVar | UdpProbe::NtpMonlist => "ntp",
+            UdpProbe::SnmpV1 | UdpProbe::SnmpV2c => "snmp",
+            UdpProbe::Ssdp => "ssdp",
+            UdpProbe::NetbiosNbstat => "netbios-ns",
+            UdpProbe::Mdns => "mdns",
+            UdpProbe::MemcachedStats => "memcache",
+        }
+    }
+    /// Build probe payload
+    pub fn payload(&self) -> Vec<u8> {
+        match self {
+            UdpProbe::DnsVersionBind => dns::build_version_bind_query(rand::random()),
+            UdpProbe::NtpReadVar => ntp::build_readvar_request(1),
+            UdpProbe::NtpMonlist => ntp::build_monlist_request(),
+            UdpProbe::SnmpV1 => snmp::build_get_request(snmp::SnmpVersion::V1, "public", (rand::random::<u32>() >> 1) as i32, &[snmp::parse_oid(SYS_DESCR_OID)]),
+            UdpProbe::SnmpV2c => snmp::build_get_request(snmp::SnmpVersion::V2c, "public", (rand::random::<u32>() >> 1) as i32, &[snmp::parse_oid(SYS_DESCR_OID)]),
+            UdpProbe::Ssdp => "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n".as_bytes().to_vec(),
+            UdpProbe::NetbiosNbstat => netbios::build_nbstat_request(rand::random()),
+            UdpProbe::Mdns => dns::build_query(0, "_services._dns-sd._udp.local.", RecordType::PTR, DNSClass::IN, false),
+            UdpProbe::MemcachedStats => {
+                // UDP frame header: Request ID, Sequence number, Total datagrams, Reserved
+                let mut payload: Vec<u8> = vec![0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00];
+                payload.extend_from_slice(b"stats\r\n");
+                payload
+            }
+        }
+    }
+    /// Parse reply to this probe. Returns None if the reply does not match.
+    pub fn parse(&self, res: &[u8]) -> Option<Option<String>> {
+        match self {
+            UdpProbe::DnsVersionBind => parse_dns_version(res),
+            UdpProbe::NtpReadVar => parse_ntp_readvar(res),
+            UdpProbe::NtpMonlist => parse_ntp_monlist(res),
+            UdpProbe::SnmpV1 | UdpProbe::SnmpV2c => parse_snmp(res),
+            UdpProbe::Ssdp => parse_ssdp(res),
+            UdpProbe::NetbiosNbstat => parse_nbstat(res),
+            UdpProbe::Mdns => parse_mdns(res),
+            UdpProbe::MemcachedStats => parse_memcached(res),
+        }
+    }
+}
+
+fn parse_dns_version(res: &[u8]) -> Option<Option<String>> {
+    let message = dns::parse_response(res)?;
+    let version: Vec<String> = message
+        .answers()
+        .iter()
+        .flat_map(dns::txt_strings)
+        .collect();
+    if version.is_empty() {
+        Some(None)
+    } else {
+        Some(Some(version.join(" ")))
+    }
+}
+
+fn parse_ntp_readvar(res: &[u8]) -> Option<Option<String>> {
+    let variables = ntp::parse_readvar_response(res)?;
+    let mut details: Vec<String> = vec![];
+    for (name, value) in variables {
+        if name == "version" || name == "system" {
+            details.push(value);
+        }
+    }
+    if details.is_empty() {
+        Some(None)
+    } else {
+        Some(Some(details.join(", ")))
+    }
+}
+
+fn parse_ntp_monlist(res: &[u8]) -> Option<Option<String>> {
+    let (err, items) = ntp::parse_monlist_response(res)?;
+    if err == 0 {
+        Some(Some(format!("monlist enabled ({} entries)", items)))
+    } else {
+        Some(None)
+    }
+}
+
+fn parse_snmp(res: &[u8]) -> Option<Option<String>> {
+    let message = snmp::parse_message(res)?;
+    if message.pdu_type != snmp::PDU_GET_RESPONSE {
+        return None;
+    }
+    let descr = message
+        .varbinds
+        .iter()
+        .find(|(_, value)| !value.is_exception())
+        .map(|(_, value)| value.to_string_lossy());
+    Some(descr)
+}
+
+fn parse_ssdp(res: &[u8]) -> Option<Option<String>> {
+    let response = parse_http_response(res)?;
+    let mut details: Vec<String> = vec![];
+    if let Some(server) = get_header(&response.headers, "SERVER") {
+        details.push(server.to_string());
+    }
+    if let Some(location) = get_header(&response.headers, "LOCATION") {
+        details.push(location.to_string());
+    }
+    if details.is_empty() {
+        Some(None)
+    } else {
+        Some(Some(details.join(", ")))
+    }
+}
+
+fn parse_nbstat(res: &[u8]) -> Option<Option<String>> {
+    let status = netbios::parse_nbstat_response(res)?;
+    let mut details: Vec<String> = vec![];
+    if let Some(hostname) = status.hostname() {
+        details.push(hostname);
+    }
+    if let Some(workgroup) = status.workgroup() {
+        details.push(format!("workgroup: {}", workgroup));
+    }
+    details.push(format!("MAC: {}", status.mac_addr));
+    Some(Some(details.join(", ")))
+}
+
+fn parse_mdns(res: &[u8]) -> Option<Option<String>> {
+    let message = dns::parse_response(res)?;
+    let services: Vec<String> = message
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data() {
+            Some(RData::PTR(ptr)) => Some(dns::name_to_string(&ptr.0)),
+            _ => None,
+        })
+        .collect();
+    if services.is_empty() {
+        Some(None)
+    } else {
+        Some(Some(services.join(", ")))
+    }
+}
+
+fn parse_memcached(res: &[u8]) -> Option<Option<String>> {
+    if res.len() < 8 {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&res[8..]);
+    if !text.starts_with("STAT ") {
+        return None;
+    }
+    let version = text
+        .lines()
+        .find_map(|line| line.strip_prefix("STAT version "))
+        .map(|version| format!("memcached {}", version.trim()));
+    Some(version)
+}
+
+/// Identify the service from the reply by trying all probe parsers
+fn identify(res: &[u8]) -> Option<(UdpProbe, Option<String>)> {
+    for probe in UdpProbe::all() {
+        if let Some(detail) = probe.parse(res) {
+            return Some((probe, detail));
+        }
+    }
+    None
+}
+
+/// Send datagram from a new socket and wait for the reply from the destination.
+///
+/// Each probe uses its own socket so that a late reply to the previous probe is not taken as the reply to this one.
+async fn send_and_receive(dst: SocketAddr, payload: &[u8], timeout: Duration) -> std::io::Result<Vec<u8>> {
+    let bind_addr: SocketAddr = match dst.ip() {
+        IpAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        IpAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    let socket = Async::<UdpSocket>::bind(bind_addr)?;
+    socket.send_to(payload, dst).await?;
+    let mut buf: Vec<u8> = vec![0; 65535];
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        let (n, src) = socket.recv_from(&mut buf).or(async {
+            Timer::after(remaining).await;
+            Err(std::io::ErrorKind::TimedOut.into())
+        }).await?;
+        if src.ip() == dst.ip() {
+            buf.truncate(n);
+            return Ok(buf);
+        }
+    }
+}
+
+/// Probe UDP port with protocol-specific payloads.
+///
+/// If `payload_map` has an entry for the port, only those payloads are sent and the reply is identified by all known parsers.
+pub(crate) async fn probe_udp_port(setting: &ServiceProbeSetting, port: u16) -> ServiceProbeResult {
+    let probes: Vec<(Option<UdpProbe>, Vec<u8>)> = match setting.payload_map.get(&port) {
+        Some(payloads) => payloads.iter().map(|payload_info| (None, payload_info.payload.clone())).collect(),
+        None => {
+            let probes: Vec<(Option<UdpProbe>, Vec<u8>)> = UdpProbe::for_port(port)
+                .into_iter()
+                .map(|probe| (Some(probe), probe.payload()))
+                .collect();
+            if probes.is_empty() { vec![(None, vec![])] } else { probes }
+        }
+    };
+    let default_service_name: String = probes
+        .iter()
+        .find_map(|(probe, _)| probe.map(|p| p.service_name().to_string()))
+        .unwrap_or_default();
+    let dst: SocketAddr = SocketAddr::new(setting.ip_addr, port);
+    let mut service_name: Option<String> = None;
+    let mut details: Vec<String> = vec![];
+    let mut response: Vec<u8> = vec![];
+    let mut last_error: Option<ServiceProbeError> = None;
+    for (probe, payload) in probes {
+        let res = match send_and_receive(dst, &payload, setting.read_timeout).await {
+            Ok(res) => res,
+            Err(e) => {
+                last_error = Some(match e.kind() {
+                    std::io::ErrorKind::TimedOut => ServiceProbeError::ReadError(e.to_string()),
+                    std::io::ErrorKind::AddrInUse | std::io::ErrorKind::AddrNotAvailable => ServiceProbeError::ConnectionError(e.to_string()),
+                    _ => ServiceProbeError::WriteError(e.to_string()),
+                });
+                continue;
+            }
+        };
+        let parsed = match probe {
+            Some(probe) => probe.parse(&res).map(|detail| (probe, detail)),
+            None => identify(&res),
+        };
+        if let Some((probe, detail)) = parsed {
+            if service_name.is_none() {
+                service_name = Some(probe.service_name().to_string());
+            }
+            if let Some(detail) = detail {
+                details.push(detail);
+            }
+        }
+        if response.is_empty() {
+            response = res;
+        }
+    }
+    if response.is_empty() {
+        let error = last_error.unwrap_or(ServiceProbeError::ReadError(String::from("No response")));
+        return ServiceProbeResult::with_error(port, default_service_name, error);
+    }
+    let mut result = ServiceProbeResult::new(port, service_name.unwrap_or(default_service_name), response);
+    if !details.is_empty() {
+        result.service_detail = Some(details.join("; "));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn late_reply_is_not_taken_by_next_probe() {
+        let server = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+        let dst: SocketAddr = server.local_addr().unwrap();
+        // Reply to each request after a delay. The reply to the first request arrives while the second probe is waiting
+        thread::spawn(move || {
+            let mut buf: [u8; 16] = [0; 16];
+            for delay in [150, 200] {
+                let (n, src) = server.recv_from(&mut buf).unwrap();
+                let reply: Vec<u8> = [b"reply-", &buf[..n]].concat();
+                let socket = server.try_clone().unwrap();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(delay));
+                    let _ = socket.send_to(&reply, src);
+                });
+            }
+        });
+        async_io::block_on(async {
+            let first = send_and_receive(dst, b"first", Duration::from_millis(50)).await;
+            assert_eq!(first.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+            let second = send_and_receive(dst, b"second", Duration::from_millis(1000)).await.unwrap();
+            assert_eq!(second, b"reply-second");
+        });
+    }
+}
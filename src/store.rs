@@ -0,0 +1,706 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use netdev::mac::MacAddr;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use crate::host::{Host, Port, RttStats};
+use crate::report::nmap::{format_port_ranges, target_ports};
+use crate::report::parse_port_status;
+use crate::scan::result::{ScanResult, ScanStatus, ServiceProbeError, ServiceProbeResult};
+use crate::scan::setting::{HostScanSetting, PortScanSetting};
+use crate::scan::tls::TlsInfo;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Tables of the result store. Times are milliseconds and RTTs are microseconds
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scan_type TEXT NOT NULL,
+    protocol TEXT NOT NULL,
+    targets TEXT NOT NULL,
+    ports TEXT NOT NULL,
+    args TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT
+);
+CREATE TABLE IF NOT EXISTS hosts (
+    scan_id INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+    ip_addr TEXT NOT NULL,
+    hostname TEXT NOT NULL,
+    mac_addr TEXT NOT NULL,
+    ttl INTEGER NOT NULL,
+    os_family TEXT NOT NULL,
+    os_confidence INTEGER NOT NULL,
+    rtt_min INTEGER,
+    rtt_avg INTEGER,
+    rtt_max INTEGER,
+    rtt_count INTEGER,
+    PRIMARY KEY (scan_id, ip_addr)
+);
+CREATE TABLE IF NOT EXISTS ports (
+    scan_id INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+    ip_addr TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    service_name TEXT NOT NULL,
+    service_version TEXT NOT NULL,
+    rtt INTEGER,
+    PRIMARY KEY (scan_id, ip_addr, port)
+);
+CREATE TABLE IF NOT EXISTS services (
+    scan_id INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+    ip_addr TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    service_name TEXT NOT NULL,
+    service_detail TEXT,
+    response BLOB NOT NULL,
+    error_kind TEXT,
+    error TEXT,
+    PRIMARY KEY (scan_id, ip_addr, port)
+);
+CREATE TABLE IF NOT EXISTS tls (
+    scan_id INTEGER NOT NULL REFERENCES scans(id) ON DELETE CASCADE,
+    ip_addr TEXT NOT NULL,
+    port INTEGER NOT NULL,
+    version TEXT NOT NULL,
+    cipher_suite TEXT NOT NULL,
+    alpn_protocol TEXT,
+    certificate_valid INTEGER NOT NULL,
+    certificate_error TEXT,
+    PRIMARY KEY (scan_id, ip_addr, port)
+);
+CREATE INDEX IF NOT EXISTS hosts_ip_addr ON hosts (ip_addr);
+CREATE INDEX IF NOT EXISTS ports_ip_addr ON ports (ip_addr, port);
+";
+
+/// Columns of the scan listing, in the order read by `scan_entry`
+const SCAN_COLUMNS: &str = "s.id, s.scan_type, s.protocol, s.targets, s.ports, s.args, s.start_time, s.end_time, s.status, s.error,
+    (SELECT COUNT(*) FROM hosts h WHERE h.scan_id = s.id),
+    (SELECT COUNT(*) FROM ports p WHERE p.scan_id = s.id AND p.status = 'open')";
+
+/// Metadata of the scan to be stored with the result
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanRecord {
+    scan_type: String,
+    protocol: String,
+    targets: Vec<IpAddr>,
+    ports: BTreeSet<u16>,
+    /// Command line or other description of the scan
+    args: String,
+    /// Start time of the scan. Taken from the result (e.g. parsed by the importers), or derived from its scan time, if not set
+    start_time: Option<SystemTime>,
+    /// Service probe results by host
+    service_results: HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>,
+}
+
+impl ScanRecord {
+    /// Create new ScanRecord without settings (e.g. for imported or passive scan results)
+    pub fn new() -> Self {
+        ScanRecord::default()
+    }
+    /// Create new ScanRecord for the result of the port scan
+    pub fn from_port_scan_setting(setting: &PortScanSetting) -> Self {
+        ScanRecord {
+            scan_type: setting.scan_type.to_str().to_string(),
+            protocol: setting.protocol.to_str().to_string(),
+            targets: setting.targets.iter().map(|target| target.ip_addr).collect(),
+            ports: target_ports(&setting.targets),
+            ..ScanRecord::new()
+        }
+    }
+    /// Create new ScanRecord for the result of the host scan
+    pub fn from_host_scan_setting(setting: &HostScanSetting) -> Self {
+        ScanRecord {
+            scan_type: setting.scan_type.to_str().to_string(),
+            protocol: setting.protocol.to_str().to_string(),
+            targets: setting.targets.iter().map(|target| target.ip_addr).collect(),
+            ports: target_ports(&setting.targets),
+            ..ScanRecord::new()
+        }
+    }
+    pub fn set_args(mut self, args: String) -> Self {
+        self.args = args;
+        self
+    }
+    pub fn set_start_time(mut self, start_time: SystemTime) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+    pub fn set_service_results(mut self, service_results: HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>) -> Self {
+        self.service_results = service_results;
+        self
+    }
+}
+
+/// Stored scan with its metadata
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanEntry {
+    /// Row ID of the scan
+    pub id: i64,
+    /// Scan type (e.g. TCP-SYN, ICMP-PING). Empty if not recorded
+    pub scan_type: String,
+    pub protocol: String,
+    pub targets: Vec<IpAddr>,
+    /// Target ports as ranges (e.g. `22,80-90,443`)
+    pub ports: String,
+    pub args: String,
+    pub start_time: SystemTime,
+    pub end_time: SystemTime,
+    pub scan_status: ScanStatus,
+    /// Number of the found hosts
+    pub host_count: usize,
+    /// Number of the open ports of all hosts
+    pub open_port_count: usize,
+}
+
+/// State of the host in a stored scan
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HostSnapshot {
+    /// Scan that found the host
+    pub scan: ScanEntry,
+    pub host: Host,
+    /// Service probe results by port
+    pub services: HashMap<u16, ServiceProbeResult>,
+}
+
+/// Port found in a stored scan
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PortSighting {
+    /// Scan that found the port
+    pub scan: ScanEntry,
+    pub port: Port,
+}
+
+/// SQLite database of scan results.
+///
+/// Hosts, ports, service probe results and TLS sessions are stored per scan.
+/// Uptime estimates, protocol-specific host details, HTTP fingerprints and packet fingerprints are not stored.
+pub struct ScanStore {
+    conn: Connection,
+}
+
+impl ScanStore {
+    /// Open the database file, creating it and the tables if they do not exist
+    pub fn open(path: &Path) -> Result<ScanStore, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+        ScanStore::init(conn)
+    }
+    /// Open a database in memory
+    pub fn open_in_memory() -> Result<ScanStore, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        ScanStore::init(conn)
+    }
+    fn init(conn: Connection) -> Result<ScanStore, String> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;").map_err(|e| e.to_string())?;
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        Ok(ScanStore { conn })
+    }
+    /// Store the scan result with the metadata. Returns the ID of the scan
+    pub fn insert(&mut self, record: &ScanRecord, result: &ScanResult) -> Result<i64, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let scan_id: i64 = insert_scan(&tx, record, result).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(scan_id)
+    }
+    /// Delete the scan and its hosts, ports and services
+    pub fn delete_scan(&mut self, scan_id: i64) -> Result<(), String> {
+        self.conn
+            .execute("DELETE FROM scans WHERE id = ?1", params![scan_id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    /// List the stored scans in order of the start time
+    pub fn scans(&self) -> Result<Vec<ScanEntry>, String> {
+        let sql = format!("SELECT {} FROM scans s ORDER BY s.start_time, s.id", SCAN_COLUMNS);
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let entries = stmt
+            .query_map([], scan_entry)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<ScanEntry>>>())
+            .map_err(|e| e.to_string())?;
+        Ok(entries)
+    }
+    /// List the stored scans that found the host, in order of the start time
+    pub fn host_scans(&self, ip_addr: IpAddr) -> Result<Vec<ScanEntry>, String> {
+        let sql = format!(
+            "SELECT {} FROM scans s JOIN hosts h ON h.scan_id = s.id WHERE h.ip_addr = ?1 ORDER BY s.start_time, s.id",
+            SCAN_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let entries = stmt
+            .query_map(params![ip_addr.to_string()], scan_entry)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<ScanEntry>>>())
+            .map_err(|e| e.to_string())?;
+        Ok(entries)
+    }
+    /// Get the metadata of the scan
+    pub fn scan(&self, scan_id: i64) -> Result<Option<ScanEntry>, String> {
+        let sql = format!("SELECT {} FROM scans s WHERE s.id = ?1", SCAN_COLUMNS);
+        self.conn
+            .query_row(&sql, params![scan_id], scan_entry)
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+    /// Load the result of the scan. Fingerprints are empty
+    pub fn load_result(&self, scan_id: i64) -> Result<Option<ScanResult>, String> {
+        let entry = match self.scan(scan_id)? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let mut stmt = self
+            .conn
+            .prepare("SELECT ip_addr FROM hosts WHERE scan_id = ?1")
+            .map_err(|e| e.to_string())?;
+        let ip_addrs = stmt
+            .query_map(params![scan_id], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+            .map_err(|e| e.to_string())?;
+        let mut result = ScanResult::new();
+        for ip_addr in ip_addrs {
+            let ip_addr: IpAddr = match IpAddr::from_str(&ip_addr) {
+                Ok(ip_addr) => ip_addr,
+                Err(_) => continue,
+            };
+            if let Some(host) = self.load_host(scan_id, ip_addr)? {
+                result.hosts.push(host);
+            }
+        }
+        result.sort_hosts();
+        result.scan_time = entry.end_time.duration_since(entry.start_time).unwrap_or(Duration::ZERO);
+        result.start_time = Some(entry.start_time);
+        result.scan_status = entry.scan_status;
+        Ok(Some(result))
+    }
+    /// Load the service probe results of the scan by host
+    pub fn load_service_results(&self, scan_id: i64) -> Result<HashMap<IpAddr, HashMap<u16, ServiceProbeResult>>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT DISTINCT ip_addr FROM services WHERE scan_id = ?1")
+            .map_err(|e| e.to_string())?;
+        let ip_addrs = stmt
+            .query_map(params![scan_id], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+            .map_err(|e| e.to_string())?;
+        let mut service_results: HashMap<IpAddr, HashMap<u16, ServiceProbeResult>> = HashMap::new();
+        for ip_addr in ip_addrs {
+            if let Ok(ip_addr) = IpAddr::from_str(&ip_addr) {
+                service_results.insert(ip_addr, self.load_services(scan_id, ip_addr)?);
+            }
+        }
+        Ok(service_results)
+    }
+    /// Latest state of the host. Taken from the most recent scan that found it
+    pub fn latest_host(&self, ip_addr: IpAddr) -> Result<Option<HostSnapshot>, String> {
+        let sql = format!(
+            "SELECT {} FROM scans s JOIN hosts h ON h.scan_id = s.id WHERE h.ip_addr = ?1 ORDER BY s.end_time DESC, s.id DESC LIMIT 1",
+            SCAN_COLUMNS
+        );
+        let scan = match self
+            .conn
+            .query_row(&sql, params![ip_addr.to_string()], scan_entry)
+            .optional()
+            .map_err(|e| e.to_string())?
+        {
+            Some(scan) => scan,
+            None => return Ok(None),
+        };
+        let host = match self.load_host(scan.id, ip_addr)? {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+        let services = self.load_services(scan.id, ip_addr)?;
+        Ok(Some(HostSnapshot { scan, host, services }))
+    }
+    /// First scan that found the port open on the host
+    pub fn port_first_seen(&self, ip_addr: IpAddr, port: u16) -> Result<Option<PortSighting>, String> {
+        let sql = format!(
+            "SELECT {}, p.port, p.status, p.service_name, p.service_version, p.rtt FROM scans s JOIN ports p ON p.scan_id = s.id
+            WHERE p.ip_addr = ?1 AND p.port = ?2 AND p.status = 'open' ORDER BY s.start_time, s.id LIMIT 1",
+            SCAN_COLUMNS
+        );
+        self.conn
+            .query_row(&sql, params![ip_addr.to_string(), port], |row| {
+                Ok(PortSighting {
+                    scan: scan_entry(row)?,
+                    port: port_row(row, 12)?,
+                })
+            })
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+    fn load_host(&self, scan_id: i64, ip_addr: IpAddr) -> Result<Option<Host>, String> {
+        let host = self
+            .conn
+            .query_row(
+                "SELECT hostname, mac_addr, ttl, os_family, os_confidence, rtt_min, rtt_avg, rtt_max, rtt_count
+                FROM hosts WHERE scan_id = ?1 AND ip_addr = ?2",
+                params![scan_id, ip_addr.to_string()],
+                |row| {
+                    let mut host = Host::new(ip_addr, row.get(0)?);
+                    host.mac_addr = MacAddr::from_str(&row.get::<_, String>(1)?).unwrap_or(MacAddr::zero());
+                    host.ttl = row.get(2)?;
+                    host.os_family = row.get(3)?;
+                    host.os_confidence = row.get(4)?;
+                    let rtt: (Option<i64>, Option<i64>, Option<i64>, Option<i64>) = (row.get(5)?, row.get(6)?, row.get(7)?, row.get(8)?);
+                    host.rtt = match rtt {
+                        (Some(min), Some(avg), Some(max), Some(count)) => Some(RttStats {
+                            min: from_micros(min),
+                            avg: from_micros(avg),
+                            max: from_micros(max),
+                            count: count as usize,
+                        }),
+                        _ => None,
+                    };
+                    Ok(host)
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let mut host = match host {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+        let mut stmt = self
+            .conn
+            .prepare("SELECT port, status, service_name, service_version, rtt FROM ports WHERE scan_id = ?1 AND ip_addr = ?2 ORDER BY port")
+            .map_err(|e| e.to_string())?;
+        host.ports = stmt
+            .query_map(params![scan_id, ip_addr.to_string()], |row| port_row(row, 0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Port>>>())
+            .map_err(|e| e.to_string())?;
+        Ok(Some(host))
+    }
+    fn load_services(&self, scan_id: i64, ip_addr: IpAddr) -> Result<HashMap<u16, ServiceProbeResult>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT s.port, s.service_name, s.service_detail, s.response, s.error_kind, s.error, t.version, t.cipher_suite, t.alpn_protocol,
+                t.certificate_valid, t.certificate_error
+                FROM services s LEFT JOIN tls t ON t.scan_id = s.scan_id AND t.ip_addr = s.ip_addr AND t.port = s.port
+                WHERE s.scan_id = ?1 AND s.ip_addr = ?2",
+            )
+            .map_err(|e| e.to_string())?;
+        let results = stmt
+            .query_map(params![scan_id, ip_addr.to_string()], |row| {
+                let mut result = ServiceProbeResult::new(row.get(0)?, row.get(1)?, row.get(3)?);
+                result.service_detail = row.get(2)?;
+                result.error = match (row.get::<_, Option<String>>(4)?, row.get::<_, Option<String>>(5)?) {
+                    (Some(kind), Some(message)) => Some(parse_error(&kind, message)),
+                    _ => None,
+                };
+                result.tls_info = match (row.get::<_, Option<String>>(6)?, row.get::<_, Option<String>>(7)?) {
+                    (Some(version), Some(cipher_suite)) => Some(TlsInfo {
+                        version,
+                        cipher_suite,
+                        alpn_protocol: row.get(8)?,
+                        certificate_valid: row.get(9)?,
+                        certificate_error: row.get(10)?,
+                    }),
+                    _ => None,
+                };
+                Ok(result)
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<ServiceProbeResult>>>())
+            .map_err(|e| e.to_string())?;
+        Ok(results.into_iter().map(|result| (result.port, result)).collect())
+    }
+}
+
+fn insert_scan(conn: &Connection, record: &ScanRecord, result: &ScanResult) -> rusqlite::Result<i64> {
+    let end_time: SystemTime = match record.start_time.or(result.start_time) {
+        Some(start_time) => start_time + result.scan_time,
+        None => SystemTime::now(),
+    };
+    let start_time: SystemTime = match record.start_time.or(result.start_time) {
+        Some(start_time) => start_time,
+        None => end_time.checked_sub(result.scan_time).unwrap_or(end_time),
+    };
+    let (status, error): (&str, Option<&str>) = match &result.scan_status {
+        ScanStatus::Done => ("done", None),
+        ScanStatus::Timeout => ("timeout", None),
+        ScanStatus::Error(message) => ("error", Some(message)),
+    };
+    let targets: Vec<String> = record.targets.iter().map(|ip_addr| ip_addr.to_string()).collect();
+    conn.execute(
+        "INSERT INTO scans (scan_type, protocol, targets, ports, args, start_time, end_time, status, error)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            record.scan_type,
+            record.protocol,
+            targets.join(","),
+            format_port_ranges(&record.ports),
+            record.args,
+            to_millis(start_time),
+            to_millis(end_time),
+            status,
+            error
+        ],
+    )?;
+    let scan_id: i64 = conn.last_insert_rowid();
+    for host in &result.hosts {
+        let ip_addr: String = host.ip_addr.to_string();
+        conn.execute(
+            "INSERT OR REPLACE INTO hosts (scan_id, ip_addr, hostname, mac_addr, ttl, os_family, os_confidence, rtt_min, rtt_avg, rtt_max, rtt_count)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                scan_id,
+                ip_addr,
+                host.hostname,
+                host.mac_addr.to_string(),
+                host.ttl,
+                host.os_family,
+                host.os_confidence,
+                host.rtt.map(|rtt| to_micros(rtt.min)),
+                host.rtt.map(|rtt| to_micros(rtt.avg)),
+                host.rtt.map(|rtt| to_micros(rtt.max)),
+                host.rtt.map(|rtt| rtt.count as i64)
+            ],
+        )?;
+        for port in &host.ports {
+            conn.execute(
+                "INSERT OR REPLACE INTO ports (scan_id, ip_addr, port, status, service_name, service_version, rtt)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    scan_id,
+                    ip_addr,
+                    port.number,
+                    port.status.id(),
+                    port.service_name,
+                    port.service_version,
+                    port.rtt.map(to_micros)
+                ],
+            )?;
+        }
+    }
+    for (ip_addr, results) in &record.service_results {
+        let ip_addr: String = ip_addr.to_string();
+        for result in results.values() {
+            let (error_kind, error): (Option<&str>, Option<&String>) = match &result.error {
+                Some(error) => {
+                    let (kind, message) = error_kind(error);
+                    (Some(kind), Some(message))
+                }
+                None => (None, None),
+            };
+            conn.execute(
+                "INSERT OR REPLACE INTO services (scan_id, ip_addr, port, service_name, service_detail, response, error_kind, error)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    scan_id,
+                    ip_addr,
+                    result.port,
+                    result.service_name,
+                    result.service_detail,
+                    result.response,
+                    error_kind,
+                    error
+                ],
+            )?;
+            if let Some(tls_info) = &result.tls_info {
+                conn.execute(
+                    "INSERT OR REPLACE INTO tls (scan_id, ip_addr, port, version, cipher_suite, alpn_protocol, certificate_valid, certificate_error)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        scan_id,
+                        ip_addr,
+                        result.port,
+                        tls_info.version,
+                        tls_info.cipher_suite,
+                        tls_info.alpn_protocol,
+                        tls_info.certificate_valid,
+                        tls_info.certificate_error
+                    ],
+                )?;
+            }
+        }
+    }
+    Ok(scan_id)
+}
+
+/// Read the scan columns of `SCAN_COLUMNS`
+fn scan_entry(row: &Row) -> rusqlite::Result<ScanEntry> {
+    let targets: String = row.get(3)?;
+    let status: String = row.get(8)?;
+    let scan_status: ScanStatus = match status.as_str() {
+        "timeout" => ScanStatus::Timeout,
+        "error" => ScanStatus::Error(row.get::<_, Option<String>>(9)?.unwrap_or_default()),
+        _ => ScanStatus::Done,
+    };
+    Ok(ScanEntry {
+        id: row.get(0)?,
+        scan_type: row.get(1)?,
+        protocol: row.get(2)?,
+        targets: targets.split(',').filter_map(|target| IpAddr::from_str(target).ok()).collect(),
+        ports: row.get(4)?,
+        args: row.get(5)?,
+        start_time: from_millis(row.get(6)?),
+        end_time: from_millis(row.get(7)?),
+        scan_status,
+        host_count: row.get::<_, i64>(10)? as usize,
+        open_port_count: row.get::<_, i64>(11)? as usize,
+    })
+}
+
+/// Read the port, status, service name, service version and RTT columns from the index
+fn port_row(row: &Row, index: usize) -> rusqlite::Result<Port> {
+    let status: String = row.get(index + 1)?;
+    Ok(Port {
+        number: row.get(index)?,
+        status: parse_port_status(&status),
+        service_name: row.get(index + 2)?,
+        service_version: row.get(index + 3)?,
+        rtt: row.get::<_, Option<i64>>(index + 4)?.map(from_micros),
+    })
+}
+
+fn error_kind(error: &ServiceProbeError) -> (&'static str, &String) {
+    match error {
+        ServiceProbeError::ConnectionError(message) => ("connection", message),
+        ServiceProbeError::WriteError(message) => ("write", message),
+        ServiceProbeError::ReadError(message) => ("read", message),
+        ServiceProbeError::TlsError(message) => ("tls", message),
+        ServiceProbeError::CustomError(message) => ("custom", message),
+    }
+}
+
+fn parse_error(kind: &str, message: String) -> ServiceProbeError {
+    match kind {
+        "connection" => ServiceProbeError::ConnectionError(message),
+        "write" => ServiceProbeError::WriteError(message),
+        "read" => ServiceProbeError::ReadError(message),
+        "tls" => ServiceProbeError::TlsError(message),
+        _ => ServiceProbeError::CustomError(message),
+    }
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i64,
+        Err(_) => 0,
+    }
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+fn to_micros(duration: Duration) -> i64 {
+    duration.as_micros() as i64
+}
+
+fn from_micros(micros: i64) -> Duration {
+    Duration::from_micros(micros.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::PortStatus;
+
+    fn scan_result(ip_addr: IpAddr, ports: &[(u16, PortStatus)], start_time: Option<SystemTime>) -> ScanResult {
+        let mut host = Host::new(ip_addr, String::from("server.local"));
+        host.ttl = 64;
+        for (number, status) in ports {
+            let mut port = Port::new(*number);
+            port.status = *status;
+            host.ports.push(port);
+        }
+        let mut result = ScanResult::new();
+        result.hosts.push(host);
+        result.scan_time = Duration::from_secs(10);
+        result.start_time = start_time;
+        result
+    }
+
+    fn time(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn insert_and_load_result() {
+        let mut store = ScanStore::open_in_memory().unwrap();
+        let ip_addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let result = scan_result(ip_addr, &[(22, PortStatus::Open), (80, PortStatus::Closed)], None);
+        let mut service = ServiceProbeResult::new(22, String::from("ssh"), b"SSH-2.0-OpenSSH_8.9".to_vec());
+        service.tls_info = Some(TlsInfo {
+            version: String::from("TLSv1_3"),
+            cipher_suite: String::from("TLS13_AES_256_GCM_SHA384"),
+            alpn_protocol: None,
+            certificate_valid: false,
+            certificate_error: Some(String::from("UnknownIssuer")),
+        });
+        let record = ScanRecord::new()
+            .set_start_time(time(1_700_000_000))
+            .set_service_results(HashMap::from([(ip_addr, HashMap::from([(22, service.clone())]))]));
+        let scan_id = store.insert(&record, &result).unwrap();
+
+        let entry = store.scan(scan_id).unwrap().unwrap();
+        assert_eq!(entry.start_time, time(1_700_000_000));
+        assert_eq!(entry.end_time, time(1_700_000_010));
+        assert_eq!(entry.host_count, 1);
+        assert_eq!(entry.open_port_count, 1);
+
+        let loaded = store.load_result(scan_id).unwrap().unwrap();
+        assert_eq!(loaded.scan_time, Duration::from_secs(10));
+        assert_eq!(loaded.start_time, Some(time(1_700_000_000)));
+        assert_eq!(loaded.hosts.len(), 1);
+        assert_eq!(loaded.hosts[0].hostname, "server.local");
+        assert_eq!(loaded.hosts[0].ttl, 64);
+        assert_eq!(loaded.hosts[0].ports, result.hosts[0].ports);
+        let services = store.load_service_results(scan_id).unwrap();
+        assert_eq!(services[&ip_addr][&22], service);
+    }
+
+    #[test]
+    fn imported_result_keeps_start_time() {
+        let mut store = ScanStore::open_in_memory().unwrap();
+        let ip_addr: IpAddr = "10.0.0.1".parse().unwrap();
+        let result = scan_result(ip_addr, &[(443, PortStatus::Open)], Some(time(1_600_000_000)));
+        let scan_id = store.insert(&ScanRecord::new(), &result).unwrap();
+        let entry = store.scan(scan_id).unwrap().unwrap();
+        assert_eq!(entry.start_time, time(1_600_000_000));
+        assert_eq!(entry.end_time, time(1_600_000_010));
+    }
+
+    #[test]
+    fn history_ordered_by_scan_time() {
+        let mut store = ScanStore::open_in_memory().unwrap();
+        let ip_addr: IpAddr = "10.0.0.1".parse().unwrap();
+        // Newer scan inserted before the imported older scan
+        let newer = scan_result(ip_addr, &[(22, PortStatus::Open), (443, PortStatus::Open)], Some(time(1_700_000_000)));
+        let newer_id = store.insert(&ScanRecord::new(), &newer).unwrap();
+        let older = scan_result(ip_addr, &[(22, PortStatus::Open)], Some(time(1_600_000_000)));
+        let older_id = store.insert(&ScanRecord::new(), &older).unwrap();
+
+        let scans: Vec<i64> = store.host_scans(ip_addr).unwrap().iter().map(|entry| entry.id).collect();
+        assert_eq!(scans, vec![older_id, newer_id]);
+        let latest = store.latest_host(ip_addr).unwrap().unwrap();
+        assert_eq!(latest.scan.id, newer_id);
+        assert_eq!(latest.host.ports.len(), 2);
+        assert_eq!(store.port_first_seen(ip_addr, 22).unwrap().unwrap().scan.id, older_id);
+        assert_eq!(store.port_first_seen(ip_addr, 443).unwrap().unwrap().scan.id, newer_id);
+        assert!(store.port_first_seen(ip_addr, 80).unwrap().is_none());
+        assert!(store.latest_host("10.0.0.2".parse().unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn delete_scan_removes_hosts() {
+        let mut store = ScanStore::open_in_memory().unwrap();
+        let ip_addr: IpAddr = "10.0.0.1".parse().unwrap();
+        let scan_id = store.insert(&ScanRecord::new(), &scan_result(ip_addr, &[(22, PortStatus::Open)], None)).unwrap();
+        store.delete_scan(scan_id).unwrap();
+        assert!(store.scan(scan_id).unwrap().is_none());
+        assert!(store.load_result(scan_id).unwrap().is_none());
+        assert!(store.latest_host(ip_addr).unwrap().is_none());
+        assert!(store.port_first_seen(ip_addr, 22).unwrap().is_none());
+        assert!(store.scans().unwrap().is_empty());
+    }
+}